@@ -6,8 +6,20 @@
 //! <http://mozilla.org/MPL/2.0/>.
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum Action {
+    /// Consumes the input, the output may be delayed.
     Run,
+    /// Ends the current block, for deflate this is the same as `SyncFlush`.
     Flush,
+    /// Deflate: ends the current block and appends an empty stored block,
+    /// so that the output ends on a byte boundary.
+    SyncFlush,
+    /// Deflate: as `SyncFlush`, and also discards the history, so that
+    /// the decoding can restart from this point.
+    FullFlush,
+    /// Deflate: ends the current block without aligning the output.
+    Block,
+    /// Writes out all pending output and ends the stream.
     Finish,
 }
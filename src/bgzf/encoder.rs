@@ -0,0 +1,169 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::action::Action;
+use crate::bgzf::{
+    BSIZE_POS, EOF_MARKER, HEADER_LEN, MAX_BLOCK_DATA, MAX_BLOCK_SIZE,
+};
+use crate::deflate::options::DeflateOptions;
+use crate::error::CompressionError;
+use crate::gzip::encoder::GZipEncoder;
+use crate::gzip::header::{GzHeader, GzHeaderBuilder};
+use crate::traits::encoder::{EncodeExt, Encoder};
+#[cfg(not(feature = "std"))]
+use alloc::collections::vec_deque::VecDeque;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::collections::vec_deque::VecDeque;
+
+/// The BGZF (blocked gzip) encoder.
+///
+/// The data is split into gzip members of at most 64 KiB, each of which
+/// carries its length in the `BC` extra subfield. `Action::Finish`
+/// appends the empty block which marks the end of the file, and the
+/// other actions except `Action::Run` close the current block.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "bgzf")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut BgzfEncoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     # #[cfg(feature = "bgzf")]
+///     let decompressed = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut GZipDecoder::new())
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BgzfEncoder {
+    options: DeflateOptions,
+    header: GzHeader,
+    block: Vec<u8>,
+    queue: VecDeque<u8>,
+    block_offset: u64,
+    finished: bool,
+}
+
+impl Default for BgzfEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BgzfEncoder {
+    pub fn new() -> Self {
+        Self::with_options(&DeflateOptions::default())
+    }
+
+    pub fn with_options(options: &DeflateOptions) -> Self {
+        options.validate();
+        Self {
+            options: *options,
+            header: GzHeaderBuilder::new()
+                .extra_subfield(*b"BC", &[0, 0])
+                .build(),
+            block: Vec::with_capacity(MAX_BLOCK_DATA),
+            queue: VecDeque::new(),
+            block_offset: 0,
+            finished: false,
+        }
+    }
+
+    /// Returns the virtual file offset of the next byte to be encoded.
+    ///
+    /// The upper 48 bits are the offset of the block in the compressed
+    /// file and the lower 16 bits are the offset in the block.
+    pub fn virtual_offset(&self) -> u64 {
+        (self.block_offset << 16) | self.block.len() as u64
+    }
+
+    fn compress_block(&self, options: &DeflateOptions) -> Vec<u8> {
+        self.block
+            .iter()
+            .cloned()
+            .encode(
+                &mut GZipEncoder::with_options_and_header(
+                    options,
+                    &self.header,
+                ),
+                Action::Finish,
+            )
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    fn write_block(&mut self) {
+        let mut block = self.compress_block(&self.options);
+        if block.len() > MAX_BLOCK_SIZE {
+            block = self.compress_block(&DeflateOptions::new(0));
+        }
+        debug_assert!(block.len() > HEADER_LEN);
+        let bsize = (block.len() - 1) as u16;
+        block[BSIZE_POS..BSIZE_POS + 2].copy_from_slice(&bsize.to_le_bytes());
+        self.block_offset += block.len() as u64;
+        self.queue.extend(block);
+        self.block.clear();
+    }
+}
+
+impl Encoder for BgzfEncoder {
+    type Error = CompressionError;
+    type In = u8;
+    type Out = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<u8, CompressionError>> {
+        while self.queue.is_empty() {
+            if self.block.len() == MAX_BLOCK_DATA {
+                self.write_block();
+                continue;
+            }
+            match iter.next() {
+                Some(s) => self.block.push(s),
+                None => {
+                    if self.finished {
+                        self.finished = false;
+                        return None;
+                    }
+                    match action {
+                        Action::Run => return None,
+                        Action::Finish => {
+                            if !self.block.is_empty() {
+                                self.write_block();
+                            }
+                            self.block_offset += EOF_MARKER.len() as u64;
+                            self.queue.extend(EOF_MARKER.iter());
+                        }
+                        _ => {
+                            if !self.block.is_empty() {
+                                self.write_block();
+                            }
+                        }
+                    }
+                    self.finished = true;
+                }
+            }
+        }
+        self.queue.pop_front().map(Ok)
+    }
+}
@@ -0,0 +1,119 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+#[cfg(feature = "std")]
+use crate::bgzf::{block_size, HEADER_LEN};
+use crate::core::convert::TryInto;
+use crate::error::CompressionError;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// The `.gzi` index of a BGZF file, as written by `bgzip -i`.
+///
+/// The index holds the compressed and the uncompressed offsets of the
+/// start of every block except the first one.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GziIndex {
+    entries: Vec<(u64, u64)>,
+}
+
+impl GziIndex {
+    /// Returns the pairs of the compressed and the uncompressed offsets.
+    pub fn entries(&self) -> &[(u64, u64)] {
+        &self.entries
+    }
+
+    /// Converts the offset in the uncompressed data to the virtual file
+    /// offset.
+    ///
+    /// The offset has to be in the data of the file.
+    pub fn virtual_offset(&self, offset: u64) -> u64 {
+        let (coffset, uoffset) = self.find(offset);
+        (coffset << 16) | (offset - uoffset)
+    }
+
+    /// Returns the offsets of the block which contains `offset`.
+    pub(crate) fn find(&self, offset: u64) -> (u64, u64) {
+        match self.entries.binary_search_by_key(&offset, |e| e.1) {
+            Ok(i) => self.entries[i],
+            Err(0) => (0, 0),
+            Err(i) => self.entries[i - 1],
+        }
+    }
+
+    /// Builds the index by scanning the headers of the blocks in `reader`
+    /// from its start.
+    #[cfg(feature = "std")]
+    pub fn build<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
+        let mut entries = Vec::new();
+        let mut coffset = reader.seek(SeekFrom::Start(0))?;
+        let mut uoffset = 0;
+        let mut header = [0; HEADER_LEN];
+        loop {
+            match reader.read(&mut header[..1])? {
+                0 => break,
+                _ => reader.read_exact(&mut header[1..])?,
+            }
+            let len = block_size(&header)
+                .filter(|&l| l >= HEADER_LEN + 8)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        CompressionError::DataError,
+                    )
+                })?;
+            let _ = reader
+                .seek(SeekFrom::Current((len - HEADER_LEN - 4) as i64))?;
+            let mut i_size = [0; 4];
+            reader.read_exact(&mut i_size)?;
+            let i_size = u64::from(u32::from_le_bytes(i_size));
+            if i_size != 0 && coffset != 0 {
+                entries.push((coffset, uoffset));
+            }
+            coffset += len as u64;
+            uoffset += i_size;
+        }
+        Ok(Self { entries })
+    }
+
+    /// Serializes the index in the `.gzi` format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(8 + self.entries.len() * 16);
+        ret.extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+        for &(coffset, uoffset) in &self.entries {
+            ret.extend_from_slice(&coffset.to_le_bytes());
+            ret.extend_from_slice(&uoffset.to_le_bytes());
+        }
+        ret
+    }
+
+    /// Deserializes an index in the `.gzi` format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CompressionError> {
+        let read_u64 = |pos: usize| {
+            bytes
+                .get(pos..pos + 8)
+                .map(|x| u64::from_le_bytes(x.try_into().unwrap()))
+                .ok_or(CompressionError::UnexpectedEof)
+        };
+        let len = read_u64(0)?;
+        if (bytes.len() as u64 - 8) / 16 != len || bytes.len() % 16 != 8 {
+            return Err(CompressionError::DataError);
+        }
+        let entries = (0..len as usize)
+            .map(|i| Ok((read_u64(8 + i * 16)?, read_u64(16 + i * 16)?)))
+            .collect::<Result<Vec<_>, CompressionError>>()?;
+        if entries
+            .windows(2)
+            .any(|w| w[0].0 >= w[1].0 || w[0].1 > w[1].1)
+        {
+            return Err(CompressionError::DataError);
+        }
+        Ok(Self { entries })
+    }
+}
@@ -0,0 +1,162 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "bgzf")]
+
+pub(crate) mod encoder;
+pub(crate) mod index;
+pub(crate) mod reader;
+
+/// The maximum length of the uncompressed data in a block.
+const MAX_BLOCK_DATA: usize = 0xFF00;
+/// The maximum length of a compressed block.
+const MAX_BLOCK_SIZE: usize = 0x1_0000;
+/// The length of the header with the `BC` subfield.
+const HEADER_LEN: usize = 18;
+/// The offset of `BSIZE` in the header.
+const BSIZE_POS: usize = 16;
+
+/// An empty block which marks the end of the file.
+const EOF_MARKER: [u8; 28] = [
+    0x1F, 0x8B, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x06, 0x00,
+    0x42, 0x43, 0x02, 0x00, 0x1B, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00,
+];
+
+/// Returns the length of the block starting with `header`, or `None` if
+/// it is not a BGZF block.
+#[cfg(any(feature = "std", test))]
+fn block_size(header: &[u8]) -> Option<usize> {
+    if header.len() < HEADER_LEN
+        || header[..4] != [0x1F, 0x8B, 0x08, 0x04]
+        || header[10..16] != [0x06, 0x00, 0x42, 0x43, 0x02, 0x00]
+    {
+        return None;
+    }
+    Some(
+        (usize::from(header[BSIZE_POS])
+            | (usize::from(header[BSIZE_POS + 1]) << 8))
+            + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::action::Action;
+    use crate::bgzf::encoder::BgzfEncoder;
+    use crate::bgzf::{block_size, EOF_MARKER, MAX_BLOCK_DATA};
+    use crate::gzip::decoder::GZipDecoder;
+    use crate::traits::decoder::DecodeExt;
+    use crate::traits::encoder::EncodeExt;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    fn testdata() -> Vec<u8> {
+        let rng = thread_rng();
+        let mut data = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cycle()
+            .take(150_000)
+            .cloned()
+            .collect::<Vec<u8>>();
+        data.extend(rng.sample_iter::<u8, _>(&Standard).take(150_000));
+        data
+    }
+
+    #[test]
+    fn test_empty() {
+        let encoded = Vec::<u8>::new()
+            .encode(&mut BgzfEncoder::new(), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(encoded, EOF_MARKER.to_vec());
+    }
+
+    #[test]
+    fn test_blocks() {
+        let data = testdata();
+        let encoded = data
+            .iter()
+            .cloned()
+            .encode(&mut BgzfEncoder::new(), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(encoded.ends_with(&EOF_MARKER));
+
+        // every block is a gzip member
+        let decoded = encoded
+            .iter()
+            .cloned()
+            .decode(&mut GZipDecoder::new())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(data, decoded);
+
+        let mut pos = 0;
+        let mut count = 0;
+        while pos < encoded.len() {
+            let len = block_size(&encoded[pos..]).unwrap();
+            let block = encoded[pos..pos + len]
+                .iter()
+                .cloned()
+                .decode(&mut GZipDecoder::single_member())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert!(block.len() <= MAX_BLOCK_DATA);
+            pos += len;
+            count += 1;
+        }
+        assert_eq!(pos, encoded.len());
+        assert_eq!(count, data.len().div_ceil(MAX_BLOCK_DATA) + 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_reader() {
+        use crate::bgzf::index::GziIndex;
+        use crate::bgzf::reader::BgzfReader;
+        use crate::io::encoder::EncoderWriter;
+        use std::io::{Cursor, Read, Write};
+
+        let data = testdata();
+        let mut writer = EncoderWriter::new(Vec::new(), BgzfEncoder::new());
+        let mut offsets = Vec::new();
+        for (i, chunk) in data.chunks(10_000).enumerate() {
+            offsets.push((i * 10_000, writer.encoder().virtual_offset()));
+            writer.write_all(chunk).unwrap();
+            if i % 7 == 0 {
+                writer.flush().unwrap();
+            }
+        }
+        let encoded = writer.finish().unwrap();
+
+        let mut reader = BgzfReader::new(Cursor::new(&encoded));
+        let mut decoded = Vec::new();
+        let _ = reader.read_to_end(&mut decoded).unwrap();
+        assert_eq!(data, decoded);
+        assert_eq!(reader.virtual_offset(), (encoded.len() as u64) << 16);
+
+        let index = GziIndex::build(&mut Cursor::new(&encoded)).unwrap();
+        assert_eq!(index, GziIndex::from_bytes(&index.to_bytes()).unwrap());
+        assert!(GziIndex::from_bytes(&index.to_bytes()[1..]).is_err());
+
+        let mut buf = vec![0; 100];
+        for &(pos, voffset) in offsets.iter().rev() {
+            reader.seek_virtual(voffset).unwrap();
+            assert_eq!(reader.virtual_offset(), voffset);
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(&data[pos..pos + 100], &buf[..]);
+            assert_eq!(index.virtual_offset(pos as u64), voffset);
+
+            let pos = (pos + 12_345) % (data.len() - 100);
+            reader.seek_uncompressed(&index, pos as u64).unwrap();
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(&data[pos..pos + 100], &buf[..]);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "std")]
+
+use crate::bgzf::index::GziIndex;
+use crate::bgzf::{block_size, HEADER_LEN};
+use crate::error::CompressionError;
+use crate::gzip::decoder::GZipDecoder;
+use crate::io::to_io_error;
+use crate::traits::decoder::DecodeExt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// A `Read` adapter which decompresses a BGZF file block by block and
+/// seeks to the virtual file offsets.
+///
+/// The underlying reader has to be positioned at the start of the file.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+/// use std::io::{Cursor, Read};
+///
+/// fn main() {
+///     # #[cfg(feature = "bgzf")]
+///     # {
+///     let data = b"aabbaabbaabbaabb\n".repeat(10_000);
+///     let compressed = data
+///         .iter()
+///         .cloned()
+///         .encode(&mut BgzfEncoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     let index = GziIndex::build(&mut Cursor::new(&compressed)).unwrap();
+///     let mut reader = BgzfReader::new(Cursor::new(&compressed));
+///     reader.seek_virtual(index.virtual_offset(100_000)).unwrap();
+///     let mut buf = vec![0; 17];
+///     reader.read_exact(&mut buf).unwrap();
+///     assert_eq!(&data[100_000..100_017], &buf[..]);
+///     # }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BgzfReader<R: Read + Seek> {
+    inner: R,
+    block: Vec<u8>,
+    pos: usize,
+    block_offset: u64,
+    next_block_offset: u64,
+}
+
+impl<R: Read + Seek> BgzfReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            block: Vec::new(),
+            pos: 0,
+            block_offset: 0,
+            next_block_offset: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns the virtual file offset of the next byte to be read.
+    pub fn virtual_offset(&self) -> u64 {
+        if self.pos == self.block.len() {
+            self.next_block_offset << 16
+        } else {
+            (self.block_offset << 16) | self.pos as u64
+        }
+    }
+
+    /// Seeks to the virtual file offset `offset`.
+    pub fn seek_virtual(&mut self, offset: u64) -> io::Result<()> {
+        let coffset = offset >> 16;
+        let uoffset = (offset & 0xFFFF) as usize;
+        let _ = self.inner.seek(SeekFrom::Start(coffset))?;
+        self.next_block_offset = coffset;
+        self.block.clear();
+        self.pos = 0;
+        if uoffset > 0 {
+            if !self.read_block()? || uoffset > self.block.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "invalid virtual offset",
+                ));
+            }
+            self.pos = uoffset;
+        }
+        Ok(())
+    }
+
+    /// Seeks to the offset `offset` in the uncompressed data with the
+    /// help of `index`.
+    pub fn seek_uncompressed(
+        &mut self,
+        index: &GziIndex,
+        offset: u64,
+    ) -> io::Result<()> {
+        let (coffset, uoffset) = index.find(offset);
+        self.seek_virtual(coffset << 16)?;
+        let mut rest = offset - uoffset;
+        while rest > 0 {
+            if self.pos == self.block.len() && !self.read_block()? {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "offset is beyond the end of the file",
+                ));
+            }
+            let len = rest.min((self.block.len() - self.pos) as u64);
+            self.pos += len as usize;
+            rest -= len;
+        }
+        Ok(())
+    }
+
+    fn read_block(&mut self) -> io::Result<bool> {
+        let mut block = vec![0; HEADER_LEN];
+        match self.inner.read(&mut block[..1])? {
+            0 => return Ok(false),
+            _ => self.inner.read_exact(&mut block[1..])?,
+        }
+        let len = block_size(&block)
+            .filter(|&l| l >= HEADER_LEN + 8)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    CompressionError::DataError,
+                )
+            })?;
+        block.resize(len, 0);
+        self.inner.read_exact(&mut block[HEADER_LEN..])?;
+        self.block = block
+            .into_iter()
+            .decode(&mut GZipDecoder::single_member())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(to_io_error)?;
+        self.pos = 0;
+        self.block_offset = self.next_block_offset;
+        self.next_block_offset += len as u64;
+        Ok(true)
+    }
+}
+
+impl<R: Read + Seek> Read for BgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.block.len() {
+            if !self.read_block()? {
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.block.len() - self.pos);
+        buf[..len].copy_from_slice(&self.block[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
@@ -0,0 +1,122 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "zstd")]
+
+use crate::bitio::direction::left::Left;
+use crate::bitio::reader::{BitRead, BitReader};
+use crate::core::iter::{Cloned, Rev};
+use crate::core::slice::Iter;
+use crate::huffman::decoder::HuffmanDecoder;
+#[cfg(not(feature = "std"))]
+use alloc::borrow::ToOwned;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// A bit reader over a stream which is read from its end.
+///
+/// The stream is written from its least significant bit and closed by a
+/// 1 bit, as the FSE and the Huffman streams of Zstandard. Reading the
+/// bytes from the last one and their bits from the most significant one
+/// returns the values in the reverse order of the writing.
+#[derive(Debug)]
+pub(crate) struct BackwardBitReader<'a> {
+    reader: BitReader<Left>,
+    iter: Rev<Cloned<Iter<'a, u8>>>,
+    len: u64,
+    overrun: u64,
+}
+
+impl<'a> BackwardBitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Result<Self, String> {
+        let last = match data.last() {
+            Some(&l) if l != 0 => l,
+            _ => return Err("no end mark".to_owned()),
+        };
+        let padding = last.leading_zeros() as usize + 1;
+        let mut reader = BitReader::new();
+        let mut iter = data.iter().cloned().rev();
+        let _ = reader.skip_bits(padding, &mut iter)?;
+        Ok(Self {
+            reader,
+            iter,
+            len: (data.len() as u64) << 3,
+            overrun: 0,
+        })
+    }
+
+    /// Reads `len` bits. The bits before the start of the stream are
+    /// read as zeros.
+    pub(crate) fn read(&mut self, len: usize) -> u64 {
+        if len == 0 {
+            return 0;
+        }
+        match self.reader.read_bits::<u64, _>(len, &mut self.iter) {
+            Ok(b) => {
+                self.overrun += (len - b.len()) as u64;
+                b.data() << (len - b.len())
+            }
+            Err(_) => {
+                self.overrun += len as u64;
+                0
+            }
+        }
+    }
+
+    pub(crate) fn decode(
+        &mut self,
+        decoder: &mut HuffmanDecoder<Left>,
+    ) -> Result<u16, String> {
+        decoder
+            .dec(&mut self.reader, &mut self.iter)?
+            .ok_or_else(|| "no more code".to_owned())
+    }
+
+    /// Returns the number of the bits left, which is negative if more
+    /// bits than the stream has have been read.
+    pub(crate) fn remaining(&self) -> i64 {
+        self.len as i64 - (self.reader.position() + self.overrun) as i64
+    }
+}
+
+/// The writer of the streams read by `BackwardBitReader`.
+#[derive(Debug, Default)]
+pub(crate) struct BackwardBitWriter {
+    buf: Vec<u8>,
+    acc: u64,
+    len: usize,
+}
+
+impl BackwardBitWriter {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes the lowest `len` bits of `value`, up to 56 bits.
+    pub(crate) fn write(&mut self, value: u64, len: usize) {
+        if len == 0 {
+            return;
+        }
+        self.acc |= (value & ((1 << len) - 1)) << self.len;
+        self.len += len;
+        while self.len >= 8 {
+            self.buf.push(self.acc as u8);
+            self.acc >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    /// Closes the stream with a 1 bit, and returns the bytes.
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        self.write(1, 1);
+        if self.len > 0 {
+            self.buf.push(self.acc as u8);
+        }
+        self.buf
+    }
+}
@@ -4,14 +4,14 @@
 //! This Source Code is subject to the terms of the Mozilla Public License
 //! version 2.0 (the "License"). You can obtain a copy of the License at
 //! <http://mozilla.org/MPL/2.0/>.
-#![cfg(any(feature = "lzhuf", feature = "bzip2", test))]
+#![cfg(any(feature = "lzhuf", feature = "bzip2", feature = "zstd", test))]
 
 use crate::bitio::direction::Direction;
 use crate::core::mem::size_of;
 use crate::core::ops::{Shl, Shr};
 use num_traits::Zero;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct Left;
 
 impl Direction for Left {
@@ -4,7 +4,16 @@
 //! This Source Code is subject to the terms of the Mozilla Public License
 //! version 2.0 (the "License"). You can obtain a copy of the License at
 //! <http://mozilla.org/MPL/2.0/>.
-#![cfg(any(feature = "deflate", test))]
+#![cfg(any(
+    feature = "brotli",
+    feature = "deflate",
+    feature = "lz4",
+    feature = "lzma",
+    feature = "lzw",
+    feature = "snappy",
+    feature = "zstd",
+    test
+))]
 
 use crate::bitio::direction::Direction;
 use crate::core::mem::size_of;
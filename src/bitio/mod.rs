@@ -5,6 +5,7 @@
 //! version 2.0 (the "License"). You can obtain a copy of the License at
 //! <http://mozilla.org/MPL/2.0/>.
 
+pub(crate) mod backward;
 pub(crate) mod direction;
 pub(crate) mod reader;
 pub(crate) mod small_bit_vec;
@@ -16,11 +16,15 @@ use crate::core::ops::{BitOrAssign, Shl, Shr};
 #[cfg(not(feature = "std"))]
 use alloc::borrow::ToOwned;
 #[cfg(not(feature = "std"))]
+use alloc::collections::vec_deque::VecDeque;
+#[cfg(not(feature = "std"))]
 use alloc::string::String;
 #[cfg(not(feature = "std"))]
 #[allow(unused_imports)]
 use alloc::{vec, vec::Vec};
 use num_traits::sign::Unsigned;
+#[cfg(feature = "std")]
+use std::collections::vec_deque::VecDeque;
 
 pub(crate) trait BitRead
 where
@@ -28,13 +32,14 @@ where
 {
     type Direction;
 
-    fn peek_bits<T: Unsigned, R: Iterator<Item = u8>>(
+    fn peek_bits<T, R: Iterator<Item = u8>>(
         &mut self,
         len: usize,
         iter: &mut R,
     ) -> Result<SmallBitVec<T>, String>
     where
-        T: BitOrAssign
+        T: Unsigned
+            + BitOrAssign
             + Shl<usize, Output = T>
             + Shr<usize, Output = T>
             + From<u8>;
@@ -44,39 +49,68 @@ where
         len: usize,
         iter: &mut R,
     ) -> Result<usize, String>;
-    fn read_bits<T: Unsigned, R: Iterator<Item = u8>>(
+    fn read_bits<T, R: Iterator<Item = u8>>(
         &mut self,
         len: usize,
         iter: &mut R,
     ) -> Result<SmallBitVec<T>, String>
     where
-        T: BitOrAssign
+        T: Unsigned
+            + BitOrAssign
             + Shl<usize, Output = T>
             + Shr<usize, Output = T>
             + From<u8>;
 
     fn skip_to_next_byte(&mut self) -> usize;
+
+    /// Returns the number of bits read from the input so far.
+    fn position(&self) -> u64;
+}
+
+/// The state of `BitReader` to roll back to.
+#[derive(Clone, Copy, Debug, Default)]
+struct Mark {
+    buf: u8,
+    counter: usize,
+    pos: usize,
+    consumed: u64,
 }
 
+/// A bit reader over an iterator of bytes.
+///
+/// In the partial mode the end of the iterator means that the input has
+/// not arrived yet: the reads fail and set `is_starved`, and `rollback`
+/// returns the reader to the last `commit`, keeping the bytes read since
+/// then to be read again.
 #[derive(Clone, Debug)]
 pub(crate) struct BitReader<D: Direction> {
     buf: u8,
     counter: usize,
     cbuf: CircularBuffer<u8>,
     pos: usize,
+    consumed: u64,
+    partial: bool,
+    starved: bool,
+    mark: Mark,
+    // マーク以降に読み込んだバイト列
+    journal: Vec<u8>,
+    // 巻き戻しにより再度読み込むバイト列
+    replay: VecDeque<u8>,
+    retry_len: usize,
     phantom: PhantomData<fn() -> D>,
 }
 
 impl<D: Direction> BitRead for BitReader<D> {
     type Direction = D;
 
-    fn peek_bits<T: Unsigned, R: Iterator<Item = u8>>(
+    fn peek_bits<T, R: Iterator<Item = u8>>(
         &mut self,
         len: usize,
         iter: &mut R,
     ) -> Result<SmallBitVec<T>, String>
     where
-        T: BitOrAssign
+        T: Unsigned
+            + BitOrAssign
             + Shl<usize, Output = T>
             + Shr<usize, Output = T>
             + From<u8>,
@@ -90,9 +124,10 @@ impl<D: Direction> BitRead for BitReader<D> {
                 if needlen + self.pos > self.buffer_cap() {
                     return Err("len is too long".to_owned());
                 }
-                let rbuf = iter.take(needlen - self.pos).collect::<Vec<u8>>();
+                let rbuf = self.pull(needlen - self.pos, iter)?;
                 self.cbuf.append(&rbuf);
                 self.pos += rbuf.len();
+                self.consumed += rbuf.len() as u64;
             }
             let mut ret = Self::conv_u8_to_t(self.buf);
             let mut count = self.counter;
@@ -135,9 +170,10 @@ impl<D: Direction> BitRead for BitReader<D> {
                 if needlen + self.pos > self.buffer_cap() {
                     return Err("len is too long".to_owned());
                 }
-                let rbuf = iter.take(needlen - self.pos).collect::<Vec<u8>>();
+                let rbuf = self.pull(needlen - self.pos, iter)?;
                 self.cbuf.append(&rbuf);
                 self.pos += rbuf.len();
+                self.consumed += rbuf.len() as u64;
             }
             if midlen == self.pos {
                 lastlen = 0;
@@ -159,13 +195,14 @@ impl<D: Direction> BitRead for BitReader<D> {
         }
     }
 
-    fn read_bits<T: Unsigned, R: Iterator<Item = u8>>(
+    fn read_bits<T, R: Iterator<Item = u8>>(
         &mut self,
         len: usize,
         iter: &mut R,
     ) -> Result<SmallBitVec<T>, String>
     where
-        T: BitOrAssign
+        T: Unsigned
+            + BitOrAssign
             + Shl<usize, Output = T>
             + Shr<usize, Output = T>
             + From<u8>,
@@ -183,11 +220,18 @@ impl<D: Direction> BitRead for BitReader<D> {
         self.counter = 0;
         len
     }
+
+    #[inline]
+    fn position(&self) -> u64 {
+        ((self.consumed - self.pos as u64) << 3) - self.counter as u64
+    }
 }
 
 const DEFAULT_BUF_SIZE: usize = 8; // u64まで対応可能
 
 impl<D: Direction> BitReader<D> {
+    const RETRY_MIN: usize = 0x400;
+
     #[inline]
     pub(crate) fn new() -> Self {
         Self::with_capacity(DEFAULT_BUF_SIZE)
@@ -200,19 +244,112 @@ impl<D: Direction> BitReader<D> {
             counter: 0,
             cbuf: CircularBuffer::<u8>::new(cap),
             pos: 0,
+            consumed: 0,
+            partial: false,
+            starved: false,
+            mark: Mark::default(),
+            journal: Vec::new(),
+            replay: VecDeque::new(),
+            retry_len: 0,
             phantom: PhantomData,
         }
     }
 
+    /// Reads `len` bytes, the bytes to be read again first.
+    fn pull<R: Iterator<Item = u8>>(
+        &mut self,
+        len: usize,
+        iter: &mut R,
+    ) -> Result<Vec<u8>, String> {
+        let replayed = cmp::min(len, self.replay.len());
+        let mut rbuf = self.replay.drain(..replayed).collect::<Vec<u8>>();
+        rbuf.extend(iter.take(len - replayed));
+        if self.partial {
+            self.journal.extend_from_slice(&rbuf);
+            if rbuf.len() < len {
+                self.starved = true;
+                return Err("need more input".to_owned());
+            }
+        }
+        Ok(rbuf)
+    }
+
+    /// Switches the partial mode, in which the input may continue after
+    /// the end of the iterator.
+    pub(crate) fn set_partial(&mut self, partial: bool) {
+        self.partial = partial;
+        self.starved = false;
+        self.journal.clear();
+        self.commit();
+    }
+
+    /// Whether a read has failed for the lack of the input in the
+    /// partial mode.
+    #[inline]
+    pub(crate) fn is_starved(&self) -> bool {
+        self.starved
+    }
+
+    /// Marks the current position as the one to roll back to.
+    pub(crate) fn commit(&mut self) {
+        if self.partial {
+            self.mark = Mark {
+                buf: self.buf,
+                counter: self.counter,
+                pos: self.pos,
+                consumed: self.consumed,
+            };
+            self.journal.clear();
+            let cbuf = &self.cbuf;
+            self.journal.extend((0..self.pos).rev().map(|i| cbuf[i]));
+        }
+    }
+
+    /// Returns to the last `commit`, so that the bytes read since then
+    /// are read again.
+    pub(crate) fn rollback(&mut self) {
+        for &b in self.journal.iter().rev() {
+            self.replay.push_front(b);
+        }
+        self.journal.clear();
+        self.buf = self.mark.buf;
+        self.counter = self.mark.counter;
+        self.consumed = self.mark.consumed - self.mark.pos as u64;
+        self.pos = 0;
+        self.starved = false;
+        // 大きな単位の読み込みは入力が倍になるまで再試行しない
+        self.retry_len = if self.replay.len() >= Self::RETRY_MIN {
+            self.replay.len() << 1
+        } else {
+            0
+        };
+    }
+
+    /// Keeps `input` to be read later if too little input has arrived
+    /// since the last `rollback` to retry, and returns whether it has
+    /// been kept.
+    pub(crate) fn defer(&mut self, input: &[u8]) -> bool {
+        if self.replay.len() + input.len() < self.retry_len {
+            self.replay.extend(input);
+            true
+        } else {
+            self.retry_len = 0;
+            false
+        }
+    }
+
     #[inline]
     pub(crate) fn buffer_cap(&self) -> usize {
         self.cbuf.cap() - self.pos
     }
 
     #[inline]
-    fn conv_u8_to_t<T: Unsigned>(value: u8) -> T
+    fn conv_u8_to_t<T>(value: u8) -> T
     where
-        T: Shl<usize, Output = T> + Shr<usize, Output = T> + From<u8>,
+        T: Unsigned
+            + Shl<usize, Output = T>
+            + Shr<usize, Output = T>
+            + From<u8>,
     {
         D::convert(T::from(value), size_of::<u8>() << 3, size_of::<T>() << 3)
     }
@@ -49,7 +49,17 @@ impl<T> SmallBitVec<T> {
 
 impl<T: Copy> SmallBitVec<T> {
     #[inline]
-    #[cfg(any(feature = "bzip2", feature = "deflate", feature = "lzhuf"))]
+    #[cfg(any(
+        feature = "brotli",
+        feature = "bzip2",
+        feature = "deflate",
+        feature = "lz4",
+        feature = "lzma",
+        feature = "lzhuf",
+        feature = "lzw",
+        feature = "snappy",
+        feature = "zstd"
+    ))]
     pub(crate) fn data(&self) -> T {
         self.data
     }
@@ -64,7 +74,7 @@ impl SmallBitVecReverse for SmallBitVec<u8> {
         let mut x = self.data;
         x = (x & 0x55) << 1 | (x & 0xAA) >> 1;
         x = (x & 0x33) << 2 | (x & 0xCC) >> 2;
-        x = x << 4 | x >> 4;
+        x = x.rotate_right(4);
         x >>= 8 - self.len;
         Self::new(x, self.len)
     }
@@ -76,7 +86,7 @@ impl SmallBitVecReverse for SmallBitVec<u16> {
         x = (x & 0x5555) << 1 | (x & 0xAAAA) >> 1;
         x = (x & 0x3333) << 2 | (x & 0xCCCC) >> 2;
         x = (x & 0x0F0F) << 4 | (x & 0xF0F0) >> 4;
-        x = x << 8 | x >> 8;
+        x = x.rotate_right(8);
         x >>= 16 - self.len;
         Self::new(x, self.len)
     }
@@ -89,7 +99,7 @@ impl SmallBitVecReverse for SmallBitVec<u32> {
         x = (x & 0x3333_3333) << 2 | (x & 0xCCCC_CCCC) >> 2;
         x = (x & 0x0F0F_0F0F) << 4 | (x & 0xF0F0_F0F0) >> 4;
         x = (x & 0x00FF_00FF) << 8 | (x & 0xFF00_FF00) >> 8;
-        x = x << 16 | x >> 16;
+        x = x.rotate_right(16);
         x >>= 32 - self.len;
         Self::new(x, self.len)
     }
@@ -104,7 +114,7 @@ impl SmallBitVecReverse for SmallBitVec<u64> {
         x = (x & 0x00FF_00FF_00FF_00FF) << 8 | (x & 0xFF00_FF00_FF00_FF00) >> 8;
         x = (x & 0x0000_FFFF_0000_FFFF) << 16
             | (x & 0xFFFF_FFFF_0000_0000) >> 16;
-        x = x << 32 | x >> 32;
+        x = x.rotate_right(32);
         x >>= 64 - self.len;
         Self::new(x, self.len)
     }
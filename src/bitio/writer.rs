@@ -5,9 +5,11 @@
 //! version 2.0 (the "License"). You can obtain a copy of the License at
 //! <http://mozilla.org/MPL/2.0/>.
 
+#[cfg(test)]
 use crate::action::Action;
 use crate::bitio::direction::Direction;
 use crate::bitio::small_bit_vec::SmallBitVec;
+#[cfg(test)]
 use crate::core::borrow::BorrowMut;
 use crate::core::marker::PhantomData;
 use crate::core::mem::size_of;
@@ -15,9 +17,12 @@ use crate::core::ops::{BitOr, Shl, Shr};
 #[cfg(not(feature = "std"))]
 #[allow(unused_imports)]
 use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
 use num_traits::sign::Unsigned;
 use num_traits::{cast, NumCast};
 
+#[cfg(test)]
 pub(crate) trait BitWriteExt<T, I>
 where
     T: Copy
@@ -36,6 +41,7 @@ where
     ) -> BitIterator<T, D, I, W>;
 }
 
+#[cfg(test)]
 impl<T, I> BitWriteExt<T, I::IntoIter> for I
 where
     T: Copy
@@ -60,6 +66,7 @@ where
     }
 }
 
+#[cfg(test)]
 pub(crate) struct BitIterator<T, D, I, W>
 where
     T: Copy
@@ -82,6 +89,7 @@ where
     phantom: PhantomData<fn() -> D>,
 }
 
+#[cfg(test)]
 impl<T, D, I, W> BitIterator<T, D, I, W>
 where
     T: Copy
@@ -108,6 +116,7 @@ where
     }
 }
 
+#[cfg(test)]
 impl<T, D, I, W> Iterator for BitIterator<T, D, I, W>
 where
     T: Copy
@@ -240,6 +249,61 @@ impl<D: Direction> BitWriter<D> {
             None
         }
     }
+
+    /// Writes `data`, and appends the completed bytes to `out`.
+    pub(crate) fn write_bytes<T>(
+        &mut self,
+        data: &SmallBitVec<T>,
+        out: &mut Vec<u8>,
+    ) where
+        T: Copy
+            + BitOr<Output = T>
+            + From<u8>
+            + Shl<usize, Output = T>
+            + Shr<usize, Output = T>
+            + Unsigned
+            + NumCast,
+    {
+        let (bits, len) = self.write_bits(data);
+        Self::push_bytes(bits, len, out);
+    }
+
+    /// Pads the incomplete byte with zeros, and appends it to `out`.
+    pub(crate) fn flush_bytes<T>(&mut self, out: &mut Vec<u8>)
+    where
+        T: Copy
+            + BitOr<Output = T>
+            + From<u8>
+            + Shl<usize, Output = T>
+            + Shr<usize, Output = T>
+            + Unsigned
+            + NumCast,
+    {
+        if let Some((bits, len)) = self.flush::<T>() {
+            Self::push_bytes(bits, len, out);
+        }
+    }
+
+    fn push_bytes<T>(mut bits: T, len: usize, out: &mut Vec<u8>)
+    where
+        T: Copy
+            + Shl<usize, Output = T>
+            + Shr<usize, Output = T>
+            + Unsigned
+            + NumCast,
+    {
+        for _ in 0..len {
+            out.push(
+                cast::<T, u8>(D::convert(
+                    bits,
+                    size_of::<T>() << 3,
+                    size_of::<u8>() << 3,
+                ))
+                .unwrap(),
+            );
+            bits = D::forward(bits, size_of::<u8>() << 3);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -289,7 +353,7 @@ mod tests {
         .to_bytes(&mut writer, Action::Run)
         .collect::<Vec<_>>();
         assert_eq!(ret.len(), 0);
-        let ret = vec![SmallBitVec::<u32>::default(); 0]
+        let ret = Vec::<SmallBitVec<u32>>::new()
             .to_bytes(&mut writer, Action::Flush)
             .collect::<Vec<_>>();
         assert_eq!(ret, vec![204]);
@@ -360,7 +424,7 @@ mod tests {
         .to_bytes(&mut writer, Action::Run)
         .collect::<Vec<_>>();
         assert_eq!(ret.len(), 0);
-        let ret = vec![SmallBitVec::<u32>::default(); 0]
+        let ret = Vec::<SmallBitVec<u32>>::new()
             .to_bytes(&mut writer, Action::Flush)
             .collect::<Vec<_>>();
         assert_eq!(ret, vec![0b0001_1101]);
@@ -377,7 +441,7 @@ mod tests {
         .to_bytes(&mut writer, Action::Run)
         .collect::<Vec<_>>();
         assert_eq!(ret.len(), 0);
-        let ret = vec![SmallBitVec::<u8>::default(); 0]
+        let ret = Vec::<SmallBitVec<u8>>::new()
             .to_bytes(&mut writer, Action::Flush)
             .collect::<Vec<_>>();
         assert_eq!(ret, vec![0b0001_1101]);
@@ -394,7 +458,7 @@ mod tests {
         .to_bytes(&mut writer, Action::Run)
         .collect::<Vec<_>>();
         assert_eq!(ret.len(), 0);
-        let ret = vec![SmallBitVec::<u16>::default(); 0]
+        let ret = Vec::<SmallBitVec<u16>>::new()
             .to_bytes(&mut writer, Action::Flush)
             .collect::<Vec<_>>();
         assert_eq!(ret, vec![0b0001_1101]);
@@ -411,7 +475,7 @@ mod tests {
         .to_bytes(&mut writer, Action::Run)
         .collect::<Vec<_>>();
         assert_eq!(ret.len(), 0);
-        let ret = vec![SmallBitVec::<u64>::default(); 0]
+        let ret = Vec::<SmallBitVec<u64>>::new()
             .to_bytes(&mut writer, Action::Flush)
             .collect::<Vec<_>>();
         assert_eq!(ret, vec![0b0001_1101]);
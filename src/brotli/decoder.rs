@@ -0,0 +1,835 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bitio::direction::right::Right;
+use crate::bitio::reader::{BitRead, BitReader};
+use crate::brotli::context::{
+    ContextMode, DISTANCE_CONTEXTS, LITERAL_CONTEXTS,
+};
+use crate::brotli::dictionary;
+use crate::brotli::prefix::PrefixCode;
+use crate::brotli::{
+    BLOCK_COUNT_ALPHABET_SIZE, BLOCK_COUNT_CODES, CATEGORIES, COMMAND,
+    COMMAND_ALPHABET_SIZE, COMMAND_CELLS, COPY_CODES, DISTANCE,
+    IMPLICIT_DISTANCE_COMMANDS, INITIAL_DISTANCES, INSERT_CODES, LITERAL,
+    LITERAL_ALPHABET_SIZE, MAX_DISTANCE_BITS, SHORT_DISTANCE_CODES, WINDOW_GAP,
+};
+use crate::core::cmp;
+use crate::error::CompressionError;
+use crate::status::Status;
+use crate::traits::decoder::{BitDecodeService, BitDecoderImpl, Decoder};
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The number of the decoded bytes which a step outputs at most.
+const OUT_CHUNK: usize = 0x1000;
+/// The differences of the distance codes 4 to 9 from the last distance,
+/// and of the codes 10 to 15 from the second last distance.
+const DISTANCE_DELTAS: [isize; 6] = [-1, 1, -2, 2, -3, 3];
+
+pub(crate) fn read_bits<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+    len: usize,
+) -> Result<u32, CompressionError> {
+    if len == 0 {
+        return Ok(0);
+    }
+    match reader.read_bits::<u32, _>(len, iter) {
+        Ok(b) if b.len() == len => Ok(b.data()),
+        _ => Err(CompressionError::UnexpectedEof),
+    }
+}
+
+fn at_end<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<bool, CompressionError> {
+    reader
+        .peek_bits::<u8, _>(8, iter)
+        .map(|b| b.is_empty())
+        .map_err(|_| CompressionError::UnexpectedEof)
+}
+
+/// Skips the bits to the byte boundary, which have to be zeros.
+fn skip_padding<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<(), CompressionError> {
+    let position = reader.position();
+    let len = (8 - (position & 7) as usize) & 7;
+    if read_bits(reader, iter, len)? != 0 {
+        return Err(CompressionError::InvalidBlock { position });
+    }
+    Ok(())
+}
+
+/// Reads the number `1..=256` of the block types or of the prefix codes
+/// of a context map.
+fn read_count<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<usize, CompressionError> {
+    if read_bits(reader, iter, 1)? == 0 {
+        return Ok(1);
+    }
+    let n = read_bits(reader, iter, 3)? as usize;
+    if n == 0 {
+        return Ok(2);
+    }
+    Ok((1 << n) + read_bits(reader, iter, n)? as usize + 1)
+}
+
+fn read_block_count<I: Iterator<Item = u8>>(
+    code: &mut PrefixCode,
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<usize, CompressionError> {
+    let (base, extra) = BLOCK_COUNT_CODES[code.decode(reader, iter)? as usize];
+    Ok((base + read_bits(reader, iter, extra)?) as usize)
+}
+
+fn inverse_mtf(map: &mut [u8]) {
+    let mut mtf = (0..=255).collect::<Vec<u8>>();
+    for v in map {
+        let i = usize::from(*v);
+        *v = mtf[i];
+        mtf[..=i].rotate_right(1);
+    }
+}
+
+/// Reads the context map of `len` contexts, and returns the number of
+/// the prefix codes and the map.
+fn read_context_map<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+    len: usize,
+) -> Result<(usize, Vec<u8>), CompressionError> {
+    let trees = read_count(reader, iter)?;
+    let mut map = vec![0; len];
+    if trees == 1 {
+        return Ok((trees, map));
+    }
+    let position = reader.position();
+    let rle_max = if read_bits(reader, iter, 1)? == 1 {
+        read_bits(reader, iter, 4)? as usize + 1
+    } else {
+        0
+    };
+    let mut code = PrefixCode::read(reader, iter, trees + rle_max)?;
+    let mut i = 0;
+    while i < len {
+        let s = code.decode(reader, iter)? as usize;
+        if s == 0 {
+            i += 1;
+        } else if s <= rle_max {
+            // ゼロの連続
+            let run = (1 << s) + read_bits(reader, iter, s)? as usize;
+            if i + run > len {
+                return Err(CompressionError::InvalidHuffmanTable { position });
+            }
+            i += run;
+        } else {
+            map[i] = (s - rle_max) as u8;
+            i += 1;
+        }
+    }
+    if read_bits(reader, iter, 1)? == 1 {
+        inverse_mtf(&mut map);
+    }
+    Ok((trees, map))
+}
+
+/// The ring buffer of the decoded data, whose bytes not output yet are
+/// the last `pending` bytes.
+#[derive(Debug)]
+struct Window {
+    buf: Vec<u8>,
+    size: usize,
+    pos: usize,
+    pending: usize,
+    total: u64,
+}
+
+impl Window {
+    fn new(size: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            size,
+            pos: 0,
+            pending: 0,
+            total: 0,
+        }
+    }
+
+    fn put(&mut self, b: u8) {
+        // 窓は使った分だけ確保する
+        if self.buf.len() < self.size {
+            self.buf.push(b);
+        } else {
+            self.buf[self.pos] = b;
+        }
+        self.pos += 1;
+        if self.pos == self.size {
+            self.pos = 0;
+        }
+        self.pending += 1;
+        self.total += 1;
+    }
+
+    /// Returns the byte `dist` bytes before the end.
+    fn get(&self, dist: usize) -> u8 {
+        if self.pos >= dist {
+            self.buf[self.pos - dist]
+        } else {
+            self.buf[self.pos + self.size - dist]
+        }
+    }
+
+    /// Returns the byte `dist` bytes before the end, or zero before the
+    /// start.
+    fn last(&self, dist: usize) -> u8 {
+        if self.total >= dist as u64 {
+            self.get(dist)
+        } else {
+            0
+        }
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.pending == 0 {
+            return None;
+        }
+        let b = self.get(self.pending);
+        self.pending -= 1;
+        Some(b)
+    }
+}
+
+/// The block types of a category in a meta-block.
+#[derive(Debug)]
+struct BlockSwitch {
+    types: usize,
+    /// The prefix codes of the block types and of the block counts.
+    codes: Option<(PrefixCode, PrefixCode)>,
+    current: usize,
+    prev: usize,
+    /// The number of the symbols of the current block left.
+    left: usize,
+}
+
+impl BlockSwitch {
+    fn new() -> Self {
+        Self {
+            types: 1,
+            codes: None,
+            current: 0,
+            prev: 1,
+            left: usize::MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The stream header of the window size.
+    Header,
+    MetaBlock,
+    /// The length of the metadata left.
+    Metadata(usize),
+    /// The length of the uncompressed data left.
+    Uncompressed(usize),
+    /// The block types of the category.
+    BlockTypes(usize),
+    ContextMaps,
+    /// The prefix codes of the literals, the insert-and-copy lengths and
+    /// the distances.
+    Trees,
+    Command,
+    /// The literals of the command left, the copy length, and whether the
+    /// distance is the last one without the distance code.
+    Literals {
+        insert: usize,
+        copy: usize,
+        implicit: bool,
+    },
+    Distance {
+        copy: usize,
+        implicit: bool,
+    },
+    Copy {
+        distance: usize,
+        len: usize,
+    },
+    MetaBlockEnd,
+    Finished,
+}
+
+#[derive(Debug)]
+pub(crate) struct BrotliDecoderBase {
+    state: State,
+    window: Window,
+    /// The maximum distance of the back references.
+    max_distance: usize,
+    last: bool,
+    /// The length of the meta-block left.
+    remaining: usize,
+    blocks: [BlockSwitch; CATEGORIES],
+    postfix_bits: usize,
+    direct_codes: usize,
+    modes: Vec<ContextMode>,
+    literal_map: Vec<u8>,
+    distance_map: Vec<u8>,
+    /// The number of the prefix codes of each category.
+    tree_counts: [usize; CATEGORIES],
+    trees: [Vec<PrefixCode>; CATEGORIES],
+    /// The last distances, the last one first.
+    distances: [usize; 4],
+}
+
+impl Default for BrotliDecoderBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BrotliDecoderBase {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: State::Header,
+            window: Window::new(0),
+            max_distance: 0,
+            last: false,
+            remaining: 0,
+            blocks: [
+                BlockSwitch::new(),
+                BlockSwitch::new(),
+                BlockSwitch::new(),
+            ],
+            postfix_bits: 0,
+            direct_codes: 0,
+            modes: Vec::new(),
+            literal_map: Vec::new(),
+            distance_map: Vec::new(),
+            tree_counts: [0; CATEGORIES],
+            trees: [Vec::new(), Vec::new(), Vec::new()],
+            distances: INITIAL_DISTANCES,
+        }
+    }
+
+    fn read_header<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        let window_bits = if read_bits(reader, iter, 1)? == 0 {
+            16
+        } else {
+            match read_bits(reader, iter, 3)? {
+                0 => match read_bits(reader, iter, 3)? {
+                    0 => 17,
+                    1 => {
+                        return Err(CompressionError::InvalidHeader {
+                            position,
+                        })
+                    }
+                    n => 8 + n,
+                },
+                n => 17 + n,
+            }
+        };
+        self.max_distance = (1 << window_bits) - WINDOW_GAP;
+        self.window = Window::new((1 << window_bits) + (OUT_CHUNK << 1));
+        self.state = State::MetaBlock;
+        Ok(())
+    }
+
+    fn read_meta_block_header<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        let invalid = CompressionError::InvalidHeader { position };
+        let last = read_bits(reader, iter, 1)? == 1;
+        if last && read_bits(reader, iter, 1)? == 1 {
+            self.last = true;
+            self.state = State::MetaBlockEnd;
+            return Ok(());
+        }
+        let nibbles = read_bits(reader, iter, 2)? as usize;
+        if nibbles == 3 {
+            if read_bits(reader, iter, 1)? != 0 {
+                return Err(invalid);
+            }
+            let bytes = read_bits(reader, iter, 2)? as usize;
+            let mut len = 0;
+            for i in 0..bytes {
+                let b = read_bits(reader, iter, 8)? as usize;
+                if i + 1 == bytes && bytes > 1 && b == 0 {
+                    return Err(invalid);
+                }
+                len |= b << (i << 3);
+            }
+            if bytes > 0 {
+                len += 1;
+            }
+            skip_padding(reader, iter)?;
+            self.last = last;
+            self.state = State::Metadata(len);
+            return Ok(());
+        }
+
+        let nibbles = nibbles + 4;
+        let mut len = 0;
+        for i in 0..nibbles {
+            let n = read_bits(reader, iter, 4)? as usize;
+            // 長さは最短のニブル数で書く
+            if i + 1 == nibbles && nibbles > 4 && n == 0 {
+                return Err(invalid);
+            }
+            len |= n << (i << 2);
+        }
+        let len = len + 1;
+        let uncompressed = !last && read_bits(reader, iter, 1)? == 1;
+        if uncompressed {
+            skip_padding(reader, iter)?;
+        }
+        self.last = last;
+        self.remaining = len;
+        self.state = if uncompressed {
+            State::Uncompressed(len)
+        } else {
+            State::BlockTypes(LITERAL)
+        };
+        Ok(())
+    }
+
+    fn read_block_types<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+        category: usize,
+    ) -> Result<(), CompressionError> {
+        let mut block = BlockSwitch::new();
+        block.types = read_count(reader, iter)?;
+        if block.types > 1 {
+            let type_code = PrefixCode::read(reader, iter, block.types + 2)?;
+            let mut count_code =
+                PrefixCode::read(reader, iter, BLOCK_COUNT_ALPHABET_SIZE)?;
+            block.left = read_block_count(&mut count_code, reader, iter)?;
+            block.codes = Some((type_code, count_code));
+        }
+        self.blocks[category] = block;
+        self.state = if category + 1 < CATEGORIES {
+            State::BlockTypes(category + 1)
+        } else {
+            State::ContextMaps
+        };
+        Ok(())
+    }
+
+    fn read_context_maps<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let postfix_bits = read_bits(reader, iter, 2)? as usize;
+        let direct_codes =
+            (read_bits(reader, iter, 4)? as usize) << postfix_bits;
+        let modes = (0..self.blocks[LITERAL].types)
+            .map(|_| read_bits(reader, iter, 2).map(ContextMode::from_bits))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (literal_trees, literal_map) =
+            read_context_map(reader, iter, modes.len() * LITERAL_CONTEXTS)?;
+        let (distance_trees, distance_map) = read_context_map(
+            reader,
+            iter,
+            self.blocks[DISTANCE].types * DISTANCE_CONTEXTS,
+        )?;
+        self.postfix_bits = postfix_bits;
+        self.direct_codes = direct_codes;
+        self.modes = modes;
+        self.literal_map = literal_map;
+        self.distance_map = distance_map;
+        self.tree_counts =
+            [literal_trees, self.blocks[COMMAND].types, distance_trees];
+        self.trees = [Vec::new(), Vec::new(), Vec::new()];
+        self.state = State::Trees;
+        Ok(())
+    }
+
+    /// Reads the next prefix code of the meta-block.
+    fn read_tree<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let alphabet_sizes = [
+            LITERAL_ALPHABET_SIZE,
+            COMMAND_ALPHABET_SIZE,
+            SHORT_DISTANCE_CODES
+                + self.direct_codes
+                + ((MAX_DISTANCE_BITS << 1) << self.postfix_bits),
+        ];
+        match (0..CATEGORIES)
+            .find(|&c| self.trees[c].len() < self.tree_counts[c])
+        {
+            Some(c) => {
+                let code = PrefixCode::read(reader, iter, alphabet_sizes[c])?;
+                self.trees[c].push(code);
+            }
+            None => self.state = State::Command,
+        }
+        Ok(())
+    }
+
+    /// Reads the block switch of the category whose block has ended.
+    fn switch_block<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+        category: usize,
+    ) -> Result<(), CompressionError> {
+        let block = &mut self.blocks[category];
+        let (type_code, count_code) = block.codes.as_mut().unwrap();
+        let block_type = match type_code.decode(reader, iter)? as usize {
+            0 => block.prev,
+            1 => (block.current + 1) % block.types,
+            t => t - 2,
+        };
+        block.left = read_block_count(count_code, reader, iter)?;
+        block.prev = block.current;
+        block.current = block_type;
+        reader.commit();
+        Ok(())
+    }
+
+    fn read_command<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        if self.blocks[COMMAND].left == 0 {
+            self.switch_block(reader, iter, COMMAND)?;
+        }
+        let position = reader.position();
+        let tree = self.blocks[COMMAND].current;
+        let symbol = self.trees[COMMAND][tree].decode(reader, iter)? as usize;
+        let (insert_code, copy_code) = COMMAND_CELLS[symbol >> 6];
+        let (base, extra) = INSERT_CODES[insert_code + ((symbol >> 3) & 7)];
+        let insert = (base + read_bits(reader, iter, extra)?) as usize;
+        let (base, extra) = COPY_CODES[copy_code + (symbol & 7)];
+        let copy = (base + read_bits(reader, iter, extra)?) as usize;
+        if insert > self.remaining {
+            return Err(CompressionError::InvalidBlock { position });
+        }
+        self.blocks[COMMAND].left -= 1;
+        self.state = State::Literals {
+            insert,
+            copy,
+            implicit: symbol < IMPLICIT_DISTANCE_COMMANDS,
+        };
+        Ok(())
+    }
+
+    fn read_literals<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+        mut insert: usize,
+        copy: usize,
+        implicit: bool,
+    ) -> Result<(), CompressionError> {
+        while insert > 0 && self.window.pending < OUT_CHUNK {
+            if self.blocks[LITERAL].left == 0 {
+                self.switch_block(reader, iter, LITERAL)?;
+            }
+            let block = self.blocks[LITERAL].current;
+            let context = self.modes[block]
+                .context(self.window.last(1), self.window.last(2));
+            let tree = self.literal_map[block * LITERAL_CONTEXTS + context];
+            let b =
+                self.trees[LITERAL][usize::from(tree)].decode(reader, iter)?;
+            self.window.put(b as u8);
+            self.blocks[LITERAL].left -= 1;
+            self.remaining -= 1;
+            insert -= 1;
+            self.state = State::Literals {
+                insert,
+                copy,
+                implicit,
+            };
+            // 1バイトずつ復号を確定する
+            reader.commit();
+        }
+        if insert == 0 {
+            // メタブロックの末尾のコピー長は使わない
+            self.state = if self.remaining == 0 {
+                State::MetaBlockEnd
+            } else {
+                State::Distance { copy, implicit }
+            };
+        }
+        Ok(())
+    }
+
+    /// Returns the distance of the distance code, reading its extra bits.
+    fn distance<I: Iterator<Item = u8>>(
+        &self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+        code: usize,
+        position: u64,
+    ) -> Result<usize, CompressionError> {
+        if code < SHORT_DISTANCE_CODES {
+            let d = &self.distances;
+            let (base, delta) = match code {
+                0..=3 => (d[code], 0),
+                4..=9 => (d[0], DISTANCE_DELTAS[code - 4]),
+                _ => (d[1], DISTANCE_DELTAS[code - 10]),
+            };
+            let distance = base as isize + delta;
+            if distance <= 0 {
+                return Err(CompressionError::InvalidDistance {
+                    distance: 0,
+                    position,
+                });
+            }
+            return Ok(distance as usize);
+        }
+        let code = code - SHORT_DISTANCE_CODES;
+        if code < self.direct_codes {
+            return Ok(code + 1);
+        }
+        let x = code - self.direct_codes;
+        let bits = 1 + (x >> (self.postfix_bits + 1));
+        let high = (x >> self.postfix_bits) & 1;
+        let low = x & ((1 << self.postfix_bits) - 1);
+        let offset = ((2 + high) << bits) - 4;
+        let extra = read_bits(reader, iter, bits)? as usize;
+        Ok(((offset + extra) << self.postfix_bits)
+            + low
+            + self.direct_codes
+            + 1)
+    }
+
+    fn read_distance<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+        copy: usize,
+        implicit: bool,
+    ) -> Result<(), CompressionError> {
+        let code = if implicit {
+            0
+        } else {
+            if self.blocks[DISTANCE].left == 0 {
+                self.switch_block(reader, iter, DISTANCE)?;
+            }
+            let block = self.blocks[DISTANCE].current;
+            let context = cmp::min(copy, 5) - 2;
+            let tree = self.distance_map[block * DISTANCE_CONTEXTS + context];
+            self.trees[DISTANCE][usize::from(tree)].decode(reader, iter)?
+                as usize
+        };
+        let position = reader.position();
+        let distance = self.distance(reader, iter, code, position)?;
+        if !implicit {
+            self.blocks[DISTANCE].left -= 1;
+        }
+
+        let max =
+            cmp::min(self.max_distance as u64, self.window.total) as usize;
+        if distance > max {
+            // 窓より遠い距離は静的辞書の単語
+            let word = dictionary::word(copy, distance - max - 1).ok_or(
+                CompressionError::InvalidDistance { distance, position },
+            )?;
+            if word.len() > self.remaining {
+                return Err(CompressionError::InvalidBlock { position });
+            }
+            for &b in &word {
+                self.window.put(b);
+            }
+            self.remaining -= word.len();
+            self.state = self.next_command();
+            return Ok(());
+        }
+        if code != 0 {
+            self.distances = [
+                distance,
+                self.distances[0],
+                self.distances[1],
+                self.distances[2],
+            ];
+        }
+        if copy > self.remaining {
+            return Err(CompressionError::InvalidBlock { position });
+        }
+        self.state = State::Copy {
+            distance,
+            len: copy,
+        };
+        Ok(())
+    }
+
+    fn next_command(&self) -> State {
+        if self.remaining == 0 {
+            State::MetaBlockEnd
+        } else {
+            State::Command
+        }
+    }
+
+    fn end_meta_block<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        if !self.last {
+            self.state = State::MetaBlock;
+            return Ok(());
+        }
+        skip_padding(reader, iter)?;
+        let position = reader.position();
+        if !at_end(reader, iter)? {
+            return Err(CompressionError::TrailingGarbage { position });
+        }
+        self.state = State::Finished;
+        Ok(())
+    }
+}
+
+impl BitDecodeService for BrotliDecoderBase {
+    type Direction = Right;
+    type Error = CompressionError;
+    type Output = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Self::Direction>,
+        iter: &mut I,
+    ) -> Result<Option<u8>, Self::Error> {
+        loop {
+            if let Some(b) = self.window.pop() {
+                return Ok(Some(b));
+            }
+            reader.commit();
+            match self.state {
+                State::Header => self.read_header(reader, iter)?,
+                State::MetaBlock => {
+                    self.read_meta_block_header(reader, iter)?
+                }
+                State::Metadata(len) => {
+                    let n = cmp::min(len, OUT_CHUNK);
+                    for _ in 0..n {
+                        let _ = read_bits(reader, iter, 8)?;
+                    }
+                    self.state = if n < len {
+                        State::Metadata(len - n)
+                    } else {
+                        State::MetaBlockEnd
+                    };
+                }
+                State::Uncompressed(len) => {
+                    let n = cmp::min(len, OUT_CHUNK);
+                    let data = (0..n)
+                        .map(|_| read_bits(reader, iter, 8).map(|b| b as u8))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    for b in data {
+                        self.window.put(b);
+                    }
+                    self.state = if n < len {
+                        State::Uncompressed(len - n)
+                    } else {
+                        State::MetaBlockEnd
+                    };
+                }
+                State::BlockTypes(category) => {
+                    self.read_block_types(reader, iter, category)?
+                }
+                State::ContextMaps => self.read_context_maps(reader, iter)?,
+                State::Trees => self.read_tree(reader, iter)?,
+                State::Command => self.read_command(reader, iter)?,
+                State::Literals {
+                    insert,
+                    copy,
+                    implicit,
+                } => {
+                    self.read_literals(reader, iter, insert, copy, implicit)?
+                }
+                State::Distance { copy, implicit } => {
+                    self.read_distance(reader, iter, copy, implicit)?
+                }
+                State::Copy { distance, len } => {
+                    let n = cmp::min(len, OUT_CHUNK);
+                    for _ in 0..n {
+                        let b = self.window.get(distance);
+                        self.window.put(b);
+                    }
+                    self.remaining -= n;
+                    self.state = if n < len {
+                        State::Copy {
+                            distance,
+                            len: len - n,
+                        }
+                    } else {
+                        self.next_command()
+                    };
+                }
+                State::MetaBlockEnd => self.end_meta_block(reader, iter)?,
+                State::Finished => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The Brotli decoder.
+#[derive(Debug)]
+pub struct BrotliDecoder {
+    inner: BitDecoderImpl<BrotliDecoderBase>,
+}
+
+impl BrotliDecoder {
+    pub fn new() -> Self {
+        Self {
+            inner: BitDecoderImpl::<BrotliDecoderBase>::new(),
+        }
+    }
+}
+
+impl Default for BrotliDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for BrotliDecoder {
+    type Input = u8;
+    type Output = u8;
+    type Error = CompressionError;
+
+    fn next<I: Iterator<Item = Self::Input>>(
+        &mut self,
+        iter: &mut I,
+    ) -> Option<Result<Self::Output, Self::Error>> {
+        self.inner.next(iter)
+    }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        self.inner.decompress(input, output, finish)
+    }
+}
@@ -0,0 +1,223 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The static dictionary of RFC 7932, appendix A.
+static DICTIONARY: &[u8] = include_bytes!("dictionary.bin");
+
+pub(crate) const MIN_WORD_LEN: usize = 4;
+pub(crate) const MAX_WORD_LEN: usize = 24;
+
+/// The offsets of the words of each length in the dictionary.
+const OFFSETS: [usize; MAX_WORD_LEN + 1] = [
+    0, 0, 0, 0, 0, 4096, 9216, 21504, 35840, 44032, 53248, 63488, 74752, 87040,
+    93696, 100864, 104704, 106752, 108928, 113536, 115968, 118528, 119872,
+    121280, 122016,
+];
+
+/// The base two logarithms of the number of the words of each length.
+const SIZE_BITS: [usize; MAX_WORD_LEN + 1] = [
+    0, 0, 0, 0, 10, 10, 11, 11, 10, 10, 10, 10, 10, 9, 9, 8, 7, 7, 8, 7, 7, 6,
+    6, 5, 5,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transform {
+    Identity,
+    OmitLast(usize),
+    UppercaseFirst,
+    UppercaseAll,
+    OmitFirst(usize),
+}
+
+use self::Transform::*;
+
+/// The prefixes, the transforms and the suffixes of RFC 7932, appendix B.
+const TRANSFORMS: [(&[u8], Transform, &[u8]); 121] = [
+    (b"", Identity, b""),
+    (b"", Identity, b" "),
+    (b" ", Identity, b" "),
+    (b"", OmitFirst(1), b""),
+    (b"", UppercaseFirst, b" "),
+    (b"", Identity, b" the "),
+    (b" ", Identity, b""),
+    (b"s ", Identity, b" "),
+    (b"", Identity, b" of "),
+    (b"", UppercaseFirst, b""),
+    (b"", Identity, b" and "),
+    (b"", OmitFirst(2), b""),
+    (b"", OmitLast(1), b""),
+    (b", ", Identity, b" "),
+    (b"", Identity, b", "),
+    (b" ", UppercaseFirst, b" "),
+    (b"", Identity, b" in "),
+    (b"", Identity, b" to "),
+    (b"e ", Identity, b" "),
+    (b"", Identity, b"\""),
+    (b"", Identity, b"."),
+    (b"", Identity, b"\">"),
+    (b"", Identity, b"\n"),
+    (b"", OmitLast(3), b""),
+    (b"", Identity, b"]"),
+    (b"", Identity, b" for "),
+    (b"", OmitFirst(3), b""),
+    (b"", OmitLast(2), b""),
+    (b"", Identity, b" a "),
+    (b"", Identity, b" that "),
+    (b" ", UppercaseFirst, b""),
+    (b"", Identity, b". "),
+    (b".", Identity, b""),
+    (b" ", Identity, b", "),
+    (b"", OmitFirst(4), b""),
+    (b"", Identity, b" with "),
+    (b"", Identity, b"'"),
+    (b"", Identity, b" from "),
+    (b"", Identity, b" by "),
+    (b"", OmitFirst(5), b""),
+    (b"", OmitFirst(6), b""),
+    (b" the ", Identity, b""),
+    (b"", OmitLast(4), b""),
+    (b"", Identity, b". The "),
+    (b"", UppercaseAll, b""),
+    (b"", Identity, b" on "),
+    (b"", Identity, b" as "),
+    (b"", Identity, b" is "),
+    (b"", OmitLast(7), b""),
+    (b"", OmitLast(1), b"ing "),
+    (b"", Identity, b"\n\t"),
+    (b"", Identity, b":"),
+    (b" ", Identity, b". "),
+    (b"", Identity, b"ed "),
+    (b"", OmitFirst(9), b""),
+    (b"", OmitFirst(7), b""),
+    (b"", OmitLast(6), b""),
+    (b"", Identity, b"("),
+    (b"", UppercaseFirst, b", "),
+    (b"", OmitLast(8), b""),
+    (b"", Identity, b" at "),
+    (b"", Identity, b"ly "),
+    (b" the ", Identity, b" of "),
+    (b"", OmitLast(5), b""),
+    (b"", OmitLast(9), b""),
+    (b" ", UppercaseFirst, b", "),
+    (b"", UppercaseFirst, b"\""),
+    (b".", Identity, b"("),
+    (b"", UppercaseAll, b" "),
+    (b"", UppercaseFirst, b"\">"),
+    (b"", Identity, b"=\""),
+    (b" ", Identity, b"."),
+    (b".com/", Identity, b""),
+    (b" the ", Identity, b" of the "),
+    (b"", UppercaseFirst, b"'"),
+    (b"", Identity, b". This "),
+    (b"", Identity, b","),
+    (b".", Identity, b" "),
+    (b"", UppercaseFirst, b"("),
+    (b"", UppercaseFirst, b"."),
+    (b"", Identity, b" not "),
+    (b" ", Identity, b"=\""),
+    (b"", Identity, b"er "),
+    (b" ", UppercaseAll, b" "),
+    (b"", Identity, b"al "),
+    (b" ", UppercaseAll, b""),
+    (b"", Identity, b"='"),
+    (b"", UppercaseAll, b"\""),
+    (b"", UppercaseFirst, b". "),
+    (b" ", Identity, b"("),
+    (b"", Identity, b"ful "),
+    (b" ", UppercaseFirst, b". "),
+    (b"", Identity, b"ive "),
+    (b"", Identity, b"less "),
+    (b"", UppercaseAll, b"'"),
+    (b"", Identity, b"est "),
+    (b" ", UppercaseFirst, b"."),
+    (b"", UppercaseAll, b"\">"),
+    (b" ", Identity, b"='"),
+    (b"", UppercaseFirst, b","),
+    (b"", Identity, b"ize "),
+    (b"", UppercaseAll, b"."),
+    (b"\xC2\xA0", Identity, b""),
+    (b" ", Identity, b","),
+    (b"", UppercaseFirst, b"=\""),
+    (b"", UppercaseAll, b"=\""),
+    (b"", Identity, b"ous "),
+    (b"", UppercaseAll, b", "),
+    (b"", UppercaseFirst, b"='"),
+    (b" ", UppercaseFirst, b","),
+    (b" ", UppercaseAll, b"=\""),
+    (b" ", UppercaseAll, b", "),
+    (b"", UppercaseAll, b","),
+    (b"", UppercaseAll, b"("),
+    (b"", UppercaseAll, b". "),
+    (b" ", UppercaseAll, b"."),
+    (b"", UppercaseAll, b"='"),
+    (b" ", UppercaseAll, b". "),
+    (b" ", UppercaseFirst, b"=\""),
+    (b" ", UppercaseAll, b"='"),
+    (b" ", UppercaseFirst, b"='"),
+];
+
+/// Turns the character at the start of `word` to upper case in the way of
+/// RFC 7932, and returns its length.
+fn ferment(word: &mut [u8]) -> usize {
+    match word[0] {
+        b'a'..=b'z' => {
+            word[0] ^= 0x20;
+            1
+        }
+        0x00..=0xBF => 1,
+        0xC0..=0xDF => {
+            if let Some(b) = word.get_mut(1) {
+                *b ^= 0x20;
+            }
+            2
+        }
+        _ => {
+            if let Some(b) = word.get_mut(2) {
+                *b ^= 0x05;
+            }
+            3
+        }
+    }
+}
+
+/// Returns the transformed word of `len` bytes referred by `word_id`, or
+/// `None` if it is not in the dictionary.
+pub(crate) fn word(len: usize, word_id: usize) -> Option<Vec<u8>> {
+    if !(MIN_WORD_LEN..=MAX_WORD_LEN).contains(&len) {
+        return None;
+    }
+    let bits = SIZE_BITS[len];
+    let index = word_id & ((1 << bits) - 1);
+    let (prefix, transform, suffix) = *TRANSFORMS.get(word_id >> bits)?;
+    let start = OFFSETS[len] + index * len;
+    let base = &DICTIONARY[start..start + len];
+
+    let mut out = prefix.to_vec();
+    let word_start = out.len();
+    match transform {
+        OmitFirst(n) => out.extend_from_slice(&base[n.min(len)..]),
+        OmitLast(n) => out.extend_from_slice(&base[..len.saturating_sub(n)]),
+        _ => out.extend_from_slice(base),
+    }
+    match transform {
+        UppercaseFirst if out.len() > word_start => {
+            let _ = ferment(&mut out[word_start..]);
+        }
+        UppercaseAll => {
+            let mut i = word_start;
+            while i < out.len() {
+                i += ferment(&mut out[i..]);
+            }
+        }
+        _ => {}
+    }
+    out.extend_from_slice(suffix);
+    Some(out)
+}
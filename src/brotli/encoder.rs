@@ -0,0 +1,622 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::action::Action;
+use crate::brotli::context::{ContextMode, LITERAL_CONTEXTS};
+use crate::brotli::options::BrotliOptions;
+use crate::brotli::prefix::{BitSink, PrefixEncoder};
+use crate::brotli::{
+    code_of, CATEGORIES, COMMAND_ALPHABET_SIZE, COMMAND_CELLS, COPY_CODES,
+    INITIAL_DISTANCES, INSERT_CODES, LITERAL_ALPHABET_SIZE, MAX_DISTANCE_BITS,
+    SHORT_DISTANCE_CODES,
+};
+use crate::core::cmp::{self, Ordering};
+use crate::error::CompressionError;
+use crate::lzss::encoder::LzssEncoder;
+use crate::lzss::LzssCode;
+use crate::traits::encoder::Encoder;
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = 0x1000;
+const META_BLOCK_SIZE: usize = 1 << 18;
+const DISTANCE_ALPHABET_SIZE: usize =
+    SHORT_DISTANCE_CODES + (MAX_DISTANCE_BITS << 1);
+/// The context mode of the literals, which suits texts.
+const CONTEXT_MODE: ContextMode = ContextMode::Utf8;
+/// The literal contexts of a group share a prefix code, which is the
+/// context by the last byte in the UTF-8 mode.
+const CONTEXT_GROUP_BITS: usize = 2;
+/// The estimated length of the prefix code of a symbol in 1/256 bits.
+const SYMBOL_COST: usize = 5 << 8;
+
+fn lzss_comparison(lhs: LzssCode, rhs: LzssCode) -> Ordering {
+    match (lhs, rhs) {
+        (
+            LzssCode::Reference {
+                len: llen,
+                pos: lpos,
+            },
+            LzssCode::Reference {
+                len: rlen,
+                pos: rpos,
+            },
+        ) => {
+            // 1バイトの長さは距離の1ビットに勝る
+            ((rlen << 3) + rpos.leading_zeros() as usize)
+                .cmp(&((llen << 3) + lpos.leading_zeros() as usize))
+        }
+        (LzssCode::Symbol(_), LzssCode::Symbol(_)) => Ordering::Equal,
+        (_, LzssCode::Symbol(_)) => Ordering::Greater,
+        (LzssCode::Symbol(_), _) => Ordering::Less,
+    }
+}
+
+type BrotliLzssEncoder = LzssEncoder<fn(LzssCode, LzssCode) -> Ordering>;
+
+fn highbit(n: usize) -> usize {
+    (63 - (n as u64).leading_zeros()) as usize
+}
+
+/// Returns the estimated length of the symbols of `freq` coded with their
+/// own prefix code in 1/256 bits.
+fn histogram_cost(freq: &[usize]) -> usize {
+    let log2 = |n: usize| {
+        let hb = highbit(n);
+        (hb << 8) + ((n << 8) >> hb) - 256
+    };
+    let total = freq.iter().sum::<usize>();
+    if total == 0 {
+        return 0;
+    }
+    freq.iter()
+        .filter(|&&f| f > 0)
+        .map(|&f| f * (log2(total) - log2(f)) + SYMBOL_COST)
+        .sum()
+}
+
+/// Writes the number `1..=256` of the block types or of the prefix codes
+/// of a context map.
+fn write_count(sink: &mut BitSink, count: usize) {
+    if count == 1 {
+        sink.write(0, 1);
+        return;
+    }
+    sink.write(1, 1);
+    if count == 2 {
+        sink.write(0, 3);
+        return;
+    }
+    let n = highbit(count - 1);
+    sink.write(n as u32, 3);
+    sink.write((count - 1 - (1 << n)) as u32, n);
+}
+
+/// Groups the literal contexts into at most `limit` clusters while it
+/// saves bits, and returns the number of the clusters and the context map.
+fn cluster_literals(
+    histograms: &[Vec<usize>],
+    limit: usize,
+) -> (usize, Vec<u8>) {
+    let groups = LITERAL_CONTEXTS >> CONTEXT_GROUP_BITS;
+    let mut clusters = (0..groups)
+        .map(|g| {
+            let mut freq = vec![0; LITERAL_ALPHABET_SIZE];
+            for h in &histograms[g << CONTEXT_GROUP_BITS..]
+                [..1 << CONTEXT_GROUP_BITS]
+            {
+                for (f, &n) in freq.iter_mut().zip(h) {
+                    *f += n;
+                }
+            }
+            (vec![g], histogram_cost(&freq), freq)
+        })
+        .filter(|c| c.1 > 0)
+        .collect::<Vec<_>>();
+
+    // 最もビットの増えない組を併合する
+    while clusters.len() > 1 {
+        let mut best: Option<(isize, usize, usize, usize, Vec<usize>)> = None;
+        for i in 0..clusters.len() {
+            for j in i + 1..clusters.len() {
+                let freq = clusters[i]
+                    .2
+                    .iter()
+                    .zip(&clusters[j].2)
+                    .map(|(a, b)| a + b)
+                    .collect::<Vec<_>>();
+                let cost = histogram_cost(&freq);
+                let delta =
+                    cost as isize - (clusters[i].1 + clusters[j].1) as isize;
+                if !matches!(best, Some((d, ..)) if d <= delta) {
+                    best = Some((delta, i, j, cost, freq));
+                }
+            }
+        }
+        let (delta, i, j, cost, freq) = best.unwrap();
+        if delta > 0 && clusters.len() <= limit {
+            break;
+        }
+        let (groups, _, _) = clusters.swap_remove(j);
+        clusters[i].0.extend(groups);
+        clusters[i].1 = cost;
+        clusters[i].2 = freq;
+    }
+
+    let mut map = vec![0; LITERAL_CONTEXTS];
+    for (c, (groups, _, _)) in clusters.iter().enumerate() {
+        for &g in groups {
+            for m in
+                &mut map[g << CONTEXT_GROUP_BITS..][..1 << CONTEXT_GROUP_BITS]
+            {
+                *m = c as u8;
+            }
+        }
+    }
+    (cmp::max(clusters.len(), 1), map)
+}
+
+/// Writes the context map of `trees` prefix codes without the run length
+/// codes of zeros.
+fn write_context_map(sink: &mut BitSink, trees: usize, map: &[u8]) {
+    write_count(sink, trees);
+    if trees == 1 {
+        return;
+    }
+    // RLEMAX
+    sink.write(0, 1);
+    let mut freq = vec![0; trees];
+    for &m in map {
+        freq[usize::from(m)] += 1;
+    }
+    let code = PrefixEncoder::new(&freq, trees);
+    code.write_description(sink);
+    for &m in map {
+        code.write(sink, usize::from(m));
+    }
+    // IMTF
+    sink.write(0, 1);
+}
+
+/// Returns the prefix code and the extra bits of a length, as the value
+/// and the length of the bits.
+fn length_code(codes: &[(u32, usize)], value: usize) -> (usize, u32, usize) {
+    let code = code_of(codes, value);
+    let (base, extra) = codes[code];
+    (code, value as u32 - base, extra)
+}
+
+/// The command of the literals to insert and the back reference after
+/// them.
+#[derive(Clone, Copy, Debug)]
+struct Command {
+    insert: usize,
+    /// The copy length, which is zero after the last literals of the
+    /// meta-block.
+    copy: usize,
+    distance: usize,
+}
+
+/// The command with its codes.
+#[derive(Clone, Copy, Debug)]
+struct CommandCode {
+    symbol: usize,
+    insert_extra: (u32, usize),
+    copy_extra: (u32, usize),
+    /// The distance code and its extra bits, or `None` with the implicit
+    /// distance or without the distance.
+    distance: Option<(usize, u32, usize)>,
+}
+
+/// Returns the distance code and its extra bits of `distance`, which is
+/// one of the last distances if possible.
+fn distance_code(
+    distances: &[usize; 4],
+    distance: usize,
+) -> (usize, u32, usize) {
+    if let Some(code) = distances.iter().position(|&d| d == distance) {
+        return (code, 0, 0);
+    }
+    let v = distance + 3;
+    let bits = highbit(v) - 1;
+    let prefix = (v >> bits) & 1;
+    let code = SHORT_DISTANCE_CODES + ((bits - 1) << 1) + prefix;
+    (code, (v - ((2 + prefix) << bits)) as u32, bits)
+}
+
+/// The Brotli encoder, which writes a stream of compressed or uncompressed
+/// meta-blocks.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "brotli")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut BrotliEncoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     # #[cfg(feature = "brotli")]
+///     let decompressed = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut BrotliDecoder::new())
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BrotliEncoder {
+    lzss: BrotliLzssEncoder,
+    options: BrotliOptions,
+    sink: BitSink,
+    // 入力のうち、まだメタブロックに書いていない部分
+    input: Vec<u8>,
+    block_len: usize,
+    commands: Vec<Command>,
+    insert: usize,
+    /// The last distances, the last one first.
+    distances: [usize; 4],
+    /// The last byte and the second last byte of the meta-blocks written.
+    last: (u8, u8),
+    out: Vec<u8>,
+    out_pos: usize,
+    header_written: bool,
+    finished: bool,
+}
+
+impl Default for BrotliEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BrotliEncoder {
+    pub fn new() -> Self {
+        Self::with_options(&BrotliOptions::default())
+    }
+
+    pub fn with_options(options: &BrotliOptions) -> Self {
+        options.validate();
+        let mut lzss = LzssEncoder::new(
+            lzss_comparison as fn(LzssCode, LzssCode) -> Ordering,
+            options.window_size(),
+            MAX_MATCH,
+            MIN_MATCH,
+            cmp::max(options.lazy_level, 1),
+        );
+        lzss.set_search_limit(options.max_chain, options.nice_length);
+        Self {
+            lzss,
+            options: *options,
+            sink: BitSink::new(),
+            input: Vec::new(),
+            block_len: 0,
+            commands: Vec::new(),
+            insert: 0,
+            distances: INITIAL_DISTANCES,
+            last: (0, 0),
+            out: Vec::new(),
+            out_pos: 0,
+            header_written: false,
+            finished: false,
+        }
+    }
+
+    fn write_header(&mut self) {
+        match self.options.window_bits {
+            16 => self.sink.write(0, 1),
+            17 => self.sink.write(1, 7),
+            w if w > 17 => self.sink.write((((w - 17) << 1) | 1) as u32, 4),
+            w => self.sink.write((((w - 8) << 4) | 1) as u32, 7),
+        }
+    }
+
+    /// Writes ISLAST, MNIBBLES, MLEN-1 and ISUNCOMPRESSED.
+    fn write_meta_block_header(
+        sink: &mut BitSink,
+        len: usize,
+        uncompressed: bool,
+    ) {
+        let nibbles = cmp::max((highbit(cmp::max(len - 1, 1)) >> 2) + 1, 4);
+        sink.write(0, 1);
+        sink.write((nibbles - 4) as u32, 2);
+        sink.write((len - 1) as u32, nibbles << 2);
+        sink.write(u32::from(uncompressed), 1);
+    }
+
+    /// Returns the codes of the commands, updating the last distances.
+    fn command_codes(&mut self) -> Vec<CommandCode> {
+        let mut codes = Vec::with_capacity(self.commands.len());
+        for c in &self.commands {
+            let (insert_code, insert_extra, insert_bits) =
+                length_code(&INSERT_CODES, c.insert);
+            let (copy_code, copy_extra, copy_bits) =
+                length_code(&COPY_CODES, cmp::max(c.copy, 2));
+            let distance = if c.copy == 0 {
+                None
+            } else {
+                let code = distance_code(&self.distances, c.distance);
+                if code.0 != 0 {
+                    self.distances = [
+                        c.distance,
+                        self.distances[0],
+                        self.distances[1],
+                        self.distances[2],
+                    ];
+                }
+                Some(code)
+            };
+            // 直前の距離は距離の符号を省ける
+            let implicit = distance.map_or(0, |d| d.0) == 0
+                && insert_code < 8
+                && copy_code < 16;
+            let cell = if implicit {
+                copy_code >> 3
+            } else {
+                COMMAND_CELLS
+                    .iter()
+                    .skip(2)
+                    .position(|&(i, c)| {
+                        i == insert_code & !7 && c == copy_code & !7
+                    })
+                    .unwrap()
+                    + 2
+            };
+            codes.push(CommandCode {
+                symbol: (cell << 6)
+                    | ((insert_code & 7) << 3)
+                    | (copy_code & 7),
+                insert_extra: (insert_extra, insert_bits),
+                copy_extra: (copy_extra, copy_bits),
+                distance: if implicit { None } else { distance },
+            });
+        }
+        codes
+    }
+
+    /// Returns the contexts of the literals of the meta-block.
+    fn literal_contexts(&self) -> Vec<usize> {
+        let data = &self.input[..self.block_len];
+        let (mut p1, mut p2) = self.last;
+        let mut contexts = Vec::with_capacity(self.block_len);
+        let mut pos = 0;
+        for c in &self.commands {
+            for &b in &data[pos..pos + c.insert] {
+                contexts.push(CONTEXT_MODE.context(p1, p2));
+                p2 = p1;
+                p1 = b;
+            }
+            pos += c.insert + c.copy;
+            if c.copy > 0 {
+                p1 = data[pos - 1];
+                p2 = data[pos - 2];
+            }
+        }
+        contexts
+    }
+
+    /// Returns the compressed meta-block.
+    fn compress(&mut self) -> BitSink {
+        let codes = self.command_codes();
+        let contexts = self.literal_contexts();
+
+        let mut histograms =
+            vec![vec![0; LITERAL_ALPHABET_SIZE]; LITERAL_CONTEXTS];
+        let mut command_freq = vec![0; COMMAND_ALPHABET_SIZE];
+        let mut distance_freq = vec![0; DISTANCE_ALPHABET_SIZE];
+        let mut pos = 0;
+        let mut literal = 0;
+        for (c, code) in self.commands.iter().zip(&codes) {
+            for &b in &self.input[pos..pos + c.insert] {
+                histograms[contexts[literal]][usize::from(b)] += 1;
+                literal += 1;
+            }
+            pos += c.insert + c.copy;
+            command_freq[code.symbol] += 1;
+            if let Some((d, _, _)) = code.distance {
+                distance_freq[d] += 1;
+            }
+        }
+        let (trees, map) =
+            cluster_literals(&histograms, self.options.literal_trees);
+        let mut literal_freq = vec![vec![0; LITERAL_ALPHABET_SIZE]; trees];
+        for (h, &m) in histograms.iter().zip(&map) {
+            for (f, &n) in literal_freq[usize::from(m)].iter_mut().zip(h) {
+                *f += n;
+            }
+        }
+
+        let mut sink = BitSink::new();
+        Self::write_meta_block_header(&mut sink, self.block_len, false);
+        // 各カテゴリーのブロック種別は1つ
+        for _ in 0..CATEGORIES {
+            write_count(&mut sink, 1);
+        }
+        // NPOSTFIX, NDIRECT
+        sink.write(0, 6);
+        sink.write(CONTEXT_MODE.bits(), 2);
+        write_context_map(&mut sink, trees, &map);
+        write_context_map(&mut sink, 1, &[]);
+
+        let literal_codes = literal_freq
+            .iter()
+            .map(|f| PrefixEncoder::new(f, LITERAL_ALPHABET_SIZE))
+            .collect::<Vec<_>>();
+        let command_code =
+            PrefixEncoder::new(&command_freq, COMMAND_ALPHABET_SIZE);
+        let distance_code =
+            PrefixEncoder::new(&distance_freq, DISTANCE_ALPHABET_SIZE);
+        for code in &literal_codes {
+            code.write_description(&mut sink);
+        }
+        command_code.write_description(&mut sink);
+        distance_code.write_description(&mut sink);
+
+        let mut pos = 0;
+        let mut literal = 0;
+        for (c, code) in self.commands.iter().zip(&codes) {
+            command_code.write(&mut sink, code.symbol);
+            sink.write(code.insert_extra.0, code.insert_extra.1);
+            sink.write(code.copy_extra.0, code.copy_extra.1);
+            for &b in &self.input[pos..pos + c.insert] {
+                let tree = usize::from(map[contexts[literal]]);
+                literal_codes[tree].write(&mut sink, usize::from(b));
+                literal += 1;
+            }
+            pos += c.insert + c.copy;
+            if let Some((d, extra, bits)) = code.distance {
+                distance_code.write(&mut sink, d);
+                sink.write(extra, bits);
+            }
+        }
+        sink
+    }
+
+    fn write_meta_block(&mut self) {
+        let len = self.block_len;
+        if self.insert > 0 {
+            self.commands.push(Command {
+                insert: self.insert,
+                copy: 0,
+                distance: 0,
+            });
+        }
+
+        let distances = self.distances;
+        let compressed = self.compress();
+        let mut header = BitSink::new();
+        Self::write_meta_block_header(&mut header, len, true);
+        let start = self.sink.bits;
+        let uncompressed_bits =
+            ((start + header.bits + 7) & !7) - start + ((len as u64) << 3);
+        if compressed.bits < uncompressed_bits {
+            self.sink.append(compressed);
+        } else {
+            // 無圧縮のメタブロックは直前の距離を変えない
+            self.distances = distances;
+            self.sink.append(header);
+            self.sink.align();
+            for &b in &self.input[..len] {
+                self.sink.write(u32::from(b), 8);
+            }
+        }
+
+        let data = &self.input[..len];
+        self.last = match len {
+            1 => (data[0], self.last.0),
+            _ => (data[len - 1], data[len - 2]),
+        };
+        let _ = self.input.drain(..len);
+        self.commands.clear();
+        self.insert = 0;
+        self.block_len = 0;
+    }
+
+    fn push_code(&mut self, code: LzssCode) {
+        let len = match code {
+            LzssCode::Symbol(_) => 1,
+            LzssCode::Reference { len, .. } => len,
+        };
+        if self.block_len + len > META_BLOCK_SIZE {
+            self.write_meta_block();
+        }
+        match code {
+            LzssCode::Symbol(_) => self.insert += 1,
+            LzssCode::Reference { len, pos } => {
+                self.commands.push(Command {
+                    insert: self.insert,
+                    copy: len,
+                    distance: pos + 1,
+                });
+                self.insert = 0;
+            }
+        }
+        self.block_len += len;
+    }
+
+    /// Moves the complete bytes of the bits to the output.
+    fn drain_sink(&mut self) {
+        self.out.append(&mut self.sink.out);
+    }
+}
+
+impl Encoder for BrotliEncoder {
+    type Error = CompressionError;
+    type In = u8;
+    type Out = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<u8, CompressionError>> {
+        loop {
+            if self.out_pos < self.out.len() {
+                self.out_pos += 1;
+                return Some(Ok(self.out[self.out_pos - 1]));
+            }
+            self.out.clear();
+            self.out_pos = 0;
+            if self.finished {
+                return None;
+            }
+            if !self.header_written {
+                self.write_header();
+                self.header_written = true;
+                continue;
+            }
+
+            let input = &mut self.input;
+            let code = self
+                .lzss
+                .next(&mut iter.by_ref().inspect(|&b| input.push(b)), action);
+            match code {
+                Some(Ok(code)) => {
+                    self.push_code(code);
+                    self.drain_sink();
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => match action {
+                    Action::Run => return None,
+                    Action::Finish => {
+                        if self.block_len > 0 {
+                            self.write_meta_block();
+                        }
+                        // ISLAST, ISLASTEMPTY
+                        self.sink.write(3, 2);
+                        self.sink.align();
+                        self.drain_sink();
+                        self.finished = true;
+                    }
+                    _ => {
+                        if self.block_len == 0 && self.sink.is_aligned() {
+                            return None;
+                        }
+                        if self.block_len > 0 {
+                            self.write_meta_block();
+                        }
+                        if !self.sink.is_aligned() {
+                            // 空のメタデータでバイト境界に揃える
+                            self.sink.write(0x06, 6);
+                            self.sink.align();
+                        }
+                        self.drain_sink();
+                    }
+                },
+            }
+        }
+    }
+}
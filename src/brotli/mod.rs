@@ -0,0 +1,343 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "brotli")]
+
+pub(crate) mod context;
+pub(crate) mod decoder;
+pub(crate) mod dictionary;
+pub(crate) mod encoder;
+pub(crate) mod options;
+pub(crate) mod prefix;
+
+const MIN_WINDOW_BITS: usize = 10;
+const MAX_WINDOW_BITS: usize = 24;
+/// The number of the bytes at the end of the window which can not be
+/// referred.
+const WINDOW_GAP: usize = 16;
+
+/// The number of the categories of the block types, the literals, the
+/// insert-and-copy lengths and the distances.
+const CATEGORIES: usize = 3;
+const LITERAL: usize = 0;
+const COMMAND: usize = 1;
+const DISTANCE: usize = 2;
+
+const LITERAL_ALPHABET_SIZE: usize = 256;
+const COMMAND_ALPHABET_SIZE: usize = 704;
+const BLOCK_COUNT_ALPHABET_SIZE: usize = 26;
+/// The number of the distance codes referring to the last distances.
+const SHORT_DISTANCE_CODES: usize = 16;
+const MAX_DISTANCE_BITS: usize = 24;
+
+/// The base values and the numbers of the extra bits of the block counts.
+const BLOCK_COUNT_CODES: [(u32, usize); BLOCK_COUNT_ALPHABET_SIZE] = [
+    (1, 2),
+    (5, 2),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 3),
+    (41, 3),
+    (49, 4),
+    (65, 4),
+    (81, 4),
+    (97, 4),
+    (113, 5),
+    (145, 5),
+    (177, 5),
+    (209, 5),
+    (241, 6),
+    (305, 6),
+    (369, 7),
+    (497, 8),
+    (753, 9),
+    (1265, 10),
+    (2289, 11),
+    (4337, 12),
+    (8433, 13),
+    (16625, 24),
+];
+
+/// The base values and the numbers of the extra bits of the insert
+/// lengths.
+const INSERT_CODES: [(u32, usize); 24] = [
+    (0, 0),
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 1),
+    (8, 1),
+    (10, 2),
+    (14, 2),
+    (18, 3),
+    (26, 3),
+    (34, 4),
+    (50, 4),
+    (66, 5),
+    (98, 5),
+    (130, 6),
+    (194, 7),
+    (322, 8),
+    (578, 9),
+    (1090, 10),
+    (2114, 12),
+    (6210, 14),
+    (22594, 24),
+];
+
+/// The base values and the numbers of the extra bits of the copy
+/// lengths.
+const COPY_CODES: [(u32, usize); 24] = [
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 1),
+    (12, 1),
+    (14, 2),
+    (18, 2),
+    (22, 3),
+    (30, 3),
+    (38, 4),
+    (54, 4),
+    (70, 5),
+    (102, 5),
+    (134, 6),
+    (198, 7),
+    (326, 8),
+    (582, 9),
+    (1094, 10),
+    (2118, 24),
+];
+
+/// The first insert and copy length codes of the cells of 64
+/// insert-and-copy length codes. The first two cells use the last
+/// distance without the distance code.
+const COMMAND_CELLS: [(usize, usize); 11] = [
+    (0, 0),
+    (0, 8),
+    (0, 0),
+    (0, 8),
+    (8, 0),
+    (8, 8),
+    (0, 16),
+    (16, 0),
+    (8, 16),
+    (16, 8),
+    (16, 16),
+];
+/// The number of the insert-and-copy length codes with the implicit
+/// distance.
+const IMPLICIT_DISTANCE_COMMANDS: usize = 128;
+
+/// The last distances before any distance is decoded, the last one first.
+const INITIAL_DISTANCES: [usize; 4] = [4, 11, 15, 16];
+
+/// Returns the code of `value` in `codes`.
+fn code_of(codes: &[(u32, usize)], value: usize) -> usize {
+    codes
+        .iter()
+        .rposition(|&(base, _)| base as usize <= value)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::action::Action;
+    use crate::brotli::decoder::BrotliDecoder;
+    use crate::brotli::encoder::BrotliEncoder;
+    use crate::brotli::options::BrotliOptions;
+    use crate::error::CompressionError;
+    use crate::traits::decoder::DecodeExt;
+    use crate::traits::encoder::{EncodeExt, Encoder};
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    fn decode(encoded: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        encoded
+            .iter()
+            .cloned()
+            .decode(&mut BrotliDecoder::new())
+            .collect()
+    }
+
+    fn encode(data: &[u8], options: &BrotliOptions) -> Vec<u8> {
+        data.iter()
+            .cloned()
+            .encode(&mut BrotliEncoder::with_options(options), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    fn check_encode(data: &[u8], options: &BrotliOptions) -> usize {
+        let encoded = encode(data, options);
+        assert!(decode(&encoded).unwrap()[..] == data[..]);
+        encoded.len()
+    }
+
+    #[test]
+    fn test_samples() {
+        for &(encoded, expected) in &[
+            (
+                &include_bytes!("../../data/sample1.br")[..],
+                &include_bytes!("../../data/sample1.ref")[..],
+            ),
+            (
+                &include_bytes!("../../data/sample2.br")[..],
+                &include_bytes!("../../data/sample2.ref")[..],
+            ),
+            (
+                &include_bytes!("../../data/sample3.br")[..],
+                &include_bytes!("../../data/sample3.ref")[..],
+            ),
+        ] {
+            let decoded = decode(encoded).unwrap();
+            assert_eq!(decoded.len(), expected.len());
+            assert!(decoded[..] == expected[..]);
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        use crate::traits::decoder::tests::decompress_slices;
+
+        let encoded = include_bytes!("../../data/sample1.br");
+        let expected = include_bytes!("../../data/sample1.ref");
+        for &(chunk, out_len) in &[(1, 0x1000), (7, 1), (0x1000, 0x8000)] {
+            let decoded = decompress_slices(
+                &mut BrotliDecoder::new(),
+                encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded[..] == expected[..]);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let encoded = include_bytes!("../../data/sample3.br");
+        let len = encoded.len() as u64;
+        assert_eq!(decode(&[]), Err(CompressionError::UnexpectedEof));
+        assert_eq!(
+            decode(&encoded[..encoded.len() - 1]),
+            Err(CompressionError::UnexpectedEof)
+        );
+        let mut garbage = encoded.to_vec();
+        garbage.extend_from_slice(b"garbage");
+        assert_eq!(
+            decode(&garbage),
+            Err(CompressionError::TrailingGarbage { position: len << 3 })
+        );
+
+        // 予約された窓のサイズ
+        assert_eq!(
+            decode(&[0x11, 0x03]),
+            Err(CompressionError::InvalidHeader { position: 0 })
+        );
+        // 空のストリームと、予約ビットの立ったメタデータ
+        assert_eq!(decode(&[0x06]), Ok(Vec::new()));
+        assert_eq!(
+            decode(&[0x1C, 0x03]),
+            Err(CompressionError::InvalidHeader { position: 1 })
+        );
+
+        let mut corrupted = include_bytes!("../../data/sample1.br").to_vec();
+        let mid = corrupted.len() / 2;
+        corrupted[mid] ^= 0x55;
+        assert!(decode(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        let options = BrotliOptions::default();
+        check_encode(b"aabbaabbaabbaabb\n", &options);
+        check_encode(&[], &options);
+        check_encode(b"a", &options);
+        check_encode(&[0x55; 0x50_0000], &options);
+        let data = include_bytes!("../../data/sample2.ref");
+        let lens = [0, 5, 11]
+            .iter()
+            .map(|&quality| {
+                let options = BrotliOptions::new(quality);
+                check_encode(
+                    include_bytes!("../../data/sample4.ref"),
+                    &options,
+                );
+                check_encode(data, &options)
+            })
+            .collect::<Vec<_>>();
+        assert!(lens[2] < lens[0]);
+
+        for &window_bits in &[10, 16, 17, 24] {
+            let options = BrotliOptions {
+                window_bits,
+                ..BrotliOptions::new(2)
+            };
+            check_encode(data, &options);
+        }
+
+        // 圧縮できないデータは非圧縮のメタブロックになる
+        let mut x = 1u32;
+        let random = (0..0x2_0000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect::<Vec<_>>();
+        let len = check_encode(&random, &options);
+        assert!(len < random.len() + 16);
+    }
+
+    #[test]
+    fn test_encode_flush() {
+        let data = include_bytes!("../../data/sample1.ref");
+        let mut encoder = BrotliEncoder::new();
+        let mut encoded = Vec::new();
+        for (i, chunk) in data.chunks(0x1000).enumerate() {
+            let action = if i % 3 == 0 {
+                Action::Flush
+            } else {
+                Action::Run
+            };
+            let mut iter = chunk.iter().cloned();
+            while let Some(b) = encoder.next(&mut iter, action) {
+                encoded.push(b.unwrap());
+            }
+            if action == Action::Flush {
+                // 書き出したデータだけで、ここまでを復号できる
+                let end = i * 0x1000 + chunk.len();
+                let decoded = encoded
+                    .iter()
+                    .cloned()
+                    .decode(&mut BrotliDecoder::new())
+                    .take(end)
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                assert!(decoded[..] == data[..end]);
+            }
+        }
+        encoded.extend(
+            Vec::new()
+                .into_iter()
+                .encode(&mut encoder, Action::Finish)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+        );
+        assert!(decode(&encoded).unwrap()[..] == data[..]);
+    }
+}
@@ -0,0 +1,114 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::brotli::{MAX_WINDOW_BITS, MIN_WINDOW_BITS, WINDOW_GAP};
+
+/// The parameters of the Brotli encoder.
+///
+/// `BrotliOptions::new(quality)` fills in the search parameters for the
+/// quality `0..=11`; the fields may be tuned afterwards.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "brotli")]
+///     let options = BrotliOptions {
+///         window_bits: 16,
+///         ..BrotliOptions::new(9)
+///     };
+///     # #[cfg(feature = "brotli")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut BrotliEncoder::with_options(&options), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrotliOptions {
+    /// `0` is the fastest and `11` is the best compression.
+    pub quality: usize,
+    /// The base two logarithm of the window size, `10..=24`.
+    pub window_bits: usize,
+    /// The number of following positions searched for a longer match
+    /// before a match is emitted. `1` is greedy matching.
+    pub lazy_level: usize,
+    /// The maximum number of hash chain entries examined per search.
+    pub max_chain: usize,
+    /// The match length which stops the search.
+    pub nice_length: usize,
+    /// The maximum number of the prefix codes of the literals of a
+    /// meta-block, `1..=16`.
+    pub literal_trees: usize,
+}
+
+impl Default for BrotliOptions {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_QUALITY)
+    }
+}
+
+impl BrotliOptions {
+    const DEFAULT_QUALITY: usize = 6;
+    const MAX_QUALITY: usize = 11;
+    /// The number of the groups of the literal contexts which share a
+    /// prefix code.
+    pub(crate) const MAX_LITERAL_TREES: usize = 16;
+
+    // window_bits, lazy_level, max_chain, nice_length, literal_trees
+    const LEVEL_TABLE: [(usize, usize, usize, usize, usize); 12] = [
+        (18, 1, 4, 16, 1),
+        (18, 1, 8, 24, 1),
+        (19, 1, 16, 32, 1),
+        (20, 2, 16, 32, 1),
+        (21, 2, 32, 64, 2),
+        (22, 2, 64, 96, 4),
+        (22, 3, 128, 128, 4),
+        (22, 3, 256, 192, 6),
+        (22, 3, 512, 256, 8),
+        (22, 4, 1024, 256, 8),
+        (22, 4, 2048, 512, 12),
+        (22, 4, 4096, 1024, 16),
+    ];
+
+    pub fn new(quality: usize) -> Self {
+        if quality > Self::MAX_QUALITY {
+            panic!("invalid quality");
+        }
+        let (window_bits, lazy_level, max_chain, nice_length, literal_trees) =
+            Self::LEVEL_TABLE[quality];
+        Self {
+            quality,
+            window_bits,
+            lazy_level,
+            max_chain,
+            nice_length,
+            literal_trees,
+        }
+    }
+
+    pub(crate) fn validate(&self) {
+        if self.quality > Self::MAX_QUALITY {
+            panic!("invalid quality");
+        }
+        if !(MIN_WINDOW_BITS..=MAX_WINDOW_BITS).contains(&self.window_bits) {
+            panic!("invalid window bits");
+        }
+        if !(1..=Self::MAX_LITERAL_TREES).contains(&self.literal_trees) {
+            panic!("invalid number of literal trees");
+        }
+    }
+
+    /// Returns the maximum distance of the back references.
+    pub(crate) fn window_size(&self) -> usize {
+        (1 << self.window_bits) - WINDOW_GAP
+    }
+}
@@ -0,0 +1,441 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bitio::direction::right::Right;
+use crate::bitio::reader::{BitRead, BitReader};
+use crate::bitio::small_bit_vec::SmallBitVec;
+use crate::bitio::writer::BitWriter;
+use crate::brotli::decoder::read_bits;
+use crate::error::CompressionError;
+use crate::huffman::cano_huff_table::make_table;
+use crate::huffman::decoder::HuffmanDecoder;
+use crate::huffman::encoder::HuffmanEncoder;
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+pub(crate) const MAX_CODE_LEN: usize = 15;
+/// The number of the symbols of the code of the code lengths.
+const CODE_LENGTH_CODES: usize = 18;
+/// The maximum length of the code of the code lengths.
+const MAX_CODE_LENGTH_CODE_LEN: usize = 5;
+const CODE_LENGTH_ORDER: [usize; CODE_LENGTH_CODES] =
+    [1, 2, 3, 4, 0, 5, 17, 6, 16, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+/// The code lengths of 8 which the repeat code 16 repeats first.
+const INITIAL_REPEAT_LEN: u8 = 8;
+const REPEAT_PREVIOUS: u8 = 16;
+const REPEAT_ZERO: u8 = 17;
+/// The fixed code of the lengths of the code of the code lengths, as the
+/// value and the length of the bits.
+const CODE_LENGTH_LENGTHS: [(u32, usize); 6] =
+    [(0, 2), (7, 4), (3, 3), (2, 2), (1, 2), (15, 4)];
+const STAB_BITS: usize = 8;
+
+/// Returns the number of the bits of the symbols of the simple prefix
+/// codes of the alphabet.
+pub(crate) fn alphabet_bits(alphabet_size: usize) -> usize {
+    alphabet_size.next_power_of_two().trailing_zeros() as usize
+}
+
+/// The prefix code of a symbol, which takes no bits, or of more symbols.
+#[derive(Debug)]
+pub(crate) enum PrefixCode {
+    Single(u16),
+    Huffman {
+        decoder: HuffmanDecoder<Right>,
+        lens: Vec<u8>,
+    },
+}
+
+impl PrefixCode {
+    fn with_lens(
+        lens: Vec<u8>,
+        position: u64,
+    ) -> Result<Self, CompressionError> {
+        let mut symbols = lens.iter().enumerate().filter(|&(_, &l)| l != 0);
+        let first = symbols.next().map_or(0, |(s, _)| s);
+        if symbols.next().is_none() {
+            return Ok(PrefixCode::Single(first as u16));
+        }
+        let decoder = HuffmanDecoder::new(&lens, STAB_BITS)
+            .map_err(|_| CompressionError::InvalidHuffmanTable { position })?;
+        Ok(PrefixCode::Huffman { decoder, lens })
+    }
+
+    /// Reads the prefix code of the alphabet of `alphabet_size` symbols.
+    pub(crate) fn read<I: Iterator<Item = u8>>(
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+        alphabet_size: usize,
+    ) -> Result<Self, CompressionError> {
+        let position = reader.position();
+        let invalid = CompressionError::InvalidHuffmanTable { position };
+        let hskip = read_bits(reader, iter, 2)? as usize;
+        if hskip == 1 {
+            return Self::read_simple(reader, iter, alphabet_size, position);
+        }
+
+        // 符号長の符号の符号長
+        let mut code_lens = [0; CODE_LENGTH_CODES];
+        let mut space = 32;
+        let mut codes = 0;
+        for &s in &CODE_LENGTH_ORDER[hskip..] {
+            let peek = reader
+                .peek_bits::<u32, _>(4, iter)
+                .map_err(|_| CompressionError::UnexpectedEof)?;
+            let value = peek.data() as usize;
+            let (len, bits) = CODE_LENGTH_LENGTHS
+                .iter()
+                .enumerate()
+                .map(|(l, &(v, n))| (l, (v as usize, n)))
+                .find(|&(_, (v, n))| value & ((1 << n) - 1) == v)
+                .map(|(l, (_, n))| (l, n))
+                .unwrap();
+            if peek.len() < bits {
+                return Err(CompressionError::UnexpectedEof);
+            }
+            let _ = reader
+                .skip_bits(bits, iter)
+                .map_err(|_| CompressionError::UnexpectedEof)?;
+            code_lens[s] = len as u8;
+            if len != 0 {
+                space -= 32 >> len;
+                codes += 1;
+                if space <= 0 {
+                    break;
+                }
+            }
+        }
+        if codes != 1 && space != 0 {
+            return Err(invalid);
+        }
+        let mut code_lens = Self::with_lens(code_lens.to_vec(), position)?;
+
+        let mut lens = vec![0; alphabet_size];
+        let mut space: i64 = 1 << MAX_CODE_LEN;
+        let mut symbol = 0;
+        let mut prev_len = INITIAL_REPEAT_LEN;
+        let mut repeat = 0;
+        let mut repeat_len = 0;
+        while symbol < alphabet_size && space > 0 {
+            let code = code_lens.decode(reader, iter)? as u8;
+            if code < REPEAT_PREVIOUS {
+                lens[symbol] = code;
+                symbol += 1;
+                repeat = 0;
+                if code != 0 {
+                    prev_len = code;
+                    space -= (1 << MAX_CODE_LEN) >> code;
+                }
+                continue;
+            }
+            let (extra, len) = if code == REPEAT_PREVIOUS {
+                (2, prev_len)
+            } else {
+                (3, 0)
+            };
+            if repeat_len != len {
+                repeat = 0;
+                repeat_len = len;
+            }
+            // 連続する繰り返しは前の繰り返し回数を上位の桁とする
+            let old = repeat;
+            if repeat > 0 {
+                repeat = (repeat - 2) << extra;
+            }
+            repeat += read_bits(reader, iter, extra)? as usize + 3;
+            let delta = repeat - old;
+            if symbol + delta > alphabet_size {
+                return Err(invalid);
+            }
+            for l in &mut lens[symbol..symbol + delta] {
+                *l = len;
+            }
+            symbol += delta;
+            if len != 0 {
+                space -= ((delta << MAX_CODE_LEN) >> len) as i64;
+            }
+        }
+        if space != 0 {
+            return Err(invalid);
+        }
+        Self::with_lens(lens, position)
+    }
+
+    fn read_simple<I: Iterator<Item = u8>>(
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+        alphabet_size: usize,
+        position: u64,
+    ) -> Result<Self, CompressionError> {
+        let invalid = CompressionError::InvalidHuffmanTable { position };
+        let nsym = read_bits(reader, iter, 2)? as usize + 1;
+        let bits = alphabet_bits(alphabet_size);
+        let mut symbols = Vec::with_capacity(nsym);
+        for _ in 0..nsym {
+            let s = read_bits(reader, iter, bits)? as usize;
+            if s >= alphabet_size || symbols.contains(&s) {
+                return Err(invalid);
+            }
+            symbols.push(s);
+        }
+        let code_lens: &[u8] = match nsym {
+            1 => return Ok(PrefixCode::Single(symbols[0] as u16)),
+            2 => &[1, 1],
+            3 => &[1, 2, 2],
+            _ if read_bits(reader, iter, 1)? == 0 => &[2, 2, 2, 2],
+            _ => &[1, 2, 3, 3],
+        };
+        let mut lens = vec![0; alphabet_size];
+        for (&s, &l) in symbols.iter().zip(code_lens) {
+            lens[s] = l;
+        }
+        Self::with_lens(lens, position)
+    }
+
+    pub(crate) fn decode<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<u16, CompressionError> {
+        match self {
+            PrefixCode::Single(s) => Ok(*s),
+            PrefixCode::Huffman { decoder, lens } => {
+                let position = reader.position();
+                // 入力の末尾で足りないビットを読まないようにする
+                let available = reader
+                    .peek_bits::<u16, _>(MAX_CODE_LEN, iter)
+                    .map_err(|_| CompressionError::UnexpectedEof)?
+                    .len();
+                match decoder.dec(reader, iter) {
+                    Ok(Some(s))
+                        if usize::from(lens[s as usize]) <= available =>
+                    {
+                        Ok(s)
+                    }
+                    Ok(_) => Err(CompressionError::UnexpectedEof),
+                    Err(_) => {
+                        Err(CompressionError::InvalidHuffmanCode { position })
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The writer of the bits of the encoder, which counts them.
+#[derive(Debug)]
+pub(crate) struct BitSink {
+    writer: BitWriter<Right>,
+    pub(crate) out: Vec<u8>,
+    /// The number of the bits written.
+    pub(crate) bits: u64,
+}
+
+impl BitSink {
+    pub(crate) fn new() -> Self {
+        Self {
+            writer: BitWriter::new(),
+            out: Vec::new(),
+            bits: 0,
+        }
+    }
+
+    pub(crate) fn write(&mut self, value: u32, len: usize) {
+        let (data, n) = self.writer.write_bits(&SmallBitVec::new(value, len));
+        self.out.extend((0..n).map(|i| (data >> (i << 3)) as u8));
+        self.bits += len as u64;
+    }
+
+    pub(crate) fn is_aligned(&self) -> bool {
+        self.bits & 7 == 0
+    }
+
+    /// Pads the bits with zeros to the byte boundary.
+    pub(crate) fn align(&mut self) {
+        if let Some((data, n)) = self.writer.flush::<u32>() {
+            self.out.extend((0..n).map(|i| (data >> (i << 3)) as u8));
+        }
+        self.bits = (self.bits + 7) & !7;
+    }
+
+    /// Writes the bits of `other`, which has started at a byte boundary.
+    pub(crate) fn append(&mut self, mut other: BitSink) {
+        let bits = other.bits;
+        other.align();
+        for (i, &b) in other.out.iter().enumerate() {
+            let len = byte_bits(bits, i);
+            self.write(u32::from(b), len);
+        }
+    }
+}
+
+/// Returns the number of the bits of `bits` in the byte `i`.
+fn byte_bits(bits: u64, i: usize) -> usize {
+    let rest = bits - ((i as u64) << 3);
+    if rest >= 8 {
+        8
+    } else {
+        rest as usize
+    }
+}
+
+/// The prefix code of the encoder, built from the frequencies of the
+/// symbols.
+pub(crate) struct PrefixEncoder {
+    lens: Vec<u8>,
+    encoder: HuffmanEncoder<Right, u16>,
+    alphabet_size: usize,
+    // 符号のビットを持たない1つの記号の符号
+    single: bool,
+}
+
+impl PrefixEncoder {
+    pub(crate) fn new(freq: &[usize], alphabet_size: usize) -> Self {
+        let mut lens = make_table(freq, MAX_CODE_LEN);
+        lens.resize(alphabet_size, 0);
+        Self {
+            encoder: HuffmanEncoder::new(&lens),
+            single: lens.iter().filter(|&&l| l != 0).count() <= 1,
+            lens,
+            alphabet_size,
+        }
+    }
+
+    fn symbols(&self) -> Vec<usize> {
+        (0..self.alphabet_size)
+            .filter(|&s| self.lens[s] != 0)
+            .collect()
+    }
+
+    /// Writes the code of `symbol`, which is nothing for the code of a
+    /// symbol.
+    pub(crate) fn write(&self, sink: &mut BitSink, symbol: usize) {
+        if !self.single {
+            let code = self.encoder.enc(symbol as u16).unwrap();
+            sink.write(u32::from(code.data()), code.len());
+        }
+    }
+
+    /// Writes the description of the code.
+    pub(crate) fn write_description(&self, sink: &mut BitSink) {
+        let mut symbols = self.symbols();
+        if symbols.len() <= 4 {
+            // 単純な符号は符号長の短い順に並べる
+            symbols.sort_by_key(|&s| self.lens[s]);
+            if symbols.is_empty() {
+                symbols.push(0);
+            }
+            sink.write(1, 2);
+            sink.write(symbols.len() as u32 - 1, 2);
+            let bits = alphabet_bits(self.alphabet_size);
+            for &s in &symbols {
+                sink.write(s as u32, bits);
+            }
+            if symbols.len() == 4 {
+                sink.write(u32::from(self.lens[symbols[0]] == 1), 1);
+            }
+            return;
+        }
+
+        let codes = rle_lens(&self.lens);
+        let mut freq = [0; CODE_LENGTH_CODES];
+        for &(code, _) in &codes {
+            freq[usize::from(code)] += 1;
+        }
+        let mut code_lens = make_table(&freq, MAX_CODE_LENGTH_CODE_LEN);
+        code_lens.resize(CODE_LENGTH_CODES, 0);
+        let used = code_lens.iter().filter(|&&l| l != 0).count();
+        let order_len = if used == 1 {
+            CODE_LENGTH_CODES
+        } else {
+            CODE_LENGTH_ORDER
+                .iter()
+                .rposition(|&s| code_lens[s] != 0)
+                .unwrap()
+                + 1
+        };
+        let hskip = if code_lens[1] != 0 || code_lens[2] != 0 {
+            0
+        } else if code_lens[3] != 0 {
+            2
+        } else {
+            3
+        };
+        sink.write(hskip, 2);
+        for &s in &CODE_LENGTH_ORDER[hskip as usize..order_len] {
+            let (value, len) = CODE_LENGTH_LENGTHS[usize::from(code_lens[s])];
+            sink.write(value, len);
+        }
+
+        let encoder = HuffmanEncoder::<Right, u16>::new(&code_lens);
+        for &(code, extra) in &codes {
+            if used > 1 {
+                let c = encoder.enc(code).unwrap();
+                sink.write(u32::from(c.data()), c.len());
+            }
+            match code {
+                REPEAT_PREVIOUS => sink.write(extra, 2),
+                REPEAT_ZERO => sink.write(extra, 3),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Pushes the codes of the `len` code lengths of `value` after the ones
+/// of `prev`.
+fn push_run(codes: &mut Vec<(u8, u32)>, prev: u8, value: u8, mut len: usize) {
+    let (repeat, extra, direct) = if value == 0 {
+        (REPEAT_ZERO, 3, 11)
+    } else {
+        (REPEAT_PREVIOUS, 2, 7)
+    };
+    if value != 0 && prev != value {
+        codes.push((value, 0));
+        len -= 1;
+    }
+    // 繰り返しで表せない長さ
+    if len == direct {
+        codes.push((value, 0));
+        len -= 1;
+    }
+    if len < 3 {
+        codes.extend((0..len).map(|_| (value, 0)));
+        return;
+    }
+    let start = codes.len();
+    len -= 3;
+    loop {
+        codes.push((repeat, (len & ((1 << extra) - 1)) as u32));
+        len >>= extra;
+        if len == 0 {
+            break;
+        }
+        len -= 1;
+    }
+    codes[start..].reverse();
+}
+
+/// Returns the codes of the code lengths and their extra bits.
+fn rle_lens(lens: &[u8]) -> Vec<(u8, u32)> {
+    let end = lens.iter().rposition(|&l| l != 0).map_or(0, |i| i + 1);
+    let mut codes = Vec::new();
+    let mut prev = INITIAL_REPEAT_LEN;
+    let mut i = 0;
+    while i < end {
+        let value = lens[i];
+        let len = lens[i..end].iter().take_while(|&&l| l == value).count();
+        push_run(&mut codes, prev, value, len);
+        if value != 0 {
+            prev = value;
+        }
+        i += len;
+    }
+    codes
+}
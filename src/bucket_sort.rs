@@ -4,10 +4,15 @@
 //! This Source Code is subject to the terms of the Mozilla Public License
 //! version 2.0 (the "License"). You can obtain a copy of the License at
 //! <http://mozilla.org/MPL/2.0/>.
-#![cfg(any(feature = "bzip2", feature = "deflate", feature = "lzhuf"))]
+#![cfg(any(
+    feature = "brotli",
+    feature = "bzip2",
+    feature = "deflate",
+    feature = "lzhuf",
+    feature = "zstd"
+))]
 
 use crate::core::ops::{Add, Sub};
-use crate::core::usize;
 #[cfg(not(feature = "std"))]
 #[allow(unused_imports)]
 use alloc::vec;
@@ -9,11 +9,18 @@ use crate::bitio::direction::left::Left;
 use crate::bitio::reader::{BitRead, BitReader};
 use crate::bitset::BitArray;
 use crate::bzip2::error::BZip2Error;
+use crate::bzip2::index::{BZip2BlockInfo, BZip2Index};
 use crate::bzip2::mtf::MtfPositionDecoder;
-use crate::bzip2::{HEADER_h, BZ_G_SIZE, HEADER_0, HEADER_B, HEADER_Z};
-use crate::core::hash::{BuildHasher, Hasher};
-use crate::crc32::{BuiltinDigest, IEEE_NORMAL};
+#[cfg(feature = "std")]
+use crate::bzip2::parallel::ParallelDecoder;
+#[cfg(feature = "std")]
+use crate::bzip2::BLOCK_MAGIC;
+use crate::bzip2::{
+    HEADER_h, BZ_G_SIZE, EOS_MAGIC, HEADER_0, HEADER_B, HEADER_Z,
+};
+use crate::checksum::{BufferedChecksum, Crc32Bzip2};
 use crate::huffman::decoder::HuffmanDecoder;
+use crate::status::Status;
 use crate::traits::decoder::{BitDecodeService, BitDecoderImpl, Decoder};
 #[cfg(not(feature = "std"))]
 use alloc::string::String;
@@ -91,13 +98,24 @@ impl BlockRandomise {
     }
 }
 
+/// A block decoded by `BZip2DecoderBase::decode_block`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub(crate) struct DecodedBlock {
+    pub(crate) data: Vec<u8>,
+    pub(crate) crc: u32,
+    /// The bit position following the block.
+    pub(crate) end: u64,
+}
+
 #[derive(Debug)]
 pub(crate) struct BZip2DecoderBase {
     block_no: usize,
     block_size_100k: usize,
     combined_crc: u32,
     block_crc: u32,
-    block_crc_digest: BuiltinDigest,
+    block_crc_digest: BufferedChecksum<Crc32Bzip2>,
+    block_checked: bool,
     tt: Vec<u32>,
     n_block_used: usize,
     t_pos: u32,
@@ -107,6 +125,11 @@ pub(crate) struct BZip2DecoderBase {
     result_wrote_count: usize,
     result_charactor: u8,
     stream_no: usize,
+    block_pos: u64,
+    output: u64,
+    skip_bits: usize,
+    resumed: bool,
+    index: Option<BZip2Index>,
 }
 
 impl Default for BZip2DecoderBase {
@@ -125,7 +148,8 @@ impl BZip2DecoderBase {
             block_size_100k: 0,
             combined_crc: 0,
             block_crc: 0,
-            block_crc_digest: IEEE_NORMAL.build_hasher(),
+            block_crc_digest: BufferedChecksum::default(),
+            block_checked: false,
             tt: Vec::new(),
             n_block_used: 0,
             t_pos: 0,
@@ -135,9 +159,39 @@ impl BZip2DecoderBase {
             result_wrote_count: 0,
             result_charactor: 0,
             stream_no: 1,
+            block_pos: 0,
+            output: 0,
+            skip_bits: 0,
+            resumed: false,
+            index: None,
         }
     }
 
+    pub(crate) fn with_index() -> Self {
+        let mut ret = Self::new();
+        ret.index = Some(BZip2Index::default());
+        ret
+    }
+
+    /// Creates the decoder which starts at `block`, skipping the stream
+    /// header and the combined CRC check of the stream.
+    pub(crate) fn with_block(block: &BZip2BlockInfo) -> Self {
+        if !(1..=9).contains(&block.level) {
+            panic!("invalid level");
+        }
+        let mut ret = Self::new();
+        ret.block_no = 1;
+        ret.block_size_100k = usize::from(block.level);
+        ret.output = block.output;
+        ret.skip_bits = (block.input_bits & 0x07) as usize;
+        ret.resumed = true;
+        ret
+    }
+
+    pub(crate) fn index(&self) -> Option<&BZip2Index> {
+        self.index.as_ref()
+    }
+
     fn read_u8<R: BitRead, I: Iterator<Item = u8>>(
         reader: &mut R,
         iter: &mut I,
@@ -160,375 +214,497 @@ impl BZip2DecoderBase {
         Self::read_u8(reader, iter).map(|x| x == value)
     }
 
-    fn init_block<R: BitRead, I: Iterator<Item = u8>>(
+    fn init_block<I: Iterator<Item = u8>>(
         &mut self,
-        reader: &mut R,
+        reader: &mut BitReader<Left>,
         iter: &mut I,
     ) -> Result<bool, BZip2Error> {
         loop {
             if self.block_no == 0 {
+                let position = reader.position();
                 let magic_err = if self.stream_no == 1 {
-                    BZip2Error::DataErrorMagicFirst
+                    BZip2Error::BadMagic { position }
                 } else {
-                    BZip2Error::DataErrorMagic
+                    BZip2Error::TrailingGarbage { position }
                 };
-                let _ = Self::check_u8(reader, iter, HEADER_B)
-                    .map_err(|_| magic_err)?;
-                let _ = Self::check_u8(reader, iter, HEADER_Z)
-                    .map_err(|_| magic_err)?;
-                let _ = Self::check_u8(reader, iter, HEADER_h)
-                    .map_err(|_| magic_err)?;
+                for &b in &[HEADER_B, HEADER_Z, HEADER_h] {
+                    if !Self::check_u8(reader, iter, b)
+                        .map_err(|_| magic_err)?
+                    {
+                        return Err(magic_err);
+                    }
+                }
                 self.block_size_100k = {
                     let b = Self::read_u8(reader, iter)
                         .map_err(|_| BZip2Error::UnexpectedEof)?;
-                    if b < 1 + HEADER_0 || b > 9 + HEADER_0 {
+                    if !(1 + HEADER_0..=9 + HEADER_0).contains(&b) {
                         return Err(magic_err);
                     }
                     usize::from(b - HEADER_0)
                 };
-            } else {
-                let data_block_crc = self.block_crc_digest.finish() as u32;
+                self.resumed = false;
+            } else if !self.block_checked {
+                let data_block_crc = self.block_crc_digest.value();
                 debug!(
                     " {{0x{:08x}, 0x{:08x}}}]",
                     self.block_crc, data_block_crc
                 );
                 if data_block_crc != self.block_crc {
-                    return Err(BZip2Error::DataError);
+                    return Err(BZip2Error::ChecksumMismatch {
+                        expected: self.block_crc,
+                        actual: data_block_crc,
+                        position: self.block_pos,
+                    });
                 }
-                self.combined_crc = ((self.combined_crc << 1)
-                    | (self.combined_crc >> 31))
-                    ^ self.block_crc;
-                self.block_crc_digest = IEEE_NORMAL.build_hasher();
+                self.combined_crc =
+                    self.combined_crc.rotate_left(1) ^ self.block_crc;
+                self.block_crc_digest = BufferedChecksum::default();
+                self.block_checked = true;
             }
 
-            let block_head_byte = Self::read_u8(reader, iter)
+            self.block_pos = reader.position();
+            let magic = reader
+                .read_bits::<u64, _>(48, iter)
                 .map_err(|_| BZip2Error::UnexpectedEof)?;
+            if magic.len() != 48 {
+                return Err(BZip2Error::UnexpectedEof);
+            }
 
-            if block_head_byte == 0x31 {
-                let _ = Self::check_u8(reader, iter, 0x41)
-                    .map_err(|_| BZip2Error::DataError)?;
-
-                let _ = Self::check_u8(reader, iter, 0x59)
-                    .map_err(|_| BZip2Error::DataError)?;
-
-                let _ = Self::check_u8(reader, iter, 0x26)
-                    .map_err(|_| BZip2Error::DataError)?;
-
-                let _ = Self::check_u8(reader, iter, 0x53)
-                    .map_err(|_| BZip2Error::DataError)?;
-
-                let _ = Self::check_u8(reader, iter, 0x59)
-                    .map_err(|_| BZip2Error::DataError)?;
-                self.block_no += 1;
-                debug!("    [{}: huff+mtf ", self.block_no);
-
-                self.block_crc = Self::read_u32(reader, iter)
-                    .map_err(|_| BZip2Error::UnexpectedEof)?;
-                self.block_randomised = reader
-                    .read_bits::<u8, _>(1, iter)
-                    .map_err(|_| BZip2Error::UnexpectedEof)?
-                    .data()
-                    == 1;
-
-                let orig_pos = reader
-                    .read_bits::<u32, _>(24, iter)
-                    .map_err(|_| BZip2Error::UnexpectedEof)?
-                    .data() as usize;
+            match magic.data() {
+                BLOCK_MAGIC => {
+                    debug!("    [{}: huff+mtf ", self.block_no + 1);
 
-                if orig_pos > 10 + 100_000 * self.block_size_100k {
-                    return Err(BZip2Error::DataError);
+                    if let Err(e) = self.read_block(reader, iter) {
+                        // 再開時にブロックを読み直せるよう空にしておく
+                        self.tt.clear();
+                        self.n_block_used = 0;
+                        return Err(e);
+                    }
+                    self.block_no += 1;
+                    self.block_checked = false;
+                    if let Some(ref mut index) = self.index {
+                        index.push(BZip2BlockInfo {
+                            input_bits: self.block_pos,
+                            output: self.output,
+                            crc: self.block_crc,
+                            level: self.block_size_100k as u8,
+                        });
+                    }
+                    return Ok(true);
                 }
-
-                /*--- Receive the mapping table ---*/
-                let seq2unseq = {
-                    let mut in_use16 = BitArray::new(16);
-                    for i in 0..16 {
-                        in_use16.set(
-                            i,
-                            reader
-                                .read_bits::<u8, _>(1, iter)
-                                .map_err(|_| BZip2Error::UnexpectedEof)?
-                                .data()
-                                == 1,
-                        );
+                EOS_MAGIC => {
+                    let stored_combind_crc = Self::read_u32(reader, iter)
+                        .map_err(|_| BZip2Error::UnexpectedEof)?;
+                    debug!(
+                        "    combined CRCs: stored = 0x{:08x}, computed = \
+                         0x{:08x}",
+                        stored_combind_crc, self.combined_crc
+                    );
+                    if !self.resumed && stored_combind_crc != self.combined_crc
+                    {
+                        return Err(BZip2Error::ChecksumMismatch {
+                            expected: stored_combind_crc,
+                            actual: self.combined_crc,
+                            position: self.block_pos,
+                        });
+                    }
+                    let _ = reader.skip_to_next_byte();
+                    let next = reader
+                        .peek_bits::<usize, _>(8, iter)
+                        .map_err(|_| BZip2Error::Unexpected)?;
+                    if next.len() == 8 {
+                        self.block_no = 0;
+                        self.combined_crc = 0;
+                        self.stream_no += 1;
+                        reader.commit();
+                    } else {
+                        return Ok(false);
                     }
+                }
+                _ => {
+                    return Err(BZip2Error::InvalidBlock {
+                        position: self.block_pos,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Reads the block following the block magic and sets up the inverse
+    /// BWT.
+    fn read_block<R: BitRead, I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut R,
+        iter: &mut I,
+    ) -> Result<(), BZip2Error> {
+        self.block_crc = Self::read_u32(reader, iter)
+            .map_err(|_| BZip2Error::UnexpectedEof)?;
+        self.block_randomised = reader
+            .read_bits::<u8, _>(1, iter)
+            .map_err(|_| BZip2Error::UnexpectedEof)?
+            .data()
+            == 1;
+
+        let orig_pos = reader
+            .read_bits::<u32, _>(24, iter)
+            .map_err(|_| BZip2Error::UnexpectedEof)?
+            .data() as usize;
+
+        if orig_pos > 10 + 100_000 * self.block_size_100k {
+            return Err(BZip2Error::InvalidBlock {
+                position: reader.position(),
+            });
+        }
 
-                    let mut ret = Vec::with_capacity(256);
-                    for (i, _) in
-                        in_use16.iter().enumerate().filter(|&(_, x)| x)
+        /*--- Receive the mapping table ---*/
+        let seq2unseq = {
+            let mut in_use16 = BitArray::new(16);
+            for i in 0..16 {
+                in_use16.set(
+                    i,
+                    reader
+                        .read_bits::<u8, _>(1, iter)
+                        .map_err(|_| BZip2Error::UnexpectedEof)?
+                        .data()
+                        == 1,
+                );
+            }
+
+            let mut ret = Vec::with_capacity(256);
+            for (i, _) in in_use16.iter().enumerate().filter(|&(_, x)| x) {
+                for j in 0..16 {
+                    if reader
+                        .read_bits::<u8, _>(1, iter)
+                        .map_err(|_| BZip2Error::UnexpectedEof)?
+                        .data()
+                        == 1
                     {
-                        for j in 0..16 {
-                            if reader
-                                .read_bits::<u8, _>(1, iter)
-                                .map_err(|_| BZip2Error::UnexpectedEof)?
-                                .data()
-                                == 1
-                            {
-                                ret.push(i * 16 + j)
-                            }
-                        }
+                        ret.push(i * 16 + j)
                     }
-                    ret
-                };
-
-                if seq2unseq.is_empty() {
-                    return Err(BZip2Error::DataError);
                 }
+            }
+            ret
+        };
 
-                let alpha_size = seq2unseq.len() + 2;
+        if seq2unseq.is_empty() {
+            return Err(BZip2Error::InvalidBlock {
+                position: reader.position(),
+            });
+        }
 
-                /*--- Now the selectors ---*/
-                let n_groups = reader
-                    .read_bits(3, iter)
-                    .map_err(|_| BZip2Error::UnexpectedEof)?
-                    .data();
-                if n_groups < 2 || n_groups > 6 {
-                    return Err(BZip2Error::DataError);
-                }
-                let n_selectors = reader
-                    .read_bits(15, iter)
-                    .map_err(|_| BZip2Error::UnexpectedEof)?
-                    .data();
-                if n_selectors < 1 {
-                    return Err(BZip2Error::DataError);
-                }
+        let alpha_size = seq2unseq.len() + 2;
+
+        /*--- Now the selectors ---*/
+        let n_groups = reader
+            .read_bits(3, iter)
+            .map_err(|_| BZip2Error::UnexpectedEof)?
+            .data();
+        if !(2..=6).contains(&n_groups) {
+            return Err(BZip2Error::InvalidBlock {
+                position: reader.position(),
+            });
+        }
+        let n_selectors = reader
+            .read_bits(15, iter)
+            .map_err(|_| BZip2Error::UnexpectedEof)?
+            .data();
+        if n_selectors < 1 {
+            return Err(BZip2Error::InvalidBlock {
+                position: reader.position(),
+            });
+        }
 
-                let mut selector = Vec::with_capacity(n_selectors);
+        let mut selector = Vec::with_capacity(n_selectors);
+        {
+            let mut selector_mtf_dec = MtfPositionDecoder::new(n_groups);
+            for _ in 0..n_selectors {
+                let mut j = 0;
+                while reader
+                    .read_bits::<u8, _>(1, iter)
+                    .map_err(|_| BZip2Error::UnexpectedEof)?
+                    .data()
+                    != 0
                 {
-                    let mut selector_mtf_dec =
-                        MtfPositionDecoder::new(n_groups);
-                    for _ in 0..n_selectors {
-                        let mut j = 0;
-                        while reader
-                            .read_bits::<u8, _>(1, iter)
-                            .map_err(|_| BZip2Error::UnexpectedEof)?
-                            .data()
-                            != 0
-                        {
-                            j += 1;
-                            if j >= n_groups {
-                                return Err(BZip2Error::DataError);
-                            }
-                        }
-                        /*--- Undo the MTF values for the selectors. ---*/
-                        selector.push(selector_mtf_dec.pop(j));
+                    j += 1;
+                    if j >= n_groups {
+                        return Err(BZip2Error::InvalidBlock {
+                            position: reader.position(),
+                        });
                     }
                 }
+                /*--- Undo the MTF values for the selectors. ---*/
+                selector.push(selector_mtf_dec.pop(j));
+            }
+        }
 
-                let mut len = vec![vec![0; alpha_size]; n_groups];
-                /*--- Now the coding tables ---*/
-                for t in &mut len {
-                    let mut curr = reader
-                        .read_bits::<u8, _>(5, iter)
+        let mut len = vec![vec![0; alpha_size]; n_groups];
+        /*--- Now the coding tables ---*/
+        for t in &mut len {
+            let mut curr = reader
+                .read_bits::<u8, _>(5, iter)
+                .map_err(|_| BZip2Error::UnexpectedEof)?
+                .data();
+            for i in t.iter_mut() {
+                while reader
+                    .read_bits::<u8, _>(1, iter)
+                    .map_err(|_| BZip2Error::UnexpectedEof)?
+                    .data()
+                    != 0
+                {
+                    if !(1..=20).contains(&curr) {
+                        return Err(BZip2Error::InvalidBlock {
+                            position: reader.position(),
+                        });
+                    }
+                    if reader
+                        .read_bits::<u8, _>(1, iter)
                         .map_err(|_| BZip2Error::UnexpectedEof)?
-                        .data();
-                    for i in t.iter_mut() {
-                        while reader
-                            .read_bits::<u8, _>(1, iter)
-                            .map_err(|_| BZip2Error::UnexpectedEof)?
-                            .data()
-                            != 0
-                        {
-                            if curr < 1 || curr > 20 {
-                                return Err(BZip2Error::DataError);
-                            }
-                            if reader
-                                .read_bits::<u8, _>(1, iter)
-                                .map_err(|_| BZip2Error::UnexpectedEof)?
-                                .data()
-                                == 0
-                            {
-                                curr += 1;
-                            } else {
-                                curr -= 1;
-                            }
-                        }
-                        *i = curr;
+                        .data()
+                        == 0
+                    {
+                        curr += 1;
+                    } else {
+                        curr -= 1;
                     }
                 }
+                *i = curr;
+            }
+        }
 
-                /*--- Create the Huffman decoding tables ---*/
-                let mut code = Vec::with_capacity(n_groups);
-                for l in &len {
-                    code.push(
-                        HuffmanDecoder::<Left>::new(l, 12)
-                            .map_err(|_| BZip2Error::DataError)?,
-                    );
+        /*--- Create the Huffman decoding tables ---*/
+        let mut code = Vec::with_capacity(n_groups);
+        for l in &len {
+            code.push(HuffmanDecoder::<Left>::new(l, 12).map_err(|_| {
+                BZip2Error::InvalidHuffmanTable {
+                    position: reader.position(),
                 }
+            })?);
+        }
 
-                /*--- Now the MTF values ---*/
-                let eob = alpha_size as u16 - 1;
-                let nblock_max = 100_000 * self.block_size_100k;
-
-                let mut unzftab = vec![0; 257]; // LF-mapping Table
-                self.tt.clear();
-                self.tt.reserve_exact(nblock_max);
-
+        /*--- Now the MTF values ---*/
+        let eob = alpha_size as u16 - 1;
+        let nblock_max = 100_000 * self.block_size_100k;
+
+        let mut unzftab = vec![0; 257]; // LF-mapping Table
+        self.tt.clear();
+        self.tt.reserve_exact(nblock_max);
+
+        {
+            let mut group_no = 0;
+            let mut group_pos = 0;
+            let mut n = 1;
+            let mut es = 0;
+
+            let mut mtf_decoder = MtfPositionDecoder::new(seq2unseq.len());
+
+            loop {
+                if group_pos == 0 {
+                    group_no += 1;
+                    if group_no > n_selectors {
+                        return Err(BZip2Error::InvalidBlock {
+                            position: reader.position(),
+                        });
+                    }
+                    group_pos = BZ_G_SIZE;
+                }
+                group_pos -= 1;
+                let position = reader.position();
+                let next_sym = code[selector[group_no - 1]]
+                    .dec(reader, iter)
+                    .map_err(|_| BZip2Error::InvalidHuffmanCode { position })?
+                    .ok_or(BZip2Error::UnexpectedEof)?;
+
+                if es > 0 && next_sym != Self::RUN_A && next_sym != Self::RUN_B
                 {
-                    let mut group_no = 0;
-                    let mut group_pos = 0;
-                    let mut n = 1;
-                    let mut es = 0;
-
-                    let mut mtf_decoder =
-                        MtfPositionDecoder::new(seq2unseq.len());
-
-                    loop {
-                        if group_pos == 0 {
-                            group_no += 1;
-                            if group_no > n_selectors {
-                                return Err(BZip2Error::DataError);
-                            }
-                            group_pos = BZ_G_SIZE;
-                        }
-                        group_pos -= 1;
-                        let next_sym = code[selector[group_no - 1]]
-                            .dec(reader, iter)
-                            .map_err(|_| BZip2Error::DataError)?
-                            .ok_or_else(|| BZip2Error::DataError)?;
-
-                        if es > 0
-                            && next_sym != Self::RUN_A
-                            && next_sym != Self::RUN_B
-                        {
-                            let uc = seq2unseq[mtf_decoder.pop(0)];
-                            unzftab[uc + 1] += es;
-                            for _ in 0..es {
-                                self.tt.push(uc as u32);
-                            }
-                            if self.tt.len() >= nblock_max {
-                                return Err(BZip2Error::DataError);
-                            }
-                            n = 1;
-                            es = 0;
-                        }
-
-                        if next_sym == eob {
-                            break;
-                        }
-
-                        /* Check that N doesn't get too big, so that es
-                        doesn't go negative.  The maximum value that can
-                        be RUNA/RUNB encoded is equal to the block size
-                        (post the initial RLE), viz, 900k, so bounding N
-                        at 2 million should guard against overflow
-                        without rejecting any legitimate inputs. */
-                        if n >= 2 * 1024 * 1024 {
-                            return Err(BZip2Error::DataError);
-                        }
-
-                        if next_sym == Self::RUN_A {
-                            es += n;
-                            n <<= 1;
-                        } else if next_sym == Self::RUN_B {
-                            n <<= 1;
-                            es += n;
-                        } else {
-                            if self.tt.len() >= nblock_max {
-                                return Err(BZip2Error::DataError);
-                            }
-
-                            let uc = seq2unseq
-                                [mtf_decoder.pop(next_sym as usize - 1)];
-                            unzftab[uc + 1] += 1;
-                            self.tt.push(uc as u32);
-                        }
+                    let uc = seq2unseq[mtf_decoder.pop(0)];
+                    unzftab[uc + 1] += es;
+                    for _ in 0..es {
+                        self.tt.push(uc as u32);
+                    }
+                    if self.tt.len() >= nblock_max {
+                        return Err(BZip2Error::InvalidBlock {
+                            position: reader.position(),
+                        });
                     }
+                    n = 1;
+                    es = 0;
                 }
 
-                /* Now we know what nblock is, we can do a better sanity
-                check on s->origPtr. */
-                if orig_pos >= self.tt.len() {
-                    return Err(BZip2Error::DataError);
+                if next_sym == eob {
+                    break;
                 }
 
-                /*-- Set up cftab to facilitate generation of T^(-1) --*/
-                /* Actually generate cftab. */
-                if unzftab[0] != 0 {
-                    return Err(BZip2Error::DataError);
+                /* Check that N doesn't get too big, so that es
+                doesn't go negative.  The maximum value that can
+                be RUNA/RUNB encoded is equal to the block size
+                (post the initial RLE), viz, 900k, so bounding N
+                at 2 million should guard against overflow
+                without rejecting any legitimate inputs. */
+                if n >= 2 * 1024 * 1024 {
+                    return Err(BZip2Error::InvalidBlock {
+                        position: reader.position(),
+                    });
                 }
 
-                for i in 1..unzftab.len() {
-                    // /* Check: unzftab entries in range. */
-                    // if (unzftab[i] < 0 || unzftab[i] > nblock)
-                    //     throw new InvalidDataException();
-                    unzftab[i] += unzftab[i - 1];
-                    /* Check: cftab entries non-descending. */
-                    if unzftab[i - 1] > unzftab[i] {
-                        return Err(BZip2Error::DataError);
+                if next_sym == Self::RUN_A {
+                    es += n;
+                    n <<= 1;
+                } else if next_sym == Self::RUN_B {
+                    n <<= 1;
+                    es += n;
+                } else {
+                    if self.tt.len() >= nblock_max {
+                        return Err(BZip2Error::InvalidBlock {
+                            position: reader.position(),
+                        });
                     }
+
+                    let uc = seq2unseq[mtf_decoder.pop(next_sym as usize - 1)];
+                    unzftab[uc + 1] += 1;
+                    self.tt.push(uc as u32);
                 }
-                /* Check: cftab entries in range. */
-                if unzftab[unzftab.len() - 1] != self.tt.len() {
-                    return Err(BZip2Error::DataError);
-                }
+            }
+        }
 
-                debug!("rt+rld");
+        /* Now we know what nblock is, we can do a better sanity
+        check on s->origPtr. */
+        if orig_pos >= self.tt.len() {
+            return Err(BZip2Error::InvalidBlock {
+                position: reader.position(),
+            });
+        }
 
-                /*-- compute the T^(-1) vector --*/
-                for i in 0..self.tt.len() {
-                    let uc = (self.tt[i] & 0xFF) as usize;
-                    self.tt[unzftab[uc]] |= (i as u32) << 8;
-                    unzftab[uc] += 1;
-                }
+        /*-- Set up cftab to facilitate generation of T^(-1) --*/
+        /* Actually generate cftab. */
+        if unzftab[0] != 0 {
+            return Err(BZip2Error::InvalidBlock {
+                position: reader.position(),
+            });
+        }
 
-                self.t_pos = self.tt[orig_pos] >> 8;
-                self.n_block_used = 0;
+        for i in 1..unzftab.len() {
+            // /* Check: unzftab entries in range. */
+            // if (unzftab[i] < 0 || unzftab[i] > nblock)
+            //     throw new InvalidDataException();
+            unzftab[i] += unzftab[i - 1];
+            /* Check: cftab entries non-descending. */
+            if unzftab[i - 1] > unzftab[i] {
+                return Err(BZip2Error::InvalidBlock {
+                    position: reader.position(),
+                });
+            }
+        }
+        /* Check: cftab entries in range. */
+        if unzftab[unzftab.len() - 1] != self.tt.len() {
+            return Err(BZip2Error::InvalidBlock {
+                position: reader.position(),
+            });
+        }
 
-                if self.block_randomised {
-                    self.block_randomise.reset();
-                }
+        debug!("rt+rld");
 
-                self.result_count = 0;
-                self.result_wrote_count = 0;
+        /*-- compute the T^(-1) vector --*/
+        for i in 0..self.tt.len() {
+            let uc = (self.tt[i] & 0xFF) as usize;
+            self.tt[unzftab[uc]] |= (i as u32) << 8;
+            unzftab[uc] += 1;
+        }
 
-                return Ok(true);
-            } else if block_head_byte == 0x17 {
-                let _ = Self::check_u8(reader, iter, 0x72)
-                    .map_err(|_| BZip2Error::DataError)?;
+        self.t_pos = self.tt[orig_pos] >> 8;
+        self.n_block_used = 0;
 
-                let _ = Self::check_u8(reader, iter, 0x45)
-                    .map_err(|_| BZip2Error::DataError)?;
+        if self.block_randomised {
+            self.block_randomise.reset();
+        }
 
-                let _ = Self::check_u8(reader, iter, 0x38)
-                    .map_err(|_| BZip2Error::DataError)?;
+        self.result_count = 0;
+        self.result_wrote_count = 0;
+        Ok(())
+    }
 
-                let _ = Self::check_u8(reader, iter, 0x50)
-                    .map_err(|_| BZip2Error::DataError)?;
+    /// Decodes the block whose magic starts at the bit `pos` of `data`
+    /// and verifies its CRC.
+    #[cfg(feature = "std")]
+    pub(crate) fn decode_block(
+        data: &[u8],
+        pos: u64,
+        block_size_100k: usize,
+    ) -> Result<DecodedBlock, BZip2Error> {
+        let start = pos & !7;
+        let mut base = Self::new();
+        base.block_size_100k = block_size_100k;
+        base.block_pos = pos & 7;
+        base.decode_block_from(&data[(start >> 3) as usize..])
+            .map(|mut block| {
+                block.end += start;
+                block
+            })
+            .map_err(|e| e.offset_by(start))
+    }
 
-                let _ = Self::check_u8(reader, iter, 0x90)
-                    .map_err(|_| BZip2Error::DataError)?;
-                let stored_combind_crc = Self::read_u32(reader, iter)
-                    .map_err(|_| BZip2Error::UnexpectedEof)?;
-                debug!(
-                    "    combined CRCs: stored = 0x{:08x}, computed = 0x{:08x}",
-                    stored_combind_crc, self.combined_crc
-                );
-                if stored_combind_crc != self.combined_crc {
-                    return Err(BZip2Error::DataError);
-                }
-                let _ = reader.skip_to_next_byte();
-                let next = reader
-                    .peek_bits::<usize, _>(8, iter)
-                    .map_err(|_| BZip2Error::Unexpected)?;
-                if next.len() == 8 {
-                    self.block_no = 0;
-                    self.combined_crc = 0;
-                    self.stream_no += 1;
-                } else {
-                    return Ok(false);
-                }
-            } else {
-                return Err(BZip2Error::DataError);
+    #[cfg(feature = "std")]
+    fn decode_block_from(
+        &mut self,
+        data: &[u8],
+    ) -> Result<DecodedBlock, BZip2Error> {
+        let mut iter = data.iter().cloned();
+        let mut reader = BitReader::<Left>::new();
+        if self.block_pos != 0 {
+            let _ = reader
+                .skip_bits(self.block_pos as usize, &mut iter)
+                .map_err(|_| BZip2Error::UnexpectedEof)?;
+        }
+        let magic = reader
+            .read_bits::<u64, _>(48, &mut iter)
+            .map_err(|_| BZip2Error::UnexpectedEof)?;
+        if magic.len() != 48 {
+            return Err(BZip2Error::UnexpectedEof);
+        }
+        if magic.data() != BLOCK_MAGIC {
+            return Err(BZip2Error::InvalidBlock {
+                position: self.block_pos,
+            });
+        }
+        self.read_block(&mut reader, &mut iter)?;
+        let end = reader.position();
+
+        // 最も長く伸びるのは同じ4文字と繰り返し数の5シンボルの259バイト
+        let max_len = self.tt.len() / 5 * 259 + self.tt.len() % 5;
+        let mut ret = Vec::with_capacity(self.tt.len());
+        while self.n_block_used != self.tt.len()
+            || self.result_count != self.result_wrote_count
+        {
+            if ret.len() >= max_len {
+                return Err(BZip2Error::InvalidBlock {
+                    position: self.block_pos,
+                });
             }
+            ret.push(self.next_byte()?);
+        }
+        let crc = self.block_crc_digest.value();
+        if crc != self.block_crc {
+            return Err(BZip2Error::ChecksumMismatch {
+                expected: self.block_crc,
+                actual: crc,
+                position: self.block_pos,
+            });
         }
+        Ok(DecodedBlock {
+            data: ret,
+            crc: self.block_crc,
+            end,
+        })
     }
 
     fn get_next_lfm(&mut self) -> Result<u8, BZip2Error> {
         let mut position = self.t_pos;
         /* c_tPos is unsigned, hence test < 0 is pointless. */
-        if position >= 100_000 * self.block_size_100k as u32 {
-            return Err(BZip2Error::DataError);
+        if position >= 100_000 * self.block_size_100k as u32
+            || self.n_block_used >= self.tt.len()
+        {
+            return Err(BZip2Error::InvalidBlock {
+                position: self.block_pos,
+            });
         }
         position = self.tt[position as usize];
         let mut k0 = position as u8;
@@ -540,25 +716,10 @@ impl BZip2DecoderBase {
 
         Ok(k0)
     }
-}
-
-impl BitDecodeService for BZip2DecoderBase {
-    type Direction = Left;
-    type Error = BZip2Error;
-    type Output = u8;
 
-    fn next<I: Iterator<Item = u8>>(
-        &mut self,
-        reader: &mut BitReader<Self::Direction>,
-        iter: &mut I,
-    ) -> Result<Option<u8>, Self::Error> {
+    /// Returns the next byte of the current block.
+    fn next_byte(&mut self) -> Result<u8, BZip2Error> {
         if self.result_count == self.result_wrote_count {
-            if self.n_block_used == self.tt.len()
-                && !self.init_block(reader, iter)?
-            {
-                return Ok(None);
-            }
-
             let buffer = self.get_next_lfm()?;
             if buffer == self.result_charactor && self.result_count < 4 {
                 self.result_count += 1;
@@ -575,30 +736,107 @@ impl BitDecodeService for BZip2DecoderBase {
         } else {
             self.result_wrote_count += 1;
         }
-        self.block_crc_digest.write_u8(self.result_charactor);
-        Ok(Some(self.result_charactor))
+        self.block_crc_digest.push(self.result_charactor);
+        Ok(self.result_charactor)
+    }
+}
+
+impl BitDecodeService for BZip2DecoderBase {
+    type Direction = Left;
+    type Error = BZip2Error;
+    type Output = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Self::Direction>,
+        iter: &mut I,
+    ) -> Result<Option<u8>, Self::Error> {
+        if self.skip_bits > 0 {
+            let _ = reader
+                .skip_bits(self.skip_bits, iter)
+                .map_err(|_| BZip2Error::UnexpectedEof)?;
+            self.skip_bits = 0;
+            reader.commit();
+        }
+        if self.result_count == self.result_wrote_count
+            && self.n_block_used == self.tt.len()
+            && !self.init_block(reader, iter)?
+        {
+            return Ok(None);
+        }
+        self.output += 1;
+        self.next_byte().map(Some)
     }
 }
 
 #[derive(Debug)]
 pub struct BZip2Decoder {
     inner: BitDecoderImpl<BZip2DecoderBase>,
+    #[cfg(feature = "std")]
+    parallel: Option<ParallelDecoder>,
 }
 
 impl BZip2Decoder {
     pub fn new() -> Self {
         Self {
             inner: BitDecoderImpl::<BZip2DecoderBase>::new(),
+            #[cfg(feature = "std")]
+            parallel: None,
         }
     }
-}
 
-impl Default for BZip2Decoder {
-    fn default() -> Self {
+    /// Creates the decoder which decodes up to `threads` blocks at once on
+    /// multiple threads.
+    ///
+    /// The blocks are found by scanning the input for the block magic, so
+    /// the input is buffered by several blocks ahead.
+    #[cfg(feature = "std")]
+    pub fn with_threads(threads: usize) -> Self {
         Self {
             inner: BitDecoderImpl::<BZip2DecoderBase>::new(),
+            parallel: Some(ParallelDecoder::new(threads)),
+        }
+    }
+
+    /// Creates the decoder which builds a `BZip2Index` of the blocks.
+    pub fn with_index() -> Self {
+        Self::with_base(BZip2DecoderBase::with_index())
+    }
+
+    /// Creates the decoder which starts the decoding at `block`.
+    ///
+    /// The input has to start at `block.input_offset()` of the compressed
+    /// data. The decoding continues with the following streams, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the level of `block` is invalid.
+    pub fn with_block(block: &BZip2BlockInfo) -> Self {
+        Self::with_base(BZip2DecoderBase::with_block(block))
+    }
+
+    fn with_base(base: BZip2DecoderBase) -> Self {
+        Self {
+            inner: BitDecoderImpl::<BZip2DecoderBase>::with_service(
+                base,
+                BitReader::new(),
+            ),
+            #[cfg(feature = "std")]
+            parallel: None,
         }
     }
+
+    /// Returns the index built so far by a decoder created with
+    /// `with_index`.
+    pub fn index(&self) -> Option<&BZip2Index> {
+        self.inner.service().index()
+    }
+}
+
+impl Default for BZip2Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Decoder for BZip2Decoder {
@@ -610,6 +848,27 @@ impl Decoder for BZip2Decoder {
         &mut self,
         iter: &mut I,
     ) -> Option<Result<Self::Output, Self::Error>> {
+        #[cfg(feature = "std")]
+        {
+            if let Some(parallel) = self.parallel.as_mut() {
+                return parallel.next(iter);
+            }
+        }
         self.inner.next(iter)
     }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        #[cfg(feature = "std")]
+        {
+            if let Some(parallel) = self.parallel.as_mut() {
+                return parallel.decompress(input, output, finish);
+            }
+        }
+        self.inner.decompress(input, output, finish)
+    }
 }
@@ -7,22 +7,22 @@
 
 use crate::action::Action;
 use crate::bitio::direction::left::Left;
-use crate::bitio::direction::Direction;
 use crate::bitio::small_bit_vec::SmallBitVec;
 use crate::bitio::writer::BitWriter;
 use crate::bitset::BitArray;
+use crate::bzip2::index::{BZip2BlockInfo, BZip2Index};
 use crate::bzip2::mtf::MtfPosition;
 use crate::bzip2::{HEADER_h, BZ_G_SIZE, HEADER_0, HEADER_B, HEADER_Z};
+use crate::checksum::{BufferedChecksum, Crc32Bzip2};
 use crate::core::cmp;
 use crate::core::fmt;
-use crate::core::hash::{BuildHasher, Hasher};
-use crate::core::u8;
-use crate::crc32::{BuiltinDigest, IEEE_NORMAL};
+use crate::core::mem;
 use crate::error::CompressionError;
 use crate::huffman::cano_huff_table::make_tab_with_fn;
 use crate::huffman::encoder::HuffmanEncoder;
+use crate::status::Status;
 use crate::suffix_array::sais::bwt;
-use crate::traits::encoder::Encoder;
+use crate::traits::encoder::{EncodeBuffer, Encoder};
 #[cfg(not(feature = "std"))]
 use alloc::collections::vec_deque::VecDeque;
 #[cfg(not(feature = "std"))]
@@ -35,17 +35,19 @@ use alloc::vec::Vec;
 use log::{debug, log_enabled, Level};
 #[cfg(feature = "std")]
 use std::collections::vec_deque::VecDeque;
+#[cfg(feature = "std")]
+use std::thread;
 
 #[derive(Debug)]
 pub struct BZip2Encoder {
     inner: EncoderInner,
     writer: BitWriter<Left>,
     queue: VecDeque<SmallBitVec<u32>>,
-
-    finished: bool,
-    bitbuf: u32,
-    bitbuflen: usize,
-    bit_finished: bool,
+    out: Vec<u8>,
+    out_pos: usize,
+    /// The action of which the output has been written to `out`.
+    completed: Option<Action>,
+    buf: EncodeBuffer<u8, u8>,
 }
 
 impl Default for BZip2Encoder {
@@ -55,64 +57,87 @@ impl Default for BZip2Encoder {
 }
 
 impl BZip2Encoder {
+    const BUF_LEN: usize = 0x1000;
+
     pub fn new(level: usize) -> Self {
-        if level < 1 || level > 9 {
+        if !(1..=9).contains(&level) {
+            panic!("invalid level");
+        }
+
+        Self::with_inner(EncoderInner::new(level))
+    }
+
+    /// Creates the encoder which compresses up to `threads` blocks at once
+    /// on multiple threads.
+    ///
+    /// The output is identical to the one of `BZip2Encoder::new`.
+    #[cfg(feature = "std")]
+    pub fn with_threads(level: usize, threads: usize) -> Self {
+        if !(1..=9).contains(&level) {
             panic!("invalid level");
         }
+        if threads == 0 {
+            panic!("invalid threads");
+        }
+
+        let mut inner = EncoderInner::new(level);
+        inner.threads = threads;
+        Self::with_inner(inner)
+    }
 
+    /// Returns the index of the blocks written so far.
+    pub fn index(&self) -> &BZip2Index {
+        &self.inner.index
+    }
+
+    fn with_inner(inner: EncoderInner) -> Self {
         Self {
-            inner: EncoderInner::new(level),
+            inner,
             writer: BitWriter::new(),
             queue: VecDeque::new(),
-            finished: false,
-            bitbuf: 0,
-            bitbuflen: 0,
-            bit_finished: false,
+            out: Vec::new(),
+            out_pos: 0,
+            completed: None,
+            buf: EncodeBuffer::default(),
         }
     }
 
-    fn next_bits<I: Iterator<Item = u8>>(
+    /// Compresses `input` into `out` until either `out` has been filled
+    /// or the output of `action` has been written, which is told by the
+    /// second value along with the number of the bytes consumed.
+    fn fill(
         &mut self,
-        iter: &mut I,
+        input: &[u8],
         action: Action,
-    ) -> Option<Result<SmallBitVec<u32>, CompressionError>> {
-        while self.queue.is_empty() {
-            match iter.next() {
-                Some(s) => {
-                    if let Err(e) = self.inner.next(s, &mut self.queue) {
-                        return Some(Err(e));
-                    }
-                }
-                None => {
-                    if self.finished {
-                        self.finished = false;
-                        return None;
-                    } else {
-                        match action {
-                            Action::Flush => {
-                                if let Err(e) =
-                                    self.inner.flush(&mut self.queue)
-                                {
-                                    return Some(Err(e));
-                                }
-                            }
-                            Action::Finish => {
-                                if let Err(e) =
-                                    self.inner.finish(&mut self.queue)
-                                {
-                                    return Some(Err(e));
-                                }
-                            }
-                            _ => {}
-                        }
-                        self.finished = true;
-                    }
+    ) -> Result<(usize, bool), CompressionError> {
+        for (i, &s) in input.iter().enumerate() {
+            self.inner.next(s, &mut self.queue)?;
+            if !self.queue.is_empty() {
+                self.write_queue();
+                if self.out.len() >= Self::BUF_LEN {
+                    return Ok((i + 1, false));
                 }
             }
         }
-        self.queue.pop_front().map(Ok)
+        match action {
+            Action::Run => {}
+            Action::Finish => self.inner.finish(&mut self.queue)?,
+            _ => self.inner.flush(&mut self.queue)?,
+        }
+        self.write_queue();
+        if action == Action::Finish {
+            self.writer.flush_bytes::<u32>(&mut self.out);
+        }
+        Ok((input.len(), true))
+    }
+
+    fn write_queue(&mut self) {
+        while let Some(s) = self.queue.pop_front() {
+            self.writer.write_bytes(&s, &mut self.out);
+        }
     }
 }
+
 impl Encoder for BZip2Encoder {
     type Error = CompressionError;
     type In = u8;
@@ -122,42 +147,66 @@ impl Encoder for BZip2Encoder {
         iter: &mut I,
         action: Action,
     ) -> Option<Result<u8, CompressionError>> {
-        while self.bitbuflen == 0 {
-            let s = match self.next_bits(iter, action) {
-                Some(Err(e)) => return Some(Err(e)),
-                Some(Ok(ref s)) => self.writer.write_bits(s),
-                None => {
-                    if self.bit_finished {
-                        self.bit_finished = false;
-                        return None;
-                    } else {
-                        match action {
-                            Action::Finish | Action::Flush => {
-                                self.bit_finished = true;
-                                match self.writer.flush::<u32>() {
-                                    Some((x, y)) if y != 0 => (x, y),
-                                    _ => return None,
-                                }
-                            }
-                            _ => {
-                                return None;
-                            }
-                        }
-                    }
-                }
-            };
-            self.bitbuf = s.0;
-            self.bitbuflen = s.1;
+        if let Some(s) = self.buf.pop() {
+            return Some(Ok(s));
         }
+        let mut buf = mem::take(&mut self.buf);
+        let ret = buf.next(self, iter, action);
+        self.buf = buf;
+        ret
+    }
 
-        let ret = Left::convert(self.bitbuf, 32, 8) as u8;
-
-        self.bitbuf = Left::forward(self.bitbuf, 8);
-        self.bitbuflen -= 1;
-        Some(Ok(ret))
+    fn compress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        action: Action,
+    ) -> Result<Status, CompressionError> {
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            let len = cmp::min(
+                self.out.len() - self.out_pos,
+                output.len() - produced,
+            );
+            output[produced..produced + len]
+                .copy_from_slice(&self.out[self.out_pos..self.out_pos + len]);
+            produced += len;
+            self.out_pos += len;
+            if self.out_pos < self.out.len() {
+                return Ok(Status {
+                    consumed,
+                    produced,
+                    done: false,
+                });
+            }
+            self.out.clear();
+            self.out_pos = 0;
+            if consumed == input.len() && self.completed.take() == Some(action)
+            {
+                return Ok(Status {
+                    consumed,
+                    produced,
+                    done: true,
+                });
+            }
+            let (len, completed) = self.fill(&input[consumed..], action)?;
+            consumed += len;
+            self.completed = if completed { Some(action) } else { None };
+        }
     }
 }
 
+/// A block waiting to be compressed on the worker threads.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct PendingBlock {
+    data: Vec<u8>,
+    in_use: BitArray,
+    crc: u32,
+    output: u64,
+}
+
 #[derive(Debug)]
 struct EncoderInner {
     block_buf: Vec<u8>,
@@ -166,20 +215,30 @@ struct EncoderInner {
     block_max_len: usize,
     combined_crc: u32,
     block_no: usize,
-    block_crc: BuiltinDigest,
+    header_written: bool,
+    block_crc: BufferedChecksum<Crc32Bzip2>,
     rle_buffer: u8,
     rle_count: usize,
     in_use: BitArray,
-    mtf_buffer: Vec<u16>,
-    num_z: u64,
+    block_encoder: BlockEncoder,
+    index: BZip2Index,
+    bits: u64,
+    output: u64,
+    block_len: u64,
+    #[cfg(feature = "std")]
+    threads: usize,
+    #[cfg(feature = "std")]
+    pending: Vec<PendingBlock>,
 }
 
 impl EncoderInner {
     fn prepare_new_block(&mut self) {
         self.block_no += 1;
-        self.block_crc = IEEE_NORMAL.build_hasher();
+        self.block_crc = BufferedChecksum::default();
         self.block_buf.clear();
         self.in_use.set_all(false);
+        self.output += self.block_len;
+        self.block_len = 0;
     }
 
     pub(crate) fn new(level: usize) -> Self {
@@ -189,14 +248,22 @@ impl EncoderInner {
             finished: false,
             block_size_100k: level,
             block_max_len,
-            block_crc: IEEE_NORMAL.build_hasher(),
+            block_crc: BufferedChecksum::default(),
             rle_buffer: 0,
             rle_count: 0,
             block_no: 1,
+            header_written: false,
             combined_crc: 0,
             in_use: BitArray::new(256),
-            mtf_buffer: vec![0_u16; level * 100_000 + 1], // EOBの分増やす
-            num_z: 0,
+            block_encoder: BlockEncoder::new(level),
+            index: BZip2Index::default(),
+            bits: 0,
+            output: 0,
+            block_len: 0,
+            #[cfg(feature = "std")]
+            threads: 1,
+            #[cfg(feature = "std")]
+            pending: Vec::new(),
         }
     }
 
@@ -205,16 +272,24 @@ impl EncoderInner {
         queue: &mut VecDeque<SmallBitVec<u32>>,
         val: SmallBitVec<u32>,
     ) {
-        self.num_z += val.len() as u64;
+        self.bits += val.len() as u64;
         queue.push_back(val);
     }
 
-    fn write_u8(&mut self, queue: &mut VecDeque<SmallBitVec<u32>>, val: u8) {
-        self.write(queue, SmallBitVec::new(u32::from(val), 8));
+    /// Records the block starting at the current bit position, which is
+    /// `bits` long.
+    fn push_index(&mut self, output: u64, crc: u32, bits: u64) {
+        self.index.push(BZip2BlockInfo {
+            input_bits: self.bits,
+            output,
+            crc,
+            level: self.block_size_100k as u8,
+        });
+        self.bits += bits;
     }
 
-    fn write_u16(&mut self, queue: &mut VecDeque<SmallBitVec<u32>>, val: u16) {
-        self.write(queue, SmallBitVec::new(u32::from(val), 16));
+    fn write_u8(&mut self, queue: &mut VecDeque<SmallBitVec<u32>>, val: u8) {
+        self.write(queue, SmallBitVec::new(u32::from(val), 8));
     }
 
     fn write_u32(&mut self, queue: &mut VecDeque<SmallBitVec<u32>>, val: u32) {
@@ -232,17 +307,10 @@ impl EncoderInner {
         }
 
         let nblock = self.block_buf.len();
-        let block_crc = self.block_crc.finish() as u32;
-
-        self.combined_crc =
-            ((self.combined_crc << 1) | (self.combined_crc >> 31)) ^ block_crc;
+        let block_crc = self.block_crc.value();
 
-        debug!(
-            "    block {}: crc = 0x{:08X}, combined CRC = 0x{:08X}, size = {}",
-            self.block_no, block_crc, self.combined_crc, nblock
-        );
-
-        if self.block_no == 1 {
+        if !self.header_written {
+            self.header_written = true;
             self.write_u8(queue, HEADER_B);
             self.write_u8(queue, HEADER_Z);
             self.write_u8(queue, HEADER_h);
@@ -251,32 +319,22 @@ impl EncoderInner {
         }
 
         if nblock > 0 {
-            self.write_u8(queue, 0x31);
-            self.write_u8(queue, 0x41);
-            self.write_u8(queue, 0x59);
-            self.write_u8(queue, 0x26);
-            self.write_u8(queue, 0x53);
-            self.write_u8(queue, 0x59);
-
-            /*-- Now the block's CRC, so it is in a known place. --*/
-            self.write_u32(queue, block_crc);
+            self.combined_crc = self.combined_crc.rotate_left(1) ^ block_crc;
 
-            /*--
-                Now a single bit indicating (non-)randomisation.
-                As of version 0.9.5, we use a better sorting algorithm
-                which makes randomisation unnecessary.  So always set
-                the randomised bit to 'no'.  Of course, the decoder
-                still needs to be able to handle randomised blocks
-                so as to maintain backwards compatibility with
-                older versions of bzip2.
-            --*/
-            self.write(queue, SmallBitVec::new(0, 1));
+            debug!(
+                "    block {}: crc = 0x{:08X}, combined CRC = 0x{:08X}, \
+                 size = {}",
+                self.block_no, block_crc, self.combined_crc, nblock
+            );
 
-            self.write_blockdata(queue)?;
+            self.encode_block(block_crc, queue)?;
             self.prepare_new_block();
         }
         /*-- If this is the last block, add the stream trailer. --*/
         if is_final {
+            #[cfg(feature = "std")]
+            self.write_pending(queue)?;
+
             self.write_u8(queue, 0x17);
             self.write_u8(queue, 0x72);
             self.write_u8(queue, 0x45);
@@ -290,6 +348,170 @@ impl EncoderInner {
         Ok(())
     }
 
+    fn encode_block(
+        &mut self,
+        block_crc: u32,
+        queue: &mut VecDeque<SmallBitVec<u32>>,
+    ) -> Result<(), CompressionError> {
+        #[cfg(feature = "std")]
+        {
+            if self.threads > 1 {
+                let data = mem::replace(
+                    &mut self.block_buf,
+                    Vec::with_capacity(self.block_size_100k * 100_000),
+                );
+                let in_use = mem::replace(&mut self.in_use, BitArray::new(256));
+                self.pending.push(PendingBlock {
+                    data,
+                    in_use,
+                    crc: block_crc,
+                    output: self.output,
+                });
+                if self.pending.len() >= self.threads {
+                    self.write_pending(queue)?;
+                }
+                return Ok(());
+            }
+        }
+        let start = self.block_encoder.num_z;
+        self.block_encoder.write_block(
+            &self.block_buf,
+            &self.in_use,
+            block_crc,
+            queue,
+        )?;
+        let bits = self.block_encoder.num_z - start;
+        self.push_index(self.output, block_crc, bits);
+        Ok(())
+    }
+
+    /// Compresses the pending blocks on the worker threads.
+    #[cfg(feature = "std")]
+    fn write_pending(
+        &mut self,
+        queue: &mut VecDeque<SmallBitVec<u32>>,
+    ) -> Result<(), CompressionError> {
+        let level = self.block_size_100k;
+        let blocks = thread::scope(|s| {
+            self.pending
+                .iter()
+                .map(|block| {
+                    s.spawn(move || {
+                        let mut queue = VecDeque::new();
+                        BlockEncoder::new(level).write_block(
+                            &block.data,
+                            &block.in_use,
+                            block.crc,
+                            &mut queue,
+                        )?;
+                        Ok::<_, CompressionError>(queue)
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|h| h.join().map_err(|_| CompressionError::Unexpected)?)
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+        for (pending, block) in mem::take(&mut self.pending).iter().zip(blocks)
+        {
+            let bits = block.iter().map(|b| b.len() as u64).sum();
+            self.push_index(pending.output, pending.crc, bits);
+            queue.extend(block);
+        }
+        Ok(())
+    }
+
+    fn next(
+        &mut self,
+        buf: u8,
+        queue: &mut VecDeque<SmallBitVec<u32>>,
+    ) -> Result<(), CompressionError> {
+        if self.rle_count == 0 {
+            self.rle_buffer = buf;
+            self.rle_count = 1;
+            return Ok(());
+        }
+
+        if self.rle_buffer == buf && self.rle_count < 255 {
+            self.rle_count += 1;
+            return Ok(());
+        }
+
+        self.write_rle();
+
+        self.rle_count = 1;
+        self.rle_buffer = buf;
+
+        if self.block_buf.len() >= self.block_max_len {
+            self.write_block(false, queue)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn write_rle(&mut self) {
+        // RLE output
+        (0..self.rle_count).for_each(|_| self.block_crc.push(self.rle_buffer));
+        self.block_len += self.rle_count as u64;
+
+        let ret_count = cmp::min(self.rle_count, 4);
+        for _ in 0..ret_count {
+            let v = self.rle_buffer;
+            self.in_use.set(usize::from(v), true);
+            self.block_buf.push(v);
+        }
+
+        if ret_count == 4 {
+            let v = (self.rle_count - 4) as u8;
+            self.in_use.set(usize::from(v), true);
+            self.block_buf.push(v);
+        }
+    }
+
+    fn flush(
+        &mut self,
+        queue: &mut VecDeque<SmallBitVec<u32>>,
+    ) -> Result<(), CompressionError> {
+        if !self.finished {
+            self.write_rle();
+            self.rle_count = 0;
+            self.write_block(false, queue)?;
+            #[cfg(feature = "std")]
+            self.write_pending(queue)?;
+            Ok(())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn finish(
+        &mut self,
+        queue: &mut VecDeque<SmallBitVec<u32>>,
+    ) -> Result<(), CompressionError> {
+        if !self.finished {
+            self.finished = true;
+            self.write_block(true, queue)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Compresses a block with BWT, MTF and Huffman coding.
+#[derive(Debug)]
+struct BlockEncoder {
+    mtf_buffer: Vec<u16>,
+    num_z: u64,
+}
+
+impl BlockEncoder {
+    fn new(level: usize) -> Self {
+        Self {
+            mtf_buffer: vec![0_u16; level * 100_000 + 1], // EOBの分増やす
+            num_z: 0,
+        }
+    }
+
     // const BZ_N_GROUPS: usize = 6;
     const BZ_N_ITERS: usize = 4;
     const BZ_MAX_SELECTORS: usize = (2 + (900_000 / BZ_G_SIZE));
@@ -297,17 +519,54 @@ impl EncoderInner {
     const BZ_LESSER_ICOST: u8 = 0;
     const BZ_GREATER_ICOST: u8 = 15;
 
+    fn write(
+        &mut self,
+        queue: &mut VecDeque<SmallBitVec<u32>>,
+        val: SmallBitVec<u32>,
+    ) {
+        self.num_z += val.len() as u64;
+        queue.push_back(val);
+    }
+
+    fn write_block(
+        &mut self,
+        block_buf: &[u8],
+        in_use: &BitArray,
+        block_crc: u32,
+        queue: &mut VecDeque<SmallBitVec<u32>>,
+    ) -> Result<(), CompressionError> {
+        for &b in &[0x31, 0x41, 0x59, 0x26, 0x53, 0x59] {
+            self.write(queue, SmallBitVec::new(b, 8));
+        }
+
+        /*-- Now the block's CRC, so it is in a known place. --*/
+        self.write(queue, SmallBitVec::new(block_crc, 32));
+
+        /*--
+            Now a single bit indicating (non-)randomisation.
+            As of version 0.9.5, we use a better sorting algorithm
+            which makes randomisation unnecessary.  So always set
+            the randomised bit to 'no'.  Of course, the decoder
+            still needs to be able to handle randomised blocks
+            so as to maintain backwards compatibility with
+            older versions of bzip2.
+        --*/
+        self.write(queue, SmallBitVec::new(0, 1));
+
+        self.write_blockdata(block_buf, in_use, queue)
+    }
+
     fn write_blockdata(
         &mut self,
+        block_buf: &[u8],
+        in_use: &BitArray,
         queue: &mut VecDeque<SmallBitVec<u32>>,
     ) -> Result<(), CompressionError> {
         let mut in_use_count = 0;
         let mut unseq2seq = [0_u8; 256];
 
-        for (d, _) in unseq2seq
-            .iter_mut()
-            .zip(self.in_use.iter())
-            .filter(|&(_, u)| u)
+        for (d, _) in
+            unseq2seq.iter_mut().zip(in_use.iter()).filter(|&(_, u)| u)
         {
             *d = in_use_count as u8;
             in_use_count += 1;
@@ -321,21 +580,18 @@ impl EncoderInner {
         let mut mtf_freq = vec![0; in_use_count + 2];
         let mut mtf_count = 0;
 
-        for (i, &s) in bwt(&self.block_buf, usize::from(u8::max_value()))
-            .iter()
-            .enumerate()
-        {
+        for (i, &s) in bwt(block_buf, usize::from(u8::MAX)).iter().enumerate() {
             debug_assert!(mtf_count <= i, "generateMTFValues(1)");
 
             /* MTF */
             let c = {
                 let j = if s == 0 {
                     self.write(queue, SmallBitVec::new(i as u32, 24));
-                    self.block_buf.len()
+                    block_buf.len()
                 } else {
                     s
                 } - 1;
-                let val = usize::from(unseq2seq[self.block_buf[j] as usize]);
+                let val = usize::from(unseq2seq[block_buf[j] as usize]);
                 debug_assert!(val < in_use_count, "generateMTFValues(2a)");
                 mtf_table.pop(val) as u16 + 1
             };
@@ -359,7 +615,7 @@ impl EncoderInner {
 
         debug!(
             "      {} in block, {} after MTF & 1-2 coding, {}+2 syms in use",
-            self.block_buf.len(),
+            block_buf.len(),
             mtf_count,
             in_use_count
         );
@@ -527,14 +783,17 @@ impl EncoderInner {
         /*--- Transmit the mapping table. ---*/
         {
             let in_use16 =
-                self.in_use.u16_iter().map(|x| x != 0).collect::<BitArray>();
+                in_use.u16_iter().map(|x| x != 0).collect::<BitArray>();
 
             let n_bits = self.num_z;
-            self.write_u16(
+            self.write(
                 queue,
-                in_use16
-                    .iter()
-                    .fold(0, |x, y| (x << 1) + if y { 1 } else { 0 }),
+                SmallBitVec::new(
+                    in_use16
+                        .iter()
+                        .fold(0, |x, y| (x << 1) + if y { 1 } else { 0 }),
+                    16,
+                ),
             );
 
             for i in in_use16.iter().enumerate().filter_map(|(i, x)| {
@@ -545,10 +804,8 @@ impl EncoderInner {
                 }
             }) {
                 for j in (0..16).map(|x| x + i) {
-                    let bv = SmallBitVec::new(
-                        if self.in_use.get(j) { 1 } else { 0 },
-                        1,
-                    );
+                    let bv =
+                        SmallBitVec::new(if in_use.get(j) { 1 } else { 0 }, 1);
                     self.write(queue, bv)
                 }
             }
@@ -667,76 +924,6 @@ impl EncoderInner {
             }
         }
     }
-
-    fn next(
-        &mut self,
-        buf: u8,
-        queue: &mut VecDeque<SmallBitVec<u32>>,
-    ) -> Result<(), CompressionError> {
-        if self.rle_count == 0 {
-            self.rle_buffer = buf;
-            self.rle_count = 1;
-            return Ok(());
-        }
-
-        if self.rle_buffer == buf && self.rle_count < 255 {
-            self.rle_count += 1;
-            return Ok(());
-        }
-
-        self.write_rle();
-
-        self.rle_count = 1;
-        self.rle_buffer = buf;
-
-        if self.block_buf.len() >= self.block_max_len {
-            self.write_block(false, queue)
-        } else {
-            Ok(())
-        }
-    }
-
-    fn write_rle(&mut self) {
-        // RLE output
-        (0..self.rle_count)
-            .for_each(|_| self.block_crc.write_u8(self.rle_buffer));
-
-        let ret_count = cmp::min(self.rle_count, 4);
-        for _ in 0..ret_count {
-            let v = self.rle_buffer;
-            self.in_use.set(usize::from(v), true);
-            self.block_buf.push(v);
-        }
-
-        if ret_count == 4 {
-            let v = (self.rle_count - 4) as u8;
-            self.in_use.set(usize::from(v), true);
-            self.block_buf.push(v);
-        }
-    }
-
-    fn flush(
-        &mut self,
-        queue: &mut VecDeque<SmallBitVec<u32>>,
-    ) -> Result<(), CompressionError> {
-        if !self.finished {
-            self.write_block(false, queue)
-        } else {
-            Ok(())
-        }
-    }
-
-    fn finish(
-        &mut self,
-        queue: &mut VecDeque<SmallBitVec<u32>>,
-    ) -> Result<(), CompressionError> {
-        if !self.finished {
-            self.finished = true;
-            self.write_block(true, queue)
-        } else {
-            Ok(())
-        }
-    }
 }
 
 #[cfg(test)]
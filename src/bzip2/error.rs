@@ -1,18 +1,48 @@
 use crate::core::fmt;
 use crate::error::CompressionError;
 
+/// The error of the bzip2 decoder.
+///
+/// `position` is the offset in bits in the compressed input at which the
+/// error was detected. Every variant maps to the `CompressionError` of the
+/// same name.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum BZip2Error {
     DataError,
-    DataErrorMagicFirst,
-    DataErrorMagic,
     UnexpectedEof,
     Unexpected,
+    /// The first stream does not start with `BZh1` to `BZh9`.
+    BadMagic {
+        position: u64,
+    },
+    /// The CRC of a block or the combined CRC of a stream is wrong.
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+        position: u64,
+    },
+    /// The code lengths do not make a Huffman table.
+    InvalidHuffmanTable {
+        position: u64,
+    },
+    /// The code is not in the Huffman table.
+    InvalidHuffmanCode {
+        position: u64,
+    },
+    /// The block is malformed.
+    InvalidBlock {
+        position: u64,
+    },
+    /// The data following a stream is not a stream.
+    TrailingGarbage {
+        position: u64,
+    },
 }
 
 impl fmt::Display for BZip2Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.description_in())
+        fmt::Display::fmt(&CompressionError::from(*self), f)
     }
 }
 
@@ -22,21 +52,75 @@ impl ::std::error::Error for BZip2Error {
         self.description_in()
     }
 
-    fn cause(&self) -> Option<&dyn (::std::error::Error)> {
+    fn cause(&self) -> Option<&dyn ::std::error::Error> {
         None
     }
 }
 
 impl BZip2Error {
+    #[cfg(feature = "std")]
     fn description_in(&self) -> &str {
         match *self {
             BZip2Error::DataError => "data integrity (CRC) error in data",
-            BZip2Error::DataErrorMagicFirst => {
-                "bad magic number (file not created by bzip2)"
-            }
-            BZip2Error::DataErrorMagic => "trailing garbage after EOF ignored",
             BZip2Error::UnexpectedEof => "file ends unexpectedly",
             BZip2Error::Unexpected => "unexpected error",
+            BZip2Error::BadMagic { .. } => {
+                "bad magic number (file not created by bzip2)"
+            }
+            BZip2Error::ChecksumMismatch { .. } => {
+                "data integrity (CRC) error in data"
+            }
+            BZip2Error::InvalidHuffmanTable { .. } => "invalid Huffman table",
+            BZip2Error::InvalidHuffmanCode { .. } => "invalid Huffman code",
+            BZip2Error::InvalidBlock { .. } => "invalid block",
+            BZip2Error::TrailingGarbage { .. } => {
+                "trailing garbage after the end of the stream"
+            }
+        }
+    }
+
+    /// Returns the offset in bits in the compressed input at which the
+    /// error was detected, if known.
+    pub fn position(&self) -> Option<u64> {
+        CompressionError::from(*self).position()
+    }
+
+    /// Moves the position by `bits`, for the errors of the data which is
+    /// decoded apart from the start of the input.
+    #[cfg(feature = "std")]
+    pub(crate) fn offset_by(self, bits: u64) -> Self {
+        match self {
+            BZip2Error::BadMagic { position } => BZip2Error::BadMagic {
+                position: position + bits,
+            },
+            BZip2Error::ChecksumMismatch {
+                expected,
+                actual,
+                position,
+            } => BZip2Error::ChecksumMismatch {
+                expected,
+                actual,
+                position: position + bits,
+            },
+            BZip2Error::InvalidHuffmanTable { position } => {
+                BZip2Error::InvalidHuffmanTable {
+                    position: position + bits,
+                }
+            }
+            BZip2Error::InvalidHuffmanCode { position } => {
+                BZip2Error::InvalidHuffmanCode {
+                    position: position + bits,
+                }
+            }
+            BZip2Error::InvalidBlock { position } => BZip2Error::InvalidBlock {
+                position: position + bits,
+            },
+            BZip2Error::TrailingGarbage { position } => {
+                BZip2Error::TrailingGarbage {
+                    position: position + bits,
+                }
+            }
+            e => e,
         }
     }
 }
@@ -44,9 +128,33 @@ impl BZip2Error {
 impl From<BZip2Error> for CompressionError {
     fn from(error: BZip2Error) -> Self {
         match error {
+            BZip2Error::DataError => CompressionError::DataError,
             BZip2Error::UnexpectedEof => CompressionError::UnexpectedEof,
             BZip2Error::Unexpected => CompressionError::Unexpected,
-            _ => CompressionError::DataError,
+            BZip2Error::BadMagic { position } => {
+                CompressionError::BadMagic { position }
+            }
+            BZip2Error::ChecksumMismatch {
+                expected,
+                actual,
+                position,
+            } => CompressionError::ChecksumMismatch {
+                expected,
+                actual,
+                position,
+            },
+            BZip2Error::InvalidHuffmanTable { position } => {
+                CompressionError::InvalidHuffmanTable { position }
+            }
+            BZip2Error::InvalidHuffmanCode { position } => {
+                CompressionError::InvalidHuffmanCode { position }
+            }
+            BZip2Error::InvalidBlock { position } => {
+                CompressionError::InvalidBlock { position }
+            }
+            BZip2Error::TrailingGarbage { position } => {
+                CompressionError::TrailingGarbage { position }
+            }
         }
     }
 }
@@ -0,0 +1,149 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::core::convert::TryInto;
+use crate::error::CompressionError;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The position of a block in a bzip2 file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BZip2BlockInfo {
+    /// The offset of the block magic in the compressed data, in bits.
+    pub input_bits: u64,
+    /// The offset of the block in the decompressed data.
+    pub output: u64,
+    /// The CRC of the decompressed data of the block.
+    pub crc: u32,
+    /// The block size of the stream in 100 kB.
+    pub level: u8,
+}
+
+impl BZip2BlockInfo {
+    /// Returns the byte offset in the compressed data from which the input
+    /// has to be passed to a decoder starting at the block.
+    pub fn input_offset(&self) -> u64 {
+        self.input_bits >> 3
+    }
+}
+
+/// A block-level index over a bzip2 file.
+///
+/// The index is recorded by `BZip2Encoder` while encoding, or by a
+/// decoder created with `BZip2Decoder::with_index` while decoding an
+/// existing file. A decoder created with `BZip2Decoder::with_block`
+/// starts the decoding at one of the blocks.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "bzip2")]
+///     # {
+///     let data = (0..300_000_u32)
+///         .map(|x| (x % 251) as u8 ^ (x % 1021) as u8)
+///         .collect::<Vec<u8>>();
+///     let mut encoder = BZip2Encoder::new(1);
+///     let compressed = data
+///         .iter()
+///         .cloned()
+///         .encode(&mut encoder, Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///     let index = BZip2Index::from_bytes(&encoder.index().to_bytes()).unwrap();
+///
+///     let block = index.find(250_000).unwrap();
+///     let decompressed = compressed
+///         .iter()
+///         .skip(block.input_offset() as usize)
+///         .cloned()
+///         .decode(&mut BZip2Decoder::with_block(block))
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///     assert_eq!(&data[block.output as usize..], &decompressed[..]);
+///     # }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BZip2Index {
+    blocks: Vec<BZip2BlockInfo>,
+}
+
+impl BZip2Index {
+    const MAGIC: [u8; 4] = *b"BZIX";
+    const ENTRY_LEN: usize = 21;
+
+    /// Returns the blocks in the order of the file.
+    pub fn blocks(&self) -> &[BZip2BlockInfo] {
+        &self.blocks
+    }
+
+    /// Returns the block which contains `output` bytes of the decompressed
+    /// data.
+    pub fn find(&self, output: u64) -> Option<&BZip2BlockInfo> {
+        match self.blocks.binary_search_by_key(&output, |b| b.output) {
+            Ok(i) => Some(&self.blocks[i]),
+            Err(0) => None,
+            Err(i) => Some(&self.blocks[i - 1]),
+        }
+    }
+
+    pub(crate) fn push(&mut self, block: BZip2BlockInfo) {
+        self.blocks.push(block);
+    }
+
+    /// Serializes the index.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut ret =
+            Vec::with_capacity(12 + self.blocks.len() * Self::ENTRY_LEN);
+        ret.extend_from_slice(&Self::MAGIC);
+        ret.extend_from_slice(&(self.blocks.len() as u64).to_le_bytes());
+        for b in &self.blocks {
+            ret.extend_from_slice(&b.input_bits.to_le_bytes());
+            ret.extend_from_slice(&b.output.to_le_bytes());
+            ret.extend_from_slice(&b.crc.to_le_bytes());
+            ret.push(b.level);
+        }
+        ret
+    }
+
+    /// Deserializes an index written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CompressionError> {
+        if bytes.len() < 12 {
+            return Err(CompressionError::UnexpectedEof);
+        }
+        if bytes[..4] != Self::MAGIC {
+            return Err(CompressionError::DataError);
+        }
+        let len = u64::from_le_bytes(bytes[4..12].try_into().unwrap());
+        let entries = &bytes[12..];
+        if !entries.len().is_multiple_of(Self::ENTRY_LEN)
+            || (entries.len() / Self::ENTRY_LEN) as u64 != len
+        {
+            return Err(CompressionError::DataError);
+        }
+        let blocks = entries
+            .chunks(Self::ENTRY_LEN)
+            .map(|e| BZip2BlockInfo {
+                input_bits: u64::from_le_bytes(e[..8].try_into().unwrap()),
+                output: u64::from_le_bytes(e[8..16].try_into().unwrap()),
+                crc: u32::from_le_bytes(e[16..20].try_into().unwrap()),
+                level: e[20],
+            })
+            .collect::<Vec<_>>();
+        if blocks.iter().any(|b| !(1..=9).contains(&b.level))
+            || blocks.windows(2).any(|w| {
+                w[0].input_bits >= w[1].input_bits || w[0].output > w[1].output
+            })
+        {
+            return Err(CompressionError::DataError);
+        }
+        Ok(Self { blocks })
+    }
+}
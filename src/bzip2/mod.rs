@@ -9,7 +9,9 @@
 pub(crate) mod decoder;
 pub(crate) mod encoder;
 pub(crate) mod error;
+pub(crate) mod index;
 mod mtf;
+mod parallel;
 
 const HEADER_B: u8 = 0x42;
 const HEADER_Z: u8 = 0x5a;
@@ -19,6 +21,11 @@ const HEADER_0: u8 = 0x30;
 
 const BZ_G_SIZE: usize = 50;
 
+/// The 48-bit magic which starts a block.
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+/// The 48-bit magic which starts the end of stream marker.
+const EOS_MAGIC: u64 = 0x1772_4538_5090;
+
 #[cfg(test)]
 mod tests {
     use crate::action::Action;
@@ -32,7 +39,6 @@ mod tests {
     #[cfg(not(feature = "std"))]
     use alloc::vec::Vec;
     use log::debug;
-    use simple_logger;
 
     fn setup() {
         let _ = simple_logger::init();
@@ -170,4 +176,410 @@ mod tests {
         }
         assert_eq!(decompressed, Ok(data));
     }
+
+    #[test]
+    fn test_slice() {
+        use crate::traits::decoder::tests::decompress_slices;
+        use crate::traits::encoder::tests::compress_slices;
+
+        let testarray = include_bytes!("../../data/sample2.ref");
+        let expected = testarray
+            .iter()
+            .cloned()
+            .encode(&mut BZip2Encoder::new(1), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        for &(chunk, out_len) in &[(1, 0x1000), (0x1000, 7)] {
+            let encoded = compress_slices(
+                &mut BZip2Encoder::new(1),
+                testarray,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(encoded == expected);
+            let decoded = decompress_slices(
+                &mut BZip2Decoder::new(),
+                &encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded[..] == testarray[..]);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_threads() {
+        use crate::io::encoder::EncoderWriter;
+        use std::io::Write;
+
+        setup();
+        let data = include_bytes!("../../data/sample1.ref")
+            .iter()
+            .chain(include_bytes!("../../data/sample2.ref").iter())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let expected = data
+            .iter()
+            .cloned()
+            .encode(&mut BZip2Encoder::new(1), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        for &threads in &[1, 2, 3, 8] {
+            let ret = data
+                .iter()
+                .cloned()
+                .encode(
+                    &mut BZip2Encoder::with_threads(1, threads),
+                    Action::Finish,
+                )
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(ret, expected);
+        }
+
+        let write = |encoder| {
+            let mut writer = EncoderWriter::new(Vec::new(), encoder);
+            for (i, chunk) in data.chunks(150_000).enumerate() {
+                writer.write_all(chunk).unwrap();
+                if i % 2 == 1 {
+                    writer.flush().unwrap();
+                }
+            }
+            writer.finish().unwrap()
+        };
+        let ret = write(BZip2Encoder::with_threads(1, 4));
+        assert_eq!(ret, write(BZip2Encoder::new(1)));
+        check_unzip(&ret, &data);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_decode_threads() {
+        use crate::bzip2::error::BZip2Error;
+        use crate::bzip2::parallel::{bits_at, find_block_magic};
+        use crate::bzip2::BLOCK_MAGIC;
+        use crate::traits::decoder::tests::decompress_slices;
+
+        setup();
+        let data = include_bytes!("../../data/sample1.ref")
+            .iter()
+            .chain(include_bytes!("../../data/sample5.ref").iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut encoded = data
+            .iter()
+            .cloned()
+            .encode(&mut BZip2Encoder::with_threads(1, 4), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        // the second stream
+        encoded.extend_from_slice(include_bytes!("../../data/sample2.bz2"));
+        let mut expected = data;
+        expected.extend_from_slice(include_bytes!("../../data/sample2.ref"));
+
+        for &threads in &[1, 2, 5] {
+            let decoded = encoded
+                .iter()
+                .cloned()
+                .decode(&mut BZip2Decoder::with_threads(threads))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert!(decoded == expected);
+            let decoded = decompress_slices(
+                &mut BZip2Decoder::with_threads(threads),
+                &encoded,
+                0x1000,
+                0x1000,
+            )
+            .unwrap();
+            assert!(decoded == expected);
+        }
+
+        // the magics at any bit offset
+        for shift in 0..8 {
+            let pos = 24 + shift;
+            let buf =
+                (u128::from(BLOCK_MAGIC) << (128 - 48 - pos)).to_be_bytes();
+            assert_eq!(find_block_magic(&buf, 0, 10), vec![pos as u64]);
+            assert_eq!(find_block_magic(&buf, pos as u64 + 1, 10), vec![]);
+            assert_eq!(bits_at(&buf, pos as u64, 48), Some(BLOCK_MAGIC));
+        }
+
+        let mut corrupted = encoded.clone();
+        let len = corrupted.len();
+        corrupted[len / 3] ^= 0x10;
+        assert!(corrupted
+            .iter()
+            .cloned()
+            .decode(&mut BZip2Decoder::with_threads(3))
+            .collect::<Result<Vec<_>, _>>()
+            .is_err());
+
+        assert!(encoded[..len - 1]
+            .iter()
+            .cloned()
+            .decode(&mut BZip2Decoder::with_threads(3))
+            .collect::<Result<Vec<_>, _>>()
+            .is_err());
+
+        // the corrupted block which does not end where the symbols do
+        let mut corrupted = b"aaaaaaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbbbbbbbbbbb\
+                              aabbaabbaabbaabbcccccccccccccc\n"
+            .repeat(3)
+            .iter()
+            .cloned()
+            .encode(&mut BZip2Encoder::new(1), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        corrupted[278 >> 3] ^= 0x80 >> (278 & 7);
+        for &threads in &[1, 2] {
+            assert_eq!(
+                corrupted
+                    .iter()
+                    .cloned()
+                    .decode(&mut BZip2Decoder::with_threads(threads))
+                    .collect::<Result<Vec<_>, _>>(),
+                Err(BZip2Error::InvalidBlock { position: 32 })
+            );
+        }
+    }
+
+    #[test]
+    fn test_index() {
+        use crate::bzip2::index::BZip2Index;
+
+        setup();
+        let data = include_bytes!("../../data/sample1.ref")
+            .iter()
+            .chain(include_bytes!("../../data/sample2.ref").iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut encoder = BZip2Encoder::new(1);
+        let mut encoded = data
+            .iter()
+            .cloned()
+            .encode(&mut encoder, Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let index = encoder.index().clone();
+        assert_eq!(index.blocks().len(), 4);
+        assert_eq!(index.blocks()[0].input_bits, 32);
+        assert_eq!(index, BZip2Index::from_bytes(&index.to_bytes()).unwrap());
+        assert!(BZip2Index::from_bytes(&index.to_bytes()[1..]).is_err());
+
+        #[cfg(feature = "std")]
+        {
+            let mut encoder = BZip2Encoder::with_threads(1, 3);
+            let _ = data
+                .iter()
+                .cloned()
+                .encode(&mut encoder, Action::Finish)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(encoder.index(), &index);
+        }
+
+        // the following streams
+        encoded.extend_from_slice(include_bytes!("../../data/sample1.bz2"));
+        encoded.extend_from_slice(include_bytes!("../../data/sample2.bz2"));
+        let mut expected = data.clone();
+        expected.extend_from_slice(include_bytes!("../../data/sample1.ref"));
+        expected.extend_from_slice(include_bytes!("../../data/sample2.ref"));
+
+        let mut decoder = BZip2Decoder::with_index();
+        let decoded = encoded
+            .iter()
+            .cloned()
+            .decode(&mut decoder)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(decoded == expected);
+        let scanned = decoder.index().unwrap();
+        assert_eq!(&scanned.blocks()[..4], index.blocks());
+        assert_eq!(scanned.blocks().len(), 7);
+
+        for block in scanned.blocks() {
+            let decoded = encoded[block.input_offset() as usize..]
+                .iter()
+                .cloned()
+                .decode(&mut BZip2Decoder::with_block(block))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert!(decoded[..] == expected[block.output as usize..]);
+        }
+        let block = scanned.find(data.len() as u64 - 1).unwrap();
+        assert_eq!(block, &index.blocks()[3]);
+    }
+
+    #[test]
+    fn test_stream() {
+        use crate::traits::decoder::tests::decompress_slices;
+
+        let sample1 = include_bytes!("../../data/sample1.bz2");
+        let sample2 = include_bytes!("../../data/sample2.bz2");
+        let encoded = [&sample1[..], &sample2[..]].concat();
+        let expected = [
+            &include_bytes!("../../data/sample1.ref")[..],
+            &include_bytes!("../../data/sample2.ref")[..],
+        ]
+        .concat();
+        for &(chunk, out_len) in &[(1, 0x1000), (0x1000, 7)] {
+            let decoded = decompress_slices(
+                &mut BZip2Decoder::new(),
+                &encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded == expected);
+            #[cfg(feature = "std")]
+            {
+                let decoded = decompress_slices(
+                    &mut BZip2Decoder::with_threads(2),
+                    &encoded,
+                    chunk,
+                    out_len,
+                )
+                .unwrap();
+                assert!(decoded == expected);
+            }
+        }
+
+        let mut corrupted = sample1.to_vec();
+        corrupted[100] ^= 1;
+        assert!(decompress_slices(
+            &mut BZip2Decoder::new(),
+            &corrupted,
+            1,
+            0x1000
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        use crate::bzip2::error::BZip2Error;
+        use crate::bzip2::BLOCK_MAGIC;
+        use crate::error::CompressionError;
+        use crate::traits::decoder::tests::decompress_slices;
+
+        let encoded = include_bytes!("../../data/sample1.bz2");
+        let len = encoded.len();
+        let decode = |data: &[u8]| {
+            data.iter()
+                .cloned()
+                .decode(&mut BZip2Decoder::new())
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let mut data = encoded.to_vec();
+        data[0] = b'b';
+        assert_eq!(decode(&data), Err(BZip2Error::BadMagic { position: 0 }));
+
+        let mut data = encoded.to_vec();
+        data.extend_from_slice(b"garbage");
+        assert_eq!(
+            decode(&data),
+            Err(BZip2Error::TrailingGarbage {
+                position: (len as u64) << 3
+            })
+        );
+
+        // the CRC of the first block
+        let mut data = encoded.to_vec();
+        data[10] ^= 0x80;
+        let err = decode(&data).unwrap_err();
+        match err {
+            BZip2Error::ChecksumMismatch {
+                expected,
+                actual,
+                position,
+            } => {
+                assert_eq!(expected ^ actual, 0x8000_0000);
+                assert_eq!(position, 32);
+            }
+            e => panic!("{:?}", e),
+        }
+        assert_eq!(
+            CompressionError::from(err),
+            CompressionError::ChecksumMismatch {
+                expected: match err {
+                    BZip2Error::ChecksumMismatch { expected, .. } => expected,
+                    _ => unreachable!(),
+                },
+                actual: match err {
+                    BZip2Error::ChecksumMismatch { actual, .. } => actual,
+                    _ => unreachable!(),
+                },
+                position: 32,
+            }
+        );
+
+        // the magic of the first block
+        let mut data = encoded.to_vec();
+        data[4] = 0;
+        assert_eq!(
+            decode(&data),
+            Err(BZip2Error::InvalidBlock { position: 32 })
+        );
+
+        // the over-subscribed code lengths of the five symbols of 1 bit
+        let mut bits = vec![
+            (0x425A_6831, 32),
+            (BLOCK_MAGIC, 48),
+            (0, 32 + 1 + 24),
+            (0x8000, 16),
+            (0xE000, 16),
+            (2, 3),
+            (1, 15),
+            (0, 1),
+        ];
+        for _ in 0..2 {
+            bits.extend_from_slice(&[(1, 5), (0, 5)]);
+        }
+        let mut table = Vec::new();
+        let (mut acc, mut n) = (0u64, 0);
+        for (v, len) in bits {
+            acc = acc << len | v;
+            n += len;
+            while n >= 8 {
+                n -= 8;
+                table.push((acc >> n) as u8);
+            }
+        }
+        assert_eq!(
+            decode(&table),
+            Err(BZip2Error::InvalidHuffmanTable { position: 208 })
+        );
+
+        // the code lengths cut off
+        let sample = include_bytes!("../../data/sample3.bz2");
+        for len in 4..64 {
+            assert!(decode(&sample[..len]).is_err());
+            assert!(decompress_slices(
+                &mut BZip2Decoder::new(),
+                &sample[..len],
+                1,
+                7
+            )
+            .is_err());
+        }
+
+        #[cfg(feature = "std")]
+        {
+            let mut data = encoded.to_vec();
+            data[10] ^= 0x80;
+            assert_eq!(
+                data.iter()
+                    .cloned()
+                    .decode(&mut BZip2Decoder::with_threads(2))
+                    .collect::<Result<Vec<_>, _>>(),
+                Err(err)
+            );
+        }
+    }
 }
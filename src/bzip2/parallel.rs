@@ -0,0 +1,325 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "std")]
+
+use crate::bzip2::decoder::{BZip2DecoderBase, DecodedBlock};
+use crate::bzip2::error::BZip2Error;
+use crate::bzip2::{
+    HEADER_h, BLOCK_MAGIC, EOS_MAGIC, HEADER_0, HEADER_B, HEADER_Z,
+};
+use crate::status::Status;
+use std::collections::vec_deque::VecDeque;
+use std::thread;
+
+/// Returns `len` bits at the bit `pos` of `data`, or `None` if `data` is
+/// too short.
+pub(crate) fn bits_at(data: &[u8], pos: u64, len: usize) -> Option<u64> {
+    debug_assert!(len <= 56);
+    let start = (pos >> 3) as usize;
+    let skip = (pos & 7) as usize;
+    let bytes = (skip + len + 7) >> 3;
+    let buf = data.get(start..start + bytes)?;
+    let value = buf.iter().fold(0_u64, |v, &b| (v << 8) | u64::from(b));
+    Some((value >> ((bytes << 3) - skip - len)) & ((1 << len) - 1))
+}
+
+/// Returns the bit positions of the block magics which start at or after
+/// the bit `from` of `data`, up to `limit` positions.
+pub(crate) fn find_block_magic(
+    data: &[u8],
+    from: u64,
+    limit: usize,
+) -> Vec<u64> {
+    const MASK: u64 = (1 << 48) - 1;
+    let mut ret = Vec::new();
+    let mut value = 0_u64;
+    let first = (from >> 3) as usize;
+    for (i, &b) in data.iter().enumerate().skip(first) {
+        value = (value << 8) | u64::from(b);
+        let end = ((i as u64) + 1) << 3;
+        // the magic which ends at `end - k`, from the earliest
+        for k in (0..8).rev() {
+            if ((value >> k) & MASK) == BLOCK_MAGIC {
+                match (end - k).checked_sub(48) {
+                    Some(pos) if pos >= from => {
+                        ret.push(pos);
+                        if ret.len() == limit {
+                            return ret;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    ret
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Header,
+    Blocks,
+    Finished,
+}
+
+/// Decodes the blocks of a bzip2 stream on multiple threads.
+///
+/// The input is buffered, scanned for the block magics and the candidate
+/// blocks are decoded at once. A candidate is used only if it starts
+/// where the preceding block ends, so the magics which happen to appear
+/// in the compressed data are discarded.
+#[derive(Debug)]
+pub(crate) struct ParallelDecoder {
+    threads: usize,
+    input: Vec<u8>,
+    eof: bool,
+    /// Whether the input may continue after the end of the iterator.
+    partial: bool,
+    starved: bool,
+    /// The bit position of the start of `input` in the whole input.
+    base: u64,
+    pos: u64,
+    state: State,
+    stream_no: usize,
+    block_size_100k: usize,
+    combined_crc: u32,
+    output: VecDeque<u8>,
+}
+
+impl ParallelDecoder {
+    pub(crate) fn new(threads: usize) -> Self {
+        if threads == 0 {
+            panic!("invalid threads");
+        }
+        Self {
+            threads,
+            input: Vec::new(),
+            eof: false,
+            partial: false,
+            starved: false,
+            base: 0,
+            pos: 0,
+            state: State::Header,
+            stream_no: 0,
+            block_size_100k: 0,
+            combined_crc: 0,
+            output: VecDeque::new(),
+        }
+    }
+
+    /// The length of the input after the current position which surely
+    /// contains a whole block.
+    fn max_block_len(&self) -> usize {
+        self.block_size_100k * 250_000 + 1_000
+    }
+
+    /// Reads the input until `len` bytes follow the current position.
+    fn fill<I: Iterator<Item = u8>>(&mut self, iter: &mut I, len: usize) {
+        let need = (self.pos >> 3) as usize + len;
+        while !self.eof && self.input.len() < need {
+            match iter.next() {
+                Some(b) => self.input.push(b),
+                None if self.partial => {
+                    self.starved = true;
+                    break;
+                }
+                None => self.eof = true,
+            }
+        }
+    }
+
+    /// Drops the input before the current position.
+    fn compact(&mut self) {
+        let start = (self.pos >> 3) as usize;
+        let _ = self.input.drain(..start);
+        self.base += self.pos & !7;
+        self.pos &= 7;
+    }
+
+    fn read_header<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+    ) -> Result<(), BZip2Error> {
+        self.fill(iter, 4);
+        if self.starved {
+            return Ok(());
+        }
+        let start = (self.pos >> 3) as usize;
+        if self.stream_no > 0 && self.input.len() == start {
+            self.state = State::Finished;
+            return Ok(());
+        }
+        let position = self.base + self.pos;
+        let magic_err = if self.stream_no == 0 {
+            BZip2Error::BadMagic { position }
+        } else {
+            BZip2Error::TrailingGarbage { position }
+        };
+        match self.input.get(start..start + 4) {
+            Some(&[b, z, h, level])
+                if [b, z, h] == [HEADER_B, HEADER_Z, HEADER_h]
+                    && (1 + HEADER_0..=9 + HEADER_0).contains(&level) =>
+            {
+                self.block_size_100k = usize::from(level - HEADER_0);
+            }
+            _ => return Err(magic_err),
+        }
+        self.pos += 32;
+        self.stream_no += 1;
+        self.combined_crc = 0;
+        self.state = State::Blocks;
+        Ok(())
+    }
+
+    fn read_trailer<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+    ) -> Result<(), BZip2Error> {
+        let crc = bits_at(&self.input, self.pos + 48, 32)
+            .ok_or(BZip2Error::UnexpectedEof)?;
+        if crc as u32 != self.combined_crc {
+            return Err(BZip2Error::ChecksumMismatch {
+                expected: crc as u32,
+                actual: self.combined_crc,
+                position: self.base + self.pos,
+            });
+        }
+        self.pos = (self.pos + 80 + 7) & !7;
+        self.compact();
+        self.fill(iter, 1);
+        self.state = State::Header;
+        Ok(())
+    }
+
+    fn read_blocks<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+    ) -> Result<(), BZip2Error> {
+        let max_block_len = self.max_block_len();
+        self.fill(iter, max_block_len * self.threads);
+        if self.starved {
+            return Ok(());
+        }
+        match bits_at(&self.input, self.pos, 48) {
+            Some(EOS_MAGIC) => return self.read_trailer(iter),
+            Some(BLOCK_MAGIC) => {}
+            Some(_) => {
+                return Err(BZip2Error::InvalidBlock {
+                    position: self.base + self.pos,
+                })
+            }
+            None => return Err(BZip2Error::UnexpectedEof),
+        }
+
+        let mut candidates = vec![self.pos];
+        candidates.extend(find_block_magic(
+            &self.input,
+            self.pos + 1,
+            self.threads - 1,
+        ));
+        let input = &self.input;
+        let level = self.block_size_100k;
+        let blocks = thread::scope(|s| {
+            candidates
+                .iter()
+                .map(|&pos| {
+                    s.spawn(move || {
+                        BZip2DecoderBase::decode_block(input, pos, level)
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|h| h.join().unwrap_or(Err(BZip2Error::Unexpected)))
+                .collect::<Vec<_>>()
+        });
+
+        for (pos, block) in candidates.into_iter().zip(blocks) {
+            if pos != self.pos {
+                continue;
+            }
+            match block {
+                Ok(DecodedBlock { data, crc, end }) => {
+                    self.combined_crc = self.combined_crc.rotate_left(1) ^ crc;
+                    self.output.extend(data);
+                    self.pos = end;
+                }
+                // the block may be cut off
+                Err(_)
+                    if !self.eof
+                        && self.input.len() - ((pos >> 3) as usize)
+                            < max_block_len =>
+                {
+                    break;
+                }
+                Err(e) => return Err(e.offset_by(self.base)),
+            }
+        }
+        self.compact();
+        Ok(())
+    }
+
+    pub(crate) fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+    ) -> Option<Result<u8, BZip2Error>> {
+        loop {
+            if let Some(b) = self.output.pop_front() {
+                return Some(Ok(b));
+            }
+            if self.starved {
+                return None;
+            }
+            let ret = match self.state {
+                State::Header => self.read_header(iter),
+                State::Blocks => self.read_blocks(iter),
+                State::Finished => return None,
+            };
+            if let Err(e) = ret {
+                self.state = State::Finished;
+                return Some(Err(e));
+            }
+        }
+    }
+
+    /// Decodes `input` as a part of the stream, which is buffered until
+    /// enough of it has arrived unless `finish` is set.
+    pub(crate) fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, BZip2Error> {
+        let mut iter = input.iter().cloned();
+        let mut produced = 0;
+        let mut done = false;
+        self.partial = !finish;
+        self.starved = false;
+        while produced < output.len() {
+            match self.next(&mut iter) {
+                Some(Ok(s)) => {
+                    output[produced] = s;
+                    produced += 1;
+                }
+                Some(Err(e)) => {
+                    self.partial = false;
+                    return Err(e);
+                }
+                None => {
+                    done = !self.starved;
+                    break;
+                }
+            }
+        }
+        self.partial = false;
+        self.starved = false;
+        Ok(Status {
+            consumed: input.len() - iter.len(),
+            produced,
+            done,
+        })
+    }
+}
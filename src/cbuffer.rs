@@ -7,7 +7,7 @@
 
 use crate::core::iter;
 use crate::core::ops::{Index, IndexMut};
-use crate::core::{ptr, usize};
+use crate::core::ptr;
 #[cfg(not(feature = "std"))]
 use alloc::boxed::Box;
 #[cfg(not(feature = "std"))]
@@ -34,6 +34,9 @@ impl<T> CircularBuffer<T> {
     pub(crate) fn append(&mut self, data: &[T]) {
         let len = self.data.len() - self.pos;
         let count = data.len();
+        if count == 0 {
+            return;
+        }
 
         unsafe {
             let daddr = data.get_unchecked(0);
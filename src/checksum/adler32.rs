@@ -0,0 +1,157 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::core::hash::Hasher;
+
+/// The Adler-32 checksum used by zlib.
+#[derive(Clone, Debug)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+    t: u16,
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Adler32 {
+    const LOOP_SIZE: u16 = 5549;
+    const MOD_ADLER: u32 = 0xFFF1;
+    /// The largest length which can be summed up without the overflow.
+    const NMAX: usize = 5552;
+
+    pub fn new() -> Adler32 {
+        Self {
+            a: 1,
+            b: 0,
+            t: Self::LOOP_SIZE,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut a = self.a % Self::MOD_ADLER;
+        let mut b = self.b % Self::MOD_ADLER;
+        for chunk in data.chunks(Self::NMAX) {
+            let mut blocks = chunk.chunks_exact(16);
+            for block in &mut blocks {
+                for &x in block {
+                    a += u32::from(x);
+                    b += a;
+                }
+            }
+            for &x in blocks.remainder() {
+                a += u32::from(x);
+                b += a;
+            }
+            a %= Self::MOD_ADLER;
+            b %= Self::MOD_ADLER;
+        }
+        self.a = a;
+        self.b = b;
+        self.t = Self::LOOP_SIZE;
+    }
+
+    /// Returns the checksum of the data written so far.
+    pub fn value(&self) -> u32 {
+        ((self.b % Self::MOD_ADLER) << 16) | (self.a % Self::MOD_ADLER)
+    }
+
+    /// Returns the checksum of the concatenation of the data whose
+    /// checksums are `adler1` and `adler2`, where `len2` is the length of
+    /// the latter.
+    pub fn combine(adler1: u32, adler2: u32, len2: u64) -> u32 {
+        const BASE: u64 = Adler32::MOD_ADLER as u64;
+        let rem = len2 % BASE;
+        let a1 = u64::from(adler1 & 0xFFFF);
+        let b1 = u64::from(adler1 >> 16);
+        let a2 = u64::from(adler2 & 0xFFFF);
+        let b2 = u64::from(adler2 >> 16);
+        let a = (a1 + a2 + BASE - 1) % BASE;
+        let b = (rem * a1 + b1 + b2 + BASE - rem) % BASE;
+        ((b << 16) | a) as u32
+    }
+}
+
+impl Hasher for Adler32 {
+    fn write_u8(&mut self, byte: u8) {
+        let byte = u32::from(byte);
+        self.a += byte;
+        self.b += self.a;
+        if self.t == 0 {
+            self.t = Self::LOOP_SIZE;
+            self.a %= Self::MOD_ADLER;
+            self.b %= Self::MOD_ADLER;
+        } else {
+            self.t -= 1;
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        u64::from(self.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_adler32() {
+        let mut hasher = Adler32::new();
+        hasher.update(b"123456789");
+        assert_eq!(hasher.value(), 0x091e_01de);
+    }
+
+    #[test]
+    fn test_update() {
+        let data = thread_rng()
+            .sample_iter::<u8, _>(&Standard)
+            .take(100_000)
+            .collect::<Vec<_>>();
+        let mut bytewise = Adler32::new();
+        data.iter().for_each(|&x| bytewise.write_u8(x));
+        for &len in &[1, 15, 16, 5552, 5553, 40_000] {
+            let mut hasher = Adler32::new();
+            hasher.write_u8(data[0]);
+            data[1..].chunks(len).for_each(|c| hasher.update(c));
+            assert_eq!(hasher.value(), bytewise.value());
+        }
+        let mut hasher = Adler32::new();
+        hasher.update(&[0xFF; 100_000]);
+        let mut bytewise = Adler32::new();
+        (0..100_000).for_each(|_| bytewise.write_u8(0xFF));
+        assert_eq!(hasher.value(), bytewise.value());
+    }
+
+    #[test]
+    fn test_combine() {
+        let adler = |data: &[u8]| {
+            let mut hasher = Adler32::new();
+            hasher.update(data);
+            hasher.value()
+        };
+        let data = (0..90_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        for i in [0, 1, 9, 45_000, 89_999, 90_000].iter().cloned() {
+            let (a, b) = data.split_at(i);
+            assert_eq!(
+                Adler32::combine(adler(a), adler(b), b.len() as u64),
+                adler(&data)
+            );
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::core::hash::Hasher;
+
+const BUF_LEN: usize = 256;
+
+/// A checksum of the bytes given one at a time, which are gathered so
+/// that the checksum is updated with the slices.
+#[derive(Clone, Debug)]
+pub(crate) struct BufferedChecksum<H: Hasher> {
+    hasher: H,
+    buf: [u8; BUF_LEN],
+    len: usize,
+}
+
+impl<H: Hasher + Default> Default for BufferedChecksum<H> {
+    fn default() -> Self {
+        Self::new(H::default())
+    }
+}
+
+impl<H: Hasher> BufferedChecksum<H> {
+    pub(crate) fn new(hasher: H) -> Self {
+        Self {
+            hasher,
+            buf: [0; BUF_LEN],
+            len: 0,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn push(&mut self, b: u8) {
+        self.buf[self.len] = b;
+        self.len += 1;
+        if self.len == BUF_LEN {
+            self.flush();
+        }
+    }
+
+    /// Returns the checksum of the data given so far.
+    pub(crate) fn value(&mut self) -> u32 {
+        self.flush();
+        self.hasher.finish() as u32
+    }
+
+    fn flush(&mut self) {
+        if self.len > 0 {
+            self.hasher.write(&self.buf[..self.len]);
+            self.len = 0;
+        }
+    }
+}
@@ -0,0 +1,466 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::core::hash::Hasher;
+use lazy_static::lazy_static;
+
+/// The lookup tables for slice-by-16.
+///
+/// `Tables[k][i]` is the CRC of the byte `i` followed by `k` zero bytes.
+pub(crate) type Tables = [[u32; 256]; 16];
+
+lazy_static! {
+    pub(crate) static ref IEEE_REVERSE_TABLES: Tables =
+        make_tables_reverse(0xEDB8_8320);
+    pub(crate) static ref IEEE_NORMAL_TABLES: Tables =
+        make_tables_normal(0x04C1_1DB7);
+    pub(crate) static ref CASTAGNOLI_TABLES: Tables =
+        make_tables_reverse(0x82F6_3B78);
+}
+
+fn make_tables_reverse(poly: u32) -> Tables {
+    let mut tables = [[0u32; 256]; 16];
+    for (i, item) in tables[0].iter_mut().enumerate() {
+        let mut value = i as u32;
+        for _ in 0..8 {
+            value = if (value & 1) == 1 {
+                (value >> 1) ^ poly
+            } else {
+                value >> 1
+            }
+        }
+        *item = value;
+    }
+    for k in 1..16 {
+        for i in 0..256 {
+            let prev = tables[k - 1][i];
+            tables[k][i] = (prev >> 8) ^ tables[0][(prev & 0xFF) as usize];
+        }
+    }
+    tables
+}
+
+fn make_tables_normal(poly: u32) -> Tables {
+    let mut tables = [[0u32; 256]; 16];
+    for (i, item) in tables[0].iter_mut().enumerate() {
+        let mut value = (i << 24) as u32;
+        for _ in 0..8 {
+            value = if value & (1 << 31) == (1 << 31) {
+                (value << 1) ^ poly
+            } else {
+                value << 1
+            }
+        }
+        *item = value;
+    }
+    for k in 1..16 {
+        for i in 0..256 {
+            let prev = tables[k - 1][i];
+            tables[k][i] = (prev << 8) ^ tables[0][(prev >> 24) as usize];
+        }
+    }
+    tables
+}
+
+#[inline]
+fn fold_reverse(value: u32, tables: &Tables, chunk: &[u8]) -> u32 {
+    let n = chunk.len();
+    let a = value
+        ^ (u32::from(chunk[0])
+            | (u32::from(chunk[1]) << 8)
+            | (u32::from(chunk[2]) << 16)
+            | (u32::from(chunk[3]) << 24));
+    let mut ret = tables[n - 1][(a & 0xFF) as usize]
+        ^ tables[n - 2][((a >> 8) & 0xFF) as usize]
+        ^ tables[n - 3][((a >> 16) & 0xFF) as usize]
+        ^ tables[n - 4][(a >> 24) as usize];
+    for (i, &b) in chunk[4..].iter().enumerate() {
+        ret ^= tables[n - 5 - i][b as usize];
+    }
+    ret
+}
+
+#[inline]
+fn fold_normal(value: u32, tables: &Tables, chunk: &[u8]) -> u32 {
+    let n = chunk.len();
+    let a = value
+        ^ ((u32::from(chunk[0]) << 24)
+            | (u32::from(chunk[1]) << 16)
+            | (u32::from(chunk[2]) << 8)
+            | u32::from(chunk[3]));
+    let mut ret = tables[n - 1][(a >> 24) as usize]
+        ^ tables[n - 2][((a >> 16) & 0xFF) as usize]
+        ^ tables[n - 3][((a >> 8) & 0xFF) as usize]
+        ^ tables[n - 4][(a & 0xFF) as usize];
+    for (i, &b) in chunk[4..].iter().enumerate() {
+        ret ^= tables[n - 5 - i][b as usize];
+    }
+    ret
+}
+
+#[inline]
+fn update_byte_reverse(value: u32, tables: &Tables, byte: u8) -> u32 {
+    tables[0][((value as u8) ^ byte) as usize] ^ (value >> 8)
+}
+
+#[inline]
+fn update_byte_normal(value: u32, tables: &Tables, byte: u8) -> u32 {
+    tables[0][(((value >> 24) as u8) ^ byte) as usize] ^ (value << 8)
+}
+
+/// Updates the reflected CRC register `value` with slice-by-16, and
+/// slice-by-8 for the rest.
+pub(crate) fn update_reverse(
+    mut value: u32,
+    tables: &Tables,
+    data: &[u8],
+) -> u32 {
+    let mut chunks = data.chunks_exact(16);
+    for chunk in &mut chunks {
+        value = fold_reverse(value, tables, chunk);
+    }
+    let mut chunks = chunks.remainder().chunks_exact(8);
+    for chunk in &mut chunks {
+        value = fold_reverse(value, tables, chunk);
+    }
+    for &b in chunks.remainder() {
+        value = update_byte_reverse(value, tables, b);
+    }
+    value
+}
+
+/// Updates the CRC register `value` with slice-by-16, and slice-by-8 for
+/// the rest.
+pub(crate) fn update_normal(
+    mut value: u32,
+    tables: &Tables,
+    data: &[u8],
+) -> u32 {
+    let mut chunks = data.chunks_exact(16);
+    for chunk in &mut chunks {
+        value = fold_normal(value, tables, chunk);
+    }
+    let mut chunks = chunks.remainder().chunks_exact(8);
+    for chunk in &mut chunks {
+        value = fold_normal(value, tables, chunk);
+    }
+    for &b in chunks.remainder() {
+        value = update_byte_normal(value, tables, b);
+    }
+    value
+}
+
+/// Multiplies `a` and `b` modulo the reflected polynomial `poly`.
+fn mult_mod_reverse(a: u32, mut b: u32, poly: u32) -> u32 {
+    let mut m = 1 << 31;
+    let mut p = 0;
+    while m != 0 {
+        if (a & m) != 0 {
+            p ^= b;
+        }
+        m >>= 1;
+        b = if (b & 1) == 1 {
+            (b >> 1) ^ poly
+        } else {
+            b >> 1
+        };
+    }
+    p
+}
+
+/// Multiplies `a` and `b` modulo the polynomial `poly`.
+fn mult_mod_normal(a: u32, b: u32, poly: u32) -> u32 {
+    let mut p = 0u32;
+    for i in (0..32).rev() {
+        p = if p & (1 << 31) == (1 << 31) {
+            (p << 1) ^ poly
+        } else {
+            p << 1
+        };
+        if (a >> i) & 1 == 1 {
+            p ^= b;
+        }
+    }
+    p
+}
+
+/// Returns the CRC of the concatenation of the data whose CRCs are `crc1`
+/// and `crc2`, where `len2` is the length of the latter.
+///
+/// `x` is x^1 and `one` is x^0 in the representation of `mult`.
+fn combine<F: Fn(u32, u32) -> u32>(
+    crc1: u32,
+    crc2: u32,
+    len2: u64,
+    x: u32,
+    one: u32,
+    mult: F,
+) -> u32 {
+    // x^(2^k)
+    let mut x2k = x;
+    // x^(8 * len2)
+    let mut p = one;
+    let mut n = len2 << 3;
+    while n != 0 {
+        if (n & 1) == 1 {
+            p = mult(x2k, p);
+        }
+        x2k = mult(x2k, x2k);
+        n >>= 1;
+    }
+    mult(p, crc1) ^ crc2
+}
+
+/// The CRC-32 used by gzip and zip (IEEE 802.3, reflected).
+#[derive(Clone, Debug)]
+pub struct Crc32 {
+    value: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self { value: 0xFFFF_FFFF }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        #[cfg(all(feature = "std", target_arch = "x86_64"))]
+        {
+            if data.len() >= super::x86::PCLMUL_MIN_LEN
+                && super::x86::has_pclmul()
+            {
+                self.value = super::x86::crc32_pclmul(self.value, data);
+                return;
+            }
+        }
+        self.value = update_reverse(self.value, &IEEE_REVERSE_TABLES, data);
+    }
+
+    /// Returns the CRC of the data written so far.
+    pub fn value(&self) -> u32 {
+        !self.value
+    }
+
+    /// Returns the CRC of the concatenation of the data whose CRCs are
+    /// `crc1` and `crc2`, where `len2` is the length of the latter.
+    pub fn combine(crc1: u32, crc2: u32, len2: u64) -> u32 {
+        combine(crc1, crc2, len2, 1 << 30, 1 << 31, |a, b| {
+            mult_mod_reverse(a, b, 0xEDB8_8320)
+        })
+    }
+}
+
+impl Hasher for Crc32 {
+    fn finish(&self) -> u64 {
+        u64::from(self.value())
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.value = update_byte_reverse(self.value, &IEEE_REVERSE_TABLES, i);
+    }
+}
+
+/// The CRC-32 used by bzip2 (IEEE 802.3, not reflected).
+#[derive(Clone, Debug)]
+pub struct Crc32Bzip2 {
+    value: u32,
+}
+
+impl Default for Crc32Bzip2 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32Bzip2 {
+    pub fn new() -> Self {
+        Self { value: 0xFFFF_FFFF }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.value = update_normal(self.value, &IEEE_NORMAL_TABLES, data);
+    }
+
+    /// Returns the CRC of the data written so far.
+    pub fn value(&self) -> u32 {
+        !self.value
+    }
+
+    /// Returns the CRC of the concatenation of the data whose CRCs are
+    /// `crc1` and `crc2`, where `len2` is the length of the latter.
+    pub fn combine(crc1: u32, crc2: u32, len2: u64) -> u32 {
+        combine(crc1, crc2, len2, 2, 1, |a, b| {
+            mult_mod_normal(a, b, 0x04C1_1DB7)
+        })
+    }
+}
+
+impl Hasher for Crc32Bzip2 {
+    fn finish(&self) -> u64 {
+        u64::from(self.value())
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.value = update_byte_normal(self.value, &IEEE_NORMAL_TABLES, i);
+    }
+}
+
+/// The CRC-32C (Castagnoli) used by iSCSI, ext4 and snappy.
+#[derive(Clone, Debug)]
+pub struct Crc32c {
+    value: u32,
+}
+
+impl Default for Crc32c {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32c {
+    pub fn new() -> Self {
+        Self { value: 0xFFFF_FFFF }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        #[cfg(all(feature = "std", target_arch = "x86_64"))]
+        {
+            if super::x86::has_sse42() {
+                self.value = super::x86::crc32c_sse42(self.value, data);
+                return;
+            }
+        }
+        self.value = update_reverse(self.value, &CASTAGNOLI_TABLES, data);
+    }
+
+    /// Returns the CRC of the data written so far.
+    pub fn value(&self) -> u32 {
+        !self.value
+    }
+
+    /// Returns the CRC of the concatenation of the data whose CRCs are
+    /// `crc1` and `crc2`, where `len2` is the length of the latter.
+    pub fn combine(crc1: u32, crc2: u32, len2: u64) -> u32 {
+        combine(crc1, crc2, len2, 1 << 30, 1 << 31, |a, b| {
+            mult_mod_reverse(a, b, 0x82F6_3B78)
+        })
+    }
+}
+
+impl Hasher for Crc32c {
+    fn finish(&self) -> u64 {
+        u64::from(self.value())
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.value = update_byte_reverse(self.value, &CASTAGNOLI_TABLES, i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    fn check<H: Hasher + Default>(
+        expected: u32,
+        update: fn(&mut H, &[u8]),
+        combine: fn(u32, u32, u64) -> u32,
+    ) {
+        let crc = |data: &[u8]| {
+            let mut hasher = H::default();
+            update(&mut hasher, data);
+            hasher.finish() as u32
+        };
+        assert_eq!(crc(b"123456789"), expected);
+
+        let data = thread_rng()
+            .sample_iter::<u8, _>(&Standard)
+            .take(10_000)
+            .collect::<Vec<_>>();
+        let mut bytewise = H::default();
+        data.iter().for_each(|&x| bytewise.write_u8(x));
+        for &len in &[1, 7, 8, 15, 16, 17, 63, 64, 100, 1000, 10_000] {
+            let mut hasher = H::default();
+            data.chunks(len).for_each(|c| update(&mut hasher, c));
+            assert_eq!(hasher.finish(), bytewise.finish());
+        }
+
+        for &i in &[0, 1, 9, 450, 9_999, 10_000] {
+            let (a, b) = data.split_at(i);
+            assert_eq!(
+                combine(crc(a), crc(b), b.len() as u64),
+                bytewise.finish() as u32
+            );
+        }
+    }
+
+    #[test]
+    fn test_crc32() {
+        check::<Crc32>(0xcbf4_3926, Crc32::update, Crc32::combine);
+    }
+
+    #[test]
+    fn test_crc32_bzip2() {
+        check::<Crc32Bzip2>(
+            0xfc89_1918,
+            Crc32Bzip2::update,
+            Crc32Bzip2::combine,
+        );
+    }
+
+    #[test]
+    fn test_crc32c() {
+        check::<Crc32c>(0xe306_9283, Crc32c::update, Crc32c::combine);
+    }
+
+    #[test]
+    fn test_tables() {
+        let data = thread_rng()
+            .sample_iter::<u8, _>(&Standard)
+            .take(1000)
+            .collect::<Vec<_>>();
+        for &len in &[0, 3, 8, 16, 31, 1000] {
+            let data = &data[..len];
+            for tables in
+                [&*IEEE_REVERSE_TABLES, &*CASTAGNOLI_TABLES].iter().cloned()
+            {
+                let value = data
+                    .iter()
+                    .fold(!0, |v, &b| update_byte_reverse(v, tables, b));
+                assert_eq!(update_reverse(!0, tables, data), value);
+            }
+            let value = data.iter().fold(!0, |v, &b| {
+                update_byte_normal(v, &IEEE_NORMAL_TABLES, b)
+            });
+            assert_eq!(update_normal(!0, &IEEE_NORMAL_TABLES, data), value);
+        }
+    }
+}
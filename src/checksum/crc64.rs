@@ -0,0 +1,185 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::core::convert::TryInto;
+use crate::core::hash::Hasher;
+use lazy_static::lazy_static;
+
+const POLY: u64 = 0xC96C_5795_D787_0F42;
+
+/// The lookup tables for slice-by-16.
+///
+/// `Tables[k][i]` is the CRC of the byte `i` followed by `k` zero bytes.
+type Tables = [[u64; 256]; 16];
+
+lazy_static! {
+    static ref ECMA_TABLES: Tables = make_tables();
+}
+
+fn make_tables() -> Tables {
+    let mut tables = [[0u64; 256]; 16];
+    for (i, item) in tables[0].iter_mut().enumerate() {
+        let mut value = i as u64;
+        for _ in 0..8 {
+            value = if (value & 1) == 1 {
+                (value >> 1) ^ POLY
+            } else {
+                value >> 1
+            }
+        }
+        *item = value;
+    }
+    for k in 1..16 {
+        for i in 0..256 {
+            let prev = tables[k - 1][i];
+            tables[k][i] = (prev >> 8) ^ tables[0][(prev & 0xFF) as usize];
+        }
+    }
+    tables
+}
+
+#[inline]
+fn update_byte(value: u64, byte: u8) -> u64 {
+    ECMA_TABLES[0][((value as u8) ^ byte) as usize] ^ (value >> 8)
+}
+
+/// Updates the reflected CRC register `value` with slice-by-16.
+fn update(mut value: u64, data: &[u8]) -> u64 {
+    let tables = &*ECMA_TABLES;
+    let mut chunks = data.chunks_exact(16);
+    for chunk in &mut chunks {
+        let a = value ^ u64::from_le_bytes(chunk[..8].try_into().unwrap());
+        value = (0..8).fold(0, |v, i| {
+            v ^ tables[15 - i][((a >> (i << 3)) & 0xFF) as usize]
+        });
+        for (i, &b) in chunk[8..].iter().enumerate() {
+            value ^= tables[7 - i][b as usize];
+        }
+    }
+    chunks
+        .remainder()
+        .iter()
+        .fold(value, |v, &b| update_byte(v, b))
+}
+
+/// Multiplies `a` and `b` modulo the reflected polynomial.
+fn mult_mod(a: u64, mut b: u64) -> u64 {
+    let mut m = 1 << 63;
+    let mut p = 0;
+    while m != 0 {
+        if (a & m) != 0 {
+            p ^= b;
+        }
+        m >>= 1;
+        b = if (b & 1) == 1 {
+            (b >> 1) ^ POLY
+        } else {
+            b >> 1
+        };
+    }
+    p
+}
+
+/// The CRC-64 used by xz (ECMA-182, reflected).
+#[derive(Clone, Debug)]
+pub struct Crc64 {
+    value: u64,
+}
+
+impl Default for Crc64 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc64 {
+    pub fn new() -> Self {
+        Self { value: !0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.value = update(self.value, data);
+    }
+
+    /// Returns the CRC of the data written so far.
+    pub fn value(&self) -> u64 {
+        !self.value
+    }
+
+    /// Returns the CRC of the concatenation of the data whose CRCs are
+    /// `crc1` and `crc2`, where `len2` is the length of the latter.
+    pub fn combine(crc1: u64, crc2: u64, len2: u64) -> u64 {
+        // x^(2^k), where x^0 is the highest bit
+        let mut x2k = 1 << 62;
+        // x^(8 * len2)
+        let mut p = 1 << 63;
+        let mut n = len2 << 3;
+        while n != 0 {
+            if (n & 1) == 1 {
+                p = mult_mod(x2k, p);
+            }
+            x2k = mult_mod(x2k, x2k);
+            n >>= 1;
+        }
+        mult_mod(p, crc1) ^ crc2
+    }
+}
+
+impl Hasher for Crc64 {
+    fn finish(&self) -> u64 {
+        self.value()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.value = update_byte(self.value, i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    fn crc64(data: &[u8]) -> u64 {
+        let mut crc64 = Crc64::new();
+        crc64.update(data);
+        crc64.value()
+    }
+
+    #[test]
+    fn test_crc64() {
+        assert_eq!(crc64(b""), 0);
+        assert_eq!(crc64(b"123456789"), 0x995D_C9BB_DF19_39FA);
+
+        let data = thread_rng()
+            .sample_iter::<u8, _>(&Standard)
+            .take(10_000)
+            .collect::<Vec<_>>();
+        let mut bytewise = Crc64::new();
+        data.iter().for_each(|&x| bytewise.write_u8(x));
+        for &len in &[1, 7, 8, 15, 16, 17, 100, 10_000] {
+            let mut hasher = Crc64::new();
+            data.chunks(len).for_each(|c| hasher.update(c));
+            assert_eq!(hasher.value(), bytewise.value());
+        }
+        for &i in &[0, 1, 9, 450, 10_000] {
+            let (a, b) = data.split_at(i);
+            assert_eq!(
+                Crc64::combine(crc64(a), crc64(b), b.len() as u64),
+                bytewise.value()
+            );
+        }
+    }
+}
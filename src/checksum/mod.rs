@@ -0,0 +1,47 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+//! The checksums used by the compression formats.
+//!
+//! The CRCs are computed with slice-by-16 tables. With the `std` feature
+//! on x86-64, `Crc32` folds the data with `PCLMULQDQ` and `Crc32c` uses
+//! the SSE 4.2 `CRC32` instruction if the processor supports them.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use compression::checksum::Crc32;
+//!
+//! let mut crc32 = Crc32::new();
+//! crc32.update(b"1234");
+//! let first = crc32.value();
+//!
+//! let mut crc32 = Crc32::new();
+//! crc32.update(b"56789");
+//! let second = crc32.value();
+//!
+//! assert_eq!(Crc32::combine(first, second, 5), 0xCBF4_3926);
+//! ```
+
+mod adler32;
+#[cfg(any(feature = "bzip2", feature = "gzip", feature = "zlib"))]
+mod buffered;
+mod crc32;
+mod crc64;
+mod sha256;
+mod x86;
+mod xxhash32;
+mod xxhash64;
+
+pub use self::adler32::Adler32;
+#[cfg(any(feature = "bzip2", feature = "gzip", feature = "zlib"))]
+pub(crate) use self::buffered::BufferedChecksum;
+pub use self::crc32::{Crc32, Crc32Bzip2, Crc32c};
+pub use self::crc64::Crc64;
+pub use self::sha256::Sha256;
+pub use self::xxhash32::XxHash32;
+pub use self::xxhash64::XxHash64;
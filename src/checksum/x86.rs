@@ -0,0 +1,177 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(all(feature = "std", target_arch = "x86_64"))]
+
+use crate::checksum::crc32::{update_reverse, IEEE_REVERSE_TABLES};
+use std::arch::x86_64::*;
+use std::convert::TryInto;
+
+/// The shortest data which is worth folding with `PCLMULQDQ`.
+pub(crate) const PCLMUL_MIN_LEN: usize = 128;
+
+// x^(4*128+32) mod P, x^(4*128-32) mod P
+const K1: i64 = 0x1_5444_2BD4;
+const K2: i64 = 0x1_C6E4_1596;
+// x^(128+32) mod P, x^(128-32) mod P
+const K3: i64 = 0x1_7519_97D0;
+const K4: i64 = 0x0_CCAA_009E;
+// x^64 mod P
+const K5: i64 = 0x1_63CD_6124;
+// P(x) and floor(x^64 / P(x)), bit reflected
+const P_X: i64 = 0x1_DB71_0641;
+const U_PRIME: i64 = 0x1_F701_1641;
+
+pub(crate) fn has_pclmul() -> bool {
+    is_x86_feature_detected!("pclmulqdq") && is_x86_feature_detected!("sse2")
+}
+
+pub(crate) fn has_sse42() -> bool {
+    is_x86_feature_detected!("sse4.2")
+}
+
+/// Updates the register of the gzip CRC-32 by folding 128 bits at a time
+/// with the carry-less multiplication.
+///
+/// See "Fast CRC Computation for Generic Polynomials Using PCLMULQDQ
+/// Instruction" by Intel.
+pub(crate) fn crc32_pclmul(value: u32, data: &[u8]) -> u32 {
+    debug_assert!(has_pclmul());
+    unsafe { crc32_pclmul_inner(value, data) }
+}
+
+#[target_feature(enable = "pclmulqdq", enable = "sse2")]
+unsafe fn crc32_pclmul_inner(value: u32, mut data: &[u8]) -> u32 {
+    if data.len() < PCLMUL_MIN_LEN {
+        return update_reverse(value, &IEEE_REVERSE_TABLES, data);
+    }
+
+    let mut x3 = load(&mut data);
+    let mut x2 = load(&mut data);
+    let mut x1 = load(&mut data);
+    let mut x0 = load(&mut data);
+    x3 = _mm_xor_si128(x3, _mm_cvtsi32_si128(value as i32));
+
+    // fold by 4
+    let k1k2 = _mm_set_epi64x(K2, K1);
+    while data.len() >= 64 {
+        x3 = fold(x3, load(&mut data), k1k2);
+        x2 = fold(x2, load(&mut data), k1k2);
+        x1 = fold(x1, load(&mut data), k1k2);
+        x0 = fold(x0, load(&mut data), k1k2);
+    }
+
+    // fold by 1
+    let k3k4 = _mm_set_epi64x(K4, K3);
+    let mut x = fold(x3, x2, k3k4);
+    x = fold(x, x1, k3k4);
+    x = fold(x, x0, k3k4);
+    while data.len() >= 16 {
+        x = fold(x, load(&mut data), k3k4);
+    }
+
+    // 128 bits to 64 bits
+    let mask32 = _mm_set_epi32(0, 0, 0, !0);
+    let x = _mm_xor_si128(
+        _mm_clmulepi64_si128(x, k3k4, 0x10),
+        _mm_srli_si128(x, 8),
+    );
+    let x = _mm_xor_si128(
+        _mm_clmulepi64_si128(
+            _mm_and_si128(x, mask32),
+            _mm_set_epi64x(0, K5),
+            0x00,
+        ),
+        _mm_srli_si128(x, 4),
+    );
+
+    // Barrett reduction from 64 bits to 32 bits
+    let pu = _mm_set_epi64x(U_PRIME, P_X);
+    let t1 = _mm_clmulepi64_si128(_mm_and_si128(x, mask32), pu, 0x10);
+    let t2 = _mm_clmulepi64_si128(_mm_and_si128(t1, mask32), pu, 0x00);
+    let value = _mm_cvtsi128_si32(_mm_srli_si128(_mm_xor_si128(x, t2), 4));
+
+    update_reverse(value as u32, &IEEE_REVERSE_TABLES, data)
+}
+
+#[target_feature(enable = "pclmulqdq", enable = "sse2")]
+unsafe fn fold(a: __m128i, b: __m128i, keys: __m128i) -> __m128i {
+    let t1 = _mm_clmulepi64_si128(a, keys, 0x00);
+    let t2 = _mm_clmulepi64_si128(a, keys, 0x11);
+    _mm_xor_si128(_mm_xor_si128(b, t1), t2)
+}
+
+#[target_feature(enable = "sse2")]
+unsafe fn load(data: &mut &[u8]) -> __m128i {
+    let ret = _mm_loadu_si128(data.as_ptr() as *const __m128i);
+    *data = &data[16..];
+    ret
+}
+
+/// Updates the register of the CRC-32C with the `CRC32` instruction.
+pub(crate) fn crc32c_sse42(value: u32, data: &[u8]) -> u32 {
+    debug_assert!(has_sse42());
+    unsafe { crc32c_sse42_inner(value, data) }
+}
+
+#[target_feature(enable = "sse4.2")]
+unsafe fn crc32c_sse42_inner(value: u32, data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(8);
+    let mut value = u64::from(value);
+    for chunk in &mut chunks {
+        value =
+            _mm_crc32_u64(value, u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+    let mut value = value as u32;
+    for &b in chunks.remainder() {
+        value = _mm_crc32_u8(value, b);
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::crc32::CASTAGNOLI_TABLES;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_pclmul() {
+        if !has_pclmul() {
+            return;
+        }
+        let data = thread_rng()
+            .sample_iter::<u8, _>(&Standard)
+            .take(5000)
+            .collect::<Vec<_>>();
+        for &len in &[0, 127, 128, 129, 191, 192, 200, 1024, 4999, 5000] {
+            let value = 0x1234_5678;
+            assert_eq!(
+                crc32_pclmul(value, &data[..len]),
+                update_reverse(value, &IEEE_REVERSE_TABLES, &data[..len])
+            );
+        }
+    }
+
+    #[test]
+    fn test_sse42() {
+        if !has_sse42() {
+            return;
+        }
+        let data = thread_rng()
+            .sample_iter::<u8, _>(&Standard)
+            .take(1000)
+            .collect::<Vec<_>>();
+        for &len in &[0, 1, 7, 8, 9, 100, 1000] {
+            let value = 0x1234_5678;
+            assert_eq!(
+                crc32c_sse42(value, &data[..len]),
+                update_reverse(value, &CASTAGNOLI_TABLES, &data[..len])
+            );
+        }
+    }
+}
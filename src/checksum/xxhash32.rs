@@ -0,0 +1,180 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::core::convert::TryInto;
+use crate::core::hash::Hasher;
+
+const PRIME1: u32 = 0x9E37_79B1;
+const PRIME2: u32 = 0x85EB_CA77;
+const PRIME3: u32 = 0xC2B2_AE3D;
+const PRIME4: u32 = 0x27D4_EB2F;
+const PRIME5: u32 = 0x1656_67B1;
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_le_bytes(data[..4].try_into().unwrap())
+}
+
+fn round(acc: u32, input: u32) -> u32 {
+    acc.wrapping_add(input.wrapping_mul(PRIME2))
+        .rotate_left(13)
+        .wrapping_mul(PRIME1)
+}
+
+/// The 32-bit xxHash used by LZ4.
+#[derive(Clone, Debug)]
+pub struct XxHash32 {
+    seed: u32,
+    acc: [u32; 4],
+    buf: [u8; 16],
+    buf_len: usize,
+    total_len: u64,
+}
+
+impl Default for XxHash32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XxHash32 {
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    pub fn with_seed(seed: u32) -> Self {
+        Self {
+            seed,
+            acc: [
+                seed.wrapping_add(PRIME1).wrapping_add(PRIME2),
+                seed.wrapping_add(PRIME2),
+                seed,
+                seed.wrapping_sub(PRIME1),
+            ],
+            buf: [0; 16],
+            buf_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn stripe(&mut self, data: &[u8]) {
+        for (i, acc) in self.acc.iter_mut().enumerate() {
+            *acc = round(*acc, read_u32(&data[i << 2..]));
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        if self.buf_len > 0 {
+            let len = (16 - self.buf_len).min(data.len());
+            self.buf[self.buf_len..self.buf_len + len]
+                .copy_from_slice(&data[..len]);
+            self.buf_len += len;
+            data = &data[len..];
+            if self.buf_len < 16 {
+                return;
+            }
+            let buf = self.buf;
+            self.stripe(&buf);
+            self.buf_len = 0;
+        }
+        let mut stripes = data.chunks_exact(16);
+        for stripe in &mut stripes {
+            self.stripe(stripe);
+        }
+        let rem = stripes.remainder();
+        self.buf[..rem.len()].copy_from_slice(rem);
+        self.buf_len = rem.len();
+    }
+
+    /// Returns the hash of the data written so far.
+    pub fn value(&self) -> u32 {
+        let mut h = if self.total_len >= 16 {
+            let [v1, v2, v3, v4] = self.acc;
+            v1.rotate_left(1)
+                .wrapping_add(v2.rotate_left(7))
+                .wrapping_add(v3.rotate_left(12))
+                .wrapping_add(v4.rotate_left(18))
+        } else {
+            self.seed.wrapping_add(PRIME5)
+        };
+        // 長さは下位32ビットだけを使う
+        h = h.wrapping_add(self.total_len as u32);
+
+        let mut rest = &self.buf[..self.buf_len];
+        while rest.len() >= 4 {
+            h = h.wrapping_add(read_u32(rest).wrapping_mul(PRIME3));
+            h = h.rotate_left(17).wrapping_mul(PRIME4);
+            rest = &rest[4..];
+        }
+        for &b in rest {
+            h = h.wrapping_add(u32::from(b).wrapping_mul(PRIME5));
+            h = h.rotate_left(11).wrapping_mul(PRIME1);
+        }
+
+        h ^= h >> 15;
+        h = h.wrapping_mul(PRIME2);
+        h ^= h >> 13;
+        h = h.wrapping_mul(PRIME3);
+        h ^ (h >> 16)
+    }
+}
+
+impl Hasher for XxHash32 {
+    fn write_u8(&mut self, byte: u8) {
+        self.update(&[byte]);
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        u64::from(self.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    fn xxhash32(data: &[u8]) -> u32 {
+        let mut hasher = XxHash32::new();
+        hasher.update(data);
+        hasher.value()
+    }
+
+    #[test]
+    fn test_xxhash32() {
+        assert_eq!(xxhash32(b""), 0x02CC_5D05);
+        assert_eq!(xxhash32(b"a"), 0x550D_7456);
+        assert_eq!(xxhash32(b"abc"), 0x32D1_53FF);
+        assert_eq!(
+            xxhash32(b"Nobody inspects the spammish repetition"),
+            0xE229_3B2F
+        );
+    }
+
+    #[test]
+    fn test_update() {
+        let data = thread_rng()
+            .sample_iter::<u8, _>(&Standard)
+            .take(10_000)
+            .collect::<Vec<_>>();
+        let mut bytewise = XxHash32::new();
+        data.iter().for_each(|&x| bytewise.write_u8(x));
+        assert_eq!(bytewise.value(), xxhash32(&data));
+        for &len in &[1, 3, 15, 16, 17, 1000] {
+            let mut hasher = XxHash32::new();
+            data.chunks(len).for_each(|c| hasher.update(c));
+            assert_eq!(hasher.value(), xxhash32(&data));
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::core::convert::TryInto;
+use crate::core::hash::Hasher;
+
+const PRIME1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME5: u64 = 0x27D4_EB2F_1656_67C5;
+
+fn read_u64(data: &[u8]) -> u64 {
+    u64::from_le_bytes(data[..8].try_into().unwrap())
+}
+
+fn round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME1)
+}
+
+fn merge(acc: u64, val: u64) -> u64 {
+    (acc ^ round(0, val))
+        .wrapping_mul(PRIME1)
+        .wrapping_add(PRIME4)
+}
+
+/// The 64-bit xxHash used by Zstandard.
+#[derive(Clone, Debug)]
+pub struct XxHash64 {
+    seed: u64,
+    acc: [u64; 4],
+    buf: [u8; 32],
+    buf_len: usize,
+    total_len: u64,
+}
+
+impl Default for XxHash64 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XxHash64 {
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            acc: [
+                seed.wrapping_add(PRIME1).wrapping_add(PRIME2),
+                seed.wrapping_add(PRIME2),
+                seed,
+                seed.wrapping_sub(PRIME1),
+            ],
+            buf: [0; 32],
+            buf_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn stripe(&mut self, data: &[u8]) {
+        for (i, acc) in self.acc.iter_mut().enumerate() {
+            *acc = round(*acc, read_u64(&data[i << 3..]));
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        if self.buf_len > 0 {
+            let len = (32 - self.buf_len).min(data.len());
+            self.buf[self.buf_len..self.buf_len + len]
+                .copy_from_slice(&data[..len]);
+            self.buf_len += len;
+            data = &data[len..];
+            if self.buf_len < 32 {
+                return;
+            }
+            let buf = self.buf;
+            self.stripe(&buf);
+            self.buf_len = 0;
+        }
+        let mut stripes = data.chunks_exact(32);
+        for stripe in &mut stripes {
+            self.stripe(stripe);
+        }
+        let rem = stripes.remainder();
+        self.buf[..rem.len()].copy_from_slice(rem);
+        self.buf_len = rem.len();
+    }
+
+    /// Returns the hash of the data written so far.
+    pub fn value(&self) -> u64 {
+        let mut h = if self.total_len >= 32 {
+            let [v1, v2, v3, v4] = self.acc;
+            let h = v1
+                .rotate_left(1)
+                .wrapping_add(v2.rotate_left(7))
+                .wrapping_add(v3.rotate_left(12))
+                .wrapping_add(v4.rotate_left(18));
+            self.acc.iter().fold(h, |h, &v| merge(h, v))
+        } else {
+            self.seed.wrapping_add(PRIME5)
+        };
+        h = h.wrapping_add(self.total_len);
+
+        let mut rest = &self.buf[..self.buf_len];
+        while rest.len() >= 8 {
+            h ^= round(0, read_u64(rest));
+            h = h.rotate_left(27).wrapping_mul(PRIME1).wrapping_add(PRIME4);
+            rest = &rest[8..];
+        }
+        if rest.len() >= 4 {
+            let k = u32::from_le_bytes(rest[..4].try_into().unwrap());
+            h ^= u64::from(k).wrapping_mul(PRIME1);
+            h = h.rotate_left(23).wrapping_mul(PRIME2).wrapping_add(PRIME3);
+            rest = &rest[4..];
+        }
+        for &b in rest {
+            h ^= u64::from(b).wrapping_mul(PRIME5);
+            h = h.rotate_left(11).wrapping_mul(PRIME1);
+        }
+
+        h ^= h >> 33;
+        h = h.wrapping_mul(PRIME2);
+        h ^= h >> 29;
+        h = h.wrapping_mul(PRIME3);
+        h ^ (h >> 32)
+    }
+}
+
+impl Hasher for XxHash64 {
+    fn write_u8(&mut self, byte: u8) {
+        self.update(&[byte]);
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        self.value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    fn xxhash64(data: &[u8]) -> u64 {
+        let mut hasher = XxHash64::new();
+        hasher.update(data);
+        hasher.value()
+    }
+
+    #[test]
+    fn test_xxhash64() {
+        assert_eq!(xxhash64(b""), 0xEF46_DB37_51D8_E999);
+        assert_eq!(xxhash64(b"a"), 0xD24E_C4F1_A98C_6E5B);
+        assert_eq!(xxhash64(b"abc"), 0x44BC_2CF5_AD77_0999);
+    }
+
+    #[test]
+    fn test_update() {
+        let data = thread_rng()
+            .sample_iter::<u8, _>(&Standard)
+            .take(10_000)
+            .collect::<Vec<_>>();
+        let mut bytewise = XxHash64::new();
+        data.iter().for_each(|&x| bytewise.write_u8(x));
+        assert_eq!(bytewise.value(), xxhash64(&data));
+        for &len in &[1, 7, 31, 32, 33, 1000] {
+            let mut hasher = XxHash64::new();
+            data.chunks(len).for_each(|c| hasher.update(c));
+            assert_eq!(hasher.value(), xxhash64(&data));
+        }
+    }
+}
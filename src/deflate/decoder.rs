@@ -7,6 +7,7 @@
 
 use crate::bitio::direction::right::Right;
 use crate::bitio::reader::{BitRead, BitReader};
+use crate::deflate::index::{DeflateCheckpoint, DeflateIndex};
 use crate::deflate::{
     fix_offset_table, fix_symbol_table, gen_len_tab, gen_off_tab, CodeTable,
 };
@@ -14,6 +15,7 @@ use crate::error::CompressionError;
 use crate::huffman::decoder::HuffmanDecoder;
 use crate::lzss::decoder::LzssDecoder;
 use crate::lzss::LzssCode;
+use crate::status::Status;
 use crate::traits::decoder::{
     BitDecodeService, BitDecoder, BitDecoderImpl, DecodeIterator, Decoder,
 };
@@ -40,8 +42,11 @@ impl DeflateHuffmanDecoder {
                 if *end {
                     Ok(None)
                 } else {
+                    let position = reader.position();
                     rhd.dec(reader, iter)
-                        .map_err(|_| CompressionError::DataError)
+                        .map_err(|_| CompressionError::InvalidHuffmanCode {
+                            position,
+                        })
                         .and_then(|x| match x {
                             Some(256) => {
                                 *end = true;
@@ -54,11 +59,12 @@ impl DeflateHuffmanDecoder {
             }
             DeflateHuffmanDecoder::NoComp(ref mut block_size) => {
                 if *block_size > 0 {
-                    *block_size -= 1;
-                    reader
+                    let ret = reader
                         .read_bits::<u16, _>(8, iter)
-                        .map(|x| Some(x.data()))
-                        .map_err(|_| CompressionError::UnexpectedEof)
+                        .map_err(|_| CompressionError::UnexpectedEof)?
+                        .data();
+                    *block_size -= 1;
+                    Ok(Some(ret))
                 } else {
                     Ok(None)
                 }
@@ -79,6 +85,11 @@ struct DeflaterInner {
     symbol_decoder: Option<DeflateHuffmanDecoder>,
     offset_decoder: Option<DeflateHuffmanDecoder>,
     is_final: bool,
+    // 索引作成時はブロック境界で一旦停止する
+    stop_at_block: bool,
+    stopped: bool,
+    // 参照可能な復号済みデータの長さ
+    window_len: usize,
     len_tab: CodeTable,
     offset_tab: CodeTable,
 }
@@ -91,6 +102,9 @@ impl DeflaterInner {
             symbol_decoder: None,
             offset_decoder: None,
             is_final: false,
+            stop_at_block: false,
+            stopped: false,
+            window_len: 0,
             len_tab: gen_len_tab(),
             offset_tab: gen_off_tab(),
         }
@@ -113,8 +127,11 @@ impl DeflaterInner {
                 .data_ref();
         }
         Ok(DeflateHuffmanDecoder::HuffmanDecoder(
-            HuffmanDecoder::new(&len_list, Self::SEARCH_TAB_LEN)
-                .map_err(|_| CompressionError::DataError)?,
+            HuffmanDecoder::new(&len_list, Self::SEARCH_TAB_LEN).map_err(
+                |_| CompressionError::InvalidHuffmanTable {
+                    position: reader.position(),
+                },
+            )?,
             false,
         ))
     }
@@ -126,14 +143,26 @@ impl DeflaterInner {
         reader: &mut R,
         iter: &mut I,
     ) -> Result<DeflateHuffmanDecoder, CompressionError> {
+        let ll = self.dec_code_lens(len_decoder, len, reader, iter)?;
+        Self::huff_decoder(&ll, reader)
+    }
+
+    fn dec_code_lens<R: BitRead, I: Iterator<Item = u8>>(
+        &mut self,
+        len_decoder: &mut DeflateHuffmanDecoder,
+        len: usize,
+        reader: &mut R,
+        iter: &mut I,
+    ) -> Result<Vec<u8>, CompressionError> {
         let mut ll = Vec::new();
         while ll.len() < len {
             match len_decoder.dec(reader, iter)? {
                 None => return Err(CompressionError::UnexpectedEof),
                 Some(16) => {
-                    let last = ll.iter().last().map_or_else(
-                        || Err(CompressionError::UnexpectedEof),
-                        |&l| Ok(l),
+                    let last = *ll.last().ok_or(
+                        CompressionError::InvalidHuffmanTable {
+                            position: reader.position(),
+                        },
                     )?;
                     for _ in 0..(reader
                         .read_bits::<u8, _>(2, iter)
@@ -145,30 +174,36 @@ impl DeflaterInner {
                     }
                 }
                 Some(17) => {
-                    for _ in 0..(3 + reader
+                    let count = 3 + reader
                         .read_bits::<u8, _>(3, iter)
                         .map_err(|_| CompressionError::UnexpectedEof)?
-                        .data())
-                    {
-                        ll.push(0);
-                    }
+                        .data();
+                    ll.resize(ll.len() + usize::from(count), 0);
                 }
                 Some(18) => {
-                    for _ in 0..(11
+                    let count = 11
                         + reader
                             .read_bits::<u8, _>(7, iter)
                             .map_err(|_| CompressionError::UnexpectedEof)?
-                            .data())
-                    {
-                        ll.push(0);
-                    }
+                            .data();
+                    ll.resize(ll.len() + usize::from(count), 0);
                 }
                 Some(n) => ll.push(n as u8),
             }
         }
+        Ok(ll)
+    }
+
+    fn huff_decoder<R: BitRead>(
+        ll: &[u8],
+        reader: &R,
+    ) -> Result<DeflateHuffmanDecoder, CompressionError> {
         Ok(DeflateHuffmanDecoder::HuffmanDecoder(
-            HuffmanDecoder::new(&ll, Self::SEARCH_TAB_LEN)
-                .map_err(|_| CompressionError::DataError)?,
+            HuffmanDecoder::new(ll, Self::SEARCH_TAB_LEN).map_err(|_| {
+                CompressionError::InvalidHuffmanTable {
+                    position: reader.position(),
+                }
+            })?,
             false,
         ))
     }
@@ -178,12 +213,14 @@ impl DeflaterInner {
         reader: &mut R,
         iter: &mut I,
     ) -> Result<(), CompressionError> {
-        self.is_final = reader
+        // 途中で入力が尽きても再開できるよう、読み終えてから状態を更新する
+        let position = reader.position();
+        let is_final = reader
             .read_bits::<u8, _>(1, iter)
             .map_err(|_| CompressionError::UnexpectedEof)?
             .data()
             == 1;
-        match reader
+        let (symbol_decoder, offset_decoder) = match reader
             .read_bits::<u8, _>(2, iter)
             .map_err(|_| CompressionError::UnexpectedEof)?
             .data()
@@ -191,6 +228,7 @@ impl DeflaterInner {
             // 無圧縮
             0 => {
                 let _ = reader.skip_to_next_byte();
+                let len_position = reader.position();
                 let block_len = reader
                     .read_bits(16, iter)
                     .map_err(|_| CompressionError::UnexpectedEof)?
@@ -200,33 +238,31 @@ impl DeflaterInner {
                     .map_err(|_| CompressionError::UnexpectedEof)?
                     .data();
                 if (block_len ^ block_len_checksum) != 0xFFFF {
-                    return Err(CompressionError::DataError);
+                    return Err(CompressionError::InvalidBlock {
+                        position: len_position,
+                    });
                 }
-                self.symbol_decoder =
-                    Some(DeflateHuffmanDecoder::NoComp(block_len));
-                self.offset_decoder = None;
+                (DeflateHuffmanDecoder::NoComp(block_len), None)
             }
             // 固定ハフマン
-            1 => {
-                self.symbol_decoder =
-                    Some(DeflateHuffmanDecoder::HuffmanDecoder(
-                        HuffmanDecoder::new(
-                            &fix_symbol_table(),
-                            Self::SEARCH_TAB_LEN,
-                        )
-                        .map_err(|_| CompressionError::DataError)?,
-                        false,
-                    ));
-                self.offset_decoder =
-                    Some(DeflateHuffmanDecoder::HuffmanDecoder(
-                        HuffmanDecoder::new(
-                            fix_offset_table(),
-                            Self::SEARCH_TAB_LEN,
-                        )
-                        .map_err(|_| CompressionError::DataError)?,
-                        false,
-                    ));
-            }
+            1 => (
+                DeflateHuffmanDecoder::HuffmanDecoder(
+                    HuffmanDecoder::new(
+                        &fix_symbol_table(),
+                        Self::SEARCH_TAB_LEN,
+                    )
+                    .map_err(|_| CompressionError::Unexpected)?,
+                    false,
+                ),
+                Some(DeflateHuffmanDecoder::HuffmanDecoder(
+                    HuffmanDecoder::new(
+                        fix_offset_table(),
+                        Self::SEARCH_TAB_LEN,
+                    )
+                    .map_err(|_| CompressionError::Unexpected)?,
+                    false,
+                )),
+            ),
             // カスタムハフマン
             2 => {
                 // リテラル/長さ符号の個数
@@ -248,22 +284,24 @@ impl DeflaterInner {
                     .data()
                     + 4;
                 let mut lt = self.dec_len_tree(hclen, reader, iter)?;
-                self.symbol_decoder = Some(self.dec_huff_tree(
-                    &mut lt,
-                    hlit as usize,
-                    reader,
-                    iter,
-                )?);
-                self.offset_decoder = Some(self.dec_huff_tree(
-                    &mut lt,
-                    hdist as usize,
-                    reader,
-                    iter,
-                )?);
+                let symbol_decoder =
+                    self.dec_huff_tree(&mut lt, hlit as usize, reader, iter)?;
+                let ol =
+                    self.dec_code_lens(&mut lt, hdist as usize, reader, iter)?;
+                // 距離符号が一つも無ければ、リテラルだけのブロック
+                let offset_decoder = if ol.iter().all(|&l| l == 0) {
+                    None
+                } else {
+                    Some(Self::huff_decoder(&ol, reader)?)
+                };
+                (symbol_decoder, offset_decoder)
             }
-            // ありえない
-            _ => unreachable!(),
-        }
+            // 予約済み
+            _ => return Err(CompressionError::InvalidBlock { position }),
+        };
+        self.is_final = is_final;
+        self.symbol_decoder = Some(symbol_decoder);
+        self.offset_decoder = offset_decoder;
         Ok(())
     }
 }
@@ -279,6 +317,7 @@ impl BitDecodeService for DeflaterInner {
         iter: &mut I,
     ) -> Result<Option<LzssCode>, CompressionError> {
         loop {
+            reader.commit();
             if self
                 .symbol_decoder
                 .as_ref()
@@ -287,15 +326,34 @@ impl BitDecodeService for DeflaterInner {
                 if self.is_final {
                     return Ok(None);
                 }
+                if self.stop_at_block && !self.stopped {
+                    self.stopped = true;
+                    return Ok(None);
+                }
                 self.init_block(reader, iter)?;
-            } else if let Some(sym) =
-                self.symbol_decoder.as_mut().unwrap().dec(reader, iter)?
-            {
+                self.stopped = false;
+            } else {
+                let position = reader.position();
+                let sym = match self
+                    .symbol_decoder
+                    .as_mut()
+                    .unwrap()
+                    .dec(reader, iter)?
+                {
+                    Some(sym) => sym,
+                    None => continue,
+                };
                 if sym <= 255 {
+                    self.window_len = self.window_len.saturating_add(1);
                     return Ok(Some(LzssCode::Symbol(sym as u8)));
                 } else {
                     let len_index = (sym - 257) as usize;
-                    let extbits = (&self.len_tab).ext_bits(len_index);
+                    if !self.len_tab.contains(len_index) {
+                        return Err(CompressionError::InvalidHuffmanCode {
+                            position,
+                        });
+                    }
+                    let extbits = self.len_tab.ext_bits(len_index);
                     let len = (self.len_tab.convert_back(
                         len_index,
                         if extbits != 0 {
@@ -310,11 +368,18 @@ impl BitDecodeService for DeflaterInner {
                     let off_index = self
                         .offset_decoder
                         .as_mut()
-                        .unwrap()
+                        .ok_or(CompressionError::InvalidHuffmanCode {
+                            position,
+                        })?
                         .dec(reader, iter)?
-                        .ok_or_else(|| CompressionError::UnexpectedEof)?
+                        .ok_or(CompressionError::UnexpectedEof)?
                         as usize;
-                    let off_extbits = (&self.offset_tab).ext_bits(off_index);
+                    if !self.offset_tab.contains(off_index) {
+                        return Err(CompressionError::InvalidHuffmanCode {
+                            position,
+                        });
+                    }
+                    let off_extbits = self.offset_tab.ext_bits(off_index);
                     let pos = self.offset_tab.convert_back(
                         off_index,
                         if off_extbits != 0 {
@@ -326,6 +391,13 @@ impl BitDecodeService for DeflaterInner {
                             0
                         },
                     ) as usize;
+                    if pos >= self.window_len {
+                        return Err(CompressionError::InvalidDistance {
+                            distance: pos + 1,
+                            position,
+                        });
+                    }
+                    self.window_len = self.window_len.saturating_add(len);
                     return Ok(Some(LzssCode::Reference { len, pos }));
                 }
             }
@@ -337,6 +409,9 @@ impl BitDecodeService for DeflaterInner {
 pub(crate) struct DeflaterBase {
     inner: DeflaterInner,
     lzss_decoder: LzssDecoder,
+    output: u64,
+    skip_bits: usize,
+    index: Option<DeflateIndex>,
 }
 
 impl Default for DeflaterBase {
@@ -349,16 +424,57 @@ impl DeflaterBase {
     const MAX_BLOCK_SIZE: usize = 0x1_0000;
 
     pub(crate) fn new() -> Self {
-        Self {
-            lzss_decoder: LzssDecoder::new(Self::MAX_BLOCK_SIZE),
-            inner: DeflaterInner::new(),
-        }
+        Self::with_dict(&[])
     }
 
     pub(crate) fn with_dict(dict: &[u8]) -> Self {
+        let mut inner = DeflaterInner::new();
+        inner.window_len = dict.len();
         Self {
             lzss_decoder: LzssDecoder::with_dict(Self::MAX_BLOCK_SIZE, dict),
-            inner: DeflaterInner::new(),
+            inner,
+            output: 0,
+            skip_bits: 0,
+            index: None,
+        }
+    }
+
+    pub(crate) fn with_index(span: usize) -> Self {
+        let mut ret = Self::new();
+        ret.inner.stop_at_block = true;
+        ret.index = Some(DeflateIndex::new(span));
+        ret
+    }
+
+    pub(crate) fn with_checkpoint(point: &DeflateCheckpoint) -> Self {
+        let mut ret = Self::with_dict(&point.window);
+        ret.output = point.output;
+        ret.skip_bits = (point.input_bits & 0x07) as usize;
+        ret
+    }
+
+    /// Prepares for the next stream, keeping the output count and the index.
+    #[cfg(feature = "gzip")]
+    pub(crate) fn reset(&mut self) {
+        let stop_at_block = self.inner.stop_at_block;
+        self.inner = DeflaterInner::new();
+        self.inner.stop_at_block = stop_at_block;
+        self.lzss_decoder = LzssDecoder::new(Self::MAX_BLOCK_SIZE);
+    }
+
+    pub(crate) fn index(&self) -> Option<&DeflateIndex> {
+        self.index.as_ref()
+    }
+
+    fn add_checkpoint(&mut self, input_bits: u64) {
+        if let Some(ref mut index) = self.index {
+            if index.needs_point(self.output) {
+                index.push(DeflateCheckpoint {
+                    output: self.output,
+                    input_bits,
+                    window: self.lzss_decoder.window(DeflateIndex::WINDOW_SIZE),
+                });
+            }
         }
     }
 }
@@ -373,13 +489,34 @@ impl BitDecodeService for DeflaterBase {
         reader: &mut BitReader<Self::Direction>,
         iter: &mut I,
     ) -> Result<Option<u8>, Self::Error> {
-        let mut bd = BitDecoder::<DeflaterInner, _, _>::with_service(
-            &mut self.inner,
-            reader,
-        );
-        self.lzss_decoder
-            .next(&mut DecodeIterator::<I, _, _>::new(iter, &mut bd).flatten())
-            .transpose()
+        if self.skip_bits > 0 {
+            let _ = reader
+                .skip_bits(self.skip_bits, iter)
+                .map_err(|_| CompressionError::UnexpectedEof)?;
+            self.skip_bits = 0;
+            reader.commit();
+        }
+        loop {
+            let ret = {
+                let mut bd = BitDecoder::<DeflaterInner, _, _>::with_service(
+                    &mut self.inner,
+                    &mut *reader,
+                );
+                self.lzss_decoder.next_code(
+                    &mut DecodeIterator::<I, _, _>::new(&mut *iter, &mut bd),
+                )
+            };
+            match ret {
+                Ok(Some(_)) => {
+                    self.output += 1;
+                    return ret;
+                }
+                Ok(None) if self.inner.stopped => {
+                    self.add_checkpoint(reader.position())
+                }
+                _ => return ret,
+            }
+        }
     }
 }
 
@@ -403,6 +540,59 @@ impl Deflater {
             ),
         }
     }
+
+    /// Creates a decoder which builds a `DeflateIndex` with a checkpoint
+    /// every `span` bytes of the decompressed data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `span` is zero.
+    pub fn with_index(span: usize) -> Self {
+        Self {
+            inner: BitDecoderImpl::<DeflaterBase>::with_service(
+                DeflaterBase::with_index(span),
+                BitReader::new(),
+            ),
+        }
+    }
+
+    /// Creates a decoder which resumes the decoding at `point`.
+    ///
+    /// The input has to start at `point.input_offset()` of the
+    /// compressed stream.
+    pub fn with_checkpoint(point: &DeflateCheckpoint) -> Self {
+        Self {
+            inner: BitDecoderImpl::<DeflaterBase>::with_service(
+                DeflaterBase::with_checkpoint(point),
+                BitReader::new(),
+            ),
+        }
+    }
+
+    /// Returns the index built so far by a decoder created with
+    /// `with_index`.
+    pub fn index(&self) -> Option<&DeflateIndex> {
+        self.inner.service().index()
+    }
+
+    /// Returns the position following the next sync flush marker
+    /// (`00 00 FF FF`) in `input`.
+    ///
+    /// A new `Deflater` can resume the decoding from a position written
+    /// with `Action::FullFlush`. Only markers that can follow a stored
+    /// block header padded to a byte boundary are returned, but the
+    /// marker may still occur by chance in the compressed data, so the
+    /// result is a candidate and the caller must be prepared for the
+    /// decoding to fail.
+    pub fn find_sync_point(input: &[u8]) -> Option<usize> {
+        input
+            .windows(5)
+            // 直前のバイトは非最終の無圧縮ブロックのヘッダと0の詰め物で終わる
+            .position(|w| {
+                w[0] & 0x80 == 0 && w[1..] == [0x00, 0x00, 0xFF, 0xFF]
+            })
+            .map(|p| p + 5)
+    }
 }
 
 impl Default for Deflater {
@@ -424,4 +614,13 @@ impl Decoder for Deflater {
     ) -> Option<Result<Self::Output, Self::Error>> {
         self.inner.next(iter)
     }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        self.inner.decompress(input, output, finish)
+    }
 }
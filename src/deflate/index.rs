@@ -0,0 +1,196 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::core::convert::TryInto;
+use crate::error::CompressionError;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// A position in a deflate stream from which the decoding can be resumed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeflateCheckpoint {
+    /// The offset of the checkpoint in the decompressed data.
+    pub output: u64,
+    /// The offset of the checkpoint in the compressed stream, in bits.
+    pub input_bits: u64,
+    /// The decompressed data preceding the checkpoint, up to 32 KiB.
+    pub window: Vec<u8>,
+}
+
+impl DeflateCheckpoint {
+    /// Returns the byte offset in the compressed stream from which the
+    /// input has to be passed to a decoder resuming at the checkpoint.
+    pub fn input_offset(&self) -> u64 {
+        self.input_bits >> 3
+    }
+}
+
+/// A random access index over a deflate, zlib or gzip stream.
+///
+/// The index is built by a decoder created with `with_index`, which
+/// records a checkpoint at the first block boundary after every `span`
+/// decompressed bytes. A decoder created with `with_checkpoint` resumes
+/// the decoding from one of the checkpoints.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "gzip")]
+///     # {
+///     let data = (0..200_000_u32)
+///         .map(|x| (x % 251) as u8 ^ (x % 1021) as u8)
+///         .collect::<Vec<u8>>();
+///     let compressed = data
+///         .iter()
+///         .cloned()
+///         .encode(&mut GZipEncoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     let mut decoder = GZipDecoder::with_index(0x1_0000);
+///     let _ = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut decoder)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///     let index = decoder.index().unwrap();
+///     let index = DeflateIndex::from_bytes(&index.to_bytes()).unwrap();
+///
+///     let point = index.find(150_000).unwrap();
+///     let decompressed = compressed
+///         .iter()
+///         .skip(point.input_offset() as usize)
+///         .cloned()
+///         .decode(&mut GZipDecoder::with_checkpoint(point))
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///     assert_eq!(&data[point.output as usize..], &decompressed[..]);
+///     # }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeflateIndex {
+    span: u64,
+    points: Vec<DeflateCheckpoint>,
+}
+
+impl DeflateIndex {
+    pub(crate) const WINDOW_SIZE: usize = 0x8000;
+    const MAGIC: [u8; 4] = *b"DFIX";
+
+    pub(crate) fn new(span: usize) -> Self {
+        if span == 0 {
+            panic!("invalid span");
+        }
+        Self {
+            span: span as u64,
+            points: Vec::new(),
+        }
+    }
+
+    /// Returns the distance between the checkpoints in the decompressed
+    /// data.
+    pub fn span(&self) -> u64 {
+        self.span
+    }
+
+    /// Returns the checkpoints in the order of the stream.
+    pub fn points(&self) -> &[DeflateCheckpoint] {
+        &self.points
+    }
+
+    /// Returns the last checkpoint at or before `output` bytes of the
+    /// decompressed data.
+    pub fn find(&self, output: u64) -> Option<&DeflateCheckpoint> {
+        match self.points.binary_search_by_key(&output, |p| p.output) {
+            Ok(i) => Some(&self.points[i]),
+            Err(0) => None,
+            Err(i) => Some(&self.points[i - 1]),
+        }
+    }
+
+    pub(crate) fn needs_point(&self, output: u64) -> bool {
+        !matches!(self.points.last(), Some(p) if output - p.output < self.span)
+    }
+
+    pub(crate) fn push(&mut self, point: DeflateCheckpoint) {
+        self.points.push(point);
+    }
+
+    /// Serializes the index.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+        ret.extend_from_slice(&Self::MAGIC);
+        ret.extend_from_slice(&self.span.to_le_bytes());
+        ret.extend_from_slice(&(self.points.len() as u64).to_le_bytes());
+        for p in &self.points {
+            ret.extend_from_slice(&p.output.to_le_bytes());
+            ret.extend_from_slice(&p.input_bits.to_le_bytes());
+            ret.extend_from_slice(&(p.window.len() as u32).to_le_bytes());
+            ret.extend_from_slice(&p.window);
+        }
+        ret
+    }
+
+    /// Deserializes an index written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CompressionError> {
+        let mut reader = SliceReader(bytes);
+        if reader.take(4)? != Self::MAGIC {
+            return Err(CompressionError::DataError);
+        }
+        let span = reader.read_u64()?;
+        let len = reader.read_u64()?;
+        if span == 0 {
+            return Err(CompressionError::DataError);
+        }
+        let mut points = Vec::<DeflateCheckpoint>::new();
+        for _ in 0..len {
+            let output = reader.read_u64()?;
+            let input_bits = reader.read_u64()?;
+            let window_len = reader.read_u32()? as usize;
+            if window_len > Self::WINDOW_SIZE
+                || matches!(points.last(), Some(p) if p.output > output)
+            {
+                return Err(CompressionError::DataError);
+            }
+            points.push(DeflateCheckpoint {
+                output,
+                input_bits,
+                window: reader.take(window_len)?.to_vec(),
+            });
+        }
+        if !reader.0.is_empty() {
+            return Err(CompressionError::DataError);
+        }
+        Ok(Self { span, points })
+    }
+}
+
+struct SliceReader<'a>(&'a [u8]);
+
+impl<'a> SliceReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CompressionError> {
+        if self.0.len() < len {
+            return Err(CompressionError::UnexpectedEof);
+        }
+        let (ret, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(ret)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, CompressionError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, CompressionError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
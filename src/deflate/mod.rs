@@ -8,9 +8,11 @@
 
 pub(crate) mod decoder;
 pub(crate) mod encoder;
+pub(crate) mod index;
+pub(crate) mod options;
+pub(crate) mod parallel;
 
 use crate::bitio::small_bit_vec::SmallBitVec;
-use crate::core::u16;
 #[cfg(not(feature = "std"))]
 #[allow(unused_imports)]
 use alloc::vec;
@@ -52,6 +54,10 @@ impl CodeTable {
         self.ext_bits[pos] as usize
     }
 
+    fn contains(&self, pos: usize) -> bool {
+        pos < self.ext_bits.len()
+    }
+
     fn convert_back(&self, pos: usize, ext: u16) -> u16 {
         self.offsets[pos] + ext
     }
@@ -130,6 +136,8 @@ mod tests {
     use crate::action::Action;
     use crate::deflate::decoder::Deflater;
     use crate::deflate::encoder::Inflater;
+    use crate::deflate::index::DeflateIndex;
+    use crate::deflate::options::{DeflateOptions, DeflateStrategy};
     use crate::traits::decoder::DecodeExt;
     use crate::traits::encoder::EncodeExt;
     #[cfg(not(feature = "std"))]
@@ -151,6 +159,20 @@ mod tests {
         assert_eq!(testarray.to_vec(), decoded);
     }
 
+    fn check_with_options(testarray: &[u8], options: &DeflateOptions) {
+        let encoded = testarray
+            .to_vec()
+            .encode(&mut Inflater::with_options(options), Action::Finish)
+            .collect::<Result<Vec<_>, _>>();
+        let decoded = encoded
+            .unwrap()
+            .decode(&mut Deflater::new())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(testarray.to_vec(), decoded);
+    }
+
     #[test]
     fn test_empty() {
         check(&[]);
@@ -233,4 +255,395 @@ mod tests {
     fn test_multiblocks5() {
         test_rand_with_len(0x10_0001);
     }
+
+    #[test]
+    fn test_levels() {
+        let rng = thread_rng();
+        let testarray = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cycle()
+            .take(0x1_0000)
+            .cloned()
+            .chain(rng.sample_iter(&Standard).take(0x1_0000))
+            .collect::<Vec<u8>>();
+
+        for level in 0..=9 {
+            check_with_options(&testarray, &DeflateOptions::new(level));
+        }
+    }
+
+    #[test]
+    fn test_level_sizes() {
+        let testarray = [
+            &include_bytes!("../../data/sample1.ref")[..],
+            &include_bytes!("../../data/sample4.ref")[..],
+        ]
+        .concat();
+
+        let sizes = (1..=9)
+            .map(|level| {
+                testarray
+                    .iter()
+                    .cloned()
+                    .encode(
+                        &mut Inflater::with_options(&DeflateOptions::new(
+                            level,
+                        )),
+                        Action::Finish,
+                    )
+                    .count()
+            })
+            .collect::<Vec<_>>();
+        // 上のレベルほど大きくなってはいけない
+        assert!(sizes.windows(2).all(|w| w[0] >= w[1]), "{:?}", sizes);
+    }
+
+    #[test]
+    fn test_options() {
+        let testarray = (0..0x2_0000)
+            .map(|x: u32| ((x % 251) ^ (x % 1021)) as u8)
+            .collect::<Vec<u8>>();
+
+        check_with_options(
+            &testarray,
+            &DeflateOptions {
+                window_bits: 9,
+                ..DeflateOptions::new(9)
+            },
+        );
+        for &strategy in &[
+            DeflateStrategy::HuffmanOnly,
+            DeflateStrategy::Rle,
+            DeflateStrategy::Fixed,
+        ] {
+            check_with_options(
+                &testarray,
+                &DeflateOptions {
+                    strategy,
+                    ..DeflateOptions::default()
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn test_strategies() {
+        let testarray = b"aaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbabababababababab"
+            .iter()
+            .cycle()
+            .take(0x1000)
+            .cloned()
+            .collect::<Vec<u8>>();
+        let encode = |strategy| {
+            testarray
+                .iter()
+                .cloned()
+                .encode(
+                    &mut Inflater::with_options(&DeflateOptions {
+                        strategy,
+                        ..DeflateOptions::default()
+                    }),
+                    Action::Finish,
+                )
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+
+        let default = encode(DeflateStrategy::Default);
+        let huffman_only = encode(DeflateStrategy::HuffmanOnly);
+        let rle = encode(DeflateStrategy::Rle);
+        let fixed = encode(DeflateStrategy::Fixed);
+
+        assert!(default.len() < rle.len());
+        assert!(rle.len() < huffman_only.len());
+        // huffman coded literals take at least one bit per byte
+        assert!(huffman_only.len() >= testarray.len() >> 3);
+        // BFINAL = 1, BTYPE = 01
+        assert_eq!(fixed[0] & 0b111, 0b011);
+    }
+
+    #[test]
+    fn test_slice() {
+        use crate::traits::decoder::tests::decompress_slices;
+        use crate::traits::encoder::tests::compress_slices;
+
+        let rng = thread_rng();
+        let testarray = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cycle()
+            .take(0x1_0000)
+            .cloned()
+            .chain(rng.sample_iter(&Standard).take(0x1_0000))
+            .collect::<Vec<u8>>();
+        let expected = testarray
+            .iter()
+            .cloned()
+            .encode(&mut Inflater::new(), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        for &(chunk, out_len) in &[(1, 0x100), (7, 1), (0x1000, 0x8000)] {
+            let encoded = compress_slices(
+                &mut Inflater::new(),
+                &testarray,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(encoded == expected);
+            let decoded = decompress_slices(
+                &mut Deflater::new(),
+                &encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded == testarray);
+        }
+    }
+
+    fn encode_with_actions(chunks: &[(&[u8], Action)]) -> Vec<u8> {
+        let mut encoder = Inflater::new();
+        chunks
+            .iter()
+            .flat_map(|&(chunk, action)| {
+                chunk
+                    .iter()
+                    .cloned()
+                    .encode(&mut encoder, action)
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_flush() {
+        let testarray = b"aabbaabbaaabbbaaabbbaabbaabb";
+        for &action in &[
+            Action::Flush,
+            Action::SyncFlush,
+            Action::FullFlush,
+            Action::Block,
+        ] {
+            let encoded = encode_with_actions(&[
+                (testarray, action),
+                (testarray, action),
+                (testarray, Action::Finish),
+            ]);
+            let decoded = encoded
+                .decode(&mut Deflater::new())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(testarray.repeat(3), decoded);
+        }
+
+        let encoded = encode_with_actions(&[
+            (testarray, Action::SyncFlush),
+            (&[], Action::Run),
+        ]);
+        assert!(encoded.ends_with(&[0x00, 0x00, 0xFF, 0xFF]));
+    }
+
+    #[test]
+    fn test_full_flush() {
+        let first = b"aabbaabbaaabbbaaabbbaabbaabb";
+        let second = b"bbaabbaabbbaaabbbaaabbaabbaa";
+        let encoded = encode_with_actions(&[
+            (first, Action::FullFlush),
+            (second, Action::Finish),
+        ]);
+
+        let pos = Deflater::find_sync_point(&encoded).unwrap();
+        let decoded = encoded[pos..]
+            .iter()
+            .cloned()
+            .decode(&mut Deflater::new())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(second.to_vec(), decoded);
+
+        // 無圧縮ブロックのヘッダが前に無ければ同期点ではない
+        assert_eq!(Deflater::find_sync_point(&[0x00, 0x00, 0xFF, 0xFF]), None);
+        assert_eq!(
+            Deflater::find_sync_point(&[0x80, 0x00, 0x00, 0xFF, 0xFF]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_index() {
+        let rng = thread_rng();
+        let testarray = rng
+            .sample_iter::<u8, _>(&Standard)
+            .map(|x| x & 0x0F)
+            .take(0x4_0000)
+            .collect::<Vec<u8>>();
+        for &level in &[0, 6] {
+            let encoded = testarray
+                .iter()
+                .cloned()
+                .encode(
+                    &mut Inflater::with_options(&DeflateOptions::new(level)),
+                    Action::Finish,
+                )
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+
+            let mut decoder = Deflater::with_index(0x8000);
+            let decoded = encoded
+                .iter()
+                .cloned()
+                .decode(&mut decoder)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(testarray, decoded);
+
+            let index = decoder.index().unwrap();
+            assert!(index.points().len() > 1);
+            assert_eq!(
+                DeflateIndex::from_bytes(&index.to_bytes()).as_ref(),
+                Ok(index)
+            );
+            for point in index.points() {
+                let decoded = encoded[point.input_offset() as usize..]
+                    .iter()
+                    .cloned()
+                    .decode(&mut Deflater::with_checkpoint(point))
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                assert_eq!(&testarray[point.output as usize..], &decoded[..]);
+            }
+            let point = index.find(0x2_0000).unwrap();
+            assert!(point.output <= 0x2_0000);
+        }
+        assert!(DeflateIndex::from_bytes(b"DFIX").is_err());
+    }
+
+    #[test]
+    fn test_stream() {
+        use crate::traits::decoder::tests::decompress_slices;
+        use crate::traits::decoder::Decoder;
+        #[cfg(not(feature = "std"))]
+        use alloc::vec;
+
+        let rng = thread_rng();
+        let testarray = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cycle()
+            .take(0x1_0000)
+            .cloned()
+            .chain(rng.sample_iter(&Standard).take(0x1_0000))
+            .collect::<Vec<u8>>();
+        for &level in &[0, 1, 6] {
+            let encoded = testarray
+                .iter()
+                .cloned()
+                .encode(
+                    &mut Inflater::with_options(&DeflateOptions::new(level)),
+                    Action::Finish,
+                )
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            for &(chunk, out_len) in &[(1, 0x100), (7, 1), (0x1000, 0x8000)] {
+                let decoded = decompress_slices(
+                    &mut Deflater::new(),
+                    &encoded,
+                    chunk,
+                    out_len,
+                )
+                .unwrap();
+                assert_eq!(testarray, decoded);
+            }
+
+            let mut decoder = Deflater::with_index(0x8000);
+            let decoded =
+                decompress_slices(&mut decoder, &encoded, 3, 0x100).unwrap();
+            assert_eq!(testarray, decoded);
+            let mut expected = Deflater::with_index(0x8000);
+            let _ = encoded
+                .iter()
+                .cloned()
+                .decode(&mut expected)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(decoder.index(), expected.index());
+
+            // 入力の途中までに復号できる分だけを出力する
+            let mut decoder = Deflater::new();
+            let mut output = vec![0; testarray.len() + 1];
+            let half = encoded.len() / 2;
+            let first = decoder
+                .decompress(&encoded[..half], &mut output, false)
+                .unwrap();
+            assert_eq!(first.consumed, half);
+            assert!(!first.done);
+            assert!(first.produced > 0);
+            assert_eq!(&testarray[..first.produced], &output[..first.produced]);
+            let second = decoder
+                .decompress(
+                    &encoded[half..],
+                    &mut output[first.produced..],
+                    true,
+                )
+                .unwrap();
+            assert!(second.done);
+            assert_eq!(first.produced + second.produced, testarray.len());
+            assert_eq!(&testarray[..], &output[..testarray.len()]);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        use crate::error::CompressionError;
+
+        let decode = |data: &[u8]| {
+            data.iter()
+                .cloned()
+                .decode(&mut Deflater::new())
+                .collect::<Result<Vec<_>, _>>()
+        };
+        // the reserved block type
+        assert_eq!(
+            decode(&[0x07]),
+            Err(CompressionError::InvalidBlock { position: 0 })
+        );
+        // LEN and NLEN of a stored block differ
+        assert_eq!(
+            decode(&[0x01, 0x01, 0x00, 0xFF, 0xFF, 0x00]),
+            Err(CompressionError::InvalidBlock { position: 8 })
+        );
+        // a dynamic block without any code length codes
+        assert_eq!(
+            decode(&[0x05, 0x00, 0x00, 0x00]),
+            Err(CompressionError::InvalidHuffmanTable { position: 29 })
+        );
+        // a dynamic block of the four code length codes of 1 bit
+        assert_eq!(
+            decode(&[0x05, 0x00, 0x92, 0x04]),
+            Err(CompressionError::InvalidHuffmanTable { position: 29 })
+        );
+        // 257 literal/length codes of 8 bits
+        let mut data = vec![0x05, 0x20, 0x00, 0x24];
+        data.extend_from_slice(&[0xFF; 32]);
+        data.push(0x01);
+        assert_eq!(
+            decode(&data),
+            Err(CompressionError::InvalidHuffmanTable { position: 289 })
+        );
+
+        let data = b"aabbaabbaaabbbaaabbbaabbaabb";
+        let encoded = data
+            .iter()
+            .cloned()
+            .encode(&mut Inflater::with_dict(data), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        match decode(&encoded) {
+            Err(CompressionError::InvalidDistance { distance, position }) => {
+                assert_eq!(distance, data.len());
+                assert_eq!(position, 3);
+            }
+            ret => panic!("{:?}", ret),
+        }
+    }
 }
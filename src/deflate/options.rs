@@ -0,0 +1,118 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+/// The match finding strategy of the deflate encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeflateStrategy {
+    #[default]
+    Default,
+    /// Encodes the literals only, without searching for matches.
+    HuffmanOnly,
+    /// Limits the match distance to one, for run-length encoding.
+    Rle,
+    /// Always uses the fixed Huffman codes.
+    Fixed,
+}
+
+/// The parameters of the deflate encoder.
+///
+/// `DeflateOptions::new(level)` fills in the search parameters for the
+/// compression level `0..=9`; the fields may be tuned afterwards.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "deflate")]
+///     let options = DeflateOptions {
+///         window_bits: 12,
+///         ..DeflateOptions::new(9)
+///     };
+///     # #[cfg(feature = "deflate")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut Inflater::with_options(&options), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeflateOptions {
+    /// `0` emits stored blocks only, `1` is the fastest and `9` is the
+    /// best compression.
+    pub level: usize,
+    /// The base two logarithm of the window size, `9..=15`.
+    pub window_bits: usize,
+    /// The number of following positions searched for a longer match
+    /// before a match is emitted. `1` is greedy matching.
+    pub lazy_level: usize,
+    /// The maximum number of hash chain entries examined per search.
+    pub max_chain: usize,
+    /// The match length which stops the search.
+    pub nice_length: usize,
+    pub strategy: DeflateStrategy,
+}
+
+impl Default for DeflateOptions {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LEVEL)
+    }
+}
+
+impl DeflateOptions {
+    pub(crate) const DEFAULT_LEVEL: usize = 6;
+    pub(crate) const MAX_WINDOW_BITS: usize = 15;
+    const MIN_WINDOW_BITS: usize = 9;
+
+    // lazy_level, max_chain, nice_length
+    // 遅延評価は2より深くしても縮まないので、上のレベルでは探索を増やす
+    const LEVEL_TABLE: [(usize, usize, usize); 10] = [
+        (0, 0, 0),
+        (1, 4, 8),
+        (1, 8, 16),
+        (1, 16, 16),
+        (2, 32, 64),
+        (2, 64, 128),
+        (2, 128, 258),
+        (2, 512, 258),
+        (2, 1024, 258),
+        (2, 4096, 258),
+    ];
+
+    pub fn new(level: usize) -> Self {
+        if level > 9 {
+            panic!("invalid level");
+        }
+        let (lazy_level, max_chain, nice_length) = Self::LEVEL_TABLE[level];
+        Self {
+            level,
+            window_bits: Self::MAX_WINDOW_BITS,
+            lazy_level,
+            max_chain,
+            nice_length,
+            strategy: DeflateStrategy::Default,
+        }
+    }
+
+    pub(crate) fn validate(&self) {
+        if self.level > 9 {
+            panic!("invalid level");
+        }
+        if !(Self::MIN_WINDOW_BITS..=Self::MAX_WINDOW_BITS)
+            .contains(&self.window_bits)
+        {
+            panic!("invalid window bits");
+        }
+    }
+
+    pub(crate) fn window_size(&self) -> usize {
+        1 << self.window_bits
+    }
+}
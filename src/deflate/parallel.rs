@@ -0,0 +1,154 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(all(feature = "std", any(feature = "gzip", feature = "zlib")))]
+
+use crate::action::Action;
+use crate::deflate::encoder::Inflater;
+use crate::deflate::options::DeflateOptions;
+use crate::error::CompressionError;
+use crate::traits::encoder::EncodeExt;
+use std::thread;
+
+/// A chunk compressed by `ParallelDeflater`.
+#[derive(Debug)]
+pub(crate) struct CompressedChunk {
+    pub(crate) data: Vec<u8>,
+    pub(crate) checksum: u32,
+    pub(crate) len: u64,
+}
+
+/// Compresses the input in chunks on multiple threads.
+///
+/// Every chunk is compressed by its own `Inflater` primed with the
+/// preceding window and terminated with a sync flush, so the
+/// concatenation of the chunks is a single deflate stream.
+#[derive(Debug)]
+pub(crate) struct ParallelDeflater {
+    options: DeflateOptions,
+    threads: usize,
+    chunk_size: usize,
+    input: Vec<u8>,
+    window: Vec<u8>,
+}
+
+impl ParallelDeflater {
+    pub(crate) const DEFAULT_CHUNK_SIZE: usize = 0x2_0000;
+
+    pub(crate) fn new(
+        options: &DeflateOptions,
+        threads: usize,
+        chunk_size: usize,
+    ) -> Self {
+        options.validate();
+        if threads == 0 {
+            panic!("invalid threads");
+        }
+        if chunk_size == 0 {
+            panic!("invalid chunk size");
+        }
+        Self {
+            options: *options,
+            threads,
+            chunk_size,
+            input: Vec::with_capacity(threads * chunk_size),
+            window: Vec::new(),
+        }
+    }
+
+    pub(crate) fn default_threads() -> usize {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.input.len() >= self.threads * self.chunk_size
+    }
+
+    pub(crate) fn push(&mut self, data: u8) {
+        self.input.push(data);
+    }
+
+    /// Returns the window which precedes `start` of the input.
+    fn dict(&self, start: usize) -> Vec<u8> {
+        let size = self.options.window_size();
+        let need = size.saturating_sub(start);
+        let mut dict =
+            self.window[self.window.len().saturating_sub(need)..].to_vec();
+        dict.extend_from_slice(&self.input[start.saturating_sub(size)..start]);
+        dict
+    }
+
+    /// Compresses the buffered input. The last chunk is terminated with
+    /// the final block if `finish` is set.
+    ///
+    /// `reset` discards the window, so that the following data can be
+    /// decoded without the preceding one.
+    pub(crate) fn compress<F>(
+        &mut self,
+        finish: bool,
+        reset: bool,
+        checksum: F,
+    ) -> Result<Vec<CompressedChunk>, CompressionError>
+    where
+        F: Fn(&[u8]) -> u32 + Sync,
+    {
+        let mut chunks = self
+            .input
+            .chunks(self.chunk_size)
+            .enumerate()
+            .map(|(i, c)| (self.dict(i * self.chunk_size), c))
+            .collect::<Vec<_>>();
+        if chunks.is_empty() {
+            chunks.push((self.dict(0), &[]));
+        }
+        let last = chunks.len() - 1;
+        let options = &self.options;
+        let checksum = &checksum;
+        let ret = thread::scope(|s| {
+            chunks
+                .iter()
+                .enumerate()
+                .map(|(i, &(ref dict, chunk))| {
+                    let action = if finish && i == last {
+                        Action::Finish
+                    } else {
+                        Action::SyncFlush
+                    };
+                    s.spawn(move || {
+                        let data = chunk
+                            .iter()
+                            .cloned()
+                            .encode(
+                                &mut Inflater::with_options_and_dict(
+                                    options, dict,
+                                ),
+                                action,
+                            )
+                            .collect::<Result<Vec<_>, _>>()?;
+                        Ok::<_, CompressionError>(CompressedChunk {
+                            data,
+                            checksum: checksum(chunk),
+                            len: chunk.len() as u64,
+                        })
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|h| h.join().map_err(|_| CompressionError::Unexpected)?)
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+
+        if reset {
+            self.window.clear();
+        } else {
+            let size = self.options.window_size();
+            self.window = self.dict(self.input.len());
+            debug_assert!(self.window.len() <= size);
+        }
+        self.input.clear();
+        Ok(ret)
+    }
+}
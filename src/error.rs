@@ -7,16 +7,108 @@
 
 use crate::core::fmt;
 
+/// The error of the encoders and the decoders.
+///
+/// `position` is the offset in bits in the compressed input at which the
+/// error was detected.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum CompressionError {
     DataError,
     UnexpectedEof,
     Unexpected,
+    /// The magic number of the stream is wrong.
+    BadMagic {
+        position: u64,
+    },
+    /// The stream is compressed with an unsupported method.
+    UnsupportedMethod {
+        method: u8,
+        position: u64,
+    },
+    /// The header has an invalid field.
+    InvalidHeader {
+        position: u64,
+    },
+    /// The checksum of the data differs from the stored one.
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+        position: u64,
+    },
+    /// The length of the data differs from the stored one.
+    LengthMismatch {
+        expected: u64,
+        actual: u64,
+        position: u64,
+    },
+    /// The code lengths do not make a Huffman table.
+    InvalidHuffmanTable {
+        position: u64,
+    },
+    /// The code is not in the Huffman table.
+    InvalidHuffmanCode {
+        position: u64,
+    },
+    /// The distance points before the start of the data.
+    InvalidDistance {
+        distance: usize,
+        position: u64,
+    },
+    /// The block is malformed.
+    InvalidBlock {
+        position: u64,
+    },
+    /// The stream needs the preset dictionary identified by `id`, which is
+    /// the Adler-32 of the dictionary for zlib.
+    DictionaryRequired {
+        id: u32,
+        position: u64,
+    },
+    /// The data following a stream is not a stream.
+    TrailingGarbage {
+        position: u64,
+    },
 }
 
 impl fmt::Display for CompressionError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.description_in())
+        match *self {
+            CompressionError::UnsupportedMethod { method, .. } => {
+                write!(f, "{} {}", self.description_in(), method)?
+            }
+            CompressionError::ChecksumMismatch {
+                expected, actual, ..
+            } => write!(
+                f,
+                "{} (expected 0x{:08x}, actual 0x{:08x})",
+                self.description_in(),
+                expected,
+                actual
+            )?,
+            CompressionError::LengthMismatch {
+                expected, actual, ..
+            } => write!(
+                f,
+                "{} (expected {}, actual {})",
+                self.description_in(),
+                expected,
+                actual
+            )?,
+            CompressionError::InvalidDistance { distance, .. } => {
+                write!(f, "{} {}", self.description_in(), distance)?
+            }
+            CompressionError::DictionaryRequired { id, .. } => {
+                write!(f, "{} 0x{:08x}", self.description_in(), id)?
+            }
+            _ => write!(f, "{}", self.description_in())?,
+        }
+        match self.position() {
+            Some(position) => {
+                write!(f, " at bit {} (byte {})", position, position >> 3)
+            }
+            None => Ok(()),
+        }
     }
 }
 
@@ -26,7 +118,7 @@ impl ::std::error::Error for CompressionError {
         self.description_in()
     }
 
-    fn cause(&self) -> Option<&dyn (::std::error::Error)> {
+    fn cause(&self) -> Option<&dyn ::std::error::Error> {
         None
     }
 }
@@ -37,6 +129,48 @@ impl CompressionError {
             CompressionError::DataError => "data integrity error in data",
             CompressionError::UnexpectedEof => "file ends unexpectedly",
             CompressionError::Unexpected => "unexpected error",
+            CompressionError::BadMagic { .. } => "bad magic number",
+            CompressionError::UnsupportedMethod { .. } => {
+                "unsupported compression method"
+            }
+            CompressionError::InvalidHeader { .. } => "invalid header",
+            CompressionError::ChecksumMismatch { .. } => "checksum mismatch",
+            CompressionError::LengthMismatch { .. } => "length mismatch",
+            CompressionError::InvalidHuffmanTable { .. } => {
+                "invalid Huffman table"
+            }
+            CompressionError::InvalidHuffmanCode { .. } => {
+                "invalid Huffman code"
+            }
+            CompressionError::InvalidDistance { .. } => "invalid distance",
+            CompressionError::InvalidBlock { .. } => "invalid block",
+            CompressionError::DictionaryRequired { .. } => {
+                "preset dictionary required"
+            }
+            CompressionError::TrailingGarbage { .. } => {
+                "trailing garbage after the end of the stream"
+            }
+        }
+    }
+
+    /// Returns the offset in bits in the compressed input at which the
+    /// error was detected, if known.
+    pub fn position(&self) -> Option<u64> {
+        match *self {
+            CompressionError::DataError
+            | CompressionError::UnexpectedEof
+            | CompressionError::Unexpected => None,
+            CompressionError::BadMagic { position }
+            | CompressionError::UnsupportedMethod { position, .. }
+            | CompressionError::InvalidHeader { position }
+            | CompressionError::ChecksumMismatch { position, .. }
+            | CompressionError::LengthMismatch { position, .. }
+            | CompressionError::InvalidHuffmanTable { position }
+            | CompressionError::InvalidHuffmanCode { position }
+            | CompressionError::InvalidDistance { position, .. }
+            | CompressionError::InvalidBlock { position }
+            | CompressionError::DictionaryRequired { position, .. }
+            | CompressionError::TrailingGarbage { position } => Some(position),
         }
     }
 }
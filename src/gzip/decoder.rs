@@ -7,10 +7,13 @@
 
 use crate::bitio::direction::right::Right;
 use crate::bitio::reader::{BitRead, BitReader};
-use crate::core::hash::{BuildHasher, Hasher};
-use crate::crc32::{BuiltinDigest, IEEE_REVERSE};
+use crate::checksum::{BufferedChecksum, Crc32};
+use crate::core::hash::Hasher;
 use crate::deflate::decoder::DeflaterBase;
+use crate::deflate::index::{DeflateCheckpoint, DeflateIndex};
 use crate::error::CompressionError;
+use crate::gzip::header::{GzHeader, FCOMMENT, FEXTRA, FHCRC, FNAME, FTEXT};
+use crate::status::Status;
 use crate::traits::decoder::{BitDecodeService, BitDecoderImpl, Decoder};
 #[cfg(not(feature = "std"))]
 #[allow(unused_imports)]
@@ -21,11 +24,17 @@ use alloc::vec::Vec;
 #[derive(Debug)]
 pub(crate) struct GZipDecoderBase {
     deflater: DeflaterBase,
-    crc32: BuiltinDigest,
+    crc32: BufferedChecksum<Crc32>,
     header: Vec<u8>,
+    header_pos: u64,
     header_needlen: usize,
     header_checked: bool,
+    member_no: usize,
     i_size: u32,
+    resumed: bool,
+    multi_member: bool,
+    finished: bool,
+    parsed_header: Option<GzHeader>,
 }
 
 impl Default for GZipDecoderBase {
@@ -38,11 +47,40 @@ impl GZipDecoderBase {
     pub(crate) fn new() -> Self {
         Self {
             deflater: DeflaterBase::new(),
-            crc32: IEEE_REVERSE.build_hasher(),
+            crc32: BufferedChecksum::default(),
             header: Vec::new(),
+            header_pos: 0,
             header_needlen: 10,
             header_checked: false,
+            member_no: 0,
             i_size: 0,
+            resumed: false,
+            multi_member: true,
+            finished: false,
+            parsed_header: None,
+        }
+    }
+
+    fn single_member() -> Self {
+        Self {
+            multi_member: false,
+            ..Self::new()
+        }
+    }
+
+    fn with_index(span: usize) -> Self {
+        Self {
+            deflater: DeflaterBase::with_index(span),
+            ..Self::new()
+        }
+    }
+
+    fn with_checkpoint(point: &DeflateCheckpoint) -> Self {
+        Self {
+            deflater: DeflaterBase::with_checkpoint(point),
+            header_checked: true,
+            resumed: true,
+            ..Self::new()
         }
     }
 
@@ -50,21 +88,27 @@ impl GZipDecoderBase {
         reader: &mut R,
         iter: &mut I,
     ) -> Result<u32, CompressionError> {
-        (0..4)
-            .map(|i| {
-                Ok(reader
-                    .read_bits::<u32, _>(8, iter)
-                    .map_err(|_| CompressionError::UnexpectedEof)?
-                    .data()
-                    << (i << 3))
-            })
-            .fold(
-                Ok(0_u32),
-                |s: Result<_, CompressionError>,
-                 x: Result<_, CompressionError>| {
-                    Ok(x? | s?)
-                },
-            )
+        (0..4).try_fold(0_u32, |s, i| {
+            let x = reader
+                .read_bits::<u32, _>(8, iter)
+                .map_err(|_| CompressionError::UnexpectedEof)?;
+            if x.len() != 8 {
+                return Err(CompressionError::UnexpectedEof);
+            }
+            Ok(x.data() << (i << 3) | s)
+        })
+    }
+
+    fn next_member(&mut self) {
+        self.deflater.reset();
+        self.crc32 = BufferedChecksum::default();
+        self.header.clear();
+        self.parsed_header = None;
+        self.header_needlen = 10;
+        self.header_checked = false;
+        self.member_no += 1;
+        self.i_size = 0;
+        self.resumed = false;
     }
 }
 
@@ -79,8 +123,14 @@ impl BitDecodeService for GZipDecoderBase {
         iter: &mut I,
     ) -> Result<Option<u8>, Self::Error> {
         loop {
-            if !self.header_checked {
+            reader.commit();
+            if self.finished {
+                return Ok(None);
+            } else if !self.header_checked {
                 if self.header.len() < self.header_needlen {
+                    if self.header.is_empty() {
+                        self.header_pos = reader.position();
+                    }
                     self.header.push(
                         reader
                             .read_bits::<u8, _>(8, iter)
@@ -88,30 +138,36 @@ impl BitDecodeService for GZipDecoderBase {
                             .data(),
                     );
                 } else {
-                    // ID1 1byte
-                    if self.header[0] != 0x1f {
-                        return Err(CompressionError::DataError);
-                    }
-                    // ID2 1byte
-                    if self.header[1] != 0x8b {
-                        return Err(CompressionError::DataError);
+                    let position = self.header_pos;
+                    // ID1, ID2 2byte
+                    if self.header[..2] != [0x1f, 0x8b] {
+                        return Err(if self.member_no == 0 {
+                            CompressionError::BadMagic { position }
+                        } else {
+                            CompressionError::TrailingGarbage { position }
+                        });
                     }
                     // CM 1byte
                     if self.header[2] != 0x08 {
-                        return Err(CompressionError::DataError);
+                        return Err(CompressionError::UnsupportedMethod {
+                            method: self.header[2],
+                            position: position + 16,
+                        });
                     }
 
                     // FLG 1byte
                     let flg = self.header[3];
                     if (flg & 0b1110_0000) != 0 {
-                        return Err(CompressionError::DataError);
+                        return Err(CompressionError::InvalidHeader {
+                            position: position + 24,
+                        });
                     }
 
                     // MTIME 4byte
                     // XFL, OS 2byte
 
                     // FEXTRA
-                    let xlen = if (flg & 0b100) != 0 {
+                    let xlen = if (flg & FEXTRA) != 0 {
                         // XLEN 2byte
                         if self.header.len() < 12 {
                             if self.header_needlen < 12 {
@@ -133,17 +189,17 @@ impl BitDecodeService for GZipDecoderBase {
                         continue;
                     }
 
-                    // FNAME
-                    let fname_len = if (flg & 0b1000) != 0 {
+                    // FNAME (終端の0を含む)
+                    let fname_len = if (flg & FNAME) != 0 {
                         // NAME
-                        if let Some(l) = (&self.header)
+                        if let Some(l) = self
+                            .header
                             .iter()
                             .enumerate()
-                            .skip(10 + xlen)
-                            .skip_while(|x| *x.1 != 0)
-                            .next()
+                            .skip(fextra_last)
+                            .find(|x| *x.1 == 0)
                         {
-                            l.0 - 10 - xlen
+                            l.0 + 1 - fextra_last
                         } else {
                             self.header_needlen += 1;
                             continue;
@@ -151,18 +207,19 @@ impl BitDecodeService for GZipDecoderBase {
                     } else {
                         0
                     };
+                    let fname_last = fextra_last + fname_len;
 
-                    // FCOMMENT
-                    let fcomment_len = if (flg & 0b1_0000) != 0 {
+                    // FCOMMENT (終端の0を含む)
+                    let fcomment_len = if (flg & FCOMMENT) != 0 {
                         // COMMENT
-                        if let Some(l) = (&self.header)
+                        if let Some(l) = self
+                            .header
                             .iter()
                             .enumerate()
-                            .skip(10 + xlen + fname_len)
-                            .skip_while(|x| *x.1 != 0)
-                            .next()
+                            .skip(fname_last)
+                            .find(|x| *x.1 == 0)
                         {
-                            l.0 - 10 - xlen - fname_len
+                            l.0 + 1 - fname_last
                         } else {
                             self.header_needlen += 1;
                             continue;
@@ -170,10 +227,10 @@ impl BitDecodeService for GZipDecoderBase {
                     } else {
                         0
                     };
+                    let comment_last = fname_last + fcomment_len;
 
                     // FHCRC
-                    if (flg & 0b10) != 0 {
-                        let comment_last = 10 + xlen + fname_len + fcomment_len;
+                    if (flg & FHCRC) != 0 {
                         if self.header.len() < comment_last + 2 {
                             if self.header_needlen < comment_last + 2 {
                                 self.header_needlen = comment_last + 2;
@@ -184,34 +241,81 @@ impl BitDecodeService for GZipDecoderBase {
                         let hcrc = (u16::from(self.header[1 + comment_last])
                             << 8)
                             | u16::from(self.header[comment_last]);
-                        let mut digest4header = IEEE_REVERSE.build_hasher();
+                        let mut digest4header = Crc32::new();
                         digest4header.write(&self.header[0..(comment_last)]);
-                        if hcrc != digest4header.finish() as u16 {
-                            return Err(CompressionError::DataError);
+                        let actual = digest4header.finish() as u16;
+                        if hcrc != actual {
+                            return Err(CompressionError::ChecksumMismatch {
+                                expected: u32::from(hcrc),
+                                actual: u32::from(actual),
+                                position: position
+                                    + ((comment_last as u64) << 3),
+                            });
                         }
                     }
 
+                    let field = |start: usize, last: usize, flag: u8| {
+                        if (flg & flag) != 0 {
+                            Some(self.header[start..last].to_vec())
+                        } else {
+                            None
+                        }
+                    };
+                    self.parsed_header = Some(GzHeader {
+                        text: (flg & FTEXT) != 0,
+                        mtime: u32::from(self.header[4])
+                            | (u32::from(self.header[5]) << 8)
+                            | (u32::from(self.header[6]) << 16)
+                            | (u32::from(self.header[7]) << 24),
+                        xfl: self.header[8],
+                        os: self.header[9],
+                        extra: field(12, fextra_last, FEXTRA),
+                        filename: field(fextra_last, fname_last - 1, FNAME),
+                        comment: field(fname_last, comment_last - 1, FCOMMENT),
+                        header_crc: (flg & FHCRC) != 0,
+                    });
                     self.header_checked = true;
                 }
             } else {
                 // body
                 match self.deflater.next(reader, iter) {
                     Ok(Some(s)) => {
-                        self.crc32.write_u8(s);
-                        self.i_size += 1;
+                        self.crc32.push(s);
+                        self.i_size = self.i_size.wrapping_add(1);
                         return Ok(Some(s));
                     }
                     Ok(None) => {
                         let _ = reader.skip_to_next_byte();
 
+                        let position = reader.position();
                         let c = Self::read_u32(reader, iter)?;
-                        if u64::from(c) != self.crc32.finish() {
-                            return Err(CompressionError::DataError);
+                        let actual = self.crc32.value();
+                        // 途中から再開した場合は検証できない
+                        if !self.resumed && c != actual {
+                            return Err(CompressionError::ChecksumMismatch {
+                                expected: c,
+                                actual,
+                                position,
+                            });
                         }
                         let i_size = Self::read_u32(reader, iter)?;
-                        if i_size != self.i_size {
-                            return Err(CompressionError::DataError);
+                        if !self.resumed && i_size != self.i_size {
+                            return Err(CompressionError::LengthMismatch {
+                                expected: u64::from(i_size),
+                                actual: u64::from(self.i_size),
+                                position: position + 32,
+                            });
                         }
+                        if self.multi_member {
+                            let next = reader
+                                .peek_bits::<u8, _>(8, iter)
+                                .map_err(|_| CompressionError::Unexpected)?;
+                            if next.len() == 8 {
+                                self.next_member();
+                                continue;
+                            }
+                        }
+                        self.finished = true;
                         return Ok(None);
                     }
                     Err(e) => return Err(e),
@@ -221,6 +325,11 @@ impl BitDecodeService for GZipDecoderBase {
     }
 }
 
+/// The gzip decoder.
+///
+/// The decoder continues across the members of a concatenated gzip file
+/// until the end of the input. `GZipDecoder::single_member` creates a
+/// decoder which stops after the first member.
 #[derive(Debug)]
 pub struct GZipDecoder {
     inner: BitDecoderImpl<GZipDecoderBase>,
@@ -232,6 +341,60 @@ impl GZipDecoder {
             inner: BitDecoderImpl::<GZipDecoderBase>::new(),
         }
     }
+
+    /// Creates a decoder which stops after the first member.
+    ///
+    /// The input following the member is left unread in the iterator,
+    /// so `Status::consumed` of `decompress` points to the end of the
+    /// member.
+    pub fn single_member() -> Self {
+        Self {
+            inner: BitDecoderImpl::<GZipDecoderBase>::with_service(
+                GZipDecoderBase::single_member(),
+                BitReader::new(),
+            ),
+        }
+    }
+
+    /// Creates a decoder which builds a `DeflateIndex` with a checkpoint
+    /// every `span` bytes of the decompressed data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `span` is zero.
+    pub fn with_index(span: usize) -> Self {
+        Self {
+            inner: BitDecoderImpl::<GZipDecoderBase>::with_service(
+                GZipDecoderBase::with_index(span),
+                BitReader::new(),
+            ),
+        }
+    }
+
+    /// Creates a decoder which resumes the decoding at `point`.
+    ///
+    /// The input has to start at `point.input_offset()` of the gzip
+    /// file. The CRC of the trailer is not verified.
+    pub fn with_checkpoint(point: &DeflateCheckpoint) -> Self {
+        Self {
+            inner: BitDecoderImpl::<GZipDecoderBase>::with_service(
+                GZipDecoderBase::with_checkpoint(point),
+                BitReader::new(),
+            ),
+        }
+    }
+
+    /// Returns the index built so far by a decoder created with
+    /// `with_index`.
+    pub fn index(&self) -> Option<&DeflateIndex> {
+        self.inner.service().deflater.index()
+    }
+
+    /// Returns the header of the member being decoded, once it has been
+    /// read.
+    pub fn header(&self) -> Option<&GzHeader> {
+        self.inner.service().parsed_header.as_ref()
+    }
 }
 
 impl Default for GZipDecoder {
@@ -253,4 +416,13 @@ impl Decoder for GZipDecoder {
     ) -> Option<Result<Self::Output, Self::Error>> {
         self.inner.next(iter)
     }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        self.inner.decompress(input, output, finish)
+    }
 }
@@ -6,56 +6,32 @@
 //! <http://mozilla.org/MPL/2.0/>.
 
 use crate::action::Action;
-use crate::core::borrow::BorrowMut;
-use crate::core::hash::{BuildHasher, Hasher};
-use crate::core::marker::PhantomData;
+use crate::checksum::Crc32;
+use crate::core::cmp;
+use crate::core::hash::Hasher;
 use crate::core::mem;
-use crate::crc32::{BuiltinDigest, IEEE_REVERSE};
 use crate::deflate::encoder::Inflater;
+use crate::deflate::options::DeflateOptions;
 use crate::error::CompressionError;
-use crate::traits::encoder::Encoder;
-
-struct ScanIterator<I: Iterator, BI: BorrowMut<I>, F: FnMut(&I::Item) -> ()> {
-    phantom: PhantomData<I>,
-    inner: BI,
-    closure: F,
-}
-
-impl<I: Iterator, BI: BorrowMut<I>, F: FnMut(&I::Item) -> ()> Iterator
-    for ScanIterator<I, BI, F>
-{
-    type Item = I::Item;
-    fn next(&mut self) -> Option<I::Item> {
-        let ret = self.inner.borrow_mut().next();
-        if let Some(ref s) = ret {
-            (self.closure)(s);
-        }
-        ret
-    }
-}
-
-impl<I: Iterator, BI: BorrowMut<I>, F: FnMut(&I::Item) -> ()>
-    ScanIterator<I, BI, F>
-{
-    pub(crate) fn new(inner: BI, closure: F) -> Self {
-        Self {
-            inner,
-            closure,
-            phantom: PhantomData,
-        }
-    }
-}
+use crate::gzip::header::GzHeader;
+use crate::status::Status;
+use crate::traits::encoder::{EncodeBuffer, Encoder};
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
 
 #[derive(Debug)]
 pub struct GZipEncoder {
     inflater: Inflater,
-    crc32: Option<BuiltinDigest>,
-    header_len: u8,
-    header: [u8; 10],
-    hash: Option<u32>,
-    hashlen: u8,
-    i_size_len: u8,
+    crc32: Crc32,
     i_size: u32,
+    /// The header and the trailer not written yet.
+    out: Vec<u8>,
+    out_pos: usize,
+    finished: bool,
+    buf: EncodeBuffer<u8, u8>,
 }
 
 impl Default for GZipEncoder {
@@ -66,17 +42,40 @@ impl Default for GZipEncoder {
 
 impl GZipEncoder {
     pub fn new() -> Self {
+        Self::with_options(&DeflateOptions::default())
+    }
+
+    pub fn with_options(options: &DeflateOptions) -> Self {
+        Self::with_options_and_header(options, &GzHeader::default())
+    }
+
+    pub fn with_header(header: &GzHeader) -> Self {
+        Self::with_options_and_header(&DeflateOptions::default(), header)
+    }
+
+    pub fn with_options_and_header(
+        options: &DeflateOptions,
+        header: &GzHeader,
+    ) -> Self {
         Self {
-            inflater: Inflater::new(),
-            crc32: Some(IEEE_REVERSE.build_hasher()),
-            header: [
-                0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF,
-            ],
-            header_len: 10,
-            hash: None,
-            hashlen: 3,
+            inflater: Inflater::with_options(options),
+            crc32: Crc32::new(),
             i_size: 0,
-            i_size_len: 4,
+            out: header.to_bytes(Self::xfl(options)),
+            out_pos: 0,
+            finished: false,
+            buf: EncodeBuffer::default(),
+        }
+    }
+}
+
+impl GZipEncoder {
+    pub(crate) fn xfl(options: &DeflateOptions) -> u8 {
+        // XFL - 2: maximum compression, 4: fastest algorithm
+        match options.level {
+            0 | 1 => 4,
+            9 => 2,
+            _ => 0,
         }
     }
 }
@@ -90,46 +89,62 @@ impl Encoder for GZipEncoder {
         iter: &mut I,
         action: Action,
     ) -> Option<Result<u8, CompressionError>> {
-        let hlen = self.header_len;
-        if hlen > 0 {
-            let hlen_all = self.header.len();
-            self.header_len = hlen - 1;
-            Some(Ok(self.header[hlen_all - hlen as usize]))
-        } else if let Some(hash) = self.hash {
-            if self.hashlen == 0 {
-                if self.i_size_len == 0 {
-                    None
-                } else {
-                    self.i_size_len -= 1;
-                    let ret = self.i_size as u8;
-                    self.i_size >>= 8;
-                    Some(Ok(ret))
-                }
-            } else {
-                self.hashlen -= 1;
-                self.hash = Some(hash >> 8);
-                Some(Ok(hash as u8))
+        if let Some(s) = self.buf.pop() {
+            return Some(Ok(s));
+        }
+        let mut buf = mem::take(&mut self.buf);
+        let ret = buf.next(self, iter, action);
+        self.buf = buf;
+        ret
+    }
+
+    fn compress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        action: Action,
+    ) -> Result<Status, CompressionError> {
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            let len = cmp::min(
+                self.out.len() - self.out_pos,
+                output.len() - produced,
+            );
+            output[produced..produced + len]
+                .copy_from_slice(&self.out[self.out_pos..self.out_pos + len]);
+            produced += len;
+            self.out_pos += len;
+            if self.out_pos < self.out.len() || self.finished {
+                return Ok(Status {
+                    consumed,
+                    produced,
+                    done: self.out_pos == self.out.len(),
+                });
             }
-        } else {
-            let mut crc32 = mem::replace(&mut self.crc32, None);
-            let mut i_size = self.i_size;
-            let ret = self.inflater.next(
-                &mut ScanIterator::<I, _, _>::new(iter, |x: &u8| {
-                    crc32.as_mut().unwrap().write_u8(*x);
-                    i_size += 1;
-                }),
+            let status = self.inflater.compress(
+                &input[consumed..],
+                &mut output[produced..],
                 action,
-            );
-            self.i_size = i_size;
-            let _ = mem::replace(&mut self.crc32, crc32);
-            if ret.is_none() {
-                let hash = self.crc32.as_mut().unwrap().finish() as u32;
-                let ret = hash as u8;
-                self.hash = Some(hash >> 8);
-                Some(Ok(ret))
-            } else {
-                ret
+            )?;
+            self.crc32
+                .write(&input[consumed..consumed + status.consumed]);
+            self.i_size = self.i_size.wrapping_add(status.consumed as u32);
+            consumed += status.consumed;
+            produced += status.produced;
+            if !status.done || action != Action::Finish {
+                return Ok(Status {
+                    consumed,
+                    produced,
+                    done: status.done,
+                });
             }
+            self.out.clear();
+            self.out_pos = 0;
+            self.out
+                .extend_from_slice(&(self.crc32.finish() as u32).to_le_bytes());
+            self.out.extend_from_slice(&self.i_size.to_le_bytes());
+            self.finished = true;
         }
     }
 }
@@ -0,0 +1,266 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::checksum::Crc32;
+use crate::core::hash::Hasher;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+pub(crate) const FTEXT: u8 = 0b1;
+pub(crate) const FHCRC: u8 = 0b10;
+pub(crate) const FEXTRA: u8 = 0b100;
+pub(crate) const FNAME: u8 = 0b1000;
+pub(crate) const FCOMMENT: u8 = 0b1_0000;
+
+/// The metadata of a gzip member.
+///
+/// The header is written by `GZipEncoder::with_header` and read by
+/// `GZipDecoder::header`.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "gzip")]
+///     # {
+///     let header = GzHeaderBuilder::new()
+///         .filename(b"data.txt")
+///         .mtime(1_500_000_000)
+///         .os(3)
+///         .build();
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .iter()
+///         .cloned()
+///         .encode(&mut GZipEncoder::with_header(&header), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     let mut decoder = GZipDecoder::new();
+///     let _ = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut decoder)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///     let header = decoder.header().unwrap();
+///     assert_eq!(header.filename(), Some(&b"data.txt"[..]));
+///     assert_eq!(header.mtime(), 1_500_000_000);
+///     # }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GzHeader {
+    pub(crate) text: bool,
+    pub(crate) mtime: u32,
+    pub(crate) xfl: u8,
+    pub(crate) os: u8,
+    pub(crate) extra: Option<Vec<u8>>,
+    pub(crate) filename: Option<Vec<u8>>,
+    pub(crate) comment: Option<Vec<u8>>,
+    pub(crate) header_crc: bool,
+}
+
+impl Default for GzHeader {
+    fn default() -> Self {
+        Self {
+            text: false,
+            mtime: 0,
+            xfl: 0,
+            os: 0xFF,
+            extra: None,
+            filename: None,
+            comment: None,
+            header_crc: false,
+        }
+    }
+}
+
+impl GzHeader {
+    /// Returns whether the data is marked as text (FTEXT).
+    pub fn is_text(&self) -> bool {
+        self.text
+    }
+
+    /// Returns the modification time in seconds since the Unix epoch,
+    /// `0` if not available.
+    pub fn mtime(&self) -> u32 {
+        self.mtime
+    }
+
+    /// Returns the extra flags (XFL).
+    pub fn xfl(&self) -> u8 {
+        self.xfl
+    }
+
+    /// Returns the operating system which wrote the file, `0xFF` if
+    /// unknown.
+    pub fn os(&self) -> u8 {
+        self.os
+    }
+
+    /// Returns the raw extra field (FEXTRA) without its length.
+    pub fn extra(&self) -> Option<&[u8]> {
+        self.extra.as_deref()
+    }
+
+    /// Returns the data of the first extra subfield with the identifier
+    /// `id`.
+    pub fn subfield(&self, id: [u8; 2]) -> Option<&[u8]> {
+        let mut extra = self.extra.as_deref()?;
+        while extra.len() >= 4 {
+            let len = usize::from(extra[2]) | (usize::from(extra[3]) << 8);
+            let data = extra.get(4..4 + len)?;
+            if extra[..2] == id {
+                return Some(data);
+            }
+            extra = &extra[4 + len..];
+        }
+        None
+    }
+
+    /// Returns the original file name (FNAME) without its terminator.
+    pub fn filename(&self) -> Option<&[u8]> {
+        self.filename.as_deref()
+    }
+
+    /// Returns the comment (FCOMMENT) without its terminator.
+    pub fn comment(&self) -> Option<&[u8]> {
+        self.comment.as_deref()
+    }
+
+    /// Returns whether the header is protected by a CRC16 (FHCRC).
+    pub fn header_crc(&self) -> bool {
+        self.header_crc
+    }
+
+    pub(crate) fn to_bytes(&self, xfl: u8) -> Vec<u8> {
+        let mut flg = 0;
+        if self.text {
+            flg |= FTEXT;
+        }
+        if self.header_crc {
+            flg |= FHCRC;
+        }
+        if self.extra.is_some() {
+            flg |= FEXTRA;
+        }
+        if self.filename.is_some() {
+            flg |= FNAME;
+        }
+        if self.comment.is_some() {
+            flg |= FCOMMENT;
+        }
+        let mut ret = vec![0x1F, 0x8B, 0x08, flg];
+        ret.extend_from_slice(&self.mtime.to_le_bytes());
+        ret.push(xfl);
+        ret.push(self.os);
+        if let Some(ref extra) = self.extra {
+            ret.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+            ret.extend_from_slice(extra);
+        }
+        for s in self.filename.iter().chain(self.comment.iter()) {
+            ret.extend_from_slice(s);
+            ret.push(0);
+        }
+        if self.header_crc {
+            let mut digest = Crc32::new();
+            digest.write(&ret);
+            ret.extend_from_slice(&(digest.finish() as u16).to_le_bytes());
+        }
+        ret
+    }
+}
+
+/// A builder of `GzHeader`.
+///
+/// The header without any field set is the one written by
+/// `GZipEncoder::new`.
+#[derive(Debug, Clone, Default)]
+pub struct GzHeaderBuilder {
+    header: GzHeader,
+}
+
+impl GzHeaderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks the data as text.
+    pub fn text(mut self, text: bool) -> Self {
+        self.header.text = text;
+        self
+    }
+
+    /// Sets the modification time in seconds since the Unix epoch.
+    pub fn mtime(mut self, mtime: u32) -> Self {
+        self.header.mtime = mtime;
+        self
+    }
+
+    /// Sets the operating system, `0xFF` if unknown.
+    pub fn os(mut self, os: u8) -> Self {
+        self.header.os = os;
+        self
+    }
+
+    /// Appends an extra subfield.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the extra field exceeds 65535 bytes.
+    pub fn extra_subfield(mut self, id: [u8; 2], data: &[u8]) -> Self {
+        let extra = self.header.extra.get_or_insert_with(Vec::new);
+        if extra.len() + data.len() + 4 > 0xFFFF {
+            panic!("extra field is too long");
+        }
+        extra.extend_from_slice(&id);
+        extra.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        extra.extend_from_slice(data);
+        self
+    }
+
+    /// Sets the original file name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `filename` contains a zero byte.
+    pub fn filename(mut self, filename: &[u8]) -> Self {
+        if filename.contains(&0) {
+            panic!("filename contains a zero byte");
+        }
+        self.header.filename = Some(filename.to_vec());
+        self
+    }
+
+    /// Sets the comment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `comment` contains a zero byte.
+    pub fn comment(mut self, comment: &[u8]) -> Self {
+        if comment.contains(&0) {
+            panic!("comment contains a zero byte");
+        }
+        self.header.comment = Some(comment.to_vec());
+        self
+    }
+
+    /// Protects the header by a CRC16.
+    pub fn header_crc(mut self, header_crc: bool) -> Self {
+        self.header.header_crc = header_crc;
+        self
+    }
+
+    pub fn build(self) -> GzHeader {
+        self.header
+    }
+}
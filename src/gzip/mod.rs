@@ -8,13 +8,16 @@
 
 pub(crate) mod decoder;
 pub(crate) mod encoder;
+pub(crate) mod header;
+pub(crate) mod parallel;
 
 #[cfg(test)]
 mod tests {
     use crate::action::Action;
     use crate::gzip::decoder::GZipDecoder;
     use crate::gzip::encoder::GZipEncoder;
-    use crate::traits::decoder::DecodeExt;
+    use crate::gzip::header::GzHeaderBuilder;
+    use crate::traits::decoder::{DecodeExt, Decoder};
     use crate::traits::encoder::EncodeExt;
     #[cfg(not(feature = "std"))]
     use alloc::vec::Vec;
@@ -106,4 +109,387 @@ mod tests {
     fn test_multiblocks5() {
         test_rand_with_len(0x10_0001);
     }
+
+    #[test]
+    fn test_slice() {
+        use crate::traits::decoder::tests::decompress_slices;
+        use crate::traits::encoder::tests::compress_slices;
+
+        let rng = thread_rng();
+        let testarray = rng
+            .sample_iter(&Standard)
+            .take(0x1_0000)
+            .chain(b"a".iter().cycle().take(0x1_0000).cloned())
+            .collect::<Vec<u8>>();
+        let expected = testarray
+            .iter()
+            .cloned()
+            .encode(&mut GZipEncoder::new(), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        for &(chunk, out_len) in &[(1, 0x100), (0x1000, 7), (0x1000, 0x8000)] {
+            let encoded = compress_slices(
+                &mut GZipEncoder::new(),
+                &testarray,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(encoded == expected);
+            let decoded = decompress_slices(
+                &mut GZipDecoder::new(),
+                &encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded == testarray);
+        }
+    }
+
+    #[test]
+    fn test_index() {
+        let rng = thread_rng();
+        let testarray = rng
+            .sample_iter::<u8, _>(&Standard)
+            .map(|x| x & 0x0F)
+            .take(0x4_0000)
+            .collect::<Vec<u8>>();
+        let mut encoded = encode(&testarray[..0x2_1000]);
+        encoded.extend(encode(&testarray[0x2_1000..]));
+
+        let mut decoder = GZipDecoder::with_index(0x8000);
+        let decoded = encoded
+            .iter()
+            .cloned()
+            .decode(&mut decoder)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(testarray, decoded);
+
+        let index = decoder.index().unwrap();
+        assert!(index.points().len() > 1);
+        for point in index.points() {
+            let decoded = encoded[point.input_offset() as usize..]
+                .iter()
+                .cloned()
+                .decode(&mut GZipDecoder::with_checkpoint(point))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(&testarray[point.output as usize..], &decoded[..]);
+        }
+    }
+
+    fn encode(testarray: &[u8]) -> Vec<u8> {
+        testarray
+            .iter()
+            .cloned()
+            .encode(&mut GZipEncoder::new(), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_multi_member() {
+        let first = b"aabbaabbaaabbbaaabbbaabbaabb";
+        let second = b"bbaabbaabbbaaabbbaaabbaabbaa";
+        let mut encoded = encode(first);
+        encoded.extend(encode(&[]));
+        encoded.extend(encode(second));
+
+        let decoded = encoded
+            .iter()
+            .cloned()
+            .decode(&mut GZipDecoder::new())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!([&first[..], &second[..]].concat(), decoded);
+
+        let mut decoder = GZipDecoder::single_member();
+        let mut output = [0; 0x100];
+        let status = decoder.decompress(&encoded, &mut output, true).unwrap();
+        assert!(status.done);
+        assert_eq!(&first[..], &output[..status.produced]);
+        let rest = &encoded[status.consumed..];
+        assert_eq!(rest.len(), encoded.len() - encode(first).len());
+
+        let decoded = rest
+            .iter()
+            .cloned()
+            .decode(&mut GZipDecoder::new())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(second.to_vec(), decoded);
+
+        encoded.push(0);
+        assert!(encoded
+            .iter()
+            .cloned()
+            .decode(&mut GZipDecoder::new())
+            .collect::<Result<Vec<_>, _>>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_stream() {
+        use crate::traits::decoder::tests::decompress_slices;
+
+        let rng = thread_rng();
+        let first = rng
+            .sample_iter::<u8, _>(&Standard)
+            .map(|x| x & 0x07)
+            .take(0x1_0000)
+            .collect::<Vec<u8>>();
+        let second = b"bbaabbaabbbaaabbbaaabbaabbaa";
+        let header = GzHeaderBuilder::new()
+            .extra_subfield(*b"AB", b"first")
+            .filename(b"hello.txt")
+            .comment(b"comment")
+            .header_crc(true)
+            .build();
+        let mut encoded = first
+            .iter()
+            .cloned()
+            .encode(&mut GZipEncoder::with_header(&header), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        encoded.extend(encode(&[]));
+        encoded.extend(encode(second));
+        let expected = [&first[..], &second[..]].concat();
+
+        for &(chunk, out_len) in &[(1, 0x100), (5, 1), (0x1000, 0x8000)] {
+            let mut decoder = GZipDecoder::new();
+            let decoded =
+                decompress_slices(&mut decoder, &encoded, chunk, out_len)
+                    .unwrap();
+            assert_eq!(expected, decoded);
+
+            let mut decoder = GZipDecoder::single_member();
+            let decoded =
+                decompress_slices(&mut decoder, &encoded, chunk, out_len)
+                    .unwrap();
+            assert_eq!(first, decoded);
+            assert_eq!(decoder.header(), Some(&header));
+        }
+
+        let mut corrupted = encoded;
+        corrupted[30] ^= 1;
+        assert!(decompress_slices(
+            &mut GZipDecoder::new(),
+            &corrupted,
+            1,
+            0x100
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_header() {
+        let testarray = b"aabbaabbaaabbbaaabbbaabbaabb";
+        let header = GzHeaderBuilder::new()
+            .text(true)
+            .mtime(1_234_567_890)
+            .os(3)
+            .extra_subfield(*b"AB", b"first")
+            .extra_subfield(*b"CD", b"second")
+            .filename(b"hello.txt")
+            .comment(b"comment")
+            .header_crc(true)
+            .build();
+        let encoded = testarray
+            .iter()
+            .cloned()
+            .encode(&mut GZipEncoder::with_header(&header), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let mut decoder = GZipDecoder::new();
+        assert_eq!(decoder.header(), None);
+        let decoded = encoded
+            .iter()
+            .cloned()
+            .decode(&mut decoder)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(testarray.to_vec(), decoded);
+        let parsed = decoder.header().unwrap();
+        assert_eq!(parsed.mtime(), 1_234_567_890);
+        assert_eq!(parsed.os(), 3);
+        assert!(parsed.is_text());
+        assert!(parsed.header_crc());
+        assert_eq!(parsed.filename(), Some(&b"hello.txt"[..]));
+        assert_eq!(parsed.comment(), Some(&b"comment"[..]));
+        assert_eq!(parsed.subfield(*b"AB"), Some(&b"first"[..]));
+        assert_eq!(parsed.subfield(*b"CD"), Some(&b"second"[..]));
+        assert_eq!(parsed.subfield(*b"EF"), None);
+        assert_eq!(parsed, &header);
+
+        let mut corrupted = encoded;
+        corrupted[12] ^= 1;
+        assert!(corrupted
+            .iter()
+            .cloned()
+            .decode(&mut GZipDecoder::new())
+            .collect::<Result<Vec<_>, _>>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_header_external() {
+        // gzip.GzipFile(filename="hello.txt", mtime=1234567890)
+        let encoded = [
+            0x1f, 0x8b, 0x08, 0x08, 0xd2, 0x02, 0x96, 0x49, 0x02, 0xff, 0x68,
+            0x65, 0x6c, 0x6c, 0x6f, 0x2e, 0x74, 0x78, 0x74, 0x00, 0xcb, 0x48,
+            0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x5c, 0x00, 0x3b, 0x7c,
+            0x8a, 0xdf, 0x12, 0x00, 0x00, 0x00,
+        ];
+        let mut decoder = GZipDecoder::new();
+        let decoded = encoded
+            .iter()
+            .cloned()
+            .decode(&mut decoder)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(b"hello hello hello\n".to_vec(), decoded);
+        let header = decoder.header().unwrap();
+        assert_eq!(header.filename(), Some(&b"hello.txt"[..]));
+        assert_eq!(header.mtime(), 1_234_567_890);
+        assert_eq!(header.xfl(), 2);
+        assert_eq!(header.comment(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_parallel() {
+        use crate::deflate::options::DeflateOptions;
+        use crate::gzip::parallel::ParallelGZipEncoder;
+        use crate::io::encoder::EncoderWriter;
+        use std::io::Write;
+
+        let rng = thread_rng();
+        let mut testarray = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cycle()
+            .take(0x1_0000)
+            .cloned()
+            .collect::<Vec<u8>>();
+        testarray.extend(rng.sample_iter::<u8, _>(&Standard).take(0x1_0000));
+        testarray.extend_from_slice(&testarray.clone()[..0x8000]);
+
+        for &len in &[0, 1, 0x1000, testarray.len()] {
+            let encoded = testarray[..len]
+                .iter()
+                .cloned()
+                .encode(
+                    &mut ParallelGZipEncoder::with_options(
+                        &DeflateOptions::default(),
+                        3,
+                        0x1000,
+                    ),
+                    Action::Finish,
+                )
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let decoded = encoded
+                .iter()
+                .cloned()
+                .decode(&mut GZipDecoder::new())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(&testarray[..len], &decoded[..]);
+        }
+
+        let mut writer = EncoderWriter::new(
+            Vec::new(),
+            ParallelGZipEncoder::with_options(
+                &DeflateOptions::new(9),
+                4,
+                0x3000,
+            ),
+        );
+        for (i, chunk) in testarray.chunks(0x1234).enumerate() {
+            writer.write_all(chunk).unwrap();
+            if i % 5 == 0 {
+                writer.flush().unwrap();
+            }
+        }
+        let encoded = writer.finish().unwrap();
+        let decoded = encoded
+            .iter()
+            .cloned()
+            .decode(&mut GZipDecoder::new())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(testarray, decoded);
+    }
+
+    #[test]
+    fn test_errors() {
+        use crate::error::CompressionError;
+
+        let encoded = b"aabbaabb"
+            .iter()
+            .cloned()
+            .encode(&mut GZipEncoder::new(), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let len = encoded.len();
+        let decode = |data: &[u8]| {
+            data.iter()
+                .cloned()
+                .decode(&mut GZipDecoder::new())
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let modify = |pos: usize, value: u8| {
+            let mut data = encoded.clone();
+            data[pos] ^= value;
+            decode(&data)
+        };
+
+        assert_eq!(
+            modify(1, 1),
+            Err(CompressionError::BadMagic { position: 0 })
+        );
+        assert_eq!(
+            modify(2, 1),
+            Err(CompressionError::UnsupportedMethod {
+                method: 9,
+                position: 16
+            })
+        );
+        assert_eq!(
+            modify(3, 0x80),
+            Err(CompressionError::InvalidHeader { position: 24 })
+        );
+        match modify(len - 8, 1) {
+            Err(CompressionError::ChecksumMismatch {
+                expected,
+                actual,
+                position,
+            }) => {
+                assert_eq!(expected ^ actual, 1);
+                assert_eq!(position, ((len as u64) - 8) << 3);
+            }
+            ret => panic!("{:?}", ret),
+        }
+        assert_eq!(
+            modify(len - 4, 1),
+            Err(CompressionError::LengthMismatch {
+                expected: 9,
+                actual: 8,
+                position: ((len as u64) - 4) << 3,
+            })
+        );
+
+        let mut data = encoded.clone();
+        data.extend_from_slice(b"trailing garbage");
+        let err = decode(&data).unwrap_err();
+        assert_eq!(
+            err,
+            CompressionError::TrailingGarbage {
+                position: (len as u64) << 3
+            }
+        );
+        assert_eq!(err.position(), Some((len as u64) << 3));
+    }
 }
@@ -0,0 +1,162 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "std")]
+
+use crate::action::Action;
+use crate::checksum::Crc32;
+use crate::deflate::options::DeflateOptions;
+use crate::deflate::parallel::ParallelDeflater;
+use crate::error::CompressionError;
+use crate::gzip::encoder::GZipEncoder;
+use crate::gzip::header::GzHeader;
+use crate::traits::encoder::Encoder;
+use std::collections::vec_deque::VecDeque;
+
+/// The gzip encoder which compresses the data on multiple threads.
+///
+/// The input is split into chunks, each of which is compressed with the
+/// end of the preceding chunk as the dictionary. The chunks are joined
+/// at sync flush boundaries, so the output is a single gzip member which
+/// any gzip decoder can read.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "gzip")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut ParallelGZipEncoder::new(4), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     # #[cfg(feature = "gzip")]
+///     let decompressed = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut GZipDecoder::new())
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ParallelGZipEncoder {
+    deflater: ParallelDeflater,
+    queue: VecDeque<u8>,
+    crc32: u32,
+    i_size: u32,
+    finished: bool,
+}
+
+impl Default for ParallelGZipEncoder {
+    fn default() -> Self {
+        Self::new(ParallelDeflater::default_threads())
+    }
+}
+
+impl ParallelGZipEncoder {
+    pub fn new(threads: usize) -> Self {
+        Self::with_options(
+            &DeflateOptions::default(),
+            threads,
+            ParallelDeflater::DEFAULT_CHUNK_SIZE,
+        )
+    }
+
+    pub fn with_options(
+        options: &DeflateOptions,
+        threads: usize,
+        chunk_size: usize,
+    ) -> Self {
+        Self::with_options_and_header(
+            options,
+            &GzHeader::default(),
+            threads,
+            chunk_size,
+        )
+    }
+
+    pub fn with_options_and_header(
+        options: &DeflateOptions,
+        header: &GzHeader,
+        threads: usize,
+        chunk_size: usize,
+    ) -> Self {
+        Self {
+            deflater: ParallelDeflater::new(options, threads, chunk_size),
+            queue: header.to_bytes(GZipEncoder::xfl(options)).into(),
+            crc32: 0,
+            i_size: 0,
+            finished: false,
+        }
+    }
+
+    fn compress(
+        &mut self,
+        finish: bool,
+        reset: bool,
+    ) -> Result<(), CompressionError> {
+        let chunks = self.deflater.compress(finish, reset, |data| {
+            let mut crc32 = Crc32::new();
+            crc32.update(data);
+            crc32.value()
+        })?;
+        for chunk in chunks {
+            self.crc32 = Crc32::combine(self.crc32, chunk.checksum, chunk.len);
+            self.i_size = self.i_size.wrapping_add(chunk.len as u32);
+            self.queue.extend(chunk.data);
+        }
+        Ok(())
+    }
+}
+
+impl Encoder for ParallelGZipEncoder {
+    type Error = CompressionError;
+    type In = u8;
+    type Out = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<u8, CompressionError>> {
+        while self.queue.is_empty() {
+            if self.deflater.is_full() {
+                if let Err(e) = self.compress(false, false) {
+                    return Some(Err(e));
+                }
+                continue;
+            }
+            match iter.next() {
+                Some(s) => self.deflater.push(s),
+                None => {
+                    if self.finished {
+                        self.finished = false;
+                        return None;
+                    }
+                    let ret = match action {
+                        Action::Run => return None,
+                        Action::Finish => self.compress(true, false),
+                        _ => self.compress(false, action == Action::FullFlush),
+                    };
+                    if let Err(e) = ret {
+                        return Some(Err(e));
+                    }
+                    if action == Action::Finish {
+                        self.queue.extend(&self.crc32.to_le_bytes());
+                        self.queue.extend(&self.i_size.to_le_bytes());
+                    }
+                    self.finished = true;
+                }
+            }
+        }
+        self.queue.pop_front().map(Ok)
+    }
+}
@@ -5,6 +5,7 @@
 //! version 2.0 (the "License"). You can obtain a copy of the License at
 //! <http://mozilla.org/MPL/2.0/>.
 
+use crate::core::cmp::Reverse;
 #[cfg(not(feature = "std"))]
 #[allow(unused_imports)]
 use alloc::vec;
@@ -66,7 +67,7 @@ fn gen_code_lm<F: Fn(usize, usize) -> usize>(
         .enumerate()
         .map(|(i, &f)| (i, f))
         .collect::<Vec<_>>();
-    freqmap.sort_by(|x, y| y.1.cmp(&x.1));
+    freqmap.sort_by_key(|x| Reverse(x.1));
     let (map, sfreq): (Vec<_>, Vec<_>) = freqmap.into_iter().unzip();
 
     let mut max_elem = vec![0; lim];
@@ -224,7 +225,13 @@ pub(crate) fn make_tab_with_fn<F: Fn(usize, usize) -> usize>(
     }
 }
 
-#[cfg(any(feature = "deflate", feature = "lzhuf", test))]
+#[cfg(any(
+    feature = "brotli",
+    feature = "deflate",
+    feature = "lzhuf",
+    feature = "zstd",
+    test
+))]
 pub(crate) fn make_table(freq: &[usize], lim: usize) -> Vec<u8> {
     make_tab_with_fn(freq, lim, |x, y| x + y)
 }
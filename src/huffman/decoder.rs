@@ -24,7 +24,7 @@ use alloc::vec;
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct HuffmanDecoder<D: Direction> {
     stab_bits: usize,
     stab: Vec<SymbolTableItem>,
@@ -99,11 +99,12 @@ impl<D: Direction> HuffmanDecoder<D> {
         symb_len: &[u8],
         mut stab_bits: usize,
     ) -> Result<Self, String> {
-        let max_len =
-            symb_len.iter().cloned().max().unwrap_or_else(|| 0) as usize;
+        let max_len = symb_len.iter().cloned().max().unwrap_or(0) as usize;
         stab_bits = cmp::min(max_len, stab_bits);
 
-        if max_len < 16 {
+        if max_len == 0 {
+            Err("empty huffman table".to_owned())
+        } else if max_len < 16 {
             Self::new_t::<u16, _>(symb_len, stab_bits, |d| d as usize)
         } else if max_len < 32 {
             Self::new_t::<u32, _>(symb_len, stab_bits, |d| d as usize)
@@ -130,7 +131,58 @@ impl<D: Direction> HuffmanDecoder<D> {
         F: Fn(T) -> usize,
         SmallBitVec<T>: SmallBitVecReverse,
     {
-        let huff_tab = create_huffman_table::<T>(symb_len, false);
+        Self::with_table(
+            create_huffman_table::<T>(symb_len, false),
+            stab_bits,
+            cast_to_usize,
+        )
+    }
+
+    /// Creates the decoder of the codes given for the symbols, whose
+    /// lengths are less than 16.
+    #[cfg(feature = "zstd")]
+    pub(crate) fn with_codes(
+        codes: Vec<Option<SmallBitVec<u16>>>,
+        stab_bits: usize,
+    ) -> Result<Self, String> {
+        let max_len = codes.iter().flatten().map(|c| c.len()).max();
+        match max_len {
+            Some(max_len) => {
+                Self::with_table(codes, cmp::min(max_len, stab_bits), |d| {
+                    d as usize
+                })
+            }
+            None => Err("empty huffman table".to_owned()),
+        }
+    }
+
+    fn with_table<T, F>(
+        huff_tab: Vec<Option<SmallBitVec<T>>>,
+        stab_bits: usize,
+        cast_to_usize: F,
+    ) -> Result<Self, String>
+    where
+        T: Add<Output = T>
+            + BitAnd<Output = T>
+            + Clone
+            + PartialOrd<T>
+            + Shl<u8, Output = T>
+            + Shl<usize, Output = T>
+            + Shr<usize, Output = T>
+            + Sub<Output = T>
+            + From<u8>,
+        F: Fn(T) -> usize,
+        SmallBitVec<T>: SmallBitVecReverse,
+    {
+        // 符号が重なる表は作れないので、クラフトの不等式を満たすか調べる
+        let kraft = huff_tab
+            .iter()
+            .flatten()
+            .map(|b| 1u64 << (32 - b.len()))
+            .sum::<u64>();
+        if kraft > 1 << 32 {
+            return Err("over-subscribed huffman table".to_owned());
+        }
         let mut stab = vec![SymbolTableItem::None; 1 << stab_bits];
         for (i, h) in huff_tab.into_iter().enumerate() {
             if let Some(b) = h {
@@ -163,7 +215,9 @@ impl<D: Direction> HuffmanDecoder<D> {
                         ld,
                     );
                     match &mut stab[cast_to_usize(head)] {
-                        &mut SymbolTableItem::Short(_, _) => unreachable!(),
+                        &mut SymbolTableItem::Short(_, _) => {
+                            return Err("huffman table error".to_owned())
+                        }
                         &mut SymbolTableItem::Long(ref mut store) => {
                             store.add(&body, i as u16)?;
                         }
@@ -227,7 +281,8 @@ impl<D: Direction> HuffmanDecoder<D> {
                 }
             }
         } else {
-            unreachable!();
+            // 不完全な符号表に含まれない符号
+            Err("huffman table error".to_owned())
         }
     }
 }
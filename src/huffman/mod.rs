@@ -4,7 +4,13 @@
 //! This Source Code is subject to the terms of the Mozilla Public License
 //! version 2.0 (the "License"). You can obtain a copy of the License at
 //! <http://mozilla.org/MPL/2.0/>.
-#![cfg(any(feature = "bzip2", feature = "deflate", feature = "lzhuf"))]
+#![cfg(any(
+    feature = "brotli",
+    feature = "bzip2",
+    feature = "deflate",
+    feature = "lzhuf",
+    feature = "zstd"
+))]
 
 pub(crate) mod cano_huff_table;
 pub(crate) mod decoder;
@@ -40,7 +46,7 @@ where
             .bucket_sort_all_by_key(|x| *x.1)
             .into_iter()
             .scan((0, T::from(0)), move |c, (s, &l)| {
-                let code = c.1.clone() << if c.0 < l { l - c.0 } else { 0 };
+                let code = c.1.clone() << l.saturating_sub(c.0);
                 *c = (l, code.clone() + T::from(1));
                 Some((
                     s,
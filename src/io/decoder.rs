@@ -0,0 +1,217 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::error::CompressionError;
+use crate::io::{to_io_error, BUF_SIZE};
+use crate::traits::decoder::Decoder;
+use std::io::{self, Read, Write};
+
+/// A `Read` adapter which reads compressed data from the underlying
+/// reader and yields it decompressed with `D`.
+///
+/// The errors of the underlying reader are returned as they are, and the
+/// reading can be retried after `io::ErrorKind::WouldBlock` or
+/// `io::ErrorKind::Interrupted`. The data after the end of the stream is
+/// left unread.
+#[derive(Debug)]
+pub struct DecoderReader<R, D>
+where
+    R: Read,
+    D: Decoder<Input = u8, Output = u8>,
+    CompressionError: From<D::Error>,
+{
+    inner: R,
+    decoder: D,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+    eof: bool,
+    finished: bool,
+}
+
+impl<R, D> DecoderReader<R, D>
+where
+    R: Read,
+    D: Decoder<Input = u8, Output = u8>,
+    CompressionError: From<D::Error>,
+{
+    pub fn new(inner: R, decoder: D) -> Self {
+        Self {
+            inner,
+            decoder,
+            buf: vec![0; BUF_SIZE],
+            pos: 0,
+            len: 0,
+            eof: false,
+            finished: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R, D> Read for DecoderReader<R, D>
+where
+    R: Read,
+    D: Decoder<Input = u8, Output = u8>,
+    CompressionError: From<D::Error>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !buf.is_empty() && !self.finished {
+            if self.pos == self.len && !self.eof {
+                match self.inner.read(&mut self.buf)? {
+                    0 => self.eof = true,
+                    n => {
+                        self.pos = 0;
+                        self.len = n;
+                    }
+                }
+            }
+            let status = self
+                .decoder
+                .decompress(&self.buf[self.pos..self.len], buf, self.eof)
+                .map_err(|e| {
+                    self.finished = true;
+                    to_io_error(e)
+                })?;
+            self.pos += status.consumed;
+            self.finished = status.done;
+            if status.produced > 0 {
+                return Ok(status.produced);
+            }
+            if self.eof && !self.finished {
+                // 終わりまで渡しても何も出てこなければ、途中で切れている
+                self.finished = true;
+                return Err(to_io_error::<CompressionError>(
+                    CompressionError::UnexpectedEof,
+                ));
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// A `Write` adapter which decompresses the written data with `D`
+/// and writes the result to the underlying writer.
+///
+/// The written data is decompressed as it arrives, in chunks of any
+/// size. The end of the stream is checked by `finish`, or on drop if
+/// `finish` was not called. The data written after the end of the stream
+/// is an error of `io::ErrorKind::InvalidData`.
+#[derive(Debug)]
+pub struct DecoderWriter<W, D>
+where
+    W: Write,
+    D: Decoder<Input = u8, Output = u8>,
+    CompressionError: From<D::Error>,
+{
+    inner: Option<W>,
+    decoder: D,
+    buf: Vec<u8>,
+    written: u64,
+    done: bool,
+}
+
+impl<W, D> DecoderWriter<W, D>
+where
+    W: Write,
+    D: Decoder<Input = u8, Output = u8>,
+    CompressionError: From<D::Error>,
+{
+    pub fn new(inner: W, decoder: D) -> Self {
+        Self {
+            inner: Some(inner),
+            decoder,
+            buf: vec![0; BUF_SIZE],
+            written: 0,
+            done: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Decompresses the rest of the written data and returns the
+    /// underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.decode(&[], true)?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn decode(&mut self, mut input: &[u8], finish: bool) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        while !self.done {
+            let status = self
+                .decoder
+                .decompress(input, &mut self.buf, finish)
+                .map_err(to_io_error)?;
+            inner.write_all(&self.buf[..status.produced])?;
+            input = &input[status.consumed..];
+            self.written += status.consumed as u64;
+            self.done = status.done;
+            if !finish && status.produced < self.buf.len() {
+                break;
+            }
+        }
+        // 終わりまで復号しても残っていれば、終わりの後に書かれている
+        if self.done && !input.is_empty() {
+            return Err(to_io_error::<CompressionError>(
+                CompressionError::TrailingGarbage {
+                    position: self.written << 3,
+                },
+            ));
+        }
+        if finish {
+            inner.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W, D> Write for DecoderWriter<W, D>
+where
+    W: Write,
+    D: Decoder<Input = u8, Output = u8>,
+    CompressionError: From<D::Error>,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.decode(buf, false)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W, D> Drop for DecoderWriter<W, D>
+where
+    W: Write,
+    D: Decoder<Input = u8, Output = u8>,
+    CompressionError: From<D::Error>,
+{
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.decode(&[], true);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::action::Action;
+use crate::error::CompressionError;
+use crate::io::{to_io_error, BUF_SIZE};
+use crate::traits::encoder::Encoder;
+use std::io::{self, Read, Write};
+
+/// A `Write` adapter which compresses the written data with `E`
+/// and writes the result to the underlying writer.
+///
+/// `flush` drives the encoder with `Action::Flush`. The stream is
+/// completed by `finish`, or on drop if `finish` was not called.
+#[derive(Debug)]
+pub struct EncoderWriter<W, E>
+where
+    W: Write,
+    E: Encoder<In = u8, Out = u8>,
+    CompressionError: From<E::Error>,
+{
+    inner: Option<W>,
+    encoder: E,
+    buf: Vec<u8>,
+}
+
+impl<W, E> EncoderWriter<W, E>
+where
+    W: Write,
+    E: Encoder<In = u8, Out = u8>,
+    CompressionError: From<E::Error>,
+{
+    pub fn new(inner: W, encoder: E) -> Self {
+        Self {
+            inner: Some(inner),
+            encoder,
+            buf: vec![0; BUF_SIZE],
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    pub fn encoder(&self) -> &E {
+        &self.encoder
+    }
+
+    /// Completes the compressed stream and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.encode(&[], Action::Finish)?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn encode(&mut self, mut input: &[u8], action: Action) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        loop {
+            let status = self
+                .encoder
+                .compress(input, &mut self.buf, action)
+                .map_err(to_io_error)?;
+            inner.write_all(&self.buf[..status.produced])?;
+            input = &input[status.consumed..];
+            if status.done {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<W, E> Write for EncoderWriter<W, E>
+where
+    W: Write,
+    E: Encoder<In = u8, Out = u8>,
+    CompressionError: From<E::Error>,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encode(buf, Action::Run)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encode(&[], Action::Flush)?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W, E> Drop for EncoderWriter<W, E>
+where
+    W: Write,
+    E: Encoder<In = u8, Out = u8>,
+    CompressionError: From<E::Error>,
+{
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.encode(&[], Action::Finish);
+        }
+    }
+}
+
+/// A `Read` adapter which reads uncompressed data from the underlying
+/// reader and yields it compressed with `E`.
+#[derive(Debug)]
+pub struct EncoderReader<R, E>
+where
+    R: Read,
+    E: Encoder<In = u8, Out = u8>,
+    CompressionError: From<E::Error>,
+{
+    inner: R,
+    encoder: E,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+    eof: bool,
+    finished: bool,
+}
+
+impl<R, E> EncoderReader<R, E>
+where
+    R: Read,
+    E: Encoder<In = u8, Out = u8>,
+    CompressionError: From<E::Error>,
+{
+    pub fn new(inner: R, encoder: E) -> Self {
+        Self {
+            inner,
+            encoder,
+            buf: vec![0; BUF_SIZE],
+            pos: 0,
+            len: 0,
+            eof: false,
+            finished: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R, E> Read for EncoderReader<R, E>
+where
+    R: Read,
+    E: Encoder<In = u8, Out = u8>,
+    CompressionError: From<E::Error>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut count = 0;
+        while count < buf.len() && !self.finished {
+            if self.pos == self.len && !self.eof {
+                if count > 0 {
+                    break;
+                }
+                match self.inner.read(&mut self.buf) {
+                    Ok(0) => self.eof = true,
+                    Ok(n) => {
+                        self.pos = 0;
+                        self.len = n;
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                        continue
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            let action = if self.eof {
+                Action::Finish
+            } else {
+                Action::Run
+            };
+            let status = self
+                .encoder
+                .compress(
+                    &self.buf[self.pos..self.len],
+                    &mut buf[count..],
+                    action,
+                )
+                .map_err(to_io_error)?;
+            self.pos += status.consumed;
+            count += status.produced;
+            self.finished = status.done && self.eof;
+        }
+        Ok(count)
+    }
+}
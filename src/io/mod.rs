@@ -0,0 +1,193 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "std")]
+
+pub(crate) mod decoder;
+pub(crate) mod encoder;
+
+use crate::error::CompressionError;
+use std::io;
+
+const BUF_SIZE: usize = 0x8000;
+
+pub(crate) fn to_io_error<E>(err: E) -> io::Error
+where
+    CompressionError: From<E>,
+{
+    let err = CompressionError::from(err);
+    let kind = match err {
+        CompressionError::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+        _ => io::ErrorKind::InvalidData,
+    };
+    io::Error::new(kind, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::action::Action;
+    use crate::error::CompressionError;
+    use crate::io::decoder::{DecoderReader, DecoderWriter};
+    use crate::io::encoder::{EncoderReader, EncoderWriter};
+    use crate::traits::decoder::{DecodeExt, Decoder};
+    use crate::traits::encoder::{EncodeExt, Encoder};
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+    use std::io::{self, Read, Write};
+
+    fn testdata() -> Vec<u8> {
+        let rng = thread_rng();
+        let mut data = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cycle()
+            .take(100_000)
+            .cloned()
+            .collect::<Vec<u8>>();
+        data.extend(rng.sample_iter::<u8, _>(&Standard).take(100_000));
+        data
+    }
+
+    fn check<E, D, FE, FD>(new_encoder: FE, new_decoder: FD)
+    where
+        E: Encoder<In = u8, Out = u8>,
+        D: Decoder<Input = u8, Output = u8>,
+        CompressionError: From<E::Error> + From<D::Error>,
+        FE: Fn() -> E,
+        FD: Fn() -> D,
+    {
+        let data = testdata();
+
+        // write side encoder, read side decoder
+        let mut writer = EncoderWriter::new(Vec::new(), new_encoder());
+        for chunk in data.chunks(4096) {
+            writer.write_all(chunk).unwrap();
+        }
+        let encoded = writer.finish().unwrap();
+        let mut reader = DecoderReader::new(&encoded[..], new_decoder());
+        let mut decoded = Vec::new();
+        let _ = reader.read_to_end(&mut decoded).unwrap();
+        assert_eq!(data, decoded);
+
+        // read side encoder, write side decoder
+        let mut reader = EncoderReader::new(&data[..], new_encoder());
+        let mut writer = DecoderWriter::new(Vec::new(), new_decoder());
+        let _ = io::copy(&mut reader, &mut writer).unwrap();
+        assert_eq!(data, writer.finish().unwrap());
+
+        // writing the compressed data in small pieces
+        let mut writer = DecoderWriter::new(Vec::new(), new_decoder());
+        for chunk in encoded.chunks(37) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(data, writer.finish().unwrap());
+
+        // flushing in the middle of the stream
+        let mut writer = EncoderWriter::new(Vec::new(), new_encoder());
+        for chunk in data.chunks(50_000) {
+            writer.write_all(chunk).unwrap();
+            writer.flush().unwrap();
+        }
+        let encoded = writer.finish().unwrap();
+        let decoded = encoded
+            .decode(&mut new_decoder())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(CompressionError::from)
+            .unwrap();
+        assert_eq!(data, decoded);
+    }
+
+    #[cfg(feature = "bzip2")]
+    #[test]
+    fn test_bzip2() {
+        use crate::bzip2::decoder::BZip2Decoder;
+        use crate::bzip2::encoder::BZip2Encoder;
+        check(|| BZip2Encoder::new(1), BZip2Decoder::new);
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn test_deflate() {
+        use crate::deflate::decoder::Deflater;
+        use crate::deflate::encoder::Inflater;
+        check(Inflater::new, Deflater::new);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip() {
+        use crate::gzip::decoder::GZipDecoder;
+        use crate::gzip::encoder::GZipEncoder;
+        check(GZipEncoder::new, GZipDecoder::new);
+    }
+
+    #[cfg(feature = "zlib")]
+    #[test]
+    fn test_zlib() {
+        use crate::zlib::decoder::ZlibDecoder;
+        use crate::zlib::encoder::ZlibEncoder;
+        check(ZlibEncoder::new, ZlibDecoder::new);
+    }
+
+    #[cfg(feature = "lzhuf")]
+    #[test]
+    fn test_lzhuf() {
+        use crate::lzhuf::decoder::LzhufDecoder;
+        use crate::lzhuf::encoder::LzhufEncoder;
+        use crate::lzhuf::LzhufMethod;
+        check(
+            || LzhufEncoder::new(&LzhufMethod::Lh7),
+            || LzhufDecoder::new(&LzhufMethod::Lh7),
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_corrupted() {
+        use crate::gzip::decoder::GZipDecoder;
+        use crate::gzip::encoder::GZipEncoder;
+        let mut encoded = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cloned()
+            .encode(&mut GZipEncoder::new(), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let len = encoded.len();
+        encoded[len - 5] ^= 0xFF;
+        let mut reader = DecoderReader::new(&encoded[..], GZipDecoder::new());
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut reader =
+            DecoderReader::new(&encoded[..len - 4], GZipDecoder::new());
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn test_trailing() {
+        use crate::deflate::decoder::Deflater;
+        use crate::deflate::encoder::Inflater;
+        let mut input = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cloned()
+            .encode(&mut Inflater::new(), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let len = input.len() as u64;
+        input.extend_from_slice(b"garbage");
+
+        let mut writer = DecoderWriter::new(Vec::new(), Deflater::new());
+        let err = writer.write_all(&input).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            *err.into_inner()
+                .unwrap()
+                .downcast::<CompressionError>()
+                .unwrap(),
+            CompressionError::TrailingGarbage { position: len << 3 }
+        );
+    }
+}
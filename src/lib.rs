@@ -46,12 +46,11 @@ pub(crate) use core;
 extern crate alloc;
 
 mod action;
-mod adler32;
 mod bitset;
 mod bucket_sort;
 mod cbuffer;
-mod crc32;
 mod error;
+mod status;
 
 mod bitio;
 mod suffix_array;
@@ -64,11 +63,26 @@ mod bzip2;
 mod deflate;
 mod lzhuf;
 
+mod bgzf;
+mod brotli;
 mod gzip;
+mod lz4;
+mod lzma;
+mod lzw;
+mod snappy;
+mod zip;
 mod zlib;
+mod zstd;
+
+mod io;
+
+pub mod checksum;
 
 pub mod prelude {
     pub use crate::action::Action;
+    pub use crate::checksum::{
+        Adler32, Crc32, Crc32Bzip2, Crc32c, Crc64, Sha256, XxHash32, XxHash64,
+    };
     use cfg_if::cfg_if;
 
     cfg_if! {
@@ -76,6 +90,15 @@ pub mod prelude {
             pub use crate::bzip2::decoder::BZip2Decoder;
             pub use crate::bzip2::encoder::BZip2Encoder;
             pub use crate::bzip2::error::BZip2Error;
+            pub use crate::bzip2::index::{BZip2BlockInfo, BZip2Index};
+        }
+    }
+
+    cfg_if! {
+        if #[cfg(feature = "brotli")] {
+            pub use crate::brotli::decoder::BrotliDecoder;
+            pub use crate::brotli::encoder::BrotliEncoder;
+            pub use crate::brotli::options::BrotliOptions;
         }
     }
 
@@ -83,12 +106,37 @@ pub mod prelude {
         if #[cfg(feature = "deflate")] {
             pub use crate::deflate::decoder::Deflater;
             pub use crate::deflate::encoder::Inflater;
+            pub use crate::deflate::index::{DeflateCheckpoint, DeflateIndex};
+            pub use crate::deflate::options::{DeflateOptions, DeflateStrategy};
         }
     }
     cfg_if! {
         if #[cfg(feature = "gzip")] {
             pub use crate::gzip::decoder::GZipDecoder;
             pub use crate::gzip::encoder::GZipEncoder;
+            pub use crate::gzip::header::{GzHeader, GzHeaderBuilder};
+        }
+    }
+    cfg_if! {
+        if #[cfg(all(feature = "gzip", feature = "std"))] {
+            pub use crate::gzip::parallel::ParallelGZipEncoder;
+        }
+    }
+    cfg_if! {
+        if #[cfg(feature = "bgzf")] {
+            pub use crate::bgzf::encoder::BgzfEncoder;
+            pub use crate::bgzf::index::GziIndex;
+        }
+    }
+    cfg_if! {
+        if #[cfg(all(feature = "bgzf", feature = "std"))] {
+            pub use crate::bgzf::reader::BgzfReader;
+        }
+    }
+    cfg_if! {
+        if #[cfg(feature = "lzw")] {
+            pub use crate::lzw::decoder::LzwDecoder;
+            pub use crate::lzw::encoder::LzwEncoder;
         }
     }
     cfg_if! {
@@ -98,12 +146,55 @@ pub mod prelude {
             pub use crate::lzhuf::encoder::LzhufEncoder;
         }
     }
+    cfg_if! {
+        if #[cfg(feature = "lz4")] {
+            pub use crate::lz4::decoder::Lz4Decoder;
+            pub use crate::lz4::encoder::Lz4Encoder;
+            pub use crate::lz4::options::{Lz4BlockSize, Lz4Options};
+        }
+    }
+    cfg_if! {
+        if #[cfg(feature = "lzma")] {
+            pub use crate::lzma::decoder::LzmaDecoder;
+            pub use crate::lzma::encoder::LzmaEncoder;
+            pub use crate::lzma::options::LzmaOptions;
+            pub use crate::lzma::xz::XzCheck;
+            pub use crate::lzma::xz_decoder::XzDecoder;
+            pub use crate::lzma::xz_encoder::XzEncoder;
+        }
+    }
+    cfg_if! {
+        if #[cfg(feature = "snappy")] {
+            pub use crate::snappy::decoder::SnappyDecoder;
+            pub use crate::snappy::encoder::SnappyEncoder;
+        }
+    }
     cfg_if! {
         if #[cfg(feature = "zlib")] {
             pub use crate::zlib::decoder::ZlibDecoder;
             pub use crate::zlib::encoder::ZlibEncoder;
         }
     }
+    cfg_if! {
+        if #[cfg(all(feature = "zlib", feature = "std"))] {
+            pub use crate::zlib::parallel::ParallelZlibEncoder;
+        }
+    }
+    cfg_if! {
+        if #[cfg(all(feature = "zip", feature = "std"))] {
+            pub use crate::zip::entry::ZipEntry;
+            pub use crate::zip::ZipMethod;
+            pub use crate::zip::reader::{ZipArchive, ZipEntryReader};
+            pub use crate::zip::writer::ZipWriter;
+        }
+    }
+    cfg_if! {
+        if #[cfg(feature = "zstd")] {
+            pub use crate::zstd::decoder::ZstdDecoder;
+            pub use crate::zstd::encoder::ZstdEncoder;
+            pub use crate::zstd::options::ZstdOptions;
+        }
+    }
     cfg_if! {
         if #[cfg(feature = "lzss")] {
             pub use crate::lzss::decoder::LzssDecoder;
@@ -111,7 +202,14 @@ pub mod prelude {
             pub use crate::lzss::LzssCode;
         }
     }
+    cfg_if! {
+        if #[cfg(feature = "std")] {
+            pub use crate::io::decoder::{DecoderReader, DecoderWriter};
+            pub use crate::io::encoder::{EncoderReader, EncoderWriter};
+        }
+    }
     pub use crate::error::CompressionError;
+    pub use crate::status::Status;
     pub use crate::traits::decoder::{DecodeExt, DecodeIterator, Decoder};
     pub use crate::traits::encoder::{EncodeExt, EncodeIterator, Encoder};
 }
@@ -0,0 +1,176 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::core::cmp;
+use crate::error::CompressionError;
+use crate::lz4::{LAST_LITERALS, MF_LIMIT, MIN_MATCH};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The literals followed by a match.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Sequence {
+    pub(crate) literal_len: usize,
+    pub(crate) offset: usize,
+    pub(crate) match_len: usize,
+}
+
+fn write_length(mut len: usize, out: &mut Vec<u8>) {
+    while len >= 0xFF {
+        out.push(0xFF);
+        len -= 0xFF;
+    }
+    out.push(len as u8);
+}
+
+/// Writes a sequence of `literals` and the match of `(offset, length)`.
+/// The last sequence of a block has no match.
+pub(crate) fn write_sequence(
+    literals: &[u8],
+    matched: Option<(usize, usize)>,
+    out: &mut Vec<u8>,
+) {
+    let literal_len = literals.len();
+    let match_len = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push(
+        ((cmp::min(literal_len, 15) << 4) | cmp::min(match_len, 15)) as u8,
+    );
+    if literal_len >= 15 {
+        write_length(literal_len - 15, out);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = matched {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_len >= 15 {
+            write_length(match_len - 15, out);
+        }
+    }
+}
+
+/// Writes the block of `data`, which `sequences` cover from its start.
+pub(crate) fn write_block(
+    data: &[u8],
+    sequences: &[Sequence],
+    out: &mut Vec<u8>,
+) {
+    let mut pos = 0;
+    for s in sequences {
+        write_sequence(
+            &data[pos..pos + s.literal_len],
+            Some((s.offset, s.match_len)),
+            out,
+        );
+        pos += s.literal_len + s.match_len;
+    }
+    write_sequence(&data[pos..], None, out);
+}
+
+/// Shortens or drops the last matches of the block of `len` bytes, so
+/// that the last match starts `MF_LIMIT` bytes and ends `LAST_LITERALS`
+/// bytes before the end of the block.
+pub(crate) fn fix_tail(sequences: &mut Vec<Sequence>, len: usize) {
+    let mut end = sequences
+        .iter()
+        .map(|s| s.literal_len + s.match_len)
+        .sum::<usize>();
+    while let Some(s) = sequences.last_mut() {
+        let start = end - s.match_len;
+        if start + MF_LIMIT > len {
+            // 一致を捨てると、その分はブロック末尾のリテラルになる
+            end = start - s.literal_len;
+            let _ = sequences.pop();
+        } else {
+            if end + LAST_LITERALS > len {
+                s.match_len = len - LAST_LITERALS - start;
+            }
+            break;
+        }
+    }
+}
+
+/// Reads the length continued from the 4 bits `base` of the token.
+fn read_length(data: &[u8], pos: &mut usize, base: usize) -> Option<usize> {
+    let mut len = base;
+    if base == 15 {
+        loop {
+            let b = *data.get(*pos)?;
+            *pos += 1;
+            len = len.checked_add(usize::from(b))?;
+            if b != 0xFF {
+                break;
+            }
+        }
+    }
+    Some(len)
+}
+
+/// Decodes the block `data` to the end of `window`, whose data from
+/// `base` can be referred to. `position` is the position of `data` in
+/// bits.
+pub(crate) fn decode_block(
+    data: &[u8],
+    window: &mut Vec<u8>,
+    base: usize,
+    max_len: usize,
+    position: u64,
+) -> Result<(), CompressionError> {
+    let start = window.len();
+    let mut pos = 0;
+    loop {
+        let invalid = CompressionError::InvalidBlock {
+            position: position + ((pos as u64) << 3),
+        };
+        let token = usize::from(*data.get(pos).ok_or(invalid)?);
+        pos += 1;
+        let literal_len =
+            read_length(data, &mut pos, token >> 4).ok_or(invalid)?;
+        let literals = data
+            .get(pos..pos.saturating_add(literal_len))
+            .ok_or(invalid)?;
+        if window.len() - start + literal_len > max_len {
+            return Err(invalid);
+        }
+        window.extend_from_slice(literals);
+        pos += literal_len;
+        if pos == data.len() {
+            return Ok(());
+        }
+
+        let offset = match data.get(pos..pos + 2) {
+            Some(o) => usize::from(u16::from_le_bytes([o[0], o[1]])),
+            None => return Err(invalid),
+        };
+        pos += 2;
+        let match_len = read_length(data, &mut pos, token & 15)
+            .and_then(|len| len.checked_add(MIN_MATCH))
+            .ok_or(invalid)?;
+        if offset == 0 || offset > window.len() - base {
+            return Err(CompressionError::InvalidDistance {
+                distance: offset,
+                position,
+            });
+        }
+        if window.len() - start + match_len > max_len {
+            return Err(invalid);
+        }
+        copy_match(window, offset, match_len);
+    }
+}
+
+/// Appends the match of `len` bytes `offset` bytes before the end of
+/// `window`.
+pub(crate) fn copy_match(window: &mut Vec<u8>, offset: usize, len: usize) {
+    let from = window.len() - offset;
+    if offset >= len {
+        window.extend_from_within(from..from + len);
+    } else {
+        for i in from..from + len {
+            let b = window[i];
+            window.push(b);
+        }
+    }
+}
@@ -0,0 +1,456 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bitio::direction::right::Right;
+use crate::bitio::reader::{BitRead, BitReader};
+use crate::checksum::XxHash32;
+use crate::core::cmp;
+use crate::error::CompressionError;
+use crate::lz4::block::{copy_match, decode_block};
+use crate::lz4::options::Lz4BlockSize;
+use crate::lz4::{MAGIC, MAX_DISTANCE, MIN_MATCH, SKIPPABLE_MAGIC, VERSION};
+use crate::status::Status;
+use crate::traits::decoder::{BitDecodeService, BitDecoderImpl, Decoder};
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The length of the skippable frame read at once.
+const SKIP_CHUNK: u64 = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Frame,
+    /// The length of the skippable frame left.
+    Skip(u64),
+    Block,
+    Trailer,
+    /// A sequence of the raw block.
+    Sequence,
+    Finished,
+}
+
+fn read_u8<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<u8, CompressionError> {
+    match reader.read_bits::<u8, _>(8, iter) {
+        Ok(b) if b.len() == 8 => Ok(b.data()),
+        _ => Err(CompressionError::UnexpectedEof),
+    }
+}
+
+fn read_le<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+    len: usize,
+) -> Result<u64, CompressionError> {
+    (0..len).try_fold(0, |v, i| {
+        Ok(v | (u64::from(read_u8(reader, iter)?) << (i << 3)))
+    })
+}
+
+fn read_bytes<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+    len: usize,
+) -> Result<Vec<u8>, CompressionError> {
+    (0..len).map(|_| read_u8(reader, iter)).collect()
+}
+
+/// Reads the length continued from the 4 bits `base` of the token.
+fn read_length<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+    base: usize,
+) -> Result<usize, CompressionError> {
+    let mut len = base;
+    if base == 15 {
+        loop {
+            let b = read_u8(reader, iter)?;
+            len += usize::from(b);
+            if b != 0xFF {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+fn xxh32(data: &[u8]) -> u32 {
+    let mut xxh32 = XxHash32::new();
+    xxh32.update(data);
+    xxh32.value()
+}
+
+#[derive(Debug)]
+pub(crate) struct Lz4DecoderBase {
+    state: State,
+    frame_no: usize,
+    // 復号したデータのうち、参照されうる部分
+    window: Vec<u8>,
+    out_pos: usize,
+    block_size: usize,
+    block_independence: bool,
+    block_checksum: bool,
+    content_size: Option<u64>,
+    produced: u64,
+    xxh32: Option<XxHash32>,
+}
+
+impl Default for Lz4DecoderBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lz4DecoderBase {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: State::Frame,
+            frame_no: 0,
+            window: Vec::new(),
+            out_pos: 0,
+            block_size: 0,
+            block_independence: false,
+            block_checksum: false,
+            content_size: None,
+            produced: 0,
+            xxh32: None,
+        }
+    }
+
+    fn raw_block() -> Self {
+        Self {
+            state: State::Sequence,
+            ..Self::new()
+        }
+    }
+
+    fn read_frame_header<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        if self.frame_no > 0 {
+            let next = reader
+                .peek_bits::<u8, _>(8, iter)
+                .map_err(|_| CompressionError::UnexpectedEof)?;
+            if next.is_empty() {
+                self.state = State::Finished;
+                return Ok(());
+            }
+        }
+        let magic = read_le(reader, iter, 4)? as u32;
+        if magic & !0x0F == SKIPPABLE_MAGIC {
+            let len = read_le(reader, iter, 4)?;
+            self.frame_no += 1;
+            self.state = State::Skip(len);
+            return Ok(());
+        }
+        if magic != MAGIC {
+            return Err(if self.frame_no == 0 {
+                CompressionError::BadMagic { position }
+            } else {
+                CompressionError::TrailingGarbage { position }
+            });
+        }
+
+        // FLG, BD
+        let flg = read_u8(reader, iter)?;
+        let bd = read_u8(reader, iter)?;
+        let invalid = CompressionError::InvalidHeader {
+            position: position + 32,
+        };
+        if flg >> 6 != VERSION || flg & 0x02 != 0 || bd & 0x8F != 0 {
+            return Err(invalid);
+        }
+        let block_size = Lz4BlockSize::from_id(bd >> 4).ok_or(invalid)?;
+        let mut descriptor = vec![flg, bd];
+        let content_size = if flg & 0x08 != 0 {
+            let s = read_le(reader, iter, 8)?;
+            descriptor.extend_from_slice(&s.to_le_bytes());
+            Some(s)
+        } else {
+            None
+        };
+        let dict_id = if flg & 0x01 != 0 {
+            let id = read_le(reader, iter, 4)? as u32;
+            descriptor.extend_from_slice(&id.to_le_bytes());
+            Some(id)
+        } else {
+            None
+        };
+        // HC
+        let expected = u32::from(read_u8(reader, iter)?);
+        let actual = (xxh32(&descriptor) >> 8) & 0xFF;
+        if expected != actual {
+            return Err(CompressionError::ChecksumMismatch {
+                expected,
+                actual,
+                position: position + ((descriptor.len() as u64 + 4) << 3),
+            });
+        }
+        if let Some(id) = dict_id {
+            return Err(CompressionError::DictionaryRequired { id, position });
+        }
+
+        self.window.clear();
+        self.out_pos = 0;
+        self.block_size = block_size.size();
+        self.block_independence = flg & 0x20 != 0;
+        self.block_checksum = flg & 0x10 != 0;
+        self.content_size = content_size;
+        self.produced = 0;
+        self.xxh32 = if flg & 0x04 != 0 {
+            Some(XxHash32::new())
+        } else {
+            None
+        };
+        self.frame_no += 1;
+        self.state = State::Block;
+        Ok(())
+    }
+
+    /// Drops the data which can no longer be referred to.
+    fn trim(&mut self) {
+        let len = self.window.len();
+        if len > MAX_DISTANCE * 2 {
+            let _ = self.window.drain(..len - MAX_DISTANCE);
+            self.out_pos -= len - MAX_DISTANCE;
+        }
+    }
+
+    fn read_block<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        let header = read_le(reader, iter, 4)? as u32;
+        if header == 0 {
+            self.state = State::Trailer;
+            return Ok(());
+        }
+        let size = (header & 0x7FFF_FFFF) as usize;
+        if size > self.block_size {
+            return Err(CompressionError::InvalidBlock { position });
+        }
+        let data = read_bytes(reader, iter, size)?;
+        if self.block_checksum {
+            let expected = read_le(reader, iter, 4)? as u32;
+            let actual = xxh32(&data);
+            if expected != actual {
+                return Err(CompressionError::ChecksumMismatch {
+                    expected,
+                    actual,
+                    position: position + ((size as u64 + 4) << 3),
+                });
+            }
+        }
+        let start = self.window.len();
+        if header & 0x8000_0000 != 0 {
+            self.window.extend_from_slice(&data);
+        } else {
+            let base = if self.block_independence { start } else { 0 };
+            decode_block(
+                &data,
+                &mut self.window,
+                base,
+                self.block_size,
+                position + 32,
+            )?;
+        }
+        if let Some(ref mut xxh32) = self.xxh32 {
+            xxh32.update(&self.window[start..]);
+        }
+        self.produced += (self.window.len() - start) as u64;
+        Ok(())
+    }
+
+    fn read_trailer<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        if let Some(expected) = self.content_size {
+            if expected != self.produced {
+                return Err(CompressionError::LengthMismatch {
+                    expected,
+                    actual: self.produced,
+                    position,
+                });
+            }
+        }
+        if let Some(ref xxh32) = self.xxh32 {
+            let expected = read_le(reader, iter, 4)? as u32;
+            let actual = xxh32.value();
+            if expected != actual {
+                return Err(CompressionError::ChecksumMismatch {
+                    expected,
+                    actual,
+                    position,
+                });
+            }
+        }
+        self.state = State::Frame;
+        Ok(())
+    }
+
+    /// Reads a sequence of the raw block, which ends after the literals
+    /// at the end of the input.
+    fn read_sequence<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        if self.window.is_empty() && Self::at_end(reader, iter)? {
+            self.state = State::Finished;
+            return Ok(());
+        }
+        let token = usize::from(read_u8(reader, iter)?);
+        let literal_len = read_length(reader, iter, token >> 4)?;
+        let literals = read_bytes(reader, iter, literal_len)?;
+        if Self::at_end(reader, iter)? {
+            self.window.extend_from_slice(&literals);
+            self.state = State::Finished;
+            return Ok(());
+        }
+        let offset = read_le(reader, iter, 2)? as usize;
+        let match_len = read_length(reader, iter, token & 15)? + MIN_MATCH;
+        if offset == 0 || offset > self.window.len() + literal_len {
+            return Err(CompressionError::InvalidDistance {
+                distance: offset,
+                position,
+            });
+        }
+        self.window.extend_from_slice(&literals);
+        copy_match(&mut self.window, offset, match_len);
+        Ok(())
+    }
+
+    fn at_end<I: Iterator<Item = u8>>(
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<bool, CompressionError> {
+        reader
+            .peek_bits::<u8, _>(8, iter)
+            .map(|b| b.is_empty())
+            .map_err(|_| CompressionError::UnexpectedEof)
+    }
+}
+
+impl BitDecodeService for Lz4DecoderBase {
+    type Direction = Right;
+    type Error = CompressionError;
+    type Output = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Self::Direction>,
+        iter: &mut I,
+    ) -> Result<Option<u8>, Self::Error> {
+        loop {
+            if self.out_pos < self.window.len() {
+                self.out_pos += 1;
+                return Ok(Some(self.window[self.out_pos - 1]));
+            }
+            reader.commit();
+            match self.state {
+                State::Frame => self.read_frame_header(reader, iter)?,
+                State::Skip(len) => {
+                    let n = cmp::min(len, SKIP_CHUNK);
+                    for _ in 0..n {
+                        let _ = read_u8(reader, iter)?;
+                    }
+                    self.state = if n == len {
+                        State::Frame
+                    } else {
+                        State::Skip(len - n)
+                    };
+                }
+                State::Block => {
+                    self.trim();
+                    self.read_block(reader, iter)?;
+                }
+                State::Trailer => self.read_trailer(reader, iter)?,
+                State::Sequence => {
+                    self.trim();
+                    self.read_sequence(reader, iter)?;
+                }
+                State::Finished => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The LZ4 decoder.
+///
+/// The decoder continues across the frames of the input until its end,
+/// skipping the skippable frames. The decoder created with `raw_block`
+/// reads a single block without the frame instead.
+#[derive(Debug)]
+pub struct Lz4Decoder {
+    inner: BitDecoderImpl<Lz4DecoderBase>,
+}
+
+impl Lz4Decoder {
+    pub fn new() -> Self {
+        Self {
+            inner: BitDecoderImpl::<Lz4DecoderBase>::new(),
+        }
+    }
+
+    /// Creates a decoder which reads the input as a single block without
+    /// the frame.
+    pub fn raw_block() -> Self {
+        Self {
+            inner: BitDecoderImpl::<Lz4DecoderBase>::with_service(
+                Lz4DecoderBase::raw_block(),
+                BitReader::new(),
+            ),
+        }
+    }
+
+    /// Returns the length of the content of the frame being decoded,
+    /// once its header has been read, if the header has it.
+    pub fn content_size(&self) -> Option<u64> {
+        self.inner.service().content_size
+    }
+}
+
+impl Default for Lz4Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for Lz4Decoder {
+    type Input = u8;
+    type Output = u8;
+    type Error = CompressionError;
+
+    fn next<I: Iterator<Item = Self::Input>>(
+        &mut self,
+        iter: &mut I,
+    ) -> Option<Result<Self::Output, Self::Error>> {
+        self.inner.next(iter)
+    }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        self.inner.decompress(input, output, finish)
+    }
+}
@@ -0,0 +1,356 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::action::Action;
+use crate::checksum::XxHash32;
+use crate::core::cmp::{self, Ordering};
+use crate::error::CompressionError;
+use crate::lz4::block::{fix_tail, write_block, write_sequence, Sequence};
+use crate::lz4::options::Lz4Options;
+use crate::lz4::{MAGIC, MAX_DISTANCE, MF_LIMIT, MIN_MATCH, VERSION};
+use crate::lzss::encoder::LzssEncoder;
+use crate::lzss::LzssCode;
+use crate::traits::encoder::Encoder;
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+const MAX_MATCH: usize = 0x1000;
+
+fn lzss_comparison(lhs: LzssCode, rhs: LzssCode) -> Ordering {
+    match (lhs, rhs) {
+        (
+            LzssCode::Reference {
+                len: llen,
+                pos: lpos,
+            },
+            LzssCode::Reference {
+                len: rlen,
+                pos: rpos,
+            },
+        ) => rlen.cmp(&llen).then(lpos.cmp(&rpos)),
+        (LzssCode::Symbol(_), LzssCode::Symbol(_)) => Ordering::Equal,
+        (_, LzssCode::Symbol(_)) => Ordering::Greater,
+        (LzssCode::Symbol(_), _) => Ordering::Less,
+    }
+}
+
+type Lz4LzssEncoder = LzssEncoder<fn(LzssCode, LzssCode) -> Ordering>;
+
+/// The LZ4 encoder, which writes a frame, or a single block without the
+/// frame with `raw_block`.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "lz4")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut Lz4Encoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     # #[cfg(feature = "lz4")]
+///     let decompressed = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut Lz4Decoder::new())
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Lz4Encoder {
+    lzss: Lz4LzssEncoder,
+    options: Lz4Options,
+    frame: bool,
+    window_size: usize,
+    block_size: usize,
+    // 入力のうち、まだブロックに書いていない部分
+    input: Vec<u8>,
+    block_len: usize,
+    sequences: Vec<Sequence>,
+    literal_len: usize,
+    content_len: u64,
+    xxh32: Option<XxHash32>,
+    out: Vec<u8>,
+    out_pos: usize,
+    written: u64,
+    header_written: bool,
+    finished: bool,
+}
+
+impl Default for Lz4Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lz4Encoder {
+    pub fn new() -> Self {
+        Self::with_options(&Lz4Options::default())
+    }
+
+    pub fn with_options(options: &Lz4Options) -> Self {
+        options.validate();
+        // 内容が窓に収まるときは内容の長さだけ探せばよい
+        let window_size = match options.content_size {
+            Some(s) if s < MAX_DISTANCE as u64 => cmp::max(s as usize, 1),
+            _ => MAX_DISTANCE,
+        };
+        Self {
+            lzss: Self::lzss(options, window_size),
+            options: *options,
+            frame: true,
+            window_size,
+            block_size: options.block_size.size(),
+            input: Vec::new(),
+            block_len: 0,
+            sequences: Vec::new(),
+            literal_len: 0,
+            content_len: 0,
+            xxh32: if options.content_checksum {
+                Some(XxHash32::new())
+            } else {
+                None
+            },
+            out: Vec::new(),
+            out_pos: 0,
+            written: 0,
+            header_written: false,
+            finished: false,
+        }
+    }
+
+    /// Creates an encoder which writes the input as a single block without
+    /// the frame, compressed at `level`.
+    pub fn raw_block(level: usize) -> Self {
+        Self {
+            frame: false,
+            block_size: usize::MAX,
+            xxh32: None,
+            ..Self::with_options(&Lz4Options::new(level))
+        }
+    }
+
+    fn lzss(options: &Lz4Options, window_size: usize) -> Lz4LzssEncoder {
+        let (lazy_level, max_chain, nice_length) = options.search();
+        let mut lzss = LzssEncoder::new(
+            lzss_comparison as fn(LzssCode, LzssCode) -> Ordering,
+            window_size,
+            MAX_MATCH,
+            MIN_MATCH,
+            lazy_level,
+        );
+        lzss.set_search_limit(max_chain, nice_length);
+        lzss
+    }
+
+    fn write_header(&mut self) {
+        self.out.extend_from_slice(&MAGIC.to_le_bytes());
+        let options = &self.options;
+        let mut flg = VERSION << 6;
+        if options.block_independence {
+            flg |= 0x20;
+        }
+        if options.block_checksum {
+            flg |= 0x10;
+        }
+        if options.content_size.is_some() {
+            flg |= 0x08;
+        }
+        if options.content_checksum {
+            flg |= 0x04;
+        }
+        let mut descriptor = vec![flg, options.block_size.id() << 4];
+        if let Some(s) = options.content_size {
+            descriptor.extend_from_slice(&s.to_le_bytes());
+        }
+        let mut xxh32 = XxHash32::new();
+        xxh32.update(&descriptor);
+        descriptor.push((xxh32.value() >> 8) as u8);
+        self.out.extend(descriptor);
+    }
+
+    fn write_block(&mut self) {
+        let len = self.block_len;
+        let content = &self.input[..len];
+        if let Some(ref mut xxh32) = self.xxh32 {
+            xxh32.update(content);
+        }
+        self.content_len += len as u64;
+
+        fix_tail(&mut self.sequences, len);
+        let mut data = Vec::new();
+        write_block(content, &self.sequences, &mut data);
+        let (size, data) = if data.len() < len {
+            (data.len() as u32, &data[..])
+        } else {
+            // 圧縮できないブロックは最上位ビットを立ててそのまま書く
+            (len as u32 | 0x8000_0000, content)
+        };
+        self.out.extend_from_slice(&size.to_le_bytes());
+        self.out.extend_from_slice(data);
+        if self.options.block_checksum {
+            let mut xxh32 = XxHash32::new();
+            xxh32.update(data);
+            self.out.extend_from_slice(&xxh32.value().to_le_bytes());
+        }
+
+        self.input.clear();
+        self.sequences.clear();
+        self.literal_len = 0;
+        self.block_len = 0;
+        if self.options.block_independence {
+            self.lzss = Self::lzss(&self.options, self.window_size);
+        }
+    }
+
+    /// Writes the sequences of the raw block which are followed by enough
+    /// data to end the block.
+    fn write_sequences(&mut self) {
+        let mut pos = 0;
+        let mut count = 0;
+        for s in &self.sequences {
+            let end = pos + s.literal_len + s.match_len;
+            if end + MF_LIMIT > self.block_len {
+                break;
+            }
+            write_sequence(
+                &self.input[pos..pos + s.literal_len],
+                Some((s.offset, s.match_len)),
+                &mut self.out,
+            );
+            pos = end;
+            count += 1;
+        }
+        let _ = self.input.drain(..pos);
+        let _ = self.sequences.drain(..count);
+        self.block_len -= pos;
+    }
+
+    fn push_code(&mut self, code: LzssCode) {
+        match code {
+            LzssCode::Symbol(_) => {
+                self.literal_len += 1;
+                self.block_len += 1;
+            }
+            LzssCode::Reference { len, pos } => {
+                self.sequences.push(Sequence {
+                    literal_len: self.literal_len,
+                    offset: pos + 1,
+                    match_len: len,
+                });
+                self.literal_len = 0;
+                self.block_len += len;
+            }
+        }
+        if !self.frame && self.block_len > MAX_DISTANCE + MF_LIMIT {
+            self.write_sequences();
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), CompressionError> {
+        self.finished = true;
+        if !self.frame {
+            fix_tail(&mut self.sequences, self.block_len);
+            write_block(&self.input, &self.sequences, &mut self.out);
+            return Ok(());
+        }
+        if self.block_len > 0 {
+            self.write_block();
+        }
+        // EndMark
+        self.out.extend_from_slice(&[0; 4]);
+        if let Some(expected) = self.options.content_size {
+            if expected != self.content_len {
+                return Err(CompressionError::LengthMismatch {
+                    expected,
+                    actual: self.content_len,
+                    position: (self.written + self.out.len() as u64) << 3,
+                });
+            }
+        }
+        if let Some(ref xxh32) = self.xxh32 {
+            self.out.extend_from_slice(&xxh32.value().to_le_bytes());
+        }
+        Ok(())
+    }
+}
+
+impl Encoder for Lz4Encoder {
+    type Error = CompressionError;
+    type In = u8;
+    type Out = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<u8, CompressionError>> {
+        loop {
+            if self.out_pos < self.out.len() {
+                self.out_pos += 1;
+                return Some(Ok(self.out[self.out_pos - 1]));
+            }
+            self.written += self.out.len() as u64;
+            self.out.clear();
+            self.out_pos = 0;
+            if self.finished {
+                return None;
+            }
+            if !self.header_written {
+                if self.frame {
+                    self.write_header();
+                }
+                self.header_written = true;
+                continue;
+            }
+
+            // ブロックが入力で満ちたら、符号を全て出させてブロックを書く
+            let full = self.input.len() == self.block_size;
+            let rest = self.block_size - self.input.len();
+            let input = &mut self.input;
+            let code = self.lzss.next(
+                &mut iter.by_ref().take(rest).inspect(|&b| input.push(b)),
+                if full { Action::Flush } else { action },
+            );
+            match code {
+                Some(Ok(code)) => self.push_code(code),
+                Some(Err(e)) => return Some(Err(e)),
+                None if self.input.len() == self.block_size => {
+                    if self.block_len == self.block_size {
+                        self.write_block();
+                    }
+                }
+                None => match action {
+                    Action::Run => return None,
+                    Action::Finish => {
+                        if let Err(e) = self.finish() {
+                            return Some(Err(e));
+                        }
+                    }
+                    _ => {
+                        if !self.frame {
+                            self.write_sequences();
+                        } else if self.block_len > 0 {
+                            self.write_block();
+                        }
+                        if self.out.is_empty() {
+                            return None;
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,383 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "lz4")]
+
+pub(crate) mod block;
+pub(crate) mod decoder;
+pub(crate) mod encoder;
+pub(crate) mod options;
+
+const MAGIC: u32 = 0x184D_2204;
+/// The magic of the skippable frames, whose lowest 4 bits are any.
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A50;
+/// The version in the highest 2 bits of the flags.
+const VERSION: u8 = 1;
+
+const MIN_MATCH: usize = 4;
+/// The maximum distance of the matches, and the length of the history.
+const MAX_DISTANCE: usize = 0xFFFF;
+/// The number of the literals which end a block.
+const LAST_LITERALS: usize = 5;
+/// The last match starts this number of bytes before the end of a block.
+const MF_LIMIT: usize = 12;
+
+#[cfg(test)]
+mod tests {
+    use crate::action::Action;
+    use crate::checksum::XxHash32;
+    use crate::error::CompressionError;
+    use crate::lz4::decoder::Lz4Decoder;
+    use crate::lz4::encoder::Lz4Encoder;
+    use crate::lz4::options::{Lz4BlockSize, Lz4Options};
+    use crate::traits::decoder::DecodeExt;
+    use crate::traits::encoder::{EncodeExt, Encoder};
+    #[cfg(not(feature = "std"))]
+    #[allow(unused_imports)]
+    use alloc::vec;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    const SKIPPABLE: [u8; 11] = [
+        0x5A, 0x2A, 0x4D, 0x18, 0x03, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03,
+    ];
+    /// A block of a literal, a match of 99 bytes and no last literals.
+    const BLOCK: [u8; 6] = [0x1F, b'a', 0x01, 0x00, 0x50, 0x00];
+
+    fn decode(
+        encoded: &[u8],
+        decoder: &mut Lz4Decoder,
+    ) -> Result<Vec<u8>, CompressionError> {
+        encoded.iter().cloned().decode(decoder).collect()
+    }
+
+    fn check(encoded: &[u8], decoder: &mut Lz4Decoder, expected: &[u8]) {
+        let decoded = decode(encoded, decoder).unwrap();
+        assert_eq!(decoded.len(), expected.len());
+        assert!(decoded == expected);
+    }
+
+    fn encode(
+        data: &[u8],
+        encoder: &mut Lz4Encoder,
+    ) -> Result<Vec<u8>, CompressionError> {
+        data.iter()
+            .cloned()
+            .encode(encoder, Action::Finish)
+            .collect()
+    }
+
+    fn check_encode(data: &[u8], options: &Lz4Options) -> Vec<u8> {
+        let encoded =
+            encode(data, &mut Lz4Encoder::with_options(options)).unwrap();
+        check(&encoded, &mut Lz4Decoder::new(), data);
+        encoded
+    }
+
+    /// Returns the magic and the frame descriptor of `flg` and `fields`
+    /// followed by the header checksum.
+    fn header(flg: u8, fields: &[u8]) -> Vec<u8> {
+        let mut header = vec![0x04, 0x22, 0x4D, 0x18, flg, 0x40];
+        header.extend_from_slice(fields);
+        let mut xxh32 = XxHash32::new();
+        xxh32.update(&header[4..]);
+        header.push((xxh32.value() >> 8) as u8);
+        header
+    }
+
+    /// Returns a frame of an uncompressed block and a compressed block
+    /// with the content checksum, and its content.
+    fn raw_frame(content_size: u64) -> (Vec<u8>, Vec<u8>) {
+        let mut content = b"lz4 frame".to_vec();
+        content.extend_from_slice(&[b'a'; 100]);
+        let mut xxh32 = XxHash32::new();
+        xxh32.update(&content);
+
+        // Block independence, content size, content checksum
+        let mut frame = header(0x6C, &content_size.to_le_bytes());
+        frame.extend_from_slice(&[0x09, 0x00, 0x00, 0x80]);
+        frame.extend_from_slice(b"lz4 frame");
+        frame.extend_from_slice(&(BLOCK.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&BLOCK);
+        frame.extend_from_slice(&[0; 4]);
+        frame.extend_from_slice(&xxh32.value().to_le_bytes());
+        (frame, content)
+    }
+
+    #[test]
+    fn test_samples() {
+        check(
+            include_bytes!("../../data/sample1.lz4"),
+            &mut Lz4Decoder::new(),
+            include_bytes!("../../data/sample1.ref"),
+        );
+        check(
+            include_bytes!("../../data/sample2.lz4"),
+            &mut Lz4Decoder::new(),
+            include_bytes!("../../data/sample2.ref"),
+        );
+        check(
+            include_bytes!("../../data/sample3.lz4"),
+            &mut Lz4Decoder::new(),
+            include_bytes!("../../data/sample3.ref"),
+        );
+    }
+
+    #[test]
+    fn test_frames() {
+        let (frame, content) = raw_frame(109);
+        let mut decoder = Lz4Decoder::new();
+        check(&frame, &mut decoder, &content);
+        assert_eq!(decoder.content_size(), Some(content.len() as u64));
+
+        let mut encoded = SKIPPABLE.to_vec();
+        encoded.extend_from_slice(&frame);
+        encoded.extend_from_slice(include_bytes!("../../data/sample3.lz4"));
+        encoded.extend_from_slice(&SKIPPABLE);
+        let mut expected = content;
+        expected.extend_from_slice(include_bytes!("../../data/sample3.ref"));
+        check(&encoded, &mut Lz4Decoder::new(), &expected);
+        check(&SKIPPABLE, &mut Lz4Decoder::new(), &[]);
+    }
+
+    #[test]
+    fn test_raw_block() {
+        check(&BLOCK, &mut Lz4Decoder::raw_block(), &[b'a'; 100]);
+        check(&[0x00], &mut Lz4Decoder::raw_block(), &[]);
+        check(&[], &mut Lz4Decoder::raw_block(), &[]);
+
+        // 最後の5バイトはリテラルになる
+        let encoded = encode(&[0x55; 100], &mut Lz4Encoder::raw_block(1));
+        assert_eq!(
+            encoded.unwrap(),
+            vec![
+                0x1F, 0x55, 0x01, 0x00, 0x4B, 0x50, 0x55, 0x55, 0x55, 0x55,
+                0x55
+            ]
+        );
+        assert_eq!(
+            encode(&[], &mut Lz4Encoder::raw_block(1)).unwrap(),
+            vec![0x00]
+        );
+        for &level in &[1, 9] {
+            for data in &[
+                &b"a"[..],
+                &b"abcdefghabcdefgh"[..],
+                &include_bytes!("../../data/sample1.ref")[..],
+                &include_bytes!("../../data/sample3.ref")[..],
+            ] {
+                let encoded =
+                    encode(data, &mut Lz4Encoder::raw_block(level)).unwrap();
+                check(&encoded, &mut Lz4Decoder::raw_block(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        use crate::traits::decoder::tests::decompress_slices;
+
+        let (frame, content) = raw_frame(109);
+        let mut encoded = SKIPPABLE.to_vec();
+        encoded.extend_from_slice(&frame);
+        encoded.extend_from_slice(include_bytes!("../../data/sample1.lz4"));
+        let mut expected = content;
+        expected.extend_from_slice(include_bytes!("../../data/sample1.ref"));
+        let data = include_bytes!("../../data/sample2.ref");
+        let raw = encode(data, &mut Lz4Encoder::raw_block(1)).unwrap();
+        for &(chunk, out_len) in &[(1, 0x1000), (7, 1), (0x1000, 0x8000)] {
+            let decoded = decompress_slices(
+                &mut Lz4Decoder::new(),
+                &encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded == expected);
+            let decoded = decompress_slices(
+                &mut Lz4Decoder::raw_block(),
+                &raw,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded[..] == data[..]);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let (frame, _) = raw_frame(109);
+        let len = frame.len() as u64;
+        assert_eq!(
+            decode(b"not lz4", &mut Lz4Decoder::new()),
+            Err(CompressionError::BadMagic { position: 0 })
+        );
+        assert_eq!(
+            decode(&[], &mut Lz4Decoder::new()),
+            Err(CompressionError::UnexpectedEof)
+        );
+
+        let mut garbage = frame.clone();
+        garbage.extend_from_slice(b"garbage");
+        assert_eq!(
+            decode(&garbage, &mut Lz4Decoder::new()),
+            Err(CompressionError::TrailingGarbage { position: len << 3 })
+        );
+
+        let mut reserved = frame.clone();
+        reserved[4] |= 0x02;
+        assert_eq!(
+            decode(&reserved, &mut Lz4Decoder::new()),
+            Err(CompressionError::InvalidHeader { position: 32 })
+        );
+
+        let mut hc = frame.clone();
+        hc[14] ^= 1;
+        assert!(matches!(
+            decode(&hc, &mut Lz4Decoder::new()),
+            Err(CompressionError::ChecksumMismatch { position, .. })
+                if position == 14 << 3
+        ));
+
+        let mut checksum = frame.clone();
+        checksum[frame.len() - 1] ^= 1;
+        assert!(matches!(
+            decode(&checksum, &mut Lz4Decoder::new()),
+            Err(CompressionError::ChecksumMismatch { position, .. })
+                if position == (len - 4) << 3
+        ));
+
+        let (length, _) = raw_frame(110);
+        assert_eq!(
+            decode(&length, &mut Lz4Decoder::new()),
+            Err(CompressionError::LengthMismatch {
+                expected: 110,
+                actual: 109,
+                position: (len - 4) << 3,
+            })
+        );
+
+        // 独立したブロックは前のブロックを参照できない
+        let mut distance = frame.clone();
+        distance[34] = 2;
+        assert_eq!(
+            decode(&distance, &mut Lz4Decoder::new()),
+            Err(CompressionError::InvalidDistance {
+                distance: 2,
+                position: 32 << 3,
+            })
+        );
+        let mut block = frame.clone();
+        block[28] = 0x05;
+        assert_eq!(
+            decode(&block, &mut Lz4Decoder::new()),
+            Err(CompressionError::InvalidBlock { position: 37 << 3 })
+        );
+
+        let dict = header(0x61, &0x1234_5678_u32.to_le_bytes());
+        assert_eq!(
+            decode(&dict, &mut Lz4Decoder::new()),
+            Err(CompressionError::DictionaryRequired {
+                id: 0x1234_5678,
+                position: 0,
+            })
+        );
+
+        let encoded = include_bytes!("../../data/sample1.lz4");
+        assert_eq!(
+            decode(&encoded[..encoded.len() - 1], &mut Lz4Decoder::new()),
+            Err(CompressionError::UnexpectedEof)
+        );
+        let mut corrupted = encoded.to_vec();
+        corrupted[encoded.len() / 2] ^= 0x55;
+        assert!(decode(&corrupted, &mut Lz4Decoder::new()).is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        let options = Lz4Options::default();
+        check_encode(&[], &options);
+        check_encode(b"a", &options);
+        check_encode(b"aabbaabbaabbaabb\n", &options);
+        check_encode(&[0x55; 0x4_0000], &options);
+        check_encode(include_bytes!("../../data/sample3.ref"), &options);
+        let data = include_bytes!("../../data/sample2.ref");
+        let mut lens = Vec::new();
+        for &level in &[1, 2, 3, 9, 12] {
+            let options = Lz4Options::new(level);
+            check_encode(include_bytes!("../../data/sample1.ref"), &options);
+            lens.push(check_encode(data, &options).len());
+        }
+        assert!(lens[3] < lens[0]);
+    }
+
+    #[test]
+    fn test_encode_options() {
+        let data = include_bytes!("../../data/sample2.ref");
+        for &independence in &[false, true] {
+            let options = Lz4Options {
+                block_size: Lz4BlockSize::Max64KB,
+                block_independence: independence,
+                block_checksum: true,
+                content_checksum: false,
+                content_size: Some(data.len() as u64),
+                ..Lz4Options::new(3)
+            };
+            let encoded = check_encode(data, &options);
+            assert_eq!(encoded[4], if independence { 0x78 } else { 0x58 });
+            assert_eq!(encoded[5], 0x40);
+            let mut decoder = Lz4Decoder::new();
+            check(&encoded, &mut decoder, data);
+            assert_eq!(decoder.content_size(), Some(data.len() as u64));
+        }
+
+        let options = Lz4Options {
+            content_size: Some(3),
+            ..Lz4Options::default()
+        };
+        assert!(matches!(
+            encode(b"abcd", &mut Lz4Encoder::with_options(&options)),
+            Err(CompressionError::LengthMismatch {
+                expected: 3,
+                actual: 4,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_encode_flush() {
+        let data = include_bytes!("../../data/sample1.ref");
+        for &independence in &[false, true] {
+            let options = Lz4Options {
+                block_independence: independence,
+                ..Lz4Options::default()
+            };
+            let mut encoder = Lz4Encoder::with_options(&options);
+            let mut encoded = Vec::new();
+            for (i, chunk) in data.chunks(0x1000).enumerate() {
+                let action = if i % 3 == 0 {
+                    Action::Flush
+                } else {
+                    Action::Run
+                };
+                let mut iter = chunk.iter().cloned();
+                while let Some(b) = encoder.next(&mut iter, action) {
+                    encoded.push(b.unwrap());
+                }
+            }
+            encoded.extend(
+                Vec::new()
+                    .into_iter()
+                    .encode(&mut encoder, Action::Finish)
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap(),
+            );
+            check(&encoded, &mut Lz4Decoder::new(), data);
+        }
+    }
+}
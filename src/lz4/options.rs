@@ -0,0 +1,137 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+/// The maximum length of the data in a block of the LZ4 frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lz4BlockSize {
+    Max64KB,
+    Max256KB,
+    Max1MB,
+    Max4MB,
+}
+
+impl Lz4BlockSize {
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            4 => Some(Lz4BlockSize::Max64KB),
+            5 => Some(Lz4BlockSize::Max256KB),
+            6 => Some(Lz4BlockSize::Max1MB),
+            7 => Some(Lz4BlockSize::Max4MB),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            Lz4BlockSize::Max64KB => 4,
+            Lz4BlockSize::Max256KB => 5,
+            Lz4BlockSize::Max1MB => 6,
+            Lz4BlockSize::Max4MB => 7,
+        }
+    }
+
+    pub(crate) fn size(self) -> usize {
+        1 << (8 + 2 * self.id())
+    }
+}
+
+/// The parameters of the LZ4 encoder.
+///
+/// The levels `1` and `2` are the fast mode, and the levels `3..=12` are
+/// the high compression (HC) mode, which searches the matches further
+/// and defers them for the longer ones.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "lz4")]
+///     let options = Lz4Options {
+///         block_size: Lz4BlockSize::Max64KB,
+///         block_checksum: true,
+///         ..Lz4Options::new(9)
+///     };
+///     # #[cfg(feature = "lz4")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut Lz4Encoder::with_options(&options), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lz4Options {
+    /// `1` is the fastest and `12` is the best compression.
+    pub level: usize,
+    pub block_size: Lz4BlockSize,
+    /// Whether the blocks are compressed without referring to the data of
+    /// the previous blocks.
+    pub block_independence: bool,
+    /// Whether the XXH32 checksum of the data follows each block.
+    pub block_checksum: bool,
+    /// Whether the XXH32 checksum of the input ends the frame.
+    pub content_checksum: bool,
+    /// The length of the input written in the frame header. Finishing the
+    /// frame fails if the input has a different length.
+    pub content_size: Option<u64>,
+}
+
+impl Default for Lz4Options {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LEVEL)
+    }
+}
+
+impl Lz4Options {
+    const DEFAULT_LEVEL: usize = 1;
+    const MAX_LEVEL: usize = 12;
+
+    // lazy_level, max_chain, nice_length
+    const LEVEL_TABLE: [(usize, usize, usize); 12] = [
+        (1, 4, 16),
+        (1, 16, 32),
+        (2, 16, 64),
+        (2, 32, 64),
+        (2, 64, 128),
+        (3, 128, 256),
+        (3, 256, 256),
+        (3, 512, 512),
+        (3, 1024, 1024),
+        (3, 2048, 2048),
+        (3, 4096, 4096),
+        (3, 4096, usize::MAX),
+    ];
+
+    pub fn new(level: usize) -> Self {
+        if !(1..=Self::MAX_LEVEL).contains(&level) {
+            panic!("invalid level");
+        }
+        Self {
+            level,
+            block_size: Lz4BlockSize::Max4MB,
+            block_independence: true,
+            block_checksum: false,
+            content_checksum: true,
+            content_size: None,
+        }
+    }
+
+    pub(crate) fn validate(&self) {
+        if !(1..=Self::MAX_LEVEL).contains(&self.level) {
+            panic!("invalid level");
+        }
+    }
+
+    /// Returns the lazy level, the maximum chain and the nice length of
+    /// the match search.
+    pub(crate) fn search(&self) -> (usize, usize, usize) {
+        Self::LEVEL_TABLE[self.level - 1]
+    }
+}
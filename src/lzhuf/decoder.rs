@@ -12,6 +12,7 @@ use crate::huffman::decoder::HuffmanDecoder;
 use crate::lzhuf::{LzhufMethod, LZSS_MIN_MATCH};
 use crate::lzss::decoder::LzssDecoder;
 use crate::lzss::LzssCode;
+use crate::status::Status;
 use crate::traits::decoder::{
     BitDecodeService, BitDecoder, BitDecoderImpl, DecodeIterator, Decoder,
 };
@@ -34,9 +35,12 @@ impl LzhufHuffmanDecoder {
         iter: &mut I,
     ) -> Result<Option<u16>, CompressionError> {
         match *self {
-            LzhufHuffmanDecoder::HuffmanDecoder(ref mut hd) => hd
-                .dec(reader, iter)
-                .map_err(|_| CompressionError::DataError),
+            LzhufHuffmanDecoder::HuffmanDecoder(ref mut hd) => {
+                let position = reader.position();
+                hd.dec(reader, iter).map_err(|_| {
+                    CompressionError::InvalidHuffmanCode { position }
+                })
+            }
             LzhufHuffmanDecoder::Default(s) => Ok(Some(s)),
         }
     }
@@ -47,6 +51,7 @@ pub(crate) struct LzhufDecoderInner {
     offset_len: usize,
     min_match: usize,
     block_len: usize,
+    window_len: usize,
     symbol_decoder: Option<LzhufHuffmanDecoder>,
     offset_decoder: Option<LzhufHuffmanDecoder>,
 }
@@ -59,6 +64,7 @@ impl LzhufDecoderInner {
             offset_len: method.offset_bits(),
             min_match: LZSS_MIN_MATCH,
             block_len: 0,
+            window_len: 0,
 
             symbol_decoder: None,
             offset_decoder: None,
@@ -108,15 +114,15 @@ impl LzhufDecoderInner {
             let mut ll = Vec::new();
             while ll.len() < len {
                 if ll.len() == 3 {
-                    for _ in 0..reader
+                    let count = reader
                         .read_bits::<u8, _>(2, iter)
                         .map_err(|_| CompressionError::UnexpectedEof)?
-                        .data()
-                    {
-                        ll.push(0);
-                    }
+                        .data();
+                    ll.resize(ll.len() + usize::from(count), 0);
                     if ll.len() > len {
-                        return Err(CompressionError::DataError);
+                        return Err(CompressionError::InvalidHuffmanTable {
+                            position: reader.position(),
+                        });
                     }
                     if ll.len() == len {
                         break;
@@ -125,8 +131,11 @@ impl LzhufDecoderInner {
                 ll.push(self.dec_len(reader, iter)?);
             }
             Ok(LzhufHuffmanDecoder::HuffmanDecoder(
-                HuffmanDecoder::new(&ll, 5)
-                    .map_err(|_| CompressionError::DataError)?,
+                HuffmanDecoder::new(&ll, 5).map_err(|_| {
+                    CompressionError::InvalidHuffmanTable {
+                        position: reader.position(),
+                    }
+                })?,
             ))
         }
     }
@@ -155,30 +164,29 @@ impl LzhufDecoderInner {
                     None => return Err(CompressionError::UnexpectedEof),
                     Some(0) => ll.push(0),
                     Some(1) => {
-                        for _ in 0..(3 + reader
+                        let count = 3 + reader
                             .read_bits::<u8, _>(4, iter)
                             .map_err(|_| CompressionError::UnexpectedEof)?
-                            .data())
-                        {
-                            ll.push(0);
-                        }
+                            .data();
+                        ll.resize(ll.len() + usize::from(count), 0);
                     }
                     Some(2) => {
-                        for _ in 0..(20
+                        let count = 20
                             + reader
                                 .read_bits::<u16, _>(9, iter)
                                 .map_err(|_| CompressionError::UnexpectedEof)?
-                                .data())
-                        {
-                            ll.push(0);
-                        }
+                                .data();
+                        ll.resize(ll.len() + usize::from(count), 0);
                     }
                     Some(n) => ll.push((n - 2) as u8),
                 }
             }
             Ok(LzhufHuffmanDecoder::HuffmanDecoder(
-                HuffmanDecoder::new(&ll, Self::SEARCH_TAB_LEN)
-                    .map_err(|_| CompressionError::DataError)?,
+                HuffmanDecoder::new(&ll, Self::SEARCH_TAB_LEN).map_err(
+                    |_| CompressionError::InvalidHuffmanTable {
+                        position: reader.position(),
+                    },
+                )?,
             ))
         }
     }
@@ -208,7 +216,11 @@ impl LzhufDecoderInner {
                         .collect::<Result<Vec<u8>, CompressionError>>()?,
                     Self::SEARCH_TAB_LEN,
                 )
-                .map_err(|_| CompressionError::DataError)?,
+                .map_err(|_| {
+                    CompressionError::InvalidHuffmanTable {
+                        position: reader.position(),
+                    }
+                })?,
             ))
         }
     }
@@ -224,13 +236,16 @@ impl LzhufDecoderInner {
             .map_err(|_| CompressionError::UnexpectedEof)?
         {
             (s, 16) if s != 0 => {
-                self.block_len = s as usize;
+                // 途中で入力が尽きても再開できるよう、読み終えてから更新する
                 let mut lt = self.dec_len_tree(5, reader, iter)?;
-                self.symbol_decoder =
-                    Some(self.dec_symb_tree(&mut lt, reader, iter)?);
+                let symbol_decoder =
+                    self.dec_symb_tree(&mut lt, reader, iter)?;
                 let offlen = self.offset_len;
-                self.offset_decoder =
-                    Some(self.dec_offs_tree(offlen, reader, iter)?);
+                let offset_decoder =
+                    self.dec_offs_tree(offlen, reader, iter)?;
+                self.block_len = s as usize;
+                self.symbol_decoder = Some(symbol_decoder);
+                self.offset_decoder = Some(offset_decoder);
                 Ok(true)
             }
             _ => Ok(false),
@@ -248,18 +263,22 @@ impl BitDecodeService for LzhufDecoderInner {
         reader: &mut BitReader<Self::Direction>,
         iter: &mut I,
     ) -> Result<Option<LzssCode>, CompressionError> {
-        if self.block_len == 0 && !self.init_block(reader, iter)? {
-            return Ok(None);
+        if self.block_len == 0 {
+            if !self.init_block(reader, iter)? {
+                return Ok(None);
+            }
+            reader.commit();
         }
-        self.block_len -= 1;
+        let position = reader.position();
         let sym = self
             .symbol_decoder
             .as_mut()
             .unwrap()
             .dec(reader, iter)?
-            .ok_or_else(|| CompressionError::UnexpectedEof)?
-            as usize;
+            .ok_or(CompressionError::UnexpectedEof)? as usize;
         if sym <= 255 {
+            self.block_len -= 1;
+            self.window_len = self.window_len.saturating_add(1);
             Ok(Some(LzssCode::Symbol(sym as u8)))
         } else {
             let len = sym - 256 + self.min_match;
@@ -268,7 +287,7 @@ impl BitDecodeService for LzhufDecoderInner {
                 .as_mut()
                 .unwrap()
                 .dec(reader, iter)?
-                .ok_or_else(|| CompressionError::UnexpectedEof)?
+                .ok_or(CompressionError::UnexpectedEof)?
                 as usize;
             if pos > 1 {
                 pos = (1 << (pos - 1))
@@ -277,6 +296,14 @@ impl BitDecodeService for LzhufDecoderInner {
                         .map_err(|_| CompressionError::UnexpectedEof)?
                         .data() as usize;
             }
+            if pos >= self.window_len {
+                return Err(CompressionError::InvalidDistance {
+                    distance: pos + 1,
+                    position,
+                });
+            }
+            self.block_len -= 1;
+            self.window_len = self.window_len.saturating_add(len);
             Ok(Some(LzssCode::Reference { len, pos }))
         }
     }
@@ -314,8 +341,7 @@ impl BitDecodeService for LzhufDecoderBase {
             reader,
         );
         self.lzss_decoder
-            .next(&mut DecodeIterator::<I, _, _>::new(iter, &mut bd).flatten())
-            .transpose()
+            .next_code(&mut DecodeIterator::<I, _, _>::new(iter, &mut bd))
     }
 }
 
@@ -346,4 +372,13 @@ impl Decoder for LzhufDecoder {
     ) -> Option<Result<Self::Output, Self::Error>> {
         self.inner.next(iter)
     }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        self.inner.decompress(input, output, finish)
+    }
 }
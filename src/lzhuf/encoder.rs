@@ -7,17 +7,18 @@
 
 use crate::action::Action;
 use crate::bitio::direction::left::Left;
-use crate::bitio::direction::Direction;
 use crate::bitio::small_bit_vec::SmallBitVec;
 use crate::bitio::writer::BitWriter;
 use crate::core::cmp::{self, Ordering};
+use crate::core::mem;
 use crate::error::CompressionError;
 use crate::huffman::cano_huff_table::make_table;
 use crate::huffman::encoder::HuffmanEncoder;
 use crate::lzhuf::{LzhufMethod, LZSS_MIN_MATCH};
 use crate::lzss::encoder::LzssEncoder;
 use crate::lzss::LzssCode;
-use crate::traits::encoder::Encoder;
+use crate::status::Status;
+use crate::traits::encoder::{EncodeBuffer, Encoder};
 #[cfg(not(feature = "std"))]
 use alloc::collections::vec_deque::VecDeque;
 #[cfg(not(feature = "std"))]
@@ -113,16 +114,19 @@ pub struct LzhufEncoder {
     lzss: LzssEncoder<fn(LzssCode, LzssCode) -> Ordering>,
     writer: BitWriter<Left>,
     queue: VecDeque<SmallBitVec<u16>>,
-    finished: bool,
-    bitbuf: u16,
-    bitbuflen: usize,
-    bit_finished: bool,
+    codes: Vec<LzssCode>,
+    out: Vec<u8>,
+    out_pos: usize,
+    /// The action of which the output has been written to `out`.
+    completed: Option<Action>,
+    buf: EncodeBuffer<u8, u8>,
 }
 
 impl LzhufEncoder {
     const LZSS_MAX_MATCH: usize = 256;
     const LZSS_LAZY_LEVEL: usize = 3;
     const LZHUF_MAX_BLOCK_LENGTH: usize = 0xFFFF;
+    const BUF_LEN: usize = 0x1000;
 
     pub fn new(method: &LzhufMethod) -> Self {
         let dic_len = 1 << method.dictionary_bits();
@@ -141,56 +145,60 @@ impl LzhufEncoder {
             ),
             writer: BitWriter::new(),
             queue: VecDeque::new(),
-            finished: false,
-            bitbuf: 0,
-            bitbuflen: 0,
-            bit_finished: false,
+            codes: Vec::new(),
+            out: Vec::new(),
+            out_pos: 0,
+            completed: None,
+            buf: EncodeBuffer::default(),
         }
     }
 
-    fn next_bits<I: Iterator<Item = u8>>(
+    /// Compresses `input` into `out` until either `out` has been filled
+    /// or the output of `action` has been written, which is told by the
+    /// second value along with the number of the bytes consumed.
+    fn fill(
         &mut self,
-        iter: &mut I,
+        input: &[u8],
         action: Action,
-    ) -> Option<Result<SmallBitVec<u16>, CompressionError>> {
-        while self.queue.is_empty() {
-            match self.lzss.next(iter, action) {
-                Some(Ok(ref s)) => {
-                    if let Err(e) = self.inner.next(s, &mut self.queue) {
-                        return Some(Err(e));
-                    }
-                }
-                Some(Err(e)) => {
-                    return Some(Err(e));
-                }
-                None => {
-                    if self.finished {
-                        self.finished = false;
-                        return None;
-                    } else {
-                        match action {
-                            Action::Flush => {
-                                if let Err(e) =
-                                    self.inner.flush(&mut self.queue)
-                                {
-                                    return Some(Err(e));
-                                }
-                            }
-                            Action::Finish => {
-                                if let Err(e) =
-                                    self.inner.finish(&mut self.queue)
-                                {
-                                    return Some(Err(e));
-                                }
-                            }
-                            _ => {}
-                        }
-                        self.finished = true;
-                    }
-                }
+    ) -> Result<(usize, bool), CompressionError> {
+        if self.codes.is_empty() {
+            self.codes = vec![LzssCode::default(); Self::BUF_LEN];
+        }
+        let mut consumed = 0;
+        loop {
+            let status = self.lzss.compress(
+                &input[consumed..],
+                &mut self.codes,
+                action,
+            )?;
+            consumed += status.consumed;
+            for code in &self.codes[..status.produced] {
+                self.inner.next(code, &mut self.queue)?;
+            }
+            self.write_queue();
+            if status.done {
+                break;
             }
+            if self.out.len() >= Self::BUF_LEN {
+                return Ok((consumed, false));
+            }
+        }
+        match action {
+            Action::Run => {}
+            Action::Finish => self.inner.finish(&mut self.queue)?,
+            _ => self.inner.flush(&mut self.queue)?,
+        }
+        self.write_queue();
+        if action == Action::Finish {
+            self.writer.flush_bytes::<u16>(&mut self.out);
+        }
+        Ok((consumed, true))
+    }
+
+    fn write_queue(&mut self) {
+        while let Some(s) = self.queue.pop_front() {
+            self.writer.write_bytes(&s, &mut self.out);
         }
-        self.queue.pop_front().map(Ok)
     }
 }
 
@@ -203,39 +211,53 @@ impl Encoder for LzhufEncoder {
         iter: &mut I,
         action: Action,
     ) -> Option<Result<u8, CompressionError>> {
-        while self.bitbuflen == 0 {
-            let s = match self.next_bits(iter, action) {
-                Some(Err(e)) => return Some(Err(e)),
-                Some(Ok(ref s)) => self.writer.write_bits(s),
-                None => {
-                    if self.bit_finished {
-                        self.bit_finished = false;
-                        return None;
-                    } else {
-                        match action {
-                            Action::Finish | Action::Flush => {
-                                self.bit_finished = true;
-                                match self.writer.flush::<u16>() {
-                                    Some((x, y)) if y != 0 => (x, y),
-                                    _ => return None,
-                                }
-                            }
-                            _ => {
-                                return None;
-                            }
-                        }
-                    }
-                }
-            };
-            self.bitbuf = s.0;
-            self.bitbuflen = s.1;
+        if let Some(s) = self.buf.pop() {
+            return Some(Ok(s));
         }
+        let mut buf = mem::take(&mut self.buf);
+        let ret = buf.next(self, iter, action);
+        self.buf = buf;
+        ret
+    }
 
-        let ret = Left::convert(self.bitbuf, 16, 8) as u8;
-
-        self.bitbuf = Left::forward(self.bitbuf, 8);
-        self.bitbuflen -= 1;
-        Some(Ok(ret))
+    fn compress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        action: Action,
+    ) -> Result<Status, CompressionError> {
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            let len = cmp::min(
+                self.out.len() - self.out_pos,
+                output.len() - produced,
+            );
+            output[produced..produced + len]
+                .copy_from_slice(&self.out[self.out_pos..self.out_pos + len]);
+            produced += len;
+            self.out_pos += len;
+            if self.out_pos < self.out.len() {
+                return Ok(Status {
+                    consumed,
+                    produced,
+                    done: false,
+                });
+            }
+            self.out.clear();
+            self.out_pos = 0;
+            if consumed == input.len() && self.completed.take() == Some(action)
+            {
+                return Ok(Status {
+                    consumed,
+                    produced,
+                    done: true,
+                });
+            }
+            let (len, completed) = self.fill(&input[consumed..], action)?;
+            consumed += len;
+            self.completed = if completed { Some(action) } else { None };
+        }
     }
 }
 
@@ -161,4 +161,53 @@ mod tests {
     fn test_multiblocks11() {
         check(include_bytes!("../../data/sample7.ref"));
     }
+
+    #[test]
+    fn test_slice() {
+        use crate::traits::decoder::tests::decompress_slices;
+        use crate::traits::encoder::tests::compress_slices;
+
+        let testarray = include_bytes!("../../data/sample4.ref");
+        for method in &[LzhufMethod::Lh5, LzhufMethod::Lh7] {
+            let expected = testarray
+                .iter()
+                .cloned()
+                .encode(&mut LzhufEncoder::new(method), Action::Finish)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            for &(chunk, out_len) in &[(1, 0x100), (0x1000, 3)] {
+                let encoded = compress_slices(
+                    &mut LzhufEncoder::new(method),
+                    testarray,
+                    chunk,
+                    out_len,
+                )
+                .unwrap();
+                assert!(encoded == expected);
+                let decoded = decompress_slices(
+                    &mut LzhufDecoder::new(method),
+                    &encoded,
+                    chunk,
+                    out_len,
+                )
+                .unwrap();
+                assert!(decoded[..] == testarray[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        use crate::error::CompressionError;
+
+        // the length tree of the three codes of 1 bit
+        assert_eq!(
+            [0x00, 0x01, 0x19, 0x24]
+                .iter()
+                .cloned()
+                .decode(&mut LzhufDecoder::new(&LzhufMethod::Lh5))
+                .collect::<Result<Vec<_>, _>>(),
+            Err(CompressionError::InvalidHuffmanTable { position: 30 })
+        );
+    }
 }
@@ -0,0 +1,497 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bitio::direction::right::Right;
+use crate::bitio::reader::{BitRead, BitReader};
+use crate::core::cmp;
+use crate::error::CompressionError;
+use crate::lzma::model::{
+    dist_state, Model, Properties, ALIGN_BITS, DIST_MODEL_END,
+    DIST_MODEL_START, DIST_SLOT_BITS,
+};
+use crate::lzma::range::RangeDecoder;
+use crate::lzma::{END_MARKER, MIN_DICT_SIZE, UNKNOWN_SIZE};
+use crate::status::Status;
+use crate::traits::decoder::{BitDecodeService, BitDecoderImpl, Decoder};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The number of the input bytes which are enough for a packet.
+const LOOKAHEAD: usize = 64;
+
+pub(crate) fn read_u8<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<u8, CompressionError> {
+    match reader.read_bits::<u8, _>(8, iter) {
+        Ok(b) if b.len() == 8 => Ok(b.data()),
+        _ => Err(CompressionError::UnexpectedEof),
+    }
+}
+
+pub(crate) fn read_le<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+    len: usize,
+) -> Result<u64, CompressionError> {
+    (0..len).try_fold(0, |v, i| {
+        Ok(v | (u64::from(read_u8(reader, iter)?) << (i << 3)))
+    })
+}
+
+pub(crate) fn read_bytes<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+    len: usize,
+) -> Result<Vec<u8>, CompressionError> {
+    (0..len).map(|_| read_u8(reader, iter)).collect()
+}
+
+pub(crate) fn at_end<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<bool, CompressionError> {
+    reader
+        .peek_bits::<u8, _>(8, iter)
+        .map(|b| b.is_empty())
+        .map_err(|_| CompressionError::UnexpectedEof)
+}
+
+/// The dictionary, whose bytes not output yet are the last `pending`
+/// bytes.
+#[derive(Debug)]
+pub(crate) struct Window {
+    buf: Vec<u8>,
+    size: usize,
+    pos: usize,
+    // 参照できるバイト数
+    full: usize,
+    pending: usize,
+    /// The number of the bytes since the last reset.
+    pub(crate) total: u64,
+}
+
+impl Window {
+    pub(crate) fn new(size: u32) -> Self {
+        Self {
+            buf: Vec::new(),
+            size: cmp::max(size, MIN_DICT_SIZE) as usize,
+            pos: 0,
+            full: 0,
+            pending: 0,
+            total: 0,
+        }
+    }
+
+    /// Forgets the history, once the pending bytes have been output.
+    pub(crate) fn reset(&mut self) {
+        self.full = 0;
+        self.total = 0;
+    }
+
+    pub(crate) fn put(&mut self, b: u8) {
+        if self.buf.len() < self.size {
+            self.buf.push(b);
+        } else {
+            self.buf[self.pos] = b;
+        }
+        self.pos = if self.pos + 1 == self.size {
+            0
+        } else {
+            self.pos + 1
+        };
+        self.full = cmp::min(self.full + 1, self.size);
+        self.pending += 1;
+        self.total += 1;
+    }
+
+    /// Returns the byte at `dist`, which is `0` before the start.
+    pub(crate) fn get(&self, dist: usize) -> u8 {
+        if dist > self.full {
+            0
+        } else if dist <= self.pos {
+            self.buf[self.pos - dist]
+        } else {
+            self.buf[self.size + self.pos - dist]
+        }
+    }
+
+    fn copy(&mut self, dist: usize, len: usize) {
+        for _ in 0..len {
+            let b = self.get(dist);
+            self.put(b);
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<u8> {
+        if self.pending == 0 {
+            return None;
+        }
+        let b = self.get(self.pending);
+        self.pending -= 1;
+        Some(b)
+    }
+}
+
+/// The model, the range decoder and the dictionary of the LZMA data.
+#[derive(Debug)]
+pub(crate) struct LzmaDecoderCore {
+    pub(crate) model: Model,
+    pub(crate) rc: RangeDecoder,
+    pub(crate) window: Window,
+}
+
+impl LzmaDecoderCore {
+    pub(crate) fn new(props: Properties, dict_size: u32) -> Self {
+        Self {
+            model: Model::new(props),
+            rc: RangeDecoder::new(),
+            window: Window::new(dict_size),
+        }
+    }
+
+    fn decode_literal(&mut self) {
+        let Self {
+            model, rc, window, ..
+        } = self;
+        let after_literal = model.after_literal();
+        let match_byte = window.get(model.reps[0] as usize + 1);
+        let probs = model.literal_probs(window.total, window.get(1));
+        let mut symbol = 1;
+        if !after_literal {
+            let mut match_byte = u32::from(match_byte);
+            while symbol < 0x100 {
+                let match_bit = (match_byte >> 7) & 1;
+                match_byte <<= 1;
+                let bit = rc.bit(
+                    &mut probs[(0x100 + (match_bit << 8) + symbol) as usize],
+                );
+                symbol = (symbol << 1) | bit;
+                if match_bit != bit {
+                    break;
+                }
+            }
+        }
+        while symbol < 0x100 {
+            symbol = (symbol << 1) | rc.bit(&mut probs[symbol as usize]);
+        }
+        window.put(symbol as u8);
+        model.update_literal();
+    }
+
+    /// Decodes the distance minus one of the match of `len` bytes.
+    fn decode_distance(&mut self, len: usize) -> u32 {
+        let Self { model, rc, .. } = self;
+        let slot =
+            rc.tree(&mut model.dist_slot[dist_state(len)], DIST_SLOT_BITS);
+        if slot < DIST_MODEL_START {
+            return slot;
+        }
+        let bits = ((slot >> 1) - 1) as usize;
+        let dist = (2 | (slot & 1)) << bits;
+        if slot < DIST_MODEL_END {
+            let probs = &mut model.dist_special[(dist - slot) as usize..];
+            dist + rc.reverse_tree(probs, bits)
+        } else {
+            dist + (rc.direct(bits - ALIGN_BITS) << ALIGN_BITS)
+                + rc.reverse_tree(&mut model.dist_align, ALIGN_BITS)
+        }
+    }
+
+    /// Decodes a literal or a match of `limit` bytes at most to the
+    /// window, and returns `false` at the end marker.
+    ///
+    /// The input running out fails as `UnexpectedEof`.
+    pub(crate) fn decode_packet(
+        &mut self,
+        limit: u64,
+    ) -> Result<bool, CompressionError> {
+        let position = self.rc.position();
+        let pos_state = self.model.pos_state(self.window.total);
+        let state = self.model.state;
+        let rc = &mut self.rc;
+        let model = &mut self.model;
+        if rc.bit(&mut model.is_match[state][pos_state]) == 0 {
+            self.decode_literal();
+            return if self.rc.is_overrun() {
+                Err(CompressionError::UnexpectedEof)
+            } else {
+                Ok(true)
+            };
+        }
+        let len = if rc.bit(&mut model.is_rep[state]) == 0 {
+            let len = model.match_len.decode(rc, pos_state);
+            model.update_match();
+            let dist = self.decode_distance(len);
+            if self.rc.is_overrun() {
+                return Err(CompressionError::UnexpectedEof);
+            }
+            if dist == END_MARKER {
+                return Ok(false);
+            }
+            let reps = &mut self.model.reps;
+            *reps = [dist, reps[0], reps[1], reps[2]];
+            len
+        } else {
+            if rc.bit(&mut model.is_rep0[state]) == 0 {
+                if rc.bit(&mut model.is_rep0_long[state][pos_state]) == 0 {
+                    model.update_short_rep();
+                    1
+                } else {
+                    model.update_rep();
+                    model.rep_len.decode(rc, pos_state)
+                }
+            } else {
+                let reps = &mut model.reps;
+                let dist = if rc.bit(&mut model.is_rep1[state]) == 0 {
+                    reps[1]
+                } else if rc.bit(&mut model.is_rep2[state]) == 0 {
+                    let dist = reps[2];
+                    reps[2] = reps[1];
+                    dist
+                } else {
+                    let dist = reps[3];
+                    reps[3] = reps[2];
+                    reps[2] = reps[1];
+                    dist
+                };
+                reps[1] = reps[0];
+                reps[0] = dist;
+                model.update_rep();
+                model.rep_len.decode(rc, pos_state)
+            }
+        };
+        if self.rc.is_overrun() {
+            return Err(CompressionError::UnexpectedEof);
+        }
+        let distance = self.model.reps[0] as usize + 1;
+        if distance > self.window.full {
+            return Err(CompressionError::InvalidDistance {
+                distance,
+                position,
+            });
+        }
+        if len as u64 > limit {
+            return Err(CompressionError::InvalidBlock { position });
+        }
+        self.window.copy(distance, len);
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Header,
+    Data,
+    Finished,
+}
+
+#[derive(Debug)]
+pub(crate) struct LzmaDecoderBase {
+    state: State,
+    core: Option<LzmaDecoderCore>,
+    size: Option<u64>,
+    initialized: bool,
+}
+
+impl Default for LzmaDecoderBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LzmaDecoderBase {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: State::Header,
+            core: None,
+            size: None,
+            initialized: false,
+        }
+    }
+
+    fn read_header<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        let props = read_u8(reader, iter)?;
+        let dict_size = read_le(reader, iter, 4)? as u32;
+        let size = read_le(reader, iter, 8)?;
+        let props = Properties::from_byte(props)
+            .ok_or(CompressionError::InvalidHeader { position })?;
+        self.core = Some(LzmaDecoderCore::new(props, dict_size));
+        self.size = if size == UNKNOWN_SIZE {
+            None
+        } else {
+            Some(size)
+        };
+        self.state = State::Data;
+        Ok(())
+    }
+
+    /// Reads the input to the range decoder until it has enough for a
+    /// packet.
+    fn fill<I: Iterator<Item = u8>>(
+        rc: &mut RangeDecoder,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        while rc.available() < LOOKAHEAD && !at_end(reader, iter)? {
+            let position = reader.position();
+            rc.push(read_u8(reader, iter)?, position);
+            // 読んだバイトは範囲復号器に移ったので巻き戻さない
+            reader.commit();
+        }
+        Ok(())
+    }
+
+    fn read_data<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let core = self.core.as_mut().unwrap();
+        Self::fill(&mut core.rc, reader, iter)?;
+        if !self.initialized {
+            let position = core.rc.position();
+            if core.rc.available() < 5 {
+                return Err(CompressionError::UnexpectedEof);
+            }
+            if !core.rc.init() {
+                return Err(CompressionError::InvalidBlock { position });
+            }
+            self.initialized = true;
+            return Ok(());
+        }
+        let total = core.window.total;
+        let more = match self.size {
+            Some(size) if total == size => false,
+            Some(size) => core.decode_packet(size - total)?,
+            None => core.decode_packet(u64::MAX)?,
+        };
+        if more {
+            return Ok(());
+        }
+        let position = core.rc.position();
+        if let Some(expected) = self.size {
+            if expected != core.window.total {
+                return Err(CompressionError::LengthMismatch {
+                    expected,
+                    actual: core.window.total,
+                    position,
+                });
+            }
+        }
+        if !core.rc.is_finished() {
+            return Err(CompressionError::InvalidBlock { position });
+        }
+        if core.rc.available() > 0 || !at_end(reader, iter)? {
+            return Err(CompressionError::TrailingGarbage { position });
+        }
+        self.state = State::Finished;
+        Ok(())
+    }
+}
+
+impl BitDecodeService for LzmaDecoderBase {
+    type Direction = Right;
+    type Error = CompressionError;
+    type Output = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Self::Direction>,
+        iter: &mut I,
+    ) -> Result<Option<u8>, Self::Error> {
+        loop {
+            if let Some(b) = self.core.as_mut().and_then(|c| c.window.pop()) {
+                return Ok(Some(b));
+            }
+            reader.commit();
+            match self.state {
+                State::Header => self.read_header(reader, iter)?,
+                State::Data => self.read_data(reader, iter)?,
+                State::Finished => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The decoder of the `.lzma` format of LZMA Utils, which is the LZMA data
+/// after the properties, the dictionary size and the uncompressed size.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "lzma")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut LzmaEncoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     # #[cfg(feature = "lzma")]
+///     let decompressed = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut LzmaDecoder::new())
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LzmaDecoder {
+    inner: BitDecoderImpl<LzmaDecoderBase>,
+}
+
+impl LzmaDecoder {
+    pub fn new() -> Self {
+        Self {
+            inner: BitDecoderImpl::<LzmaDecoderBase>::new(),
+        }
+    }
+
+    /// Returns the uncompressed size, once the header has been read, if
+    /// the header has it.
+    pub fn content_size(&self) -> Option<u64> {
+        self.inner.service().size
+    }
+}
+
+impl Default for LzmaDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for LzmaDecoder {
+    type Input = u8;
+    type Output = u8;
+    type Error = CompressionError;
+
+    fn next<I: Iterator<Item = Self::Input>>(
+        &mut self,
+        iter: &mut I,
+    ) -> Option<Result<Self::Output, Self::Error>> {
+        self.inner.next(iter)
+    }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        self.inner.decompress(input, output, finish)
+    }
+}
@@ -0,0 +1,351 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::action::Action;
+use crate::core::cmp::Ordering;
+use crate::error::CompressionError;
+use crate::lzma::model::{
+    dist_slot, dist_state, Model, ALIGN_BITS, DIST_MODEL_END, DIST_MODEL_START,
+    DIST_SLOT_BITS,
+};
+use crate::lzma::options::LzmaOptions;
+use crate::lzma::range::RangeEncoder;
+use crate::lzma::{END_MARKER, MAX_MATCH, MIN_MATCH, UNKNOWN_SIZE};
+use crate::lzss::encoder::LzssEncoder;
+use crate::lzss::LzssCode;
+use crate::traits::encoder::Encoder;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The matches are searched from this length, though the shorter ones
+/// are coded with the repeated distances.
+const MIN_SEARCH: usize = 3;
+
+fn lzss_comparison(lhs: LzssCode, rhs: LzssCode) -> Ordering {
+    match (lhs, rhs) {
+        (
+            LzssCode::Reference {
+                len: llen,
+                pos: lpos,
+            },
+            LzssCode::Reference {
+                len: rlen,
+                pos: rpos,
+            },
+        ) => {
+            // 1バイトの長さは距離の2ビットに勝る
+            ((rlen << 2) + rpos.leading_zeros() as usize)
+                .cmp(&((llen << 2) + lpos.leading_zeros() as usize))
+        }
+        (LzssCode::Symbol(_), LzssCode::Symbol(_)) => Ordering::Equal,
+        (_, LzssCode::Symbol(_)) => Ordering::Greater,
+        (LzssCode::Symbol(_), _) => Ordering::Less,
+    }
+}
+
+type LzmaLzssEncoder = LzssEncoder<fn(LzssCode, LzssCode) -> Ordering>;
+
+/// The match search, the model and the range encoder of the LZMA data.
+#[derive(Debug)]
+pub(crate) struct LzmaEncoderCore {
+    lzss: LzmaLzssEncoder,
+    pub(crate) model: Model,
+    pub(crate) rc: RangeEncoder,
+    // 符号にしたデータの末尾
+    history: Vec<u8>,
+    history_size: usize,
+    /// The number of the bytes coded.
+    pub(crate) total: u64,
+}
+
+impl LzmaEncoderCore {
+    /// Creates the encoder which keeps the last `history_size` bytes at
+    /// least, which is not less than the dictionary size.
+    pub(crate) fn new(options: &LzmaOptions, history_size: usize) -> Self {
+        let (lazy_level, max_chain, nice_length) = options.search();
+        let mut lzss = LzssEncoder::new(
+            lzss_comparison as fn(LzssCode, LzssCode) -> Ordering,
+            options.dict_size as usize,
+            MAX_MATCH,
+            MIN_SEARCH,
+            lazy_level,
+        );
+        lzss.set_search_limit(max_chain, nice_length);
+        Self {
+            lzss,
+            model: Model::new(options.props()),
+            rc: RangeEncoder::new(),
+            history: Vec::new(),
+            history_size,
+            total: 0,
+        }
+    }
+
+    /// Returns the next code of the input.
+    pub(crate) fn next_code<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<LzssCode, CompressionError>> {
+        self.lzss.next(iter, action)
+    }
+
+    /// Returns the last `len` bytes coded.
+    pub(crate) fn tail(&self, len: usize) -> &[u8] {
+        &self.history[self.history.len() - len..]
+    }
+
+    fn byte_at(&self, dist: usize) -> Option<u8> {
+        let len = self.history.len();
+        if dist <= len {
+            Some(self.history[len - dist])
+        } else {
+            None
+        }
+    }
+
+    fn encode_literal(&mut self, b: u8) {
+        let after_literal = self.model.after_literal();
+        let match_byte = self.byte_at(self.model.reps[0] as usize + 1);
+        let prev = self.byte_at(1).unwrap_or(0);
+        let total = self.total;
+        let pos_state = self.model.pos_state(total);
+        let Self { model, rc, .. } = self;
+        rc.bit(&mut model.is_match[model.state][pos_state], 0);
+        let probs = model.literal_probs(total, prev);
+        let mut symbol = 1;
+        let mut match_byte = if after_literal { None } else { match_byte };
+        for i in (0..8).rev() {
+            let bit = u32::from(b >> i) & 1;
+            match match_byte {
+                Some(m) => {
+                    let match_bit = u32::from(m >> i) & 1;
+                    rc.bit(
+                        &mut probs
+                            [(0x100 + (match_bit << 8) + symbol) as usize],
+                        bit,
+                    );
+                    if match_bit != bit {
+                        match_byte = None;
+                    }
+                }
+                None => rc.bit(&mut probs[symbol as usize], bit),
+            }
+            symbol = (symbol << 1) | bit;
+        }
+        model.update_literal();
+    }
+
+    /// Encodes the distance minus one `dist` of the match of `len` bytes.
+    fn encode_distance(&mut self, dist: u32, len: usize) {
+        let Self { model, rc, .. } = self;
+        let slot = dist_slot(dist);
+        rc.tree(&mut model.dist_slot[dist_state(len)], DIST_SLOT_BITS, slot);
+        if slot < DIST_MODEL_START {
+            return;
+        }
+        let bits = ((slot >> 1) - 1) as usize;
+        let base = (2 | (slot & 1)) << bits;
+        let rest = dist - base;
+        if slot < DIST_MODEL_END {
+            let probs = &mut model.dist_special[(base - slot) as usize..];
+            rc.reverse_tree(probs, bits, rest);
+        } else {
+            rc.direct(rest >> ALIGN_BITS, bits - ALIGN_BITS);
+            rc.reverse_tree(
+                &mut model.dist_align,
+                ALIGN_BITS,
+                rest & ((1 << ALIGN_BITS) - 1),
+            );
+        }
+    }
+
+    fn encode_match(&mut self, dist: u32, len: usize) {
+        let pos_state = self.model.pos_state(self.total);
+        let state = self.model.state;
+        let Self { model, rc, .. } = self;
+        rc.bit(&mut model.is_match[state][pos_state], 1);
+        match model.reps.iter().position(|&r| r == dist) {
+            None => {
+                rc.bit(&mut model.is_rep[state], 0);
+                model.match_len.encode(rc, len, pos_state);
+                model.update_match();
+                self.encode_distance(dist, len);
+                let reps = &mut self.model.reps;
+                *reps = [dist, reps[0], reps[1], reps[2]];
+            }
+            Some(i) => {
+                rc.bit(&mut model.is_rep[state], 1);
+                if i == 0 {
+                    rc.bit(&mut model.is_rep0[state], 0);
+                    rc.bit(&mut model.is_rep0_long[state][pos_state], 1);
+                } else {
+                    rc.bit(&mut model.is_rep0[state], 1);
+                    if i == 1 {
+                        rc.bit(&mut model.is_rep1[state], 0);
+                    } else {
+                        rc.bit(&mut model.is_rep1[state], 1);
+                        rc.bit(&mut model.is_rep2[state], (i - 2) as u32);
+                    }
+                    let reps = &mut model.reps;
+                    reps[..=i].rotate_right(1);
+                }
+                model.rep_len.encode(rc, len, pos_state);
+                model.update_rep();
+            }
+        }
+    }
+
+    fn encode_short_rep(&mut self) {
+        let pos_state = self.model.pos_state(self.total);
+        let state = self.model.state;
+        let Self { model, rc, .. } = self;
+        rc.bit(&mut model.is_match[state][pos_state], 1);
+        rc.bit(&mut model.is_rep[state], 1);
+        rc.bit(&mut model.is_rep0[state], 0);
+        rc.bit(&mut model.is_rep0_long[state][pos_state], 0);
+        model.update_short_rep();
+    }
+
+    fn push_history(&mut self, b: u8) {
+        if self.history.len() >= self.history_size << 1 {
+            let _ = self.history.drain(..self.history_size);
+        }
+        self.history.push(b);
+        self.total += 1;
+    }
+
+    /// Encodes `code`, and returns the number of the bytes coded.
+    pub(crate) fn encode(&mut self, code: LzssCode) -> usize {
+        match code {
+            LzssCode::Symbol(b) => {
+                // 直前の距離の1バイトは短い繰り返しにする
+                if self.byte_at(self.model.reps[0] as usize + 1) == Some(b) {
+                    self.encode_short_rep();
+                } else {
+                    self.encode_literal(b);
+                }
+                self.push_history(b);
+                1
+            }
+            LzssCode::Reference { len, pos } => {
+                self.encode_match(pos as u32, len);
+                for _ in 0..len {
+                    let b = self.byte_at(pos + 1).unwrap();
+                    self.push_history(b);
+                }
+                len
+            }
+        }
+    }
+
+    pub(crate) fn encode_end_marker(&mut self) {
+        self.encode_match(END_MARKER, MIN_MATCH);
+    }
+}
+
+/// The encoder of the `.lzma` format of LZMA Utils. The uncompressed size
+/// is written as unknown, and the end marker ends the data.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "lzma")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut LzmaEncoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LzmaEncoder {
+    core: LzmaEncoderCore,
+    options: LzmaOptions,
+    out: Vec<u8>,
+    out_pos: usize,
+    header_written: bool,
+    finished: bool,
+}
+
+impl Default for LzmaEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LzmaEncoder {
+    pub fn new() -> Self {
+        Self::with_options(&LzmaOptions::default())
+    }
+
+    pub fn with_options(options: &LzmaOptions) -> Self {
+        options.validate();
+        Self {
+            core: LzmaEncoderCore::new(options, options.dict_size as usize),
+            options: *options,
+            out: Vec::new(),
+            out_pos: 0,
+            header_written: false,
+            finished: false,
+        }
+    }
+
+    fn write_header(&mut self) {
+        self.out.push(self.options.props().to_byte());
+        self.out
+            .extend_from_slice(&self.options.dict_size.to_le_bytes());
+        self.out.extend_from_slice(&UNKNOWN_SIZE.to_le_bytes());
+    }
+}
+
+impl Encoder for LzmaEncoder {
+    type Error = CompressionError;
+    type In = u8;
+    type Out = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<u8, CompressionError>> {
+        loop {
+            if self.out_pos < self.out.len() {
+                self.out_pos += 1;
+                return Some(Ok(self.out[self.out_pos - 1]));
+            }
+            self.out.clear();
+            self.out_pos = 0;
+            if self.finished {
+                return None;
+            }
+            if !self.header_written {
+                self.write_header();
+                self.header_written = true;
+                continue;
+            }
+            match self.core.next_code(iter, action) {
+                Some(Ok(code)) => {
+                    let _ = self.core.encode(code);
+                    self.out = self.core.rc.take_output();
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None if action == Action::Finish => {
+                    self.core.encode_end_marker();
+                    self.out = self.core.rc.finish();
+                    self.finished = true;
+                }
+                // 範囲符号は途中で区切れないので、出力は最後まで溜まりうる
+                None => return None,
+            }
+        }
+    }
+}
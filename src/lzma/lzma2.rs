@@ -0,0 +1,340 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::action::Action;
+use crate::bitio::direction::right::Right;
+use crate::bitio::reader::{BitRead, BitReader};
+use crate::core::cmp;
+use crate::error::CompressionError;
+use crate::lzma::decoder::{read_bytes, read_u8, LzmaDecoderCore};
+use crate::lzma::encoder::LzmaEncoderCore;
+use crate::lzma::model::Properties;
+use crate::lzma::options::LzmaOptions;
+use crate::lzma::MAX_MATCH;
+use crate::lzss::LzssCode;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The maximum length of the compressed data of a chunk.
+const MAX_PACKED: usize = 0x1_0000;
+/// The maximum length of the uncompressed data of a chunk.
+const MAX_UNPACKED: usize = 0x20_0000;
+/// The compressed data of a chunk is ended before it may be longer than
+/// `MAX_PACKED` with the next packet.
+const PACKED_MARGIN: usize = 0x100;
+/// The length of the data of an uncompressed chunk moved to the window at
+/// once.
+const COPY_LEN: usize = 0x1000;
+
+const CONTROL_END: u8 = 0x00;
+const CONTROL_UNCOMPRESSED_RESET: u8 = 0x01;
+const CONTROL_UNCOMPRESSED: u8 = 0x02;
+const CONTROL_LZMA: u8 = 0x80;
+
+// LZMAチャンクの制御バイトのリセットの種類
+const RESET_STATE: u8 = 1;
+const RESET_PROPS: u8 = 2;
+const RESET_DICT: u8 = 3;
+
+/// Returns the dictionary size of the property byte of the LZMA2 filter.
+pub(crate) fn dict_size_from_byte(b: u8) -> Option<u32> {
+    match b {
+        0..=39 => Some((2 | u32::from(b & 1)) << (b / 2 + 11)),
+        40 => Some(0xFFFF_FFFF),
+        _ => None,
+    }
+}
+
+/// Returns the property byte of the smallest dictionary size of LZMA2 not
+/// less than `dict_size`.
+pub(crate) fn dict_size_to_byte(dict_size: u32) -> u8 {
+    (0..40)
+        .find(|&b| dict_size_from_byte(b).unwrap() >= dict_size)
+        .unwrap_or(40)
+}
+
+fn props_from_byte(b: u8) -> Option<Properties> {
+    Properties::from_byte(b).filter(|p| p.lc + p.lp <= 4)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Control,
+    /// The length of the uncompressed data of the LZMA chunk left.
+    Lzma(u64),
+    /// The data of the uncompressed chunk moved to the window.
+    Uncompressed(usize),
+    Finished,
+}
+
+/// The decoder of the LZMA2 data in a block of xz.
+#[derive(Debug)]
+pub(crate) struct Lzma2Decoder {
+    state: State,
+    core: LzmaDecoderCore,
+    stored: Vec<u8>,
+    need_dict_reset: bool,
+    need_props: bool,
+    need_state_reset: bool,
+}
+
+impl Lzma2Decoder {
+    pub(crate) fn new(dict_size: u32) -> Self {
+        let props = Properties {
+            lc: 0,
+            lp: 0,
+            pb: 0,
+        };
+        Self {
+            state: State::Control,
+            core: LzmaDecoderCore::new(props, dict_size),
+            stored: Vec::new(),
+            need_dict_reset: true,
+            need_props: true,
+            need_state_reset: true,
+        }
+    }
+
+    fn read_be16<I: Iterator<Item = u8>>(
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<usize, CompressionError> {
+        let high = usize::from(read_u8(reader, iter)?);
+        Ok((high << 8) | usize::from(read_u8(reader, iter)?))
+    }
+
+    fn read_chunk<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        let invalid = CompressionError::InvalidBlock { position };
+        let control = read_u8(reader, iter)?;
+        match control {
+            CONTROL_END => {
+                self.state = State::Finished;
+                return Ok(());
+            }
+            CONTROL_UNCOMPRESSED_RESET | CONTROL_UNCOMPRESSED => {
+                let len = Self::read_be16(reader, iter)? + 1;
+                if control == CONTROL_UNCOMPRESSED_RESET {
+                    self.core.window.reset();
+                } else if self.need_dict_reset {
+                    return Err(invalid);
+                }
+                self.stored = read_bytes(reader, iter, len)?;
+                self.need_dict_reset = false;
+                // 続くLZMAチャンクは状態をリセットする
+                self.need_state_reset = true;
+                self.state = State::Uncompressed(0);
+                return Ok(());
+            }
+            _ if control < CONTROL_LZMA => return Err(invalid),
+            _ => {}
+        }
+
+        let reset = (control >> 5) & 3;
+        let unpacked = ((usize::from(control & 0x1F) << 16)
+            | Self::read_be16(reader, iter)?)
+            + 1;
+        let packed = Self::read_be16(reader, iter)? + 1;
+        if reset >= RESET_DICT {
+            self.core.window.reset();
+        } else if self.need_dict_reset {
+            return Err(invalid);
+        }
+        if reset >= RESET_PROPS {
+            let props =
+                props_from_byte(read_u8(reader, iter)?).ok_or(invalid)?;
+            self.core.model.props = props;
+        } else if self.need_props {
+            return Err(invalid);
+        }
+        if reset >= RESET_STATE {
+            self.core.model.reset();
+        } else if self.need_state_reset {
+            return Err(invalid);
+        }
+        let data_position = reader.position();
+        let data = read_bytes(reader, iter, packed)?;
+        self.core.rc.set_input(data, data_position);
+        if !self.core.rc.init() {
+            return Err(CompressionError::InvalidBlock {
+                position: data_position,
+            });
+        }
+        self.need_dict_reset = false;
+        self.need_props = false;
+        self.need_state_reset = false;
+        self.state = State::Lzma(unpacked as u64);
+        Ok(())
+    }
+
+    fn decode_lzma(&mut self, rest: u64) -> Result<(), CompressionError> {
+        let rc = &self.core.rc;
+        let position = rc.position();
+        if rest == 0 {
+            if rc.available() > 0 || !rc.is_finished() {
+                return Err(CompressionError::InvalidBlock { position });
+            }
+            self.state = State::Control;
+            return Ok(());
+        }
+        let total = self.core.window.total;
+        match self.core.decode_packet(rest) {
+            Ok(true) => {}
+            // LZMA2では終端マーカーは使えない
+            Ok(false) | Err(CompressionError::UnexpectedEof) => {
+                return Err(CompressionError::InvalidBlock { position })
+            }
+            Err(e) => return Err(e),
+        }
+        self.state = State::Lzma(rest - (self.core.window.total - total));
+        Ok(())
+    }
+
+    /// Returns the next byte of the data, or `None` at the end of the
+    /// data.
+    pub(crate) fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<Option<u8>, CompressionError> {
+        loop {
+            if let Some(b) = self.core.window.pop() {
+                return Ok(Some(b));
+            }
+            reader.commit();
+            match self.state {
+                State::Control => self.read_chunk(reader, iter)?,
+                State::Lzma(rest) => self.decode_lzma(rest)?,
+                State::Uncompressed(pos) => {
+                    let end = cmp::min(pos + COPY_LEN, self.stored.len());
+                    for &b in &self.stored[pos..end] {
+                        self.core.window.put(b);
+                    }
+                    self.state = if end == self.stored.len() {
+                        State::Control
+                    } else {
+                        State::Uncompressed(end)
+                    };
+                }
+                State::Finished => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The encoder of the LZMA2 data in a block of xz.
+#[derive(Debug)]
+pub(crate) struct Lzma2Encoder {
+    core: LzmaEncoderCore,
+    props: Properties,
+    // 現在のチャンクの非圧縮の長さ
+    unpacked: usize,
+    need_dict_reset: bool,
+    need_props: bool,
+    need_state_reset: bool,
+}
+
+impl Lzma2Encoder {
+    pub(crate) fn new(options: &LzmaOptions) -> Self {
+        let history_size = options.dict_size as usize + MAX_UNPACKED;
+        Self {
+            core: LzmaEncoderCore::new(options, history_size),
+            props: options.props(),
+            unpacked: 0,
+            need_dict_reset: true,
+            need_props: true,
+            need_state_reset: true,
+        }
+    }
+
+    pub(crate) fn next_code<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<LzssCode, CompressionError>> {
+        self.core.next_code(iter, action)
+    }
+
+    /// Returns the last `len` bytes coded.
+    pub(crate) fn tail(&self, len: usize) -> &[u8] {
+        self.core.tail(len)
+    }
+
+    /// Encodes `code`, writes the chunk to `out` if it is full, and
+    /// returns the number of the bytes coded.
+    pub(crate) fn push_code(
+        &mut self,
+        code: LzssCode,
+        out: &mut Vec<u8>,
+    ) -> usize {
+        let len = self.core.encode(code);
+        self.unpacked += len;
+        if self.unpacked + MAX_MATCH > MAX_UNPACKED
+            || self.core.rc.pending_len() + PACKED_MARGIN > MAX_PACKED
+        {
+            self.write_chunk(out);
+        }
+        len
+    }
+
+    /// Writes the data encoded so far as a chunk to `out`.
+    pub(crate) fn write_chunk(&mut self, out: &mut Vec<u8>) {
+        if self.unpacked == 0 {
+            return;
+        }
+        let data = self.core.rc.finish();
+        let unpacked = self.unpacked;
+        self.unpacked = 0;
+        if data.len() >= unpacked {
+            // 圧縮できないデータは非圧縮のチャンクにする
+            for piece in self.core.tail(unpacked).chunks(MAX_PACKED) {
+                out.push(if self.need_dict_reset {
+                    CONTROL_UNCOMPRESSED_RESET
+                } else {
+                    CONTROL_UNCOMPRESSED
+                });
+                out.extend_from_slice(&(piece.len() as u16 - 1).to_be_bytes());
+                out.extend_from_slice(piece);
+                self.need_dict_reset = false;
+            }
+            self.core.model.reset();
+            self.need_state_reset = true;
+            return;
+        }
+        let reset = if self.need_dict_reset {
+            RESET_DICT
+        } else if self.need_props {
+            RESET_PROPS
+        } else if self.need_state_reset {
+            RESET_STATE
+        } else {
+            0
+        };
+        let size = unpacked - 1;
+        out.push(CONTROL_LZMA | (reset << 5) | (size >> 16) as u8);
+        out.extend_from_slice(&(size as u16).to_be_bytes());
+        out.extend_from_slice(&(data.len() as u16 - 1).to_be_bytes());
+        if reset >= RESET_PROPS {
+            out.push(self.props.to_byte());
+        }
+        out.extend(data);
+        self.need_dict_reset = false;
+        self.need_props = false;
+        self.need_state_reset = false;
+    }
+
+    /// Writes the rest of the data and the end of the LZMA2 data to
+    /// `out`.
+    pub(crate) fn finish(&mut self, out: &mut Vec<u8>) {
+        self.write_chunk(out);
+        out.push(CONTROL_END);
+    }
+}
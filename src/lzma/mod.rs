@@ -0,0 +1,363 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "lzma")]
+
+pub(crate) mod decoder;
+pub(crate) mod encoder;
+pub(crate) mod lzma2;
+pub(crate) mod model;
+pub(crate) mod options;
+pub(crate) mod range;
+pub(crate) mod xz;
+pub(crate) mod xz_decoder;
+pub(crate) mod xz_encoder;
+
+const MIN_MATCH: usize = 2;
+const MAX_MATCH: usize = 273;
+/// The dictionary is at least this size whatever the header says.
+const MIN_DICT_SIZE: u32 = 0x1000;
+/// The distance minus one of the match which ends the LZMA data.
+const END_MARKER: u32 = 0xFFFF_FFFF;
+/// The uncompressed size of `.lzma` which means the end marker.
+const UNKNOWN_SIZE: u64 = u64::MAX;
+
+#[cfg(test)]
+mod tests {
+    use crate::action::Action;
+    use crate::error::CompressionError;
+    use crate::lzma::decoder::LzmaDecoder;
+    use crate::lzma::encoder::LzmaEncoder;
+    use crate::lzma::options::LzmaOptions;
+    use crate::lzma::xz::XzCheck;
+    use crate::lzma::xz_decoder::XzDecoder;
+    use crate::lzma::xz_encoder::XzEncoder;
+    use crate::traits::decoder::{DecodeExt, Decoder};
+    use crate::traits::encoder::{EncodeExt, Encoder};
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    fn decode<D: Decoder<Input = u8, Output = u8, Error = CompressionError>>(
+        encoded: &[u8],
+        decoder: &mut D,
+    ) -> Result<Vec<u8>, CompressionError> {
+        encoded.iter().cloned().decode(decoder).collect()
+    }
+
+    fn encode<E: Encoder<In = u8, Out = u8, Error = CompressionError>>(
+        data: &[u8],
+        encoder: &mut E,
+    ) -> Vec<u8> {
+        data.iter()
+            .cloned()
+            .encode(encoder, Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    fn check_xz(encoded: &[u8], expected: &[u8]) {
+        let decoded = decode(encoded, &mut XzDecoder::new()).unwrap();
+        assert_eq!(decoded.len(), expected.len());
+        assert!(decoded == expected);
+    }
+
+    fn check_encode(data: &[u8], options: &LzmaOptions, check: XzCheck) {
+        let encoded =
+            encode(data, &mut XzEncoder::with_options(options, check));
+        let mut decoder = XzDecoder::new();
+        assert!(decode(&encoded, &mut decoder).unwrap()[..] == data[..]);
+        assert_eq!(decoder.check(), Some(check));
+    }
+
+    #[test]
+    fn test_samples() {
+        let mut decoder = XzDecoder::new();
+        let decoded =
+            decode(include_bytes!("../../data/sample2.xz"), &mut decoder);
+        assert!(
+            decoded.unwrap()[..]
+                == include_bytes!("../../data/sample2.ref")[..]
+        );
+        assert_eq!(decoder.check(), Some(XzCheck::Sha256));
+        check_xz(
+            include_bytes!("../../data/sample1.xz"),
+            include_bytes!("../../data/sample1.ref"),
+        );
+        check_xz(
+            include_bytes!("../../data/sample3.xz"),
+            include_bytes!("../../data/sample3.ref"),
+        );
+
+        for &(encoded, expected) in &[
+            (
+                &include_bytes!("../../data/sample1.lzma")[..],
+                &include_bytes!("../../data/sample1.ref")[..],
+            ),
+            (
+                &include_bytes!("../../data/sample3.lzma")[..],
+                &include_bytes!("../../data/sample3.ref")[..],
+            ),
+        ] {
+            let decoded = decode(encoded, &mut LzmaDecoder::new()).unwrap();
+            assert!(decoded[..] == expected[..]);
+        }
+    }
+
+    #[test]
+    fn test_streams() {
+        // ストリームパディングを挟んだ連結
+        let mut encoded = include_bytes!("../../data/sample3.xz").to_vec();
+        encoded.extend_from_slice(&[0; 8]);
+        encoded.extend_from_slice(include_bytes!("../../data/sample1.xz"));
+        encoded.extend_from_slice(&[0; 4]);
+        let mut expected = include_bytes!("../../data/sample3.ref").to_vec();
+        expected.extend_from_slice(include_bytes!("../../data/sample1.ref"));
+        check_xz(&encoded, &expected);
+        check_xz(&encode(&[], &mut XzEncoder::new()), &[]);
+    }
+
+    #[test]
+    fn test_stream() {
+        use crate::traits::decoder::tests::decompress_slices;
+
+        let mut encoded = include_bytes!("../../data/sample1.xz").to_vec();
+        encoded.extend_from_slice(&[0; 4]);
+        encoded.extend_from_slice(include_bytes!("../../data/sample3.xz"));
+        let mut expected = include_bytes!("../../data/sample1.ref").to_vec();
+        expected.extend_from_slice(include_bytes!("../../data/sample3.ref"));
+        let lzma = include_bytes!("../../data/sample1.lzma");
+        let data = include_bytes!("../../data/sample1.ref");
+        for &(chunk, out_len) in &[(1, 0x1000), (7, 1), (0x1000, 0x8000)] {
+            let decoded = decompress_slices(
+                &mut XzDecoder::new(),
+                &encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded == expected);
+            let decoded = decompress_slices(
+                &mut LzmaDecoder::new(),
+                lzma,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded[..] == data[..]);
+        }
+
+        // LZMA2のチャンクの間で入力が途切れるもの
+        let mut data = include_bytes!("../../data/sample2.ref").to_vec();
+        let mut x = 1u32;
+        data.extend((0..0x2_0000).map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            x as u8
+        }));
+        let encoded = encode(&data, &mut XzEncoder::new());
+        for &(chunk, out_len) in &[(1, 0x1000), (7, 0x8000), (7, 1)] {
+            let decoded = decompress_slices(
+                &mut XzDecoder::new(),
+                &encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert_eq!(decoded.len(), data.len());
+            assert!(decoded == data);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let encoded = include_bytes!("../../data/sample3.xz");
+        let len = encoded.len() as u64;
+        assert_eq!(
+            decode(b"not xz", &mut XzDecoder::new()),
+            Err(CompressionError::BadMagic { position: 0 })
+        );
+        assert_eq!(
+            decode(&[], &mut XzDecoder::new()),
+            Err(CompressionError::UnexpectedEof)
+        );
+        assert_eq!(
+            decode(&encoded[..encoded.len() - 1], &mut XzDecoder::new()),
+            Err(CompressionError::UnexpectedEof)
+        );
+
+        let mut garbage = encoded.to_vec();
+        garbage.extend_from_slice(b"garbage");
+        assert_eq!(
+            decode(&garbage, &mut XzDecoder::new()),
+            Err(CompressionError::TrailingGarbage { position: len << 3 })
+        );
+        let mut padding = encoded.to_vec();
+        padding.extend_from_slice(&[0; 3]);
+        assert_eq!(
+            decode(&padding, &mut XzDecoder::new()),
+            Err(CompressionError::UnexpectedEof)
+        );
+
+        // ストリームフラグのCRC32
+        let mut flags = encoded.to_vec();
+        flags[7] = 0x0A;
+        assert!(matches!(
+            decode(&flags, &mut XzDecoder::new()),
+            Err(CompressionError::ChecksumMismatch { position, .. })
+                if position == 64
+        ));
+
+        // ブロックのCRC32
+        let mut check = encoded.to_vec();
+        let index = encoded.len() - 12 - 12;
+        check[index - 1] ^= 1;
+        assert!(matches!(
+            decode(&check, &mut XzDecoder::new()),
+            Err(CompressionError::ChecksumMismatch { position, .. })
+                if position == (index as u64 - 4) << 3
+        ));
+
+        let mut footer = encoded.to_vec();
+        footer[encoded.len() - 1] = b'X';
+        assert_eq!(
+            decode(&footer, &mut XzDecoder::new()),
+            Err(CompressionError::InvalidHeader {
+                position: (len - 8) << 3
+            })
+        );
+
+        let mut corrupted = include_bytes!("../../data/sample1.xz").to_vec();
+        let mid = corrupted.len() / 2;
+        corrupted[mid] ^= 0x55;
+        assert!(decode(&corrupted, &mut XzDecoder::new()).is_err());
+
+        let lzma = include_bytes!("../../data/sample3.lzma");
+        assert_eq!(
+            decode(&lzma[..lzma.len() - 1], &mut LzmaDecoder::new()),
+            Err(CompressionError::UnexpectedEof)
+        );
+        let mut garbage = lzma.to_vec();
+        garbage.push(0);
+        assert!(matches!(
+            decode(&garbage, &mut LzmaDecoder::new()),
+            Err(CompressionError::TrailingGarbage { .. })
+        ));
+    }
+
+    #[test]
+    fn test_encode() {
+        let options = LzmaOptions::default();
+        for &check in &[
+            XzCheck::None,
+            XzCheck::Crc32,
+            XzCheck::Crc64,
+            XzCheck::Sha256,
+        ] {
+            check_encode(b"aabbaabbaabbaabb\n", &options, check);
+        }
+        check_encode(&[], &options, XzCheck::Crc64);
+        check_encode(b"a", &options, XzCheck::Crc64);
+        check_encode(&[0x55; 0x30_0000], &options, XzCheck::Crc64);
+        let data = include_bytes!("../../data/sample2.ref");
+        let mut lens = Vec::new();
+        for &level in &[0, 3, 9] {
+            let options = LzmaOptions::new(level);
+            check_encode(
+                include_bytes!("../../data/sample4.ref"),
+                &options,
+                XzCheck::Crc32,
+            );
+            let encoded = encode(
+                data,
+                &mut XzEncoder::with_options(&options, XzCheck::Crc32),
+            );
+            check_xz(&encoded, data);
+            lens.push(encoded.len());
+        }
+        assert!(lens[2] < lens[0]);
+
+        let options = LzmaOptions {
+            dict_size: 0x1000,
+            lc: 0,
+            lp: 4,
+            pb: 4,
+            ..LzmaOptions::new(1)
+        };
+        check_encode(data, &options, XzCheck::Crc64);
+
+        // 圧縮できないデータは非圧縮のチャンクになる
+        let mut x = 1u32;
+        let random = (0..0x2_0000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect::<Vec<_>>();
+        check_encode(&random, &LzmaOptions::default(), XzCheck::Crc64);
+    }
+
+    #[test]
+    fn test_encode_flush() {
+        let data = include_bytes!("../../data/sample1.ref");
+        let mut encoder = XzEncoder::new();
+        let mut encoded = Vec::new();
+        for (i, chunk) in data.chunks(0x1000).enumerate() {
+            let action = if i % 3 == 0 {
+                Action::Flush
+            } else {
+                Action::Run
+            };
+            let mut iter = chunk.iter().cloned();
+            while let Some(b) = encoder.next(&mut iter, action) {
+                encoded.push(b.unwrap());
+            }
+            if action == Action::Flush {
+                // 書き出したデータだけで、ここまでを復号できる
+                let end = i * 0x1000 + chunk.len();
+                let mut decoder = XzDecoder::new();
+                let decoded = encoded
+                    .iter()
+                    .cloned()
+                    .decode(&mut decoder)
+                    .take(end)
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                assert!(decoded[..] == data[..end]);
+            }
+        }
+        encoded.extend(
+            Vec::new()
+                .into_iter()
+                .encode(&mut encoder, Action::Finish)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+        );
+        check_xz(&encoded, data);
+    }
+
+    #[test]
+    fn test_lzma() {
+        for &level in &[0, 6] {
+            for data in &[
+                &b""[..],
+                &b"aabbaabbaabbaabb\n"[..],
+                &include_bytes!("../../data/sample2.ref")[..],
+            ] {
+                let options = LzmaOptions::new(level);
+                let encoded =
+                    encode(data, &mut LzmaEncoder::with_options(&options));
+                let mut decoder = LzmaDecoder::new();
+                assert!(
+                    decode(&encoded, &mut decoder).unwrap()[..] == data[..]
+                );
+                assert_eq!(decoder.content_size(), None);
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::lzma::range::{RangeDecoder, RangeEncoder, PROB_INIT};
+use crate::lzma::MIN_MATCH;
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+const STATES: usize = 12;
+/// The states after a literal are less than this.
+const LITERAL_STATES: usize = 7;
+const MAX_POS_STATES: usize = 16;
+const LEN_LOW_BITS: usize = 3;
+const LEN_MID_BITS: usize = 3;
+const LEN_HIGH_BITS: usize = 8;
+const LEN_LOW: usize = 1 << LEN_LOW_BITS;
+const LEN_MID: usize = 1 << LEN_MID_BITS;
+/// The lengths of the matches get the distance slots of their own up to
+/// this number.
+pub(crate) const DIST_STATES: usize = 4;
+pub(crate) const DIST_SLOT_BITS: usize = 6;
+/// The distance slots from this use the probabilities for their low bits.
+pub(crate) const DIST_MODEL_START: u32 = 4;
+/// The distance slots from this use the align bits for their low bits.
+pub(crate) const DIST_MODEL_END: u32 = 14;
+const FULL_DISTANCES: usize = 1 << (DIST_MODEL_END >> 1);
+pub(crate) const ALIGN_BITS: usize = 4;
+/// The length of the probabilities of a literal coder.
+const LITERAL_CODER_SIZE: usize = 0x300;
+
+/// The literal context bits `lc`, the literal position bits `lp` and the
+/// position bits `pb` of the LZMA data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Properties {
+    pub(crate) lc: u32,
+    pub(crate) lp: u32,
+    pub(crate) pb: u32,
+}
+
+impl Properties {
+    pub(crate) fn from_byte(b: u8) -> Option<Self> {
+        if b >= 9 * 5 * 5 {
+            return None;
+        }
+        let b = u32::from(b);
+        Some(Self {
+            lc: b % 9,
+            lp: (b / 9) % 5,
+            pb: b / 45,
+        })
+    }
+
+    pub(crate) fn to_byte(self) -> u8 {
+        ((self.pb * 5 + self.lp) * 9 + self.lc) as u8
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct LengthModel {
+    choice: u16,
+    choice2: u16,
+    low: [[u16; LEN_LOW]; MAX_POS_STATES],
+    mid: [[u16; LEN_MID]; MAX_POS_STATES],
+    high: [u16; 1 << LEN_HIGH_BITS],
+}
+
+impl LengthModel {
+    fn new() -> Self {
+        Self {
+            choice: PROB_INIT,
+            choice2: PROB_INIT,
+            low: [[PROB_INIT; LEN_LOW]; MAX_POS_STATES],
+            mid: [[PROB_INIT; LEN_MID]; MAX_POS_STATES],
+            high: [PROB_INIT; 1 << LEN_HIGH_BITS],
+        }
+    }
+
+    pub(crate) fn decode(
+        &mut self,
+        rc: &mut RangeDecoder,
+        pos_state: usize,
+    ) -> usize {
+        let len = if rc.bit(&mut self.choice) == 0 {
+            rc.tree(&mut self.low[pos_state], LEN_LOW_BITS)
+        } else if rc.bit(&mut self.choice2) == 0 {
+            LEN_LOW as u32 + rc.tree(&mut self.mid[pos_state], LEN_MID_BITS)
+        } else {
+            (LEN_LOW + LEN_MID) as u32 + rc.tree(&mut self.high, LEN_HIGH_BITS)
+        };
+        len as usize + MIN_MATCH
+    }
+
+    pub(crate) fn encode(
+        &mut self,
+        rc: &mut RangeEncoder,
+        len: usize,
+        pos_state: usize,
+    ) {
+        let len = (len - MIN_MATCH) as u32;
+        if len < LEN_LOW as u32 {
+            rc.bit(&mut self.choice, 0);
+            rc.tree(&mut self.low[pos_state], LEN_LOW_BITS, len);
+        } else if len < (LEN_LOW + LEN_MID) as u32 {
+            rc.bit(&mut self.choice, 1);
+            rc.bit(&mut self.choice2, 0);
+            rc.tree(
+                &mut self.mid[pos_state],
+                LEN_MID_BITS,
+                len - LEN_LOW as u32,
+            );
+        } else {
+            rc.bit(&mut self.choice, 1);
+            rc.bit(&mut self.choice2, 1);
+            rc.tree(
+                &mut self.high,
+                LEN_HIGH_BITS,
+                len - (LEN_LOW + LEN_MID) as u32,
+            );
+        }
+    }
+}
+
+/// The probabilities, the state and the repeated distances shared by the
+/// encoder and the decoder.
+#[derive(Debug, Clone)]
+pub(crate) struct Model {
+    pub(crate) props: Properties,
+    pub(crate) literal: Vec<u16>,
+    pub(crate) is_match: [[u16; MAX_POS_STATES]; STATES],
+    pub(crate) is_rep: [u16; STATES],
+    pub(crate) is_rep0: [u16; STATES],
+    pub(crate) is_rep1: [u16; STATES],
+    pub(crate) is_rep2: [u16; STATES],
+    pub(crate) is_rep0_long: [[u16; MAX_POS_STATES]; STATES],
+    pub(crate) dist_slot: [[u16; 1 << DIST_SLOT_BITS]; DIST_STATES],
+    pub(crate) dist_special: [u16; FULL_DISTANCES - DIST_MODEL_END as usize],
+    pub(crate) dist_align: [u16; 1 << ALIGN_BITS],
+    pub(crate) match_len: LengthModel,
+    pub(crate) rep_len: LengthModel,
+    pub(crate) state: usize,
+    /// The last four distances minus one, the latest first.
+    pub(crate) reps: [u32; 4],
+}
+
+impl Model {
+    pub(crate) fn new(props: Properties) -> Self {
+        Self {
+            props,
+            literal: vec![
+                PROB_INIT;
+                LITERAL_CODER_SIZE << (props.lc + props.lp)
+            ],
+            is_match: [[PROB_INIT; MAX_POS_STATES]; STATES],
+            is_rep: [PROB_INIT; STATES],
+            is_rep0: [PROB_INIT; STATES],
+            is_rep1: [PROB_INIT; STATES],
+            is_rep2: [PROB_INIT; STATES],
+            is_rep0_long: [[PROB_INIT; MAX_POS_STATES]; STATES],
+            dist_slot: [[PROB_INIT; 1 << DIST_SLOT_BITS]; DIST_STATES],
+            dist_special: [PROB_INIT; FULL_DISTANCES - DIST_MODEL_END as usize],
+            dist_align: [PROB_INIT; 1 << ALIGN_BITS],
+            match_len: LengthModel::new(),
+            rep_len: LengthModel::new(),
+            state: 0,
+            reps: [0; 4],
+        }
+    }
+
+    /// Resets the probabilities and the state.
+    pub(crate) fn reset(&mut self) {
+        *self = Self::new(self.props);
+    }
+
+    pub(crate) fn pos_state(&self, pos: u64) -> usize {
+        (pos as usize) & ((1 << self.props.pb) - 1)
+    }
+
+    /// Returns the probabilities of the literal at `pos` following `prev`.
+    pub(crate) fn literal_probs(&mut self, pos: u64, prev: u8) -> &mut [u16] {
+        let Properties { lc, lp, .. } = self.props;
+        let index = (((pos as usize) & ((1 << lp) - 1)) << lc)
+            + (usize::from(prev) >> (8 - lc));
+        let start = index * LITERAL_CODER_SIZE;
+        &mut self.literal[start..start + LITERAL_CODER_SIZE]
+    }
+
+    /// Whether the last packet is a literal, after which the literal is
+    /// coded without the byte at the distance.
+    pub(crate) fn after_literal(&self) -> bool {
+        self.state < LITERAL_STATES
+    }
+
+    pub(crate) fn update_literal(&mut self) {
+        self.state = match self.state {
+            0..=3 => 0,
+            4..=9 => self.state - 3,
+            _ => self.state - 6,
+        };
+    }
+
+    pub(crate) fn update_match(&mut self) {
+        self.state = if self.after_literal() { 7 } else { 10 };
+    }
+
+    pub(crate) fn update_rep(&mut self) {
+        self.state = if self.after_literal() { 8 } else { 11 };
+    }
+
+    pub(crate) fn update_short_rep(&mut self) {
+        self.state = if self.after_literal() { 9 } else { 11 };
+    }
+}
+
+/// Returns the distance slot of the distance minus one `dist`.
+pub(crate) fn dist_slot(dist: u32) -> u32 {
+    if dist < DIST_MODEL_START {
+        return dist;
+    }
+    let n = 31 - dist.leading_zeros();
+    (n << 1) | ((dist >> (n - 1)) & 1)
+}
+
+/// Returns the distance state of the match of `len` bytes.
+pub(crate) fn dist_state(len: usize) -> usize {
+    crate::core::cmp::min(len - MIN_MATCH, DIST_STATES - 1)
+}
@@ -0,0 +1,125 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::lzma::model::Properties;
+use crate::lzma::MIN_DICT_SIZE;
+
+/// The parameters of the LZMA encoder.
+///
+/// `LzmaOptions::new(level)` fills in the dictionary size and the search
+/// parameters for the compression level `0..=9`; the fields may be tuned
+/// afterwards.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "lzma")]
+///     let options = LzmaOptions {
+///         dict_size: 0x1_0000,
+///         lc: 0,
+///         lp: 2,
+///         ..LzmaOptions::new(9)
+///     };
+///     # #[cfg(feature = "lzma")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(
+///             &mut XzEncoder::with_options(&options, XzCheck::Crc32),
+///             Action::Finish,
+///         )
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LzmaOptions {
+    /// `0` is the fastest and `9` is the best compression.
+    pub level: usize,
+    /// The size of the dictionary, which is also the window of the match
+    /// search of the encoder.
+    pub dict_size: u32,
+    /// The number of the high bits of the previous byte in the context of
+    /// the literals, `0..=8`.
+    pub lc: u32,
+    /// The number of the low bits of the position in the context of the
+    /// literals, `0..=4`. xz requires `lc + lp <= 4`.
+    pub lp: u32,
+    /// The number of the low bits of the position in the context of the
+    /// matches, `0..=4`.
+    pub pb: u32,
+}
+
+impl Default for LzmaOptions {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LEVEL)
+    }
+}
+
+impl LzmaOptions {
+    const DEFAULT_LEVEL: usize = 6;
+    const MAX_LEVEL: usize = 9;
+    const MAX_DICT_SIZE: u32 = 1 << 30;
+
+    // dict_log, lazy_level, max_chain, nice_length
+    const LEVEL_TABLE: [(u32, usize, usize, usize); 10] = [
+        (16, 1, 4, 16),
+        (18, 1, 8, 32),
+        (19, 1, 16, 32),
+        (20, 2, 16, 48),
+        (21, 2, 32, 64),
+        (21, 2, 64, 96),
+        (22, 3, 128, 128),
+        (22, 3, 256, 192),
+        (23, 3, 512, 273),
+        (23, 4, 1024, 273),
+    ];
+
+    pub fn new(level: usize) -> Self {
+        if level > Self::MAX_LEVEL {
+            panic!("invalid level");
+        }
+        Self {
+            level,
+            dict_size: 1 << Self::LEVEL_TABLE[level].0,
+            lc: 3,
+            lp: 0,
+            pb: 2,
+        }
+    }
+
+    pub(crate) fn validate(&self) {
+        if self.level > Self::MAX_LEVEL {
+            panic!("invalid level");
+        }
+        if !(MIN_DICT_SIZE..=Self::MAX_DICT_SIZE).contains(&self.dict_size) {
+            panic!("invalid dictionary size");
+        }
+        if self.lc > 8 || self.lp > 4 || self.pb > 4 {
+            panic!("invalid literal context or position bits");
+        }
+    }
+
+    pub(crate) fn props(&self) -> Properties {
+        Properties {
+            lc: self.lc,
+            lp: self.lp,
+            pb: self.pb,
+        }
+    }
+
+    /// Returns the lazy level, the maximum chain and the nice length of
+    /// the match search.
+    pub(crate) fn search(&self) -> (usize, usize, usize) {
+        let (_, lazy_level, max_chain, nice_length) =
+            Self::LEVEL_TABLE[self.level];
+        (lazy_level, max_chain, nice_length)
+    }
+}
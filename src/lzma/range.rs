@@ -0,0 +1,301 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::core::mem;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+const TOP: u32 = 1 << 24;
+const PROB_BITS: u32 = 11;
+const MOVE_BITS: u32 = 5;
+const PROB_MAX: u16 = 1 << PROB_BITS;
+/// The initial probability of each bit, which is one half.
+pub(crate) const PROB_INIT: u16 = PROB_MAX >> 1;
+
+/// The range decoder over the input buffered in it.
+#[derive(Debug)]
+pub(crate) struct RangeDecoder {
+    range: u32,
+    code: u32,
+    input: Vec<u8>,
+    pos: usize,
+    // input[0] の位置(ビット単位)
+    base: u64,
+    overrun: bool,
+}
+
+impl RangeDecoder {
+    /// The length of the consumed input which is dropped at once.
+    const COMPACT_LEN: usize = 0x1000;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            range: 0xFFFF_FFFF,
+            code: 0,
+            input: Vec::new(),
+            pos: 0,
+            base: 0,
+            overrun: false,
+        }
+    }
+
+    /// Replaces the input with `data` at `position` in bits.
+    pub(crate) fn set_input(&mut self, data: Vec<u8>, position: u64) {
+        self.input = data;
+        self.pos = 0;
+        self.base = position;
+        self.overrun = false;
+    }
+
+    /// Appends the byte at `position` in bits to the input.
+    pub(crate) fn push(&mut self, byte: u8, position: u64) {
+        if self.pos == self.input.len() {
+            self.input.clear();
+            self.pos = 0;
+            self.base = position;
+        } else if self.pos >= Self::COMPACT_LEN {
+            let _ = self.input.drain(..self.pos);
+            self.base += (self.pos as u64) << 3;
+            self.pos = 0;
+        }
+        self.input.push(byte);
+    }
+
+    /// Returns the number of the input bytes not read yet.
+    pub(crate) fn available(&self) -> usize {
+        self.input.len() - self.pos
+    }
+
+    /// Returns the position of the next input byte in bits.
+    pub(crate) fn position(&self) -> u64 {
+        self.base + ((self.pos as u64) << 3)
+    }
+
+    /// Whether the decoder has read past the end of the input.
+    pub(crate) fn is_overrun(&self) -> bool {
+        self.overrun
+    }
+
+    /// Whether the code ends as the flushed encoder does.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.code == 0
+    }
+
+    /// Starts decoding with the first 5 bytes of the input, and returns
+    /// whether they can start the range coded data.
+    pub(crate) fn init(&mut self) -> bool {
+        self.range = 0xFFFF_FFFF;
+        self.code = 0;
+        let first = self.next_byte();
+        for _ in 0..4 {
+            self.code = (self.code << 8) | u32::from(self.next_byte());
+        }
+        first == 0 && self.code != self.range
+    }
+
+    #[inline]
+    fn next_byte(&mut self) -> u8 {
+        match self.input.get(self.pos) {
+            Some(&b) => {
+                self.pos += 1;
+                b
+            }
+            None => {
+                self.overrun = true;
+                0
+            }
+        }
+    }
+
+    #[inline]
+    fn normalize(&mut self) {
+        if self.range < TOP {
+            self.range <<= 8;
+            self.code = (self.code << 8) | u32::from(self.next_byte());
+        }
+    }
+
+    #[inline]
+    pub(crate) fn bit(&mut self, prob: &mut u16) -> u32 {
+        let bound = (self.range >> PROB_BITS) * u32::from(*prob);
+        let bit = if self.code < bound {
+            self.range = bound;
+            *prob += (PROB_MAX - *prob) >> MOVE_BITS;
+            0
+        } else {
+            self.range -= bound;
+            self.code -= bound;
+            *prob -= *prob >> MOVE_BITS;
+            1
+        };
+        self.normalize();
+        bit
+    }
+
+    /// Decodes `bits` bits from the highest with the binary tree rooted
+    /// at `probs[1]`.
+    pub(crate) fn tree(&mut self, probs: &mut [u16], bits: usize) -> u32 {
+        let mut m = 1;
+        for _ in 0..bits {
+            m = (m << 1) | self.bit(&mut probs[m as usize]);
+        }
+        m - (1 << bits)
+    }
+
+    /// Decodes `bits` bits from the lowest with the binary tree rooted
+    /// at `probs[0]`.
+    pub(crate) fn reverse_tree(
+        &mut self,
+        probs: &mut [u16],
+        bits: usize,
+    ) -> u32 {
+        let mut m = 1;
+        let mut value = 0;
+        for i in 0..bits {
+            let bit = self.bit(&mut probs[m - 1]);
+            m = (m << 1) | bit as usize;
+            value |= bit << i;
+        }
+        value
+    }
+
+    /// Decodes `bits` bits of the even probability.
+    pub(crate) fn direct(&mut self, bits: usize) -> u32 {
+        let mut value = 0;
+        for _ in 0..bits {
+            self.range >>= 1;
+            let bit = if self.code >= self.range {
+                self.code -= self.range;
+                1
+            } else {
+                0
+            };
+            value = (value << 1) | bit;
+            self.normalize();
+        }
+        value
+    }
+}
+
+/// The range encoder, which writes to a buffer until it is flushed.
+#[derive(Debug)]
+pub(crate) struct RangeEncoder {
+    low: u64,
+    range: u32,
+    // 桁上がりを待つバイトと、その後に続く0xFFの数
+    cache: u8,
+    cache_size: usize,
+    out: Vec<u8>,
+}
+
+impl RangeEncoder {
+    pub(crate) fn new() -> Self {
+        Self {
+            low: 0,
+            range: 0xFFFF_FFFF,
+            cache: 0,
+            cache_size: 1,
+            out: Vec::new(),
+        }
+    }
+
+    fn shift_low(&mut self) {
+        if (self.low as u32) < 0xFF00_0000 || (self.low >> 32) != 0 {
+            let carry = (self.low >> 32) as u8;
+            let mut b = self.cache;
+            while self.cache_size > 0 {
+                self.out.push(b.wrapping_add(carry));
+                b = 0xFF;
+                self.cache_size -= 1;
+            }
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.cache_size += 1;
+        self.low = (self.low & 0x00FF_FFFF) << 8;
+    }
+
+    #[inline]
+    fn normalize(&mut self) {
+        if self.range < TOP {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    #[inline]
+    pub(crate) fn bit(&mut self, prob: &mut u16, bit: u32) {
+        let bound = (self.range >> PROB_BITS) * u32::from(*prob);
+        if bit == 0 {
+            self.range = bound;
+            *prob += (PROB_MAX - *prob) >> MOVE_BITS;
+        } else {
+            self.low += u64::from(bound);
+            self.range -= bound;
+            *prob -= *prob >> MOVE_BITS;
+        }
+        self.normalize();
+    }
+
+    /// Encodes `bits` bits of `value` from the highest with the binary
+    /// tree rooted at `probs[1]`.
+    pub(crate) fn tree(&mut self, probs: &mut [u16], bits: usize, value: u32) {
+        let mut m = 1;
+        for i in (0..bits).rev() {
+            let bit = (value >> i) & 1;
+            self.bit(&mut probs[m as usize], bit);
+            m = (m << 1) | bit;
+        }
+    }
+
+    /// Encodes `bits` bits of `value` from the lowest with the binary
+    /// tree rooted at `probs[0]`.
+    pub(crate) fn reverse_tree(
+        &mut self,
+        probs: &mut [u16],
+        bits: usize,
+        value: u32,
+    ) {
+        let mut m = 1;
+        for i in 0..bits {
+            let bit = (value >> i) & 1;
+            self.bit(&mut probs[m - 1], bit);
+            m = (m << 1) | bit as usize;
+        }
+    }
+
+    /// Encodes `bits` bits of `value` of the even probability.
+    pub(crate) fn direct(&mut self, value: u32, bits: usize) {
+        for i in (0..bits).rev() {
+            self.range >>= 1;
+            if (value >> i) & 1 == 1 {
+                self.low += u64::from(self.range);
+            }
+            self.normalize();
+        }
+    }
+
+    /// Returns the length of the output once the encoder is flushed.
+    pub(crate) fn pending_len(&self) -> usize {
+        self.out.len() + self.cache_size + 4
+    }
+
+    /// Takes the output written so far.
+    pub(crate) fn take_output(&mut self) -> Vec<u8> {
+        mem::take(&mut self.out)
+    }
+
+    /// Flushes the encoder, returns the rest of the output and starts
+    /// the encoder again.
+    pub(crate) fn finish(&mut self) -> Vec<u8> {
+        for _ in 0..5 {
+            self.shift_low();
+        }
+        let out = mem::take(&mut self.out);
+        *self = Self::new();
+        out
+    }
+}
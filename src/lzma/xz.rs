@@ -0,0 +1,127 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::checksum::{Crc32, Crc64, Sha256};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+pub(crate) const HEADER_MAGIC: [u8; 6] = [0xFD, b'7', b'z', b'X', b'Z', 0x00];
+pub(crate) const FOOTER_MAGIC: [u8; 2] = [b'Y', b'Z'];
+/// The filter ID of LZMA2.
+pub(crate) const FILTER_LZMA2: u64 = 0x21;
+/// The maximum length of a variable-length integer.
+pub(crate) const MAX_VARINT_LEN: usize = 9;
+
+/// The integrity check of the uncompressed data of the blocks of xz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XzCheck {
+    None,
+    Crc32,
+    Crc64,
+    Sha256,
+}
+
+impl XzCheck {
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0x00 => Some(XzCheck::None),
+            0x01 => Some(XzCheck::Crc32),
+            0x04 => Some(XzCheck::Crc64),
+            0x0A => Some(XzCheck::Sha256),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            XzCheck::None => 0x00,
+            XzCheck::Crc32 => 0x01,
+            XzCheck::Crc64 => 0x04,
+            XzCheck::Sha256 => 0x0A,
+        }
+    }
+
+    pub(crate) fn len(self) -> usize {
+        match self {
+            XzCheck::None => 0,
+            XzCheck::Crc32 => 4,
+            XzCheck::Crc64 => 8,
+            XzCheck::Sha256 => 32,
+        }
+    }
+}
+
+/// The hasher of the check of a block.
+#[derive(Debug)]
+pub(crate) enum Check {
+    None,
+    Crc32(Crc32),
+    Crc64(Crc64),
+    Sha256(Sha256),
+}
+
+impl Check {
+    pub(crate) fn new(check: XzCheck) -> Self {
+        match check {
+            XzCheck::None => Check::None,
+            XzCheck::Crc32 => Check::Crc32(Crc32::new()),
+            XzCheck::Crc64 => Check::Crc64(Crc64::new()),
+            XzCheck::Sha256 => Check::Sha256(Sha256::new()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Check::None => {}
+            Check::Crc32(c) => c.update(data),
+            Check::Crc64(c) => c.update(data),
+            Check::Sha256(c) => c.update(data),
+        }
+    }
+
+    /// Writes the check as it is stored after a block.
+    pub(crate) fn write_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Check::None => {}
+            Check::Crc32(c) => out.extend_from_slice(&c.value().to_le_bytes()),
+            Check::Crc64(c) => out.extend_from_slice(&c.value().to_le_bytes()),
+            Check::Sha256(c) => out.extend_from_slice(&c.value()),
+        }
+    }
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc32 = Crc32::new();
+    crc32.update(data);
+    crc32.value()
+}
+
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Parses the variable-length integer at the start of `data`, and returns
+/// it and its length, or `None` if it is truncated, too long or not the
+/// shortest.
+pub(crate) fn parse_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0;
+    for (i, &b) in data.iter().take(MAX_VARINT_LEN).enumerate() {
+        value |= u64::from(b & 0x7F) << (i * 7);
+        if b & 0x80 == 0 {
+            return if b == 0 && i > 0 {
+                None
+            } else {
+                Some((value, i + 1))
+            };
+        }
+    }
+    None
+}
@@ -0,0 +1,538 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bitio::direction::right::Right;
+use crate::bitio::reader::{BitRead, BitReader};
+use crate::error::CompressionError;
+use crate::lzma::decoder::{at_end, read_bytes, read_le, read_u8};
+use crate::lzma::lzma2::{dict_size_from_byte, Lzma2Decoder};
+use crate::lzma::xz::{
+    crc32, parse_varint, Check, XzCheck, FILTER_LZMA2, FOOTER_MAGIC,
+    HEADER_MAGIC, MAX_VARINT_LEN,
+};
+use crate::status::Status;
+use crate::traits::decoder::{BitDecodeService, BitDecoderImpl, Decoder};
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The number of the bytes decoded and checked at once.
+const OUT_CHUNK: usize = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    StreamHeader,
+    Block,
+    BlockData,
+    BlockEnd,
+    Footer,
+    Padding,
+    Finished,
+}
+
+/// The sizes in the block header.
+#[derive(Debug, Clone, Copy)]
+struct BlockInfo {
+    header_size: u64,
+    /// The position of the start of the compressed data in bytes.
+    data_start: u64,
+    compressed_size: Option<u64>,
+    uncompressed_size: Option<u64>,
+}
+
+#[derive(Debug)]
+pub(crate) struct XzDecoderBase {
+    state: State,
+    stream_no: usize,
+    flags: [u8; 2],
+    check_type: XzCheck,
+    block: Option<BlockInfo>,
+    lzma2: Option<Lzma2Decoder>,
+    check: Check,
+    uncompressed: u64,
+    /// The unpadded size and the uncompressed size of each block of the
+    /// stream, which the index repeats.
+    records: Vec<(u64, u64)>,
+    index_size: u64,
+    out: Vec<u8>,
+    out_pos: usize,
+}
+
+impl Default for XzDecoderBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XzDecoderBase {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: State::StreamHeader,
+            stream_no: 0,
+            flags: [0; 2],
+            check_type: XzCheck::None,
+            block: None,
+            lzma2: None,
+            check: Check::None,
+            uncompressed: 0,
+            records: Vec::new(),
+            index_size: 0,
+            out: Vec::new(),
+            out_pos: 0,
+        }
+    }
+
+    fn read_stream_header<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        let magic = read_bytes(reader, iter, HEADER_MAGIC.len())?;
+        if magic[..] != HEADER_MAGIC[..] {
+            return Err(if self.stream_no == 0 {
+                CompressionError::BadMagic { position }
+            } else {
+                CompressionError::TrailingGarbage { position }
+            });
+        }
+        let flags_position = reader.position();
+        let flags = [read_u8(reader, iter)?, read_u8(reader, iter)?];
+        let expected = read_le(reader, iter, 4)? as u32;
+        let actual = crc32(&flags);
+        if expected != actual {
+            return Err(CompressionError::ChecksumMismatch {
+                expected,
+                actual,
+                position: flags_position + 16,
+            });
+        }
+        if flags[0] != 0 || flags[1] & 0xF0 != 0 {
+            return Err(CompressionError::InvalidHeader {
+                position: flags_position,
+            });
+        }
+        self.check_type = XzCheck::from_id(flags[1]).ok_or(
+            CompressionError::UnsupportedMethod {
+                method: flags[1],
+                position: flags_position + 8,
+            },
+        )?;
+        self.flags = flags;
+        self.records.clear();
+        self.stream_no += 1;
+        self.state = State::Block;
+        Ok(())
+    }
+
+    fn read_block_header<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        let size_byte = read_u8(reader, iter)?;
+        if size_byte == 0 {
+            return self.read_index(reader, iter, position);
+        }
+        let header_size = (usize::from(size_byte) + 1) << 2;
+        let mut header = vec![size_byte];
+        header.extend(read_bytes(reader, iter, header_size - 5)?);
+        let expected = read_le(reader, iter, 4)? as u32;
+        let actual = crc32(&header);
+        if expected != actual {
+            return Err(CompressionError::ChecksumMismatch {
+                expected,
+                actual,
+                position: position + ((header_size as u64 - 4) << 3),
+            });
+        }
+
+        let invalid = CompressionError::InvalidHeader { position };
+        let flags = header[1];
+        if flags & 0x3C != 0 {
+            return Err(invalid);
+        }
+        let mut pos = 2;
+        let next_varint = |pos: &mut usize| -> Result<u64, CompressionError> {
+            let (value, len) = parse_varint(&header[*pos..]).ok_or(invalid)?;
+            *pos += len;
+            Ok(value)
+        };
+        let compressed_size = if flags & 0x40 != 0 {
+            Some(next_varint(&mut pos)?)
+        } else {
+            None
+        };
+        let uncompressed_size = if flags & 0x80 != 0 {
+            Some(next_varint(&mut pos)?)
+        } else {
+            None
+        };
+        if compressed_size == Some(0) {
+            return Err(invalid);
+        }
+        let filters = (flags & 0x03) + 1;
+        let mut dict_size = None;
+        for i in 0..filters {
+            let filter_position = position + ((pos as u64) << 3);
+            let id = next_varint(&mut pos)?;
+            let props_len = next_varint(&mut pos)? as usize;
+            let props = header.get(pos..pos + props_len).ok_or(invalid)?;
+            pos += props_len;
+            // LZMA2以外のフィルターは対応しない
+            if id != FILTER_LZMA2 || i + 1 != filters {
+                return Err(CompressionError::UnsupportedMethod {
+                    method: id as u8,
+                    position: filter_position,
+                });
+            }
+            if props_len != 1 {
+                return Err(invalid);
+            }
+            dict_size = Some(dict_size_from_byte(props[0]).ok_or(invalid)?);
+        }
+        if header[pos..].iter().any(|&b| b != 0) {
+            return Err(invalid);
+        }
+
+        self.block = Some(BlockInfo {
+            header_size: header_size as u64,
+            data_start: reader.position() >> 3,
+            compressed_size,
+            uncompressed_size,
+        });
+        self.lzma2 = Some(Lzma2Decoder::new(dict_size.unwrap()));
+        self.check = Check::new(self.check_type);
+        self.uncompressed = 0;
+        self.state = State::BlockData;
+        Ok(())
+    }
+
+    fn read_block_data<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let lzma2 = self.lzma2.as_mut().unwrap();
+        self.out.clear();
+        self.out_pos = 0;
+        let mut finished = false;
+        let mut starved = false;
+        while self.out.len() < OUT_CHUNK {
+            match lzma2.next(reader, iter) {
+                Ok(Some(b)) => self.out.push(b),
+                Ok(None) => {
+                    finished = true;
+                    break;
+                }
+                Err(CompressionError::UnexpectedEof)
+                    if !self.out.is_empty() =>
+                {
+                    starved = reader.is_starved();
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        self.check.update(&self.out);
+        self.uncompressed += self.out.len() as u64;
+        // 巻き戻されるのはLZMA2の最後の手順だけなので、復号済みの出力を
+        // 残したまま入力を待つ
+        if starved {
+            return Err(CompressionError::UnexpectedEof);
+        }
+        let block = self.block.unwrap();
+        let position = reader.position();
+        if let Some(size) = block.uncompressed_size {
+            if self.uncompressed > size || finished && self.uncompressed < size
+            {
+                return Err(CompressionError::LengthMismatch {
+                    expected: size,
+                    actual: self.uncompressed,
+                    position,
+                });
+            }
+        }
+        let compressed = (position >> 3) - block.data_start;
+        if let Some(size) = block.compressed_size {
+            if compressed > size || finished && compressed < size {
+                return Err(CompressionError::InvalidBlock { position });
+            }
+        }
+        if finished {
+            self.state = State::BlockEnd;
+        }
+        Ok(())
+    }
+
+    fn read_block_end<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let block = self.block.unwrap();
+        let compressed = (reader.position() >> 3) - block.data_start;
+        for _ in compressed..(compressed + 3) & !3 {
+            let position = reader.position();
+            if read_u8(reader, iter)? != 0 {
+                return Err(CompressionError::InvalidBlock { position });
+            }
+        }
+        let position = reader.position();
+        let len = self.check_type.len();
+        let expected = read_bytes(reader, iter, len)?;
+        let mut actual = Vec::with_capacity(len);
+        self.check.write_to(&mut actual);
+        if expected != actual {
+            let value = |v: &[u8]| {
+                v.iter()
+                    .take(4)
+                    .enumerate()
+                    .fold(0, |x, (i, &b)| x | (u32::from(b) << (i << 3)))
+            };
+            return Err(CompressionError::ChecksumMismatch {
+                expected: value(&expected),
+                actual: value(&actual),
+                position,
+            });
+        }
+        self.records.push((
+            block.header_size + compressed + len as u64,
+            self.uncompressed,
+        ));
+        self.lzma2 = None;
+        self.state = State::Block;
+        Ok(())
+    }
+
+    /// Reads the index after the indicator at `position`, and checks it
+    /// against the blocks of the stream.
+    fn read_index<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+        position: u64,
+    ) -> Result<(), CompressionError> {
+        let invalid = CompressionError::InvalidBlock { position };
+        let mut index = vec![0];
+        let mut read_varint = |index: &mut Vec<u8>| {
+            let start = index.len();
+            loop {
+                let b = read_u8(reader, iter)?;
+                index.push(b);
+                if b & 0x80 == 0 || index.len() - start == MAX_VARINT_LEN {
+                    break;
+                }
+            }
+            parse_varint(&index[start..])
+                .map(|(value, _)| value)
+                .ok_or(invalid)
+        };
+        let count = read_varint(&mut index)?;
+        if count != self.records.len() as u64 {
+            return Err(invalid);
+        }
+        for i in 0..self.records.len() {
+            let unpadded = read_varint(&mut index)?;
+            let uncompressed = read_varint(&mut index)?;
+            if (unpadded, uncompressed) != self.records[i] {
+                return Err(invalid);
+            }
+        }
+        while index.len() & 3 != 0 {
+            let b = read_u8(reader, iter)?;
+            if b != 0 {
+                return Err(invalid);
+            }
+            index.push(b);
+        }
+        let crc_position = reader.position();
+        let expected = read_le(reader, iter, 4)? as u32;
+        let actual = crc32(&index);
+        if expected != actual {
+            return Err(CompressionError::ChecksumMismatch {
+                expected,
+                actual,
+                position: crc_position,
+            });
+        }
+        self.index_size = index.len() as u64 + 4;
+        self.state = State::Footer;
+        Ok(())
+    }
+
+    fn read_footer<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        let expected = read_le(reader, iter, 4)? as u32;
+        let fields = read_bytes(reader, iter, 6)?;
+        let magic = read_bytes(reader, iter, 2)?;
+        let actual = crc32(&fields);
+        if expected != actual {
+            return Err(CompressionError::ChecksumMismatch {
+                expected,
+                actual,
+                position,
+            });
+        }
+        let backward_size = u64::from(fields[0])
+            | u64::from(fields[1]) << 8
+            | u64::from(fields[2]) << 16
+            | u64::from(fields[3]) << 24;
+        if (backward_size + 1) << 2 != self.index_size
+            || fields[4..] != self.flags[..]
+            || magic[..] != FOOTER_MAGIC[..]
+        {
+            return Err(CompressionError::InvalidHeader {
+                position: position + 32,
+            });
+        }
+        self.state = State::Padding;
+        Ok(())
+    }
+
+    /// Skips the stream padding, which is a multiple of 4 null bytes.
+    fn read_padding<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        if at_end(reader, iter)? {
+            self.state = State::Finished;
+            return Ok(());
+        }
+        let position = reader.position();
+        let next = reader
+            .peek_bits::<u8, _>(8, iter)
+            .map_err(|_| CompressionError::UnexpectedEof)?;
+        if next.data() != 0 {
+            self.state = State::StreamHeader;
+            return Ok(());
+        }
+        for _ in 0..4 {
+            if read_u8(reader, iter)? != 0 {
+                return Err(CompressionError::TrailingGarbage { position });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BitDecodeService for XzDecoderBase {
+    type Direction = Right;
+    type Error = CompressionError;
+    type Output = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Self::Direction>,
+        iter: &mut I,
+    ) -> Result<Option<u8>, Self::Error> {
+        loop {
+            if self.out_pos < self.out.len() {
+                self.out_pos += 1;
+                return Ok(Some(self.out[self.out_pos - 1]));
+            }
+            reader.commit();
+            match self.state {
+                State::StreamHeader => self.read_stream_header(reader, iter)?,
+                State::Block => self.read_block_header(reader, iter)?,
+                State::BlockData => self.read_block_data(reader, iter)?,
+                State::BlockEnd => self.read_block_end(reader, iter)?,
+                State::Footer => self.read_footer(reader, iter)?,
+                State::Padding => self.read_padding(reader, iter)?,
+                State::Finished => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The decoder of xz, which continues across the concatenated streams and
+/// the stream padding until the end of the input.
+///
+/// The blocks must be filtered only with LZMA2, and the check of each
+/// block and the index are verified.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "lzma")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut XzEncoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     # #[cfg(feature = "lzma")]
+///     let decompressed = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut XzDecoder::new())
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct XzDecoder {
+    inner: BitDecoderImpl<XzDecoderBase>,
+}
+
+impl XzDecoder {
+    pub fn new() -> Self {
+        Self {
+            inner: BitDecoderImpl::<XzDecoderBase>::new(),
+        }
+    }
+
+    /// Returns the check of the current stream, once its header has been
+    /// read.
+    pub fn check(&self) -> Option<XzCheck> {
+        let service = self.inner.service();
+        if service.stream_no > 0 {
+            Some(service.check_type)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for XzDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for XzDecoder {
+    type Input = u8;
+    type Output = u8;
+    type Error = CompressionError;
+
+    fn next<I: Iterator<Item = Self::Input>>(
+        &mut self,
+        iter: &mut I,
+    ) -> Option<Result<Self::Output, Self::Error>> {
+        self.inner.next(iter)
+    }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        self.inner.decompress(input, output, finish)
+    }
+}
@@ -0,0 +1,207 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::action::Action;
+use crate::error::CompressionError;
+use crate::lzma::lzma2::{dict_size_to_byte, Lzma2Encoder};
+use crate::lzma::options::LzmaOptions;
+use crate::lzma::xz::{
+    crc32, write_varint, Check, XzCheck, FILTER_LZMA2, FOOTER_MAGIC,
+    HEADER_MAGIC,
+};
+use crate::traits::encoder::Encoder;
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Pads `out` with null bytes to a multiple of 4 bytes.
+fn pad4(out: &mut Vec<u8>) {
+    while out.len() & 3 != 0 {
+        out.push(0);
+    }
+}
+
+/// The encoder of xz, which writes a stream of a block filtered with
+/// LZMA2, or of no block for the empty input.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "lzma")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut XzEncoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct XzEncoder {
+    lzma2: Lzma2Encoder,
+    dict_size: u32,
+    check_type: XzCheck,
+    check: Check,
+    block_started: bool,
+    /// The length of the compressed data of the block.
+    compressed: u64,
+    uncompressed: u64,
+    out: Vec<u8>,
+    out_pos: usize,
+    header_written: bool,
+    finished: bool,
+}
+
+impl Default for XzEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XzEncoder {
+    /// Creates the encoder of the default level with the CRC-64 check.
+    pub fn new() -> Self {
+        Self::with_options(&LzmaOptions::default(), XzCheck::Crc64)
+    }
+
+    pub fn with_options(options: &LzmaOptions, check: XzCheck) -> Self {
+        options.validate();
+        if options.lc + options.lp > 4 {
+            panic!("invalid lc and lp");
+        }
+        Self {
+            lzma2: Lzma2Encoder::new(options),
+            dict_size: options.dict_size,
+            check_type: check,
+            check: Check::new(check),
+            block_started: false,
+            compressed: 0,
+            uncompressed: 0,
+            out: Vec::new(),
+            out_pos: 0,
+            header_written: false,
+            finished: false,
+        }
+    }
+
+    fn flags(&self) -> [u8; 2] {
+        [0, self.check_type.id()]
+    }
+
+    fn write_stream_header(&mut self) {
+        let flags = self.flags();
+        self.out.extend_from_slice(&HEADER_MAGIC);
+        self.out.extend_from_slice(&flags);
+        self.out.extend_from_slice(&crc32(&flags).to_le_bytes());
+    }
+
+    fn write_block_header(&mut self) {
+        // 1つのフィルターで、サイズは省略する
+        let mut header = vec![0x02, 0x00];
+        write_varint(&mut header, FILTER_LZMA2);
+        header.push(0x01);
+        header.push(dict_size_to_byte(self.dict_size));
+        pad4(&mut header);
+        self.out.extend_from_slice(&header);
+        self.out.extend_from_slice(&crc32(&header).to_le_bytes());
+    }
+
+    /// Writes the end of the block, the index and the stream footer.
+    fn write_trailer(&mut self) {
+        let mut records = Vec::new();
+        if self.block_started {
+            let start = self.out.len();
+            self.lzma2.finish(&mut self.out);
+            self.compressed += (self.out.len() - start) as u64;
+            let padding = self.compressed.wrapping_neg() & 3;
+            self.out.resize(self.out.len() + padding as usize, 0);
+            self.check.write_to(&mut self.out);
+            // ブロックヘッダーは12バイト
+            let unpadded = 12 + self.compressed + self.check_type.len() as u64;
+            records.push((unpadded, self.uncompressed));
+        }
+
+        let mut index = vec![0x00];
+        write_varint(&mut index, records.len() as u64);
+        for &(unpadded, uncompressed) in &records {
+            write_varint(&mut index, unpadded);
+            write_varint(&mut index, uncompressed);
+        }
+        pad4(&mut index);
+        index.extend_from_slice(&crc32(&index).to_le_bytes());
+        self.out.extend_from_slice(&index);
+
+        let mut fields =
+            (((index.len() >> 2) - 1) as u32).to_le_bytes().to_vec();
+        fields.extend_from_slice(&self.flags());
+        self.out.extend_from_slice(&crc32(&fields).to_le_bytes());
+        self.out.extend_from_slice(&fields);
+        self.out.extend_from_slice(&FOOTER_MAGIC);
+    }
+}
+
+impl Encoder for XzEncoder {
+    type Error = CompressionError;
+    type In = u8;
+    type Out = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<u8, CompressionError>> {
+        loop {
+            if self.out_pos < self.out.len() {
+                self.out_pos += 1;
+                return Some(Ok(self.out[self.out_pos - 1]));
+            }
+            self.out.clear();
+            self.out_pos = 0;
+            if self.finished {
+                return None;
+            }
+            if !self.header_written {
+                self.write_stream_header();
+                self.header_written = true;
+                continue;
+            }
+            match self.lzma2.next_code(iter, action) {
+                Some(Ok(code)) => {
+                    if !self.block_started {
+                        self.write_block_header();
+                        self.block_started = true;
+                    }
+                    let start = self.out.len();
+                    let len = self.lzma2.push_code(code, &mut self.out);
+                    self.compressed += (self.out.len() - start) as u64;
+                    self.check.update(self.lzma2.tail(len));
+                    self.uncompressed += len as u64;
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => match action {
+                    Action::Finish => {
+                        self.write_trailer();
+                        self.finished = true;
+                    }
+                    Action::Flush => {
+                        let start = self.out.len();
+                        self.lzma2.write_chunk(&mut self.out);
+                        self.compressed += (self.out.len() - start) as u64;
+                        if self.out.is_empty() {
+                            return None;
+                        }
+                    }
+                    _ => return None,
+                },
+            }
+        }
+    }
+}
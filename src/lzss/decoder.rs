@@ -6,9 +6,14 @@
 //! <http://mozilla.org/MPL/2.0/>.
 
 use crate::cbuffer::CircularBuffer;
+#[cfg(feature = "deflate")]
+use crate::core::cmp;
 use crate::error::CompressionError;
 use crate::lzss::LzssCode;
+use crate::status::Status;
 use crate::traits::decoder::Decoder;
+#[cfg(all(feature = "deflate", not(feature = "std")))]
+use alloc::vec::Vec;
 
 /// # Examples
 ///
@@ -70,6 +75,52 @@ impl LzssDecoder {
         buf.append(dict);
         Self { buf, offset: 0 }
     }
+
+    fn push(&mut self, code: LzssCode) {
+        match code {
+            LzssCode::Symbol(s) => {
+                self.buf.push(s);
+                self.offset += 1;
+            }
+            LzssCode::Reference { len, pos } => {
+                self.offset += len;
+                for _ in 0..len {
+                    let d = self.buf[pos];
+                    self.buf.push(d);
+                }
+            }
+        }
+    }
+
+    /// Decodes the next byte from `codes`, stopping at the first error.
+    #[cfg(any(feature = "deflate", feature = "lzhuf"))]
+    pub(crate) fn next_code<I>(
+        &mut self,
+        codes: &mut I,
+    ) -> Result<Option<u8>, CompressionError>
+    where
+        I: Iterator<Item = Result<LzssCode, CompressionError>>,
+    {
+        let mut error = None;
+        let ret = self.next(&mut codes.map_while(|c| match c {
+            Ok(c) => Some(c),
+            Err(e) => {
+                error = Some(e);
+                None
+            }
+        }));
+        match error {
+            Some(e) => Err(e),
+            None => ret.transpose(),
+        }
+    }
+
+    /// Returns up to `len` bytes of the data decoded last.
+    #[cfg(feature = "deflate")]
+    pub(crate) fn window(&self, len: usize) -> Vec<u8> {
+        let len = cmp::min(len, self.buf.len() - self.offset);
+        (0..len).rev().map(|i| self.buf[i + self.offset]).collect()
+    }
 }
 
 impl Decoder for LzssDecoder {
@@ -79,29 +130,45 @@ impl Decoder for LzssDecoder {
 
     fn next<I: Iterator<Item = Self::Input>>(
         &mut self,
-        s: &mut I,
+        iter: &mut I,
     ) -> Option<Result<Self::Output, Self::Error>> {
         while self.offset == 0 {
-            match s.next() {
-                Some(s) => match s {
-                    LzssCode::Symbol(s) => {
-                        self.buf.push(s);
-                        self.offset += 1;
-                    }
-                    LzssCode::Reference { len, pos } => {
-                        self.offset += len;
-                        for _ in 0..len {
-                            let d = self.buf[pos];
-                            self.buf.push(d);
-                        }
-                    }
-                },
-                None => return None,
-            }
+            self.push(iter.next()?);
         }
         self.offset -= 1;
         Some(Ok(self.buf[self.offset]))
     }
+
+    /// Decodes `input` into `output`. As the codes have no end marker,
+    /// `done` is set only with `finish`, once all of `input` has been
+    /// decoded and written.
+    fn decompress(
+        &mut self,
+        input: &[LzssCode],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, CompressionError> {
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            // 復号した分は窓の先頭から`offset`の手前に並んでいる
+            while self.offset > 0 && produced < output.len() {
+                self.offset -= 1;
+                output[produced] = self.buf[self.offset];
+                produced += 1;
+            }
+            if self.offset > 0 || consumed == input.len() {
+                break;
+            }
+            self.push(input[consumed]);
+            consumed += 1;
+        }
+        Ok(Status {
+            consumed,
+            produced,
+            done: finish && consumed == input.len() && self.offset == 0,
+        })
+    }
 }
 
 #[cfg(test)]
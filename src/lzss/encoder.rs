@@ -11,6 +11,7 @@ use crate::error::CompressionError;
 use crate::lzss::compare_match_info;
 use crate::lzss::slidedict::SlideDict;
 use crate::lzss::LzssCode;
+use crate::status::Status;
 use crate::traits::encoder::Encoder;
 #[cfg(not(feature = "std"))]
 use alloc::collections::vec_deque::VecDeque;
@@ -129,6 +130,14 @@ where
         }
     }
 
+    pub(crate) fn set_search_limit(
+        &mut self,
+        max_chain: usize,
+        nice_length: usize,
+    ) {
+        self.slide.set_search_limit(max_chain, nice_length);
+    }
+
     fn encode(&mut self) {
         let info = self.slide.search_dic(self.offset, self.max_match);
 
@@ -150,7 +159,7 @@ where
                     self.slide.search_dic(self.offset - i, self.max_match)
                 {
                     if item.len > self.min_match
-                        && compare_match_info(&self.comp, &item, &out_info)
+                        && compare_match_info(self.comp, &item, &out_info)
                             == Ordering::Less
                     {
                         out_info = item;
@@ -168,12 +177,12 @@ where
                 }
                 _ => self.lzss_queue.push_back(LzssCode::Reference {
                     len: lazy_index,
-                    pos: info.pos as usize,
+                    pos: info.pos,
                 }),
             }
             self.lzss_queue.push_back(LzssCode::Reference {
                 len: out_info.len,
-                pos: out_info.pos as usize,
+                pos: out_info.pos,
             });
             self.offset -= out_info.len + lazy_index;
         } else {
@@ -221,7 +230,7 @@ where
                         self.finished = false;
                         return None;
                     } else {
-                        if Action::Flush == action || Action::Finish == action {
+                        if Action::Run != action {
                             self.flush()
                         };
                         self.finished = true;
@@ -231,6 +240,47 @@ where
         }
         self.lzss_queue.pop_front().map(Ok)
     }
+
+    fn compress(
+        &mut self,
+        input: &[u8],
+        output: &mut [LzssCode],
+        action: Action,
+    ) -> Result<Status, CompressionError> {
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            while produced < output.len() {
+                match self.lzss_queue.pop_front() {
+                    Some(s) => {
+                        output[produced] = s;
+                        produced += 1;
+                    }
+                    None => break,
+                }
+            }
+            if !self.lzss_queue.is_empty() {
+                break;
+            }
+            if consumed < input.len() {
+                self.next_in(input[consumed]);
+                consumed += 1;
+            } else if action != Action::Run && self.offset > 0 {
+                self.flush();
+            } else {
+                return Ok(Status {
+                    consumed,
+                    produced,
+                    done: true,
+                });
+            }
+        }
+        Ok(Status {
+            consumed,
+            produced,
+            done: false,
+        })
+    }
 }
 
 #[cfg(test)]
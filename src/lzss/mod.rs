@@ -69,7 +69,7 @@ impl Default for LzssCode {
 #[derive(Clone, Debug)]
 pub(crate) struct MatchInfo {
     pub(crate) len: usize,
-    pub(crate) pos: u16,
+    pub(crate) pos: usize,
 }
 
 fn compare_match_info<F: Fn(LzssCode, LzssCode) -> Ordering>(
@@ -80,11 +80,11 @@ fn compare_match_info<F: Fn(LzssCode, LzssCode) -> Ordering>(
     comp(
         LzssCode::Reference {
             len: arg1.len,
-            pos: arg1.pos as usize,
+            pos: arg1.pos,
         },
         LzssCode::Reference {
             len: arg2.len,
-            pos: arg2.pos as usize,
+            pos: arg2.pos,
         },
     )
 }
@@ -110,4 +110,51 @@ mod tests {
             (LzssCode::Symbol(_), _) => Ordering::Less,
         }
     }
+
+    #[test]
+    fn test_slice() {
+        use crate::action::Action;
+        use crate::lzss::decoder::LzssDecoder;
+        use crate::lzss::encoder::LzssEncoder;
+        use crate::traits::decoder::tests::decompress_slices;
+        use crate::traits::encoder::tests::compress_slices;
+        use crate::traits::encoder::EncodeExt;
+        #[cfg(not(feature = "std"))]
+        use alloc::vec::Vec;
+
+        let testarray = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cycle()
+            .take(0x1000)
+            .chain(b"abcdefghijklmnopqrstuvwxyz".iter())
+            .cloned()
+            .collect::<Vec<u8>>();
+        let expected = testarray
+            .iter()
+            .cloned()
+            .encode(
+                &mut LzssEncoder::new(comparison, 0x1_0000, 256, 3, 3),
+                Action::Finish,
+            )
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        for &(chunk, out_len) in &[(1, 1), (5, 3), (0x100, 0x100)] {
+            let encoded = compress_slices(
+                &mut LzssEncoder::new(comparison, 0x1_0000, 256, 3, 3),
+                &testarray,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(encoded == expected);
+            let decoded = decompress_slices(
+                &mut LzssDecoder::new(0x1_0000),
+                &encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded == testarray);
+        }
+    }
 }
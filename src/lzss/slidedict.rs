@@ -10,7 +10,6 @@ use crate::core::cmp::{self, Ordering};
 use crate::core::mem;
 use crate::core::ops::Index;
 use crate::core::slice;
-use crate::core::u16;
 use crate::lzss::compare_match_info;
 use crate::lzss::LzssCode;
 use crate::lzss::MatchInfo;
@@ -121,6 +120,8 @@ pub(crate) struct SlideDict<F: Fn(LzssCode, LzssCode) -> Ordering> {
     pos: CircularBuffer<usize>,
     max_pos: usize,
     min_match: usize,
+    max_chain: usize,
+    nice_length: usize,
     hash_tab: HashTab,
     append_buf: Vec<u8>,
 }
@@ -137,6 +138,8 @@ impl<F: Fn(LzssCode, LzssCode) -> Ordering> SlideDict<F> {
         Self {
             comparison,
             min_match,
+            max_chain: Self::MATCH_SEARCH_COUNT - 1,
+            nice_length: usize::MAX,
             max_pos,
             buf: CircularBuffer::new(size_of_buf),
             pos: CircularBuffer::new(size_of_buf),
@@ -147,6 +150,15 @@ impl<F: Fn(LzssCode, LzssCode) -> Ordering> SlideDict<F> {
         }
     }
 
+    pub(crate) fn set_search_limit(
+        &mut self,
+        max_chain: usize,
+        nice_length: usize,
+    ) {
+        self.max_chain = max_chain;
+        self.nice_length = nice_length;
+    }
+
     #[inline]
     fn push_pos(&mut self, data: &[u8]) {
         match self.hash_tab.push(data) {
@@ -229,14 +241,14 @@ impl<F: Fn(LzssCode, LzssCode) -> Ordering> SlideDict<F> {
 
         let mut info = None;
 
-        let mut pos_count = Self::MATCH_SEARCH_COUNT - 1;
+        let mut pos_count = self.max_chain;
 
         while pos <= self.max_pos && pos_count > 0 {
             let nlen = self.check_match(offset, offset + pos, max_match);
             // debug_assert!(pos > 65536, "position overflow");
             let new_info = MatchInfo {
                 len: nlen,
-                pos: (pos - 1) as u16,
+                pos: pos - 1,
             };
 
             info = info
@@ -253,9 +265,9 @@ impl<F: Fn(LzssCode, LzssCode) -> Ordering> SlideDict<F> {
                         None
                     }
                 })
-                .or_else(|| Some(new_info));
+                .or(Some(new_info));
 
-            if nlen == max_match {
+            if nlen == max_match || nlen >= self.nice_length {
                 pos_count = 0;
             } else {
                 pos_count -= 1;
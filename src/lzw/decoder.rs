@@ -0,0 +1,328 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bitio::direction::right::Right;
+use crate::bitio::reader::{BitRead, BitReader};
+use crate::error::CompressionError;
+use crate::lzw::{
+    max_code, Format, BLOCK_MODE, GIF_MAX_BITS, MAGIC, MAX_BITS, MAX_BITS_MASK,
+    RESERVED_FLAGS,
+};
+use crate::status::Status;
+use crate::traits::decoder::{BitDecodeService, BitDecoderImpl, Decoder};
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+fn read_u8<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<u8, CompressionError> {
+    match reader.read_bits::<u8, _>(8, iter) {
+        Ok(b) if b.len() == 8 => Ok(b.data()),
+        _ => Err(CompressionError::UnexpectedEof),
+    }
+}
+
+fn at_end<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<bool, CompressionError> {
+    reader
+        .peek_bits::<u8, _>(8, iter)
+        .map(|b| b.is_empty())
+        .map_err(|_| CompressionError::UnexpectedEof)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The header of `compress(1)`.
+    Header,
+    Code,
+    Finished,
+}
+
+#[derive(Debug)]
+pub(crate) struct LzwDecoderBase {
+    state: State,
+    format: Format,
+    bits: usize,
+    /// The next code of a string.
+    free: usize,
+    /// The number of the codes read with the current width.
+    codes: usize,
+    prev: Option<usize>,
+    /// The first byte of the string of the previous code.
+    first_byte: u8,
+    prefix: Vec<u16>,
+    suffix: Vec<u8>,
+    /// The string of the last code, which is reversed.
+    stack: Vec<u8>,
+}
+
+impl Default for LzwDecoderBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LzwDecoderBase {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: State::Header,
+            ..Self::with_format(Format::compress(MAX_BITS))
+        }
+    }
+
+    fn with_format(format: Format) -> Self {
+        let mut base = Self {
+            state: State::Code,
+            format,
+            bits: 0,
+            free: 0,
+            codes: 0,
+            prev: None,
+            first_byte: 0,
+            prefix: vec![0; format.table_size()],
+            suffix: vec![0; format.table_size()],
+            stack: Vec::new(),
+        };
+        base.reset();
+        base
+    }
+
+    fn reset(&mut self) {
+        self.bits = self.format.init_bits();
+        // 最初の符号は文字列を登録しない
+        self.free = self.format.first() - 1;
+        self.codes = 0;
+        self.prev = None;
+    }
+
+    fn read_header<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        let magic = [read_u8(reader, iter)?, read_u8(reader, iter)?];
+        if magic != MAGIC {
+            return Err(CompressionError::BadMagic { position });
+        }
+        let flags = read_u8(reader, iter)?;
+        let max_bits = usize::from(flags & MAX_BITS_MASK);
+        if flags & RESERVED_FLAGS != 0 || !(9..=MAX_BITS).contains(&max_bits) {
+            return Err(CompressionError::InvalidHeader {
+                position: position + 16,
+            });
+        }
+        self.format = Format::Compress {
+            max_bits,
+            block_mode: flags & BLOCK_MODE != 0,
+        };
+        self.reset();
+        self.state = State::Code;
+        Ok(())
+    }
+
+    /// Skips the rest of the group of 8 codes of the current width, and
+    /// returns whether the input has continued.
+    fn skip_group<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<bool, CompressionError> {
+        let mut len = ((8 - (self.codes & 7)) & 7) * self.bits;
+        while len > 0 {
+            let n = len.min(8);
+            let skipped = reader
+                .skip_bits(n, iter)
+                .map_err(|_| CompressionError::UnexpectedEof)?;
+            if skipped < n {
+                return Ok(false);
+            }
+            len -= n;
+        }
+        Ok(true)
+    }
+
+    fn read_code<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        if let Format::Compress { max_bits, .. } = self.format {
+            // 符号の幅が変わるときは、8つの符号の組の残りを読み飛ばす
+            if self.free > max_code(max_bits, self.bits) {
+                if !self.skip_group(reader, iter)? {
+                    self.state = State::Finished;
+                    return Ok(());
+                }
+                self.bits += 1;
+                self.codes = 0;
+                reader.commit();
+            }
+        }
+
+        let position = reader.position();
+        let code = match reader.read_bits::<u32, _>(self.bits, iter) {
+            Ok(b) if b.len() == self.bits => b.data() as usize,
+            Ok(_) if self.format.eoi().is_none() => {
+                self.state = State::Finished;
+                return Ok(());
+            }
+            _ => return Err(CompressionError::UnexpectedEof),
+        };
+        self.codes += 1;
+
+        if Some(code) == self.format.clear() {
+            if self.format.eoi().is_none() && !self.skip_group(reader, iter)? {
+                self.state = State::Finished;
+                return Ok(());
+            }
+            self.reset();
+            return Ok(());
+        }
+        if Some(code) == self.format.eoi() {
+            let _ = reader.skip_to_next_byte();
+            let position = reader.position();
+            if !at_end(reader, iter)? {
+                return Err(CompressionError::TrailingGarbage { position });
+            }
+            self.state = State::Finished;
+            return Ok(());
+        }
+
+        let literals = self.format.literals();
+        let table_size = self.format.table_size();
+        match self.prev {
+            None if code < literals => self.stack.push(code as u8),
+            // 表が一杯のときは、登録されない文字列の符号は使えない
+            Some(prev)
+                if code < self.free
+                    || code == self.free && self.free < table_size =>
+            {
+                let mut c = code;
+                if code == self.free {
+                    // 登録する文字列そのものの符号
+                    self.stack.push(self.first_byte);
+                    c = prev;
+                }
+                while c >= literals {
+                    self.stack.push(self.suffix[c]);
+                    c = usize::from(self.prefix[c]);
+                }
+                self.stack.push(c as u8);
+                if self.free < table_size {
+                    self.prefix[self.free] = prev as u16;
+                    self.suffix[self.free] = c as u8;
+                }
+            }
+            _ => {
+                return Err(CompressionError::InvalidDistance {
+                    distance: code,
+                    position,
+                })
+            }
+        }
+        self.first_byte = *self.stack.last().unwrap();
+        self.prev = Some(code);
+        if self.free < table_size {
+            self.free += 1;
+        }
+        if self.format.eoi().is_some()
+            && self.free == 1 << self.bits
+            && self.bits < GIF_MAX_BITS
+        {
+            self.bits += 1;
+        }
+        Ok(())
+    }
+}
+
+impl BitDecodeService for LzwDecoderBase {
+    type Direction = Right;
+    type Error = CompressionError;
+    type Output = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Self::Direction>,
+        iter: &mut I,
+    ) -> Result<Option<u8>, Self::Error> {
+        loop {
+            if let Some(b) = self.stack.pop() {
+                return Ok(Some(b));
+            }
+            reader.commit();
+            match self.state {
+                State::Header => self.read_header(reader, iter)?,
+                State::Code => self.read_code(reader, iter)?,
+                State::Finished => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The LZW decoder.
+///
+/// The decoder reads the `compress(1)` format, or the GIF code stream if
+/// created with `gif`.
+#[derive(Debug)]
+pub struct LzwDecoder {
+    inner: BitDecoderImpl<LzwDecoderBase>,
+}
+
+impl LzwDecoder {
+    pub fn new() -> Self {
+        Self {
+            inner: BitDecoderImpl::<LzwDecoderBase>::new(),
+        }
+    }
+
+    /// Creates a decoder which reads the GIF code stream of the minimum
+    /// code size `2..=8`, without the sub-blocks.
+    pub fn gif(min_code_size: u8) -> Self {
+        Self {
+            inner: BitDecoderImpl::<LzwDecoderBase>::with_service(
+                LzwDecoderBase::with_format(Format::gif(usize::from(
+                    min_code_size,
+                ))),
+                BitReader::new(),
+            ),
+        }
+    }
+}
+
+impl Default for LzwDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for LzwDecoder {
+    type Input = u8;
+    type Output = u8;
+    type Error = CompressionError;
+
+    fn next<I: Iterator<Item = Self::Input>>(
+        &mut self,
+        iter: &mut I,
+    ) -> Option<Result<Self::Output, Self::Error>> {
+        self.inner.next(iter)
+    }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        self.inner.decompress(input, output, finish)
+    }
+}
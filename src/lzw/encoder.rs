@@ -0,0 +1,353 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::action::Action;
+use crate::bitio::direction::right::Right;
+use crate::bitio::small_bit_vec::SmallBitVec;
+use crate::bitio::writer::BitWriter;
+use crate::error::CompressionError;
+use crate::lzw::{max_code, Format, BLOCK_MODE, MAGIC, MAX_BITS};
+use crate::traits::encoder::Encoder;
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+const HASH_BITS: usize = 18;
+/// The interval of the input length to check the compression ratio.
+const CHECK_GAP: usize = 10000;
+
+/// The table of the strings, which maps a code and the following byte to
+/// the code of the string.
+#[derive(Debug)]
+struct StringTable {
+    // 0は空き
+    keys: Vec<u32>,
+    codes: Vec<u16>,
+}
+
+impl StringTable {
+    fn new() -> Self {
+        Self {
+            keys: vec![0; 1 << HASH_BITS],
+            codes: vec![0; 1 << HASH_BITS],
+        }
+    }
+
+    fn key(prefix: u16, c: u8) -> u32 {
+        ((u32::from(prefix) << 8) | u32::from(c)) + 1
+    }
+
+    /// Returns the slot of the key, which is empty if the key is absent.
+    fn slot(&self, key: u32) -> usize {
+        let mask = (1 << HASH_BITS) - 1;
+        let mut i =
+            (key.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize;
+        while self.keys[i] != 0 && self.keys[i] != key {
+            i = (i + 1) & mask;
+        }
+        i
+    }
+
+    fn get(&self, prefix: u16, c: u8) -> Option<u16> {
+        let i = self.slot(Self::key(prefix, c));
+        if self.keys[i] == 0 {
+            None
+        } else {
+            Some(self.codes[i])
+        }
+    }
+
+    fn insert(&mut self, prefix: u16, c: u8, code: u16) {
+        let key = Self::key(prefix, c);
+        let i = self.slot(key);
+        self.keys[i] = key;
+        self.codes[i] = code;
+    }
+
+    fn clear(&mut self) {
+        for k in &mut self.keys {
+            *k = 0;
+        }
+    }
+}
+
+/// The LZW encoder, which writes the `compress(1)` format, or the GIF code
+/// stream with `gif`.
+///
+/// As the format has no flush point, `Action::Flush` is treated as
+/// `Action::Run`.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "lzw")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut LzwEncoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     # #[cfg(feature = "lzw")]
+///     let decompressed = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut LzwDecoder::new())
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LzwEncoder {
+    format: Format,
+    table: StringTable,
+    writer: BitWriter<Right>,
+    bits: usize,
+    /// The next code of a string.
+    free: usize,
+    /// The number of the codes written with the current width.
+    codes: usize,
+    /// The code of the string being matched.
+    ent: Option<u16>,
+    // 圧縮率を調べるための入出力の長さ
+    in_count: usize,
+    bytes_out: usize,
+    checkpoint: usize,
+    ratio: usize,
+    out: Vec<u8>,
+    out_pos: usize,
+    header_written: bool,
+    finished: bool,
+}
+
+impl Default for LzwEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LzwEncoder {
+    pub fn new() -> Self {
+        Self::with_format(Format::compress(MAX_BITS))
+    }
+
+    /// Creates an encoder of `compress(1)` with the maximum code width
+    /// `9..=16`, as `compress -b`.
+    pub fn with_max_bits(max_bits: u8) -> Self {
+        Self::with_format(Format::compress(usize::from(max_bits)))
+    }
+
+    /// Creates an encoder which writes the GIF code stream of the minimum
+    /// code size `2..=8`, without the sub-blocks. The input bytes are the
+    /// color indices, which must be less than `1 << min_code_size`.
+    pub fn gif(min_code_size: u8) -> Self {
+        Self::with_format(Format::gif(usize::from(min_code_size)))
+    }
+
+    fn with_format(format: Format) -> Self {
+        Self {
+            format,
+            table: StringTable::new(),
+            writer: BitWriter::new(),
+            bits: format.init_bits(),
+            free: format.first(),
+            codes: 0,
+            ent: None,
+            in_count: 0,
+            bytes_out: MAGIC.len() + 1,
+            checkpoint: CHECK_GAP,
+            ratio: 0,
+            out: Vec::new(),
+            out_pos: 0,
+            header_written: false,
+            finished: false,
+        }
+    }
+
+    fn write_code(&mut self, code: usize) {
+        let (data, n) = self
+            .writer
+            .write_bits(&SmallBitVec::new(code as u32, self.bits));
+        self.out.extend((0..n).map(|i| (data >> (i << 3)) as u8));
+        self.codes += 1;
+        if self.codes & 7 == 0 {
+            self.bytes_out += self.bits;
+        }
+    }
+
+    /// Pads the group of 8 codes of the current width with zeros, as the
+    /// decoder of `compress(1)` skips the rest of the group when the width
+    /// changes.
+    fn pad_group(&mut self) {
+        if self.codes & 7 != 0 {
+            self.bytes_out += self.bits;
+        }
+        for _ in 0..((8 - (self.codes & 7)) & 7) {
+            let (data, n) =
+                self.writer.write_bits(&SmallBitVec::new(0u32, self.bits));
+            self.out.extend((0..n).map(|i| (data >> (i << 3)) as u8));
+        }
+        self.codes = 0;
+    }
+
+    /// Writes the code of the string being matched, and registers the
+    /// string followed by `c`.
+    fn output(&mut self, ent: u16, c: Option<u8>) {
+        self.write_code(usize::from(ent));
+        match self.format {
+            Format::Compress {
+                max_bits,
+                block_mode,
+            } => {
+                if self.free > max_code(max_bits, self.bits) {
+                    self.pad_group();
+                    self.bits += 1;
+                }
+                let c = match c {
+                    Some(c) => c,
+                    None => return,
+                };
+                if self.free < self.format.table_size() {
+                    self.table.insert(ent, c, self.free as u16);
+                    self.free += 1;
+                } else if block_mode && self.in_count >= self.checkpoint {
+                    self.check_ratio();
+                }
+            }
+            Format::Gif { .. } => {
+                // 復号器が幅を広げるのに合わせる
+                if self.free == 1 << self.bits {
+                    self.bits += 1;
+                }
+                if self.free == self.format.table_size() - 1 {
+                    self.write_code(self.format.literals());
+                    self.reset();
+                } else if let Some(c) = c {
+                    self.table.insert(ent, c, self.free as u16);
+                    self.free += 1;
+                }
+            }
+        }
+    }
+
+    /// Writes the CLEAR code if the compression ratio has fallen since the
+    /// table has been full.
+    fn check_ratio(&mut self) {
+        self.checkpoint = self.in_count + CHECK_GAP;
+        let rat = if self.in_count > 0x007F_FFFF {
+            match self.bytes_out >> 8 {
+                0 => 0x7FFF_FFFF,
+                r => self.in_count / r,
+            }
+        } else {
+            (self.in_count << 8) / self.bytes_out
+        };
+        if rat > self.ratio {
+            self.ratio = rat;
+        } else {
+            self.ratio = 0;
+            self.write_code(self.format.literals());
+            self.pad_group();
+            self.reset();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.table.clear();
+        self.bits = self.format.init_bits();
+        self.free = self.format.first();
+        self.codes = 0;
+    }
+
+    fn write_header(&mut self) {
+        match self.format {
+            Format::Compress { max_bits, .. } => {
+                self.out.extend_from_slice(&MAGIC);
+                self.out.push(max_bits as u8 | BLOCK_MODE);
+            }
+            Format::Gif { .. } => self.write_code(self.format.literals()),
+        }
+    }
+
+    fn push_byte(&mut self, c: u8) -> Result<(), CompressionError> {
+        if usize::from(c) >= self.format.literals() {
+            return Err(CompressionError::InvalidBlock {
+                position: (self.in_count as u64) << 3,
+            });
+        }
+        self.in_count += 1;
+        self.ent = match self.ent {
+            None => Some(u16::from(c)),
+            Some(ent) => match self.table.get(ent, c) {
+                Some(code) => Some(code),
+                None => {
+                    self.output(ent, Some(c));
+                    Some(u16::from(c))
+                }
+            },
+        };
+        Ok(())
+    }
+
+    fn finish(&mut self) {
+        self.finished = true;
+        if let Some(ent) = self.ent.take() {
+            self.output(ent, None);
+        }
+        if let Some(eoi) = self.format.eoi() {
+            self.write_code(eoi);
+        }
+        if let Some((data, n)) = self.writer.flush::<u32>() {
+            self.out.extend((0..n).map(|i| (data >> (i << 3)) as u8));
+        }
+    }
+}
+
+impl Encoder for LzwEncoder {
+    type Error = CompressionError;
+    type In = u8;
+    type Out = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<u8, CompressionError>> {
+        loop {
+            if self.out_pos < self.out.len() {
+                self.out_pos += 1;
+                return Some(Ok(self.out[self.out_pos - 1]));
+            }
+            self.out.clear();
+            self.out_pos = 0;
+            if self.finished {
+                return None;
+            }
+            if !self.header_written {
+                self.write_header();
+                self.header_written = true;
+                continue;
+            }
+
+            match iter.next() {
+                Some(c) => {
+                    if let Err(e) = self.push_byte(c) {
+                        return Some(Err(e));
+                    }
+                }
+                None => match action {
+                    Action::Finish => self.finish(),
+                    _ => return None,
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "lzw")]
+
+pub(crate) mod decoder;
+pub(crate) mod encoder;
+
+const MAGIC: [u8; 2] = [0x1F, 0x9D];
+/// The flag of the third byte of the header that the CLEAR code is used.
+const BLOCK_MODE: u8 = 0x80;
+const MAX_BITS_MASK: u8 = 0x1F;
+const RESERVED_FLAGS: u8 = 0x60;
+const INIT_BITS: usize = 9;
+const MAX_BITS: usize = 16;
+const GIF_MAX_BITS: usize = 12;
+
+/// The variant of LZW.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// `compress(1)` with the maximum code width, and whether the CLEAR
+    /// code is used.
+    Compress { max_bits: usize, block_mode: bool },
+    /// GIF with the minimum code size, the width of the literals.
+    Gif { min_code_size: usize },
+}
+
+impl Format {
+    fn compress(max_bits: usize) -> Self {
+        if !(INIT_BITS..=MAX_BITS).contains(&max_bits) {
+            panic!("invalid max bits");
+        }
+        Format::Compress {
+            max_bits,
+            block_mode: true,
+        }
+    }
+
+    fn gif(min_code_size: usize) -> Self {
+        if !(2..=8).contains(&min_code_size) {
+            panic!("invalid min code size");
+        }
+        Format::Gif { min_code_size }
+    }
+
+    /// Returns the number of the literal codes.
+    fn literals(self) -> usize {
+        match self {
+            Format::Compress { .. } => 0x100,
+            Format::Gif { min_code_size } => 1 << min_code_size,
+        }
+    }
+
+    fn clear(self) -> Option<usize> {
+        match self {
+            Format::Compress {
+                block_mode: false, ..
+            } => None,
+            _ => Some(self.literals()),
+        }
+    }
+
+    /// Returns the end of information code of GIF.
+    fn eoi(self) -> Option<usize> {
+        match self {
+            Format::Compress { .. } => None,
+            Format::Gif { .. } => Some(self.literals() + 1),
+        }
+    }
+
+    /// Returns the first code of the strings.
+    fn first(self) -> usize {
+        self.literals()
+            + match self {
+                Format::Compress { block_mode, .. } => usize::from(block_mode),
+                Format::Gif { .. } => 2,
+            }
+    }
+
+    fn init_bits(self) -> usize {
+        match self {
+            Format::Compress { .. } => INIT_BITS,
+            Format::Gif { min_code_size } => min_code_size + 1,
+        }
+    }
+
+    /// Returns the number of the codes.
+    fn table_size(self) -> usize {
+        match self {
+            Format::Compress { max_bits, .. } => 1 << max_bits,
+            Format::Gif { .. } => 1 << GIF_MAX_BITS,
+        }
+    }
+}
+
+/// Returns the largest code of `bits` width in `compress(1)`, after which
+/// the width increases.
+///
+/// As the original, the codes of `max_bits` width are limited when the
+/// width has increased to it, so that the maximum of 9 bits goes to 10.
+fn max_code(max_bits: usize, bits: usize) -> usize {
+    if bits == max_bits && bits > INIT_BITS {
+        1 << max_bits
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+    use crate::error::CompressionError;
+    use crate::lzw::decoder::LzwDecoder;
+    use crate::lzw::encoder::LzwEncoder;
+    use crate::traits::decoder::DecodeExt;
+    use crate::traits::encoder::{EncodeExt, Encoder};
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+
+    fn decode(
+        encoded: &[u8],
+        decoder: &mut LzwDecoder,
+    ) -> Result<Vec<u8>, CompressionError> {
+        encoded.iter().cloned().decode(decoder).collect()
+    }
+
+    fn check(encoded: &[u8], decoder: &mut LzwDecoder, expected: &[u8]) {
+        let decoded = decode(encoded, decoder).unwrap();
+        assert_eq!(decoded.len(), expected.len());
+        assert!(decoded == expected);
+    }
+
+    fn encode(data: &[u8], encoder: &mut LzwEncoder) -> Vec<u8> {
+        data.iter()
+            .cloned()
+            .encode(encoder, Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    fn random(len: usize, literals: usize) -> Vec<u8> {
+        thread_rng()
+            .sample_iter::<u8, _>(&Standard)
+            .take(len)
+            .map(|b| (usize::from(b) % literals) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_samples() {
+        check(
+            include_bytes!("../../data/sample1.Z"),
+            &mut LzwDecoder::new(),
+            include_bytes!("../../data/sample1.ref"),
+        );
+        check(
+            include_bytes!("../../data/sample2.Z"),
+            &mut LzwDecoder::new(),
+            include_bytes!("../../data/sample2.ref"),
+        );
+        check(
+            include_bytes!("../../data/sample3.Z"),
+            &mut LzwDecoder::new(),
+            include_bytes!("../../data/sample3.ref"),
+        );
+    }
+
+    #[test]
+    fn test_block_mode() {
+        // CLEARを使わない形式では、256が最初の文字列になる
+        check(
+            &[0x1F, 0x9D, 0x10, 0x61, 0xC4, 0x00, 0x14, 0x28, 0x06],
+            &mut LzwDecoder::new(),
+            b"abababab",
+        );
+        check(
+            &[0x1F, 0x9D, 0x90, 0x61, 0xC4, 0x04, 0x1C, 0x28, 0x06],
+            &mut LzwDecoder::new(),
+            b"abababab",
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(&[], &mut LzwEncoder::new()), [0x1F, 0x9D, 0x90]);
+        assert_eq!(
+            encode(b"abababab", &mut LzwEncoder::new()),
+            [0x1F, 0x9D, 0x90, 0x61, 0xC4, 0x04, 0x1C, 0x28, 0x06]
+        );
+        let sample = include_bytes!("../../data/sample1.ref");
+        let long = b"abc".iter().cycle().take(0x10_0000).cloned();
+        for data in &[
+            b"a".to_vec(),
+            sample.to_vec(),
+            long.collect(),
+            random(300_000, 0x100),
+            random(300_000, 3),
+        ] {
+            for &max_bits in &[9, 12, 16] {
+                let encoded =
+                    encode(data, &mut LzwEncoder::with_max_bits(max_bits));
+                assert_eq!(encoded[2], max_bits | BLOCK_MODE);
+                check(&encoded, &mut LzwDecoder::new(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_gif() {
+        // 10x10の画像の色番号
+        let encoded = [
+            0x8C, 0x2D, 0x99, 0x87, 0x2A, 0x1C, 0xDC, 0x33, 0xA0, 0x02, 0x75,
+            0xEC, 0x95, 0xFA, 0xA8, 0xDE, 0x60, 0x8C, 0x04, 0x91, 0x4C, 0x01,
+        ];
+        let rows: [&[u8; 10]; 10] = [
+            b"1111122222",
+            b"1111122222",
+            b"1111122222",
+            b"1110000222",
+            b"1110000222",
+            b"2220000111",
+            b"2220000111",
+            b"2222211111",
+            b"2222211111",
+            b"2222211111",
+        ];
+        let expected = rows
+            .iter()
+            .flat_map(|row| row.iter().map(|b| b - b'0'))
+            .collect::<Vec<_>>();
+        check(&encoded, &mut LzwDecoder::gif(2), &expected);
+
+        for &(min_code_size, len) in &[(2, 100_000), (4, 1), (8, 300_000)] {
+            let literals = 1 << min_code_size;
+            for data in &[random(len, literals), vec![1; len]] {
+                let encoded = encode(data, &mut LzwEncoder::gif(min_code_size));
+                check(&encoded, &mut LzwDecoder::gif(min_code_size), data);
+            }
+        }
+        check(
+            &encode(&[], &mut LzwEncoder::gif(2)),
+            &mut LzwDecoder::gif(2),
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_stream() {
+        use crate::traits::decoder::tests::decompress_slices;
+
+        let data = include_bytes!("../../data/sample3.ref");
+        for &max_bits in &[9, 16] {
+            let encoded =
+                encode(data, &mut LzwEncoder::with_max_bits(max_bits));
+            for &(chunk, out_len) in &[(1, 0x1000), (7, 1), (0x1000, 0x8000)] {
+                let decoded = decompress_slices(
+                    &mut LzwDecoder::new(),
+                    &encoded,
+                    chunk,
+                    out_len,
+                )
+                .unwrap();
+                assert!(decoded[..] == data[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            decode(b"not lzw", &mut LzwDecoder::new()),
+            Err(CompressionError::BadMagic { position: 0 })
+        );
+        assert_eq!(
+            decode(&[], &mut LzwDecoder::new()),
+            Err(CompressionError::UnexpectedEof)
+        );
+        for &flags in &[0x88, 0x91, 0xB0] {
+            assert_eq!(
+                decode(&[0x1F, 0x9D, flags], &mut LzwDecoder::new()),
+                Err(CompressionError::InvalidHeader { position: 16 })
+            );
+        }
+        // 登録されていない符号300
+        assert_eq!(
+            decode(&[0x1F, 0x9D, 0x90, 0x2C, 0x01], &mut LzwDecoder::new()),
+            Err(CompressionError::InvalidDistance {
+                distance: 300,
+                position: 24,
+            })
+        );
+        // 9ビットの表が一杯になった後の、10ビットの符号512
+        let mut encoded = vec![0x1F, 0x9D, 0x89];
+        encoded.extend_from_slice(&[0; 288]);
+        encoded.extend_from_slice(&[0x00, 0x02]);
+        assert_eq!(
+            decode(&encoded, &mut LzwDecoder::new()),
+            Err(CompressionError::InvalidDistance {
+                distance: 512,
+                position: 2328,
+            })
+        );
+        // EOIのないGIF
+        assert_eq!(
+            decode(&[0x8C, 0x2D], &mut LzwDecoder::gif(2)),
+            Err(CompressionError::UnexpectedEof)
+        );
+        assert_eq!(
+            LzwEncoder::gif(2).next(&mut [4].iter().cloned(), Action::Finish),
+            Some(Err(CompressionError::InvalidBlock { position: 0 }))
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bitio::direction::right::Right;
+use crate::bitio::reader::{BitRead, BitReader};
+use crate::checksum::Crc32c;
+use crate::error::CompressionError;
+use crate::snappy::raw::{decode, decode_element, read_varint};
+use crate::snappy::{
+    mask_crc, CHUNK_COMPRESSED, CHUNK_PADDING, CHUNK_STREAM_IDENTIFIER,
+    CHUNK_UNCOMPRESSED, MAX_CHUNK_SIZE, STREAM_IDENTIFIER,
+};
+use crate::status::Status;
+use crate::traits::decoder::{BitDecodeService, BitDecoderImpl, Decoder};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The stream identifier which starts the framing format.
+    Identifier,
+    Chunk,
+    /// The length of the skippable chunk left.
+    Skip(usize),
+    /// The length of the raw data.
+    Length,
+    /// The elements of the raw data of the length.
+    Element(usize),
+    Finished,
+}
+
+fn read_u8<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<u8, CompressionError> {
+    match reader.read_bits::<u8, _>(8, iter) {
+        Ok(b) if b.len() == 8 => Ok(b.data()),
+        _ => Err(CompressionError::UnexpectedEof),
+    }
+}
+
+fn read_le<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+    len: usize,
+) -> Result<u32, CompressionError> {
+    (0..len).try_fold(0, |v, i| {
+        Ok(v | (u32::from(read_u8(reader, iter)?) << (i << 3)))
+    })
+}
+
+fn read_bytes<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+    len: usize,
+) -> Result<Vec<u8>, CompressionError> {
+    (0..len).map(|_| read_u8(reader, iter)).collect()
+}
+
+fn at_end<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<bool, CompressionError> {
+    reader
+        .peek_bits::<u8, _>(8, iter)
+        .map(|b| b.is_empty())
+        .map_err(|_| CompressionError::UnexpectedEof)
+}
+
+#[derive(Debug)]
+pub(crate) struct SnappyDecoderBase {
+    state: State,
+    // 現在のチャンク、または生の形式で復号したデータ
+    window: Vec<u8>,
+    out_pos: usize,
+}
+
+impl Default for SnappyDecoderBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SnappyDecoderBase {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: State::Identifier,
+            window: Vec::new(),
+            out_pos: 0,
+        }
+    }
+
+    fn raw() -> Self {
+        Self {
+            state: State::Length,
+            ..Self::new()
+        }
+    }
+
+    fn read_chunk<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        if self.state == State::Chunk && at_end(reader, iter)? {
+            self.state = State::Finished;
+            return Ok(());
+        }
+        let kind = read_u8(reader, iter)?;
+        let len = read_le(reader, iter, 3)? as usize;
+        if self.state == State::Identifier && kind != CHUNK_STREAM_IDENTIFIER {
+            return Err(CompressionError::BadMagic { position });
+        }
+        let invalid = CompressionError::InvalidBlock { position };
+        match kind {
+            CHUNK_STREAM_IDENTIFIER => {
+                let data = read_bytes(reader, iter, len)?;
+                if data[..] != STREAM_IDENTIFIER[4..] {
+                    return Err(CompressionError::BadMagic { position });
+                }
+            }
+            CHUNK_COMPRESSED | CHUNK_UNCOMPRESSED => {
+                if len < 4 {
+                    return Err(invalid);
+                }
+                let expected = read_le(reader, iter, 4)?;
+                if kind == CHUNK_UNCOMPRESSED && len - 4 > MAX_CHUNK_SIZE {
+                    return Err(invalid);
+                }
+                let data = read_bytes(reader, iter, len - 4)?;
+                let data = if kind == CHUNK_COMPRESSED {
+                    decode(&data, MAX_CHUNK_SIZE, position + 64)?
+                } else {
+                    data
+                };
+                let mut crc32c = Crc32c::new();
+                crc32c.update(&data);
+                let actual = mask_crc(crc32c.value());
+                if expected != actual {
+                    return Err(CompressionError::ChecksumMismatch {
+                        expected,
+                        actual,
+                        position: position + 32,
+                    });
+                }
+                self.window = data;
+                self.out_pos = 0;
+            }
+            0x02..=0x7F => {
+                return Err(CompressionError::UnsupportedMethod {
+                    method: kind,
+                    position,
+                })
+            }
+            // CHUNK_PADDING と予約された読み飛ばせるチャンク
+            0x80..=CHUNK_PADDING => {
+                self.state = State::Skip(len);
+                return Ok(());
+            }
+        }
+        self.state = State::Chunk;
+        Ok(())
+    }
+
+    fn read_element<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+        len: usize,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        if self.window.len() == len {
+            if !at_end(reader, iter)? {
+                return Err(CompressionError::TrailingGarbage { position });
+            }
+            self.state = State::Finished;
+            return Ok(());
+        }
+        let mut next = || read_u8(reader, iter);
+        decode_element(&mut next, &mut self.window, len, position)
+    }
+}
+
+impl BitDecodeService for SnappyDecoderBase {
+    type Direction = Right;
+    type Error = CompressionError;
+    type Output = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Self::Direction>,
+        iter: &mut I,
+    ) -> Result<Option<u8>, Self::Error> {
+        loop {
+            if self.out_pos < self.window.len() {
+                self.out_pos += 1;
+                return Ok(Some(self.window[self.out_pos - 1]));
+            }
+            reader.commit();
+            match self.state {
+                State::Identifier | State::Chunk => {
+                    self.read_chunk(reader, iter)?
+                }
+                State::Skip(len) => {
+                    let _ = read_bytes(reader, iter, len)?;
+                    self.state = State::Chunk;
+                }
+                State::Length => {
+                    let position = reader.position();
+                    let mut next = || read_u8(reader, iter);
+                    match read_varint(&mut next)? {
+                        Some(len) => self.state = State::Element(len as usize),
+                        None => {
+                            return Err(CompressionError::InvalidHeader {
+                                position,
+                            })
+                        }
+                    }
+                }
+                State::Element(len) => self.read_element(reader, iter, len)?,
+                State::Finished => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The Snappy decoder.
+///
+/// The decoder reads the framing format, or the raw format if created with
+/// `raw`.
+#[derive(Debug)]
+pub struct SnappyDecoder {
+    inner: BitDecoderImpl<SnappyDecoderBase>,
+}
+
+impl SnappyDecoder {
+    pub fn new() -> Self {
+        Self {
+            inner: BitDecoderImpl::<SnappyDecoderBase>::new(),
+        }
+    }
+
+    /// Creates a decoder which reads the raw format.
+    pub fn raw() -> Self {
+        Self {
+            inner: BitDecoderImpl::<SnappyDecoderBase>::with_service(
+                SnappyDecoderBase::raw(),
+                BitReader::new(),
+            ),
+        }
+    }
+}
+
+impl Default for SnappyDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for SnappyDecoder {
+    type Input = u8;
+    type Output = u8;
+    type Error = CompressionError;
+
+    fn next<I: Iterator<Item = Self::Input>>(
+        &mut self,
+        iter: &mut I,
+    ) -> Option<Result<Self::Output, Self::Error>> {
+        self.inner.next(iter)
+    }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        self.inner.decompress(input, output, finish)
+    }
+}
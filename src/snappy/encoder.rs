@@ -0,0 +1,266 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::action::Action;
+use crate::checksum::Crc32c;
+use crate::core::cmp::Ordering;
+use crate::error::CompressionError;
+use crate::lzss::encoder::LzssEncoder;
+use crate::lzss::LzssCode;
+use crate::snappy::raw::{write_copy, write_literal, write_varint};
+use crate::snappy::{
+    mask_crc, CHUNK_COMPRESSED, CHUNK_UNCOMPRESSED, MAX_CHUNK_SIZE,
+    STREAM_IDENTIFIER,
+};
+use crate::traits::encoder::Encoder;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = 0x100;
+const WINDOW_SIZE: usize = 0xFFFF;
+const MAX_CHAIN: usize = 16;
+const NICE_LENGTH: usize = 64;
+
+fn lzss_comparison(lhs: LzssCode, rhs: LzssCode) -> Ordering {
+    match (lhs, rhs) {
+        (
+            LzssCode::Reference {
+                len: llen,
+                pos: lpos,
+            },
+            LzssCode::Reference {
+                len: rlen,
+                pos: rpos,
+            },
+        ) => rlen.cmp(&llen).then(lpos.cmp(&rpos)),
+        (LzssCode::Symbol(_), LzssCode::Symbol(_)) => Ordering::Equal,
+        (_, LzssCode::Symbol(_)) => Ordering::Greater,
+        (LzssCode::Symbol(_), _) => Ordering::Less,
+    }
+}
+
+type SnappyLzssEncoder = LzssEncoder<fn(LzssCode, LzssCode) -> Ordering>;
+
+fn lzss() -> SnappyLzssEncoder {
+    let mut lzss = LzssEncoder::new(
+        lzss_comparison as fn(LzssCode, LzssCode) -> Ordering,
+        WINDOW_SIZE,
+        MAX_MATCH,
+        MIN_MATCH,
+        1,
+    );
+    lzss.set_search_limit(MAX_CHAIN, NICE_LENGTH);
+    lzss
+}
+
+/// The Snappy encoder, which writes the framing format, or the raw format
+/// with `raw`.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "snappy")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut SnappyEncoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     # #[cfg(feature = "snappy")]
+///     let decompressed = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut SnappyDecoder::new())
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SnappyEncoder {
+    lzss: SnappyLzssEncoder,
+    frame: bool,
+    chunk_size: usize,
+    // 現在のチャンクに読んだ入力の長さと、符号にした長さ
+    input_len: usize,
+    coded_len: usize,
+    // フレームのときだけ、チャンクの入力を保持する
+    input: Vec<u8>,
+    literals: Vec<u8>,
+    body: Vec<u8>,
+    out: Vec<u8>,
+    out_pos: usize,
+    header_written: bool,
+    finished: bool,
+}
+
+impl Default for SnappyEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SnappyEncoder {
+    pub fn new() -> Self {
+        Self {
+            lzss: lzss(),
+            frame: true,
+            chunk_size: MAX_CHUNK_SIZE,
+            input_len: 0,
+            coded_len: 0,
+            input: Vec::new(),
+            literals: Vec::new(),
+            body: Vec::new(),
+            out: Vec::new(),
+            out_pos: 0,
+            header_written: false,
+            finished: false,
+        }
+    }
+
+    /// Creates an encoder which writes the raw format. The output starts
+    /// once the input has been finished, as the format starts with the
+    /// length of the data.
+    pub fn raw() -> Self {
+        Self {
+            frame: false,
+            chunk_size: usize::MAX,
+            ..Self::new()
+        }
+    }
+
+    fn push_code(&mut self, code: LzssCode) {
+        match code {
+            LzssCode::Symbol(b) => {
+                self.literals.push(b);
+                self.coded_len += 1;
+            }
+            LzssCode::Reference { len, pos } => {
+                write_literal(&self.literals, &mut self.body);
+                self.literals.clear();
+                write_copy(pos + 1, len, &mut self.body);
+                self.coded_len += len;
+            }
+        }
+    }
+
+    fn write_chunk(&mut self) {
+        write_literal(&self.literals, &mut self.body);
+        let mut crc32c = Crc32c::new();
+        crc32c.update(&self.input);
+        let crc = mask_crc(crc32c.value());
+
+        let mut data = Vec::new();
+        write_varint(self.input.len() as u64, &mut data);
+        data.extend_from_slice(&self.body);
+        // 圧縮しても8分の1以上縮まなければ、そのまま書く
+        let len = self.input.len();
+        let (kind, data) = if data.len() < len - len / 8 {
+            (CHUNK_COMPRESSED, &data[..])
+        } else {
+            (CHUNK_UNCOMPRESSED, &self.input[..])
+        };
+        self.out.push(kind);
+        self.out
+            .extend_from_slice(&(data.len() as u32 + 4).to_le_bytes()[..3]);
+        self.out.extend_from_slice(&crc.to_le_bytes());
+        self.out.extend_from_slice(data);
+
+        self.input.clear();
+        self.literals.clear();
+        self.body.clear();
+        self.input_len = 0;
+        self.coded_len = 0;
+        // チャンクはそれぞれ独立に復号される
+        self.lzss = lzss();
+    }
+
+    fn finish(&mut self) {
+        self.finished = true;
+        if self.frame {
+            if self.input_len > 0 {
+                self.write_chunk();
+            }
+        } else {
+            write_literal(&self.literals, &mut self.body);
+            write_varint(self.coded_len as u64, &mut self.out);
+            self.out.append(&mut self.body);
+        }
+    }
+}
+
+impl Encoder for SnappyEncoder {
+    type Error = CompressionError;
+    type In = u8;
+    type Out = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<u8, CompressionError>> {
+        loop {
+            if self.out_pos < self.out.len() {
+                self.out_pos += 1;
+                return Some(Ok(self.out[self.out_pos - 1]));
+            }
+            self.out.clear();
+            self.out_pos = 0;
+            if self.finished {
+                return None;
+            }
+            if !self.header_written {
+                if self.frame {
+                    self.out.extend_from_slice(&STREAM_IDENTIFIER);
+                }
+                self.header_written = true;
+                continue;
+            }
+
+            // チャンクが入力で満ちたら、符号を全て出させてチャンクを書く
+            let full = self.input_len == self.chunk_size;
+            let rest = self.chunk_size - self.input_len;
+            let frame = self.frame;
+            let input = &mut self.input;
+            let input_len = &mut self.input_len;
+            let code = self.lzss.next(
+                &mut iter.by_ref().take(rest).inspect(|&b| {
+                    *input_len += 1;
+                    if frame {
+                        input.push(b);
+                    }
+                }),
+                if full { Action::Flush } else { action },
+            );
+            match code {
+                Some(Ok(code)) => self.push_code(code),
+                Some(Err(e)) => return Some(Err(e)),
+                None if self.input_len == self.chunk_size => {
+                    if self.coded_len == self.chunk_size {
+                        self.write_chunk();
+                    }
+                }
+                None => match action {
+                    Action::Run => return None,
+                    Action::Finish => self.finish(),
+                    _ => {
+                        if self.frame && self.input_len > 0 {
+                            self.write_chunk();
+                        }
+                        if self.out.is_empty() {
+                            return None;
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
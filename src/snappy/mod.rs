@@ -0,0 +1,246 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "snappy")]
+
+pub(crate) mod decoder;
+pub(crate) mod encoder;
+pub(crate) mod raw;
+
+/// The stream identifier chunk, which starts the framing format.
+const STREAM_IDENTIFIER: [u8; 10] =
+    [0xFF, 0x06, 0x00, 0x00, b's', b'N', b'a', b'P', b'p', b'Y'];
+const CHUNK_COMPRESSED: u8 = 0x00;
+const CHUNK_UNCOMPRESSED: u8 = 0x01;
+const CHUNK_PADDING: u8 = 0xFE;
+const CHUNK_STREAM_IDENTIFIER: u8 = 0xFF;
+/// The maximum length of the uncompressed data in a chunk.
+const MAX_CHUNK_SIZE: usize = 0x1_0000;
+
+/// Masks the CRC-32C of a chunk, as the CRC of data containing the CRC
+/// is weak.
+fn mask_crc(crc: u32) -> u32 {
+    crc.rotate_right(15).wrapping_add(0xA282_EAD8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+    use crate::checksum::Crc32c;
+    use crate::error::CompressionError;
+    use crate::snappy::decoder::SnappyDecoder;
+    use crate::snappy::encoder::SnappyEncoder;
+    use crate::traits::decoder::DecodeExt;
+    use crate::traits::encoder::{EncodeExt, Encoder};
+    #[cfg(not(feature = "std"))]
+    #[allow(unused_imports)]
+    use alloc::vec;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    /// `abcdabcdabcd` of a literal and a copy with 1 byte offset.
+    const RAW: [u8; 8] = [0x0C, 0x0C, b'a', b'b', b'c', b'd', 0x11, 0x04];
+
+    fn decode(
+        encoded: &[u8],
+        decoder: &mut SnappyDecoder,
+    ) -> Result<Vec<u8>, CompressionError> {
+        encoded.iter().cloned().decode(decoder).collect()
+    }
+
+    fn check(encoded: &[u8], decoder: &mut SnappyDecoder, expected: &[u8]) {
+        let decoded = decode(encoded, decoder).unwrap();
+        assert_eq!(decoded.len(), expected.len());
+        assert!(decoded == expected);
+    }
+
+    fn encode(data: &[u8], encoder: &mut SnappyEncoder) -> Vec<u8> {
+        data.iter()
+            .cloned()
+            .encode(encoder, Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    /// Returns the chunk of `kind` with the CRC of `content`.
+    fn chunk(kind: u8, content: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut crc32c = Crc32c::new();
+        crc32c.update(content);
+        let mut chunk = vec![kind];
+        chunk.extend_from_slice(&(data.len() as u32 + 4).to_le_bytes()[..3]);
+        chunk.extend_from_slice(&mask_crc(crc32c.value()).to_le_bytes());
+        chunk.extend_from_slice(data);
+        chunk
+    }
+
+    #[test]
+    fn test_raw() {
+        check(&RAW, &mut SnappyDecoder::raw(), b"abcdabcdabcd");
+        check(&[0x00], &mut SnappyDecoder::raw(), &[]);
+        // 2バイトと4バイトのオフセットのコピー
+        check(
+            &[0x08, 0x04, b'a', b'b', 0x16, 0x02, 0x00],
+            &mut SnappyDecoder::raw(),
+            b"abababab",
+        );
+        check(
+            &[0x08, 0x04, b'a', b'b', 0x17, 0x02, 0x00, 0x00, 0x00],
+            &mut SnappyDecoder::raw(),
+            b"abababab",
+        );
+        let mut literal = vec![100, 0xF0, 99];
+        literal.extend_from_slice(&[b'x'; 100]);
+        check(&literal, &mut SnappyDecoder::raw(), &[b'x'; 100]);
+
+        assert_eq!(encode(b"abcdabcdabcd", &mut SnappyEncoder::raw()), RAW);
+        assert_eq!(encode(&[], &mut SnappyEncoder::raw()), vec![0x00]);
+        for data in &[
+            &b"a"[..],
+            &[0x55; 0x4_0000][..],
+            &include_bytes!("../../data/sample1.ref")[..],
+            &include_bytes!("../../data/sample3.ref")[..],
+        ] {
+            let encoded = encode(data, &mut SnappyEncoder::raw());
+            check(&encoded, &mut SnappyDecoder::raw(), data);
+        }
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut encoded = STREAM_IDENTIFIER.to_vec();
+        encoded.extend(chunk(CHUNK_UNCOMPRESSED, b"snappy", b"snappy"));
+        encoded.extend_from_slice(&[CHUNK_PADDING, 0x02, 0x00, 0x00, 0, 0]);
+        encoded.extend_from_slice(&STREAM_IDENTIFIER);
+        encoded.extend(chunk(CHUNK_COMPRESSED, b"abcdabcdabcd", &RAW));
+        encoded.extend_from_slice(&[0x80, 0x01, 0x00, 0x00, 0xFF]);
+        check(&encoded, &mut SnappyDecoder::new(), b"snappyabcdabcdabcd");
+        check(&STREAM_IDENTIFIER, &mut SnappyDecoder::new(), &[]);
+    }
+
+    #[test]
+    fn test_stream() {
+        use crate::traits::decoder::tests::decompress_slices;
+
+        let data = include_bytes!("../../data/sample1.ref");
+        for encoder in &mut [SnappyEncoder::new(), SnappyEncoder::raw()] {
+            let encoded = encode(data, encoder);
+            for &(chunk, out_len) in &[(1, 0x1000), (7, 1), (0x1000, 0x8000)] {
+                let mut decoder = if encoded[0] == CHUNK_STREAM_IDENTIFIER {
+                    SnappyDecoder::new()
+                } else {
+                    SnappyDecoder::raw()
+                };
+                let decoded =
+                    decompress_slices(&mut decoder, &encoded, chunk, out_len)
+                        .unwrap();
+                assert!(decoded[..] == data[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            decode(b"not snappy", &mut SnappyDecoder::new()),
+            Err(CompressionError::BadMagic { position: 0 })
+        );
+        assert_eq!(
+            decode(&[], &mut SnappyDecoder::new()),
+            Err(CompressionError::UnexpectedEof)
+        );
+
+        let mut unskippable = STREAM_IDENTIFIER.to_vec();
+        unskippable.extend_from_slice(&[0x02, 0x00, 0x00, 0x00]);
+        assert_eq!(
+            decode(&unskippable, &mut SnappyDecoder::new()),
+            Err(CompressionError::UnsupportedMethod {
+                method: 2,
+                position: 10 << 3,
+            })
+        );
+
+        let mut checksum = STREAM_IDENTIFIER.to_vec();
+        checksum.extend(chunk(CHUNK_UNCOMPRESSED, b"snappy", b"snapp!"));
+        assert!(matches!(
+            decode(&checksum, &mut SnappyDecoder::new()),
+            Err(CompressionError::ChecksumMismatch { position, .. })
+                if position == 14 << 3
+        ));
+
+        // 長さを超えるリテラル
+        let mut length = RAW;
+        length[0] = 0x03;
+        assert_eq!(
+            decode(&length, &mut SnappyDecoder::raw()),
+            Err(CompressionError::InvalidBlock { position: 8 })
+        );
+        let mut compressed = STREAM_IDENTIFIER.to_vec();
+        compressed.extend(chunk(CHUNK_COMPRESSED, b"abcdabcdabcd", &length));
+        assert_eq!(
+            decode(&compressed, &mut SnappyDecoder::new()),
+            Err(CompressionError::InvalidBlock { position: 19 << 3 })
+        );
+
+        let mut distance = RAW;
+        distance[7] = 5;
+        assert_eq!(
+            decode(&distance, &mut SnappyDecoder::raw()),
+            Err(CompressionError::InvalidDistance {
+                distance: 5,
+                position: 6 << 3,
+            })
+        );
+
+        let mut garbage = RAW.to_vec();
+        garbage.push(0);
+        assert_eq!(
+            decode(&garbage, &mut SnappyDecoder::raw()),
+            Err(CompressionError::TrailingGarbage { position: 8 << 3 })
+        );
+        assert_eq!(
+            decode(&RAW[..7], &mut SnappyDecoder::raw()),
+            Err(CompressionError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        for data in &[
+            &b""[..],
+            &b"a"[..],
+            &b"aabbaabbaabbaabb\n"[..],
+            &[0x55; 0x4_0000][..],
+            &include_bytes!("../../data/sample1.ref")[..],
+            &include_bytes!("../../data/sample2.ref")[..],
+            &include_bytes!("../../data/sample3.ref")[..],
+        ] {
+            let encoded = encode(data, &mut SnappyEncoder::new());
+            assert_eq!(encoded[..10], STREAM_IDENTIFIER);
+            check(&encoded, &mut SnappyDecoder::new(), data);
+        }
+    }
+
+    #[test]
+    fn test_encode_flush() {
+        let data = include_bytes!("../../data/sample1.ref");
+        let mut encoder = SnappyEncoder::new();
+        let mut encoded = Vec::new();
+        for (i, chunk) in data.chunks(0x1000).enumerate() {
+            let action = if i % 3 == 0 {
+                Action::Flush
+            } else {
+                Action::Run
+            };
+            let mut iter = chunk.iter().cloned();
+            while let Some(b) = encoder.next(&mut iter, action) {
+                encoded.push(b.unwrap());
+            }
+        }
+        encoded.extend(encode(&[], &mut encoder));
+        check(&encoded, &mut SnappyDecoder::new(), data);
+    }
+}
@@ -0,0 +1,184 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::error::CompressionError;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+pub(crate) fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads the varint of 32 bits with `next`.
+pub(crate) fn read_varint<F>(
+    next: &mut F,
+) -> Result<Option<u32>, CompressionError>
+where
+    F: FnMut() -> Result<u8, CompressionError>,
+{
+    let mut value = 0_u32;
+    for i in 0..5 {
+        let b = next()?;
+        let bits = u32::from(b & 0x7F);
+        if i == 4 && bits > 0x0F {
+            return Ok(None);
+        }
+        value |= bits << (i * 7);
+        if b & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
+
+pub(crate) fn write_literal(literals: &[u8], out: &mut Vec<u8>) {
+    if literals.is_empty() {
+        return;
+    }
+    let n = literals.len() - 1;
+    if n < 60 {
+        out.push((n << 2) as u8);
+    } else {
+        // 長さは1から4バイトで続く
+        let bytes = (64 - (n as u64).leading_zeros() as usize + 7) >> 3;
+        out.push(((59 + bytes) << 2) as u8);
+        out.extend_from_slice(&(n as u64).to_le_bytes()[..bytes]);
+    }
+    out.extend_from_slice(literals);
+}
+
+/// Writes the copies of `len` bytes at `offset`, which is less than
+/// `0x1_0000`.
+pub(crate) fn write_copy(offset: usize, mut len: usize, out: &mut Vec<u8>) {
+    // 最後のコピーが4バイト以上になるように分ける
+    while len >= 68 {
+        write_copy2(offset, 64, out);
+        len -= 64;
+    }
+    if len > 64 {
+        write_copy2(offset, 60, out);
+        len -= 60;
+    }
+    if len < 12 && offset < 0x800 {
+        out.push((((offset >> 8) << 5) | ((len - 4) << 2) | 1) as u8);
+        out.push(offset as u8);
+    } else {
+        write_copy2(offset, len, out);
+    }
+}
+
+fn write_copy2(offset: usize, len: usize, out: &mut Vec<u8>) {
+    out.push((((len - 1) << 2) | 2) as u8);
+    out.extend_from_slice(&(offset as u16).to_le_bytes());
+}
+
+/// Reads an element with `next` and decodes it to the end of `window`,
+/// which has `limit` bytes at most. `position` is the position of the
+/// element in bits.
+pub(crate) fn decode_element<F>(
+    next: &mut F,
+    window: &mut Vec<u8>,
+    limit: usize,
+    position: u64,
+) -> Result<(), CompressionError>
+where
+    F: FnMut() -> Result<u8, CompressionError>,
+{
+    let invalid = CompressionError::InvalidBlock { position };
+    let tag = usize::from(next()?);
+    let (offset, len) = match tag & 3 {
+        0 => {
+            let len = match tag >> 2 {
+                n if n < 60 => n + 1,
+                n => {
+                    let mut len = 0_usize;
+                    for i in 0..n - 59 {
+                        len |= usize::from(next()?) << (i << 3);
+                    }
+                    len.checked_add(1).ok_or(invalid)?
+                }
+            };
+            if len > limit - window.len() {
+                return Err(invalid);
+            }
+            // 読み切るまで窓を変えない
+            let literals =
+                (0..len).map(|_| next()).collect::<Result<Vec<_>, _>>()?;
+            window.extend_from_slice(&literals);
+            return Ok(());
+        }
+        1 => {
+            let offset = ((tag >> 5) << 8) | usize::from(next()?);
+            (offset, ((tag >> 2) & 7) + 4)
+        }
+        2 => {
+            let offset = usize::from(next()?) | (usize::from(next()?) << 8);
+            (offset, (tag >> 2) + 1)
+        }
+        _ => {
+            let mut offset = 0;
+            for i in 0..4 {
+                offset |= usize::from(next()?) << (i << 3);
+            }
+            (offset, (tag >> 2) + 1)
+        }
+    };
+    if offset == 0 || offset > window.len() {
+        return Err(CompressionError::InvalidDistance {
+            distance: offset,
+            position,
+        });
+    }
+    if len > limit - window.len() {
+        return Err(invalid);
+    }
+    let from = window.len() - offset;
+    if offset >= len {
+        window.extend_from_within(from..from + len);
+    } else {
+        for i in from..from + len {
+            let b = window[i];
+            window.push(b);
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the raw data `data` of `limit` bytes at most. `position` is the
+/// position of `data` in bits.
+pub(crate) fn decode(
+    data: &[u8],
+    limit: usize,
+    position: u64,
+) -> Result<Vec<u8>, CompressionError> {
+    let mut iter = data.iter();
+    let mut window = Vec::new();
+    let mut len = None;
+    loop {
+        let pos = position + (((data.len() - iter.len()) as u64) << 3);
+        let invalid = CompressionError::InvalidBlock { position: pos };
+        let mut next = || iter.next().cloned().ok_or(invalid);
+        match len {
+            None => match read_varint(&mut next)? {
+                Some(l) if l as usize <= limit => {
+                    window.reserve(l as usize);
+                    len = Some(l as usize);
+                }
+                _ => return Err(invalid),
+            },
+            Some(l) if window.len() < l => {
+                decode_element(&mut next, &mut window, l, pos)?
+            }
+            Some(_) if iter.len() == 0 => return Ok(window),
+            Some(_) => return Err(invalid),
+        }
+    }
+}
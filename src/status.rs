@@ -0,0 +1,17 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+/// The progress of a slice based `compress` or `decompress` call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Status {
+    /// The number of bytes read from the input.
+    pub consumed: usize,
+    /// The number of bytes written to the output.
+    pub produced: usize,
+    /// Whether the requested action has been completed.
+    pub done: bool,
+}
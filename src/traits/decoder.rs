@@ -10,6 +10,7 @@ use crate::bitio::reader::BitReader;
 use crate::core::borrow::BorrowMut;
 use crate::core::marker::PhantomData;
 use crate::error::CompressionError;
+use crate::status::Status;
 use cfg_if::cfg_if;
 
 pub trait DecodeExt<I>
@@ -96,10 +97,36 @@ where
         &mut self,
         iter: &mut I,
     ) -> Option<Result<Self::Output, Self::Error>>;
+
+    /// Decompresses `input` into `output` until either the input is
+    /// exhausted or the output is full.
+    ///
+    /// Unless `finish` is set, the end of `input` is not the end of the
+    /// compressed data: the decoder keeps the bytes it can not decode yet
+    /// and resumes with them on the next call. With `finish` the end of
+    /// `input` is the end of the data, and the bytes not consumed have to
+    /// be passed to the next call. `done` is set once the end of the
+    /// stream has been reached.
+    fn decompress(
+        &mut self,
+        input: &[Self::Input],
+        output: &mut [Self::Output],
+        finish: bool,
+    ) -> Result<Status, Self::Error>;
 }
 
 cfg_if! {
-    if #[cfg(any(feature = "bzip2", feature="deflate", feature="lzhuf"))] {
+    if #[cfg(any(
+        feature = "brotli",
+        feature = "bzip2",
+        feature = "deflate",
+        feature = "lz4",
+        feature = "lzma",
+        feature = "lzhuf",
+        feature = "lzw",
+        feature = "snappy",
+        feature = "zstd"
+    ))] {
         #[derive(Debug)]
         pub(crate) struct BitDecoder<T, R, B>
         where
@@ -113,7 +140,16 @@ cfg_if! {
             phantom: PhantomData<fn() -> T>,
         }
 
-        #[cfg(any(feature = "bzip2", feature="deflate"))]
+        #[cfg(any(
+            feature = "brotli",
+            feature = "bzip2",
+            feature = "deflate",
+            feature = "lz4",
+            feature = "lzma",
+            feature = "lzw",
+            feature = "snappy",
+            feature = "zstd"
+        ))]
         impl<T> BitDecoder<T, BitReader<T::Direction>, T>
         where
             T: BitDecodeService + Default,
@@ -130,7 +166,16 @@ cfg_if! {
             }
         }
 
-        #[cfg(any(feature="zlib", feature="deflate", feature="lzhuf"))]
+        #[cfg(any(
+            feature = "bzip2",
+            feature = "zlib",
+            feature = "deflate",
+            feature = "lz4",
+            feature = "lzhuf",
+            feature = "lzw",
+            feature = "snappy",
+            feature = "zstd"
+        ))]
         impl<T, R, B> BitDecoder<T, R, B>
         where
             T: BitDecodeService,
@@ -146,6 +191,27 @@ cfg_if! {
                 }
             }
         }
+
+        #[cfg(any(
+            feature = "bzip2",
+            feature = "deflate",
+            feature = "lz4",
+            feature = "lzma",
+            feature = "snappy",
+            feature = "zstd"
+        ))]
+        impl<T, R, B> BitDecoder<T, R, B>
+        where
+            T: BitDecodeService,
+            CompressionError: From<T::Error>,
+            R: BorrowMut<BitReader<T::Direction>>,
+            B: BorrowMut<T>,
+        {
+            pub(crate) fn service(&self) -> &T {
+                self.service.borrow()
+            }
+        }
+
         impl<T> Default for BitDecoder<T, BitReader<T::Direction>, T>
         where
             T: BitDecodeService + Default,
@@ -197,6 +263,56 @@ cfg_if! {
                     .next(self.reader.borrow_mut(), iter)
                     .transpose()
             }
+
+            fn decompress(
+                &mut self,
+                input: &[u8],
+                output: &mut [Self::Output],
+                finish: bool,
+            ) -> Result<Status, Self::Error> {
+                let reader = self.reader.borrow_mut();
+                let service = self.service.borrow_mut();
+                if !finish && reader.defer(input) {
+                    return Ok(Status {
+                        consumed: input.len(),
+                        produced: 0,
+                        done: false,
+                    });
+                }
+                let mut iter = input.iter().cloned();
+                let mut produced = 0;
+                let mut done = false;
+                // 入力が足りなくなった手順は、次の入力で始めからやり直す
+                reader.set_partial(!finish);
+                while produced < output.len() {
+                    reader.commit();
+                    let ret = service.next(reader, &mut iter);
+                    if reader.is_starved() {
+                        reader.rollback();
+                        break;
+                    }
+                    match ret {
+                        Ok(Some(s)) => {
+                            output[produced] = s;
+                            produced += 1;
+                        }
+                        Ok(None) => {
+                            done = true;
+                            break;
+                        }
+                        Err(e) => {
+                            reader.set_partial(false);
+                            return Err(e);
+                        }
+                    }
+                }
+                reader.set_partial(false);
+                Ok(Status {
+                    consumed: input.len() - iter.len(),
+                    produced,
+                    done,
+                })
+            }
         }
 
         pub(crate) type BitDecoderImpl<T> =
@@ -218,3 +334,51 @@ where
         iter: &mut I,
     ) -> Result<Option<Self::Output>, Self::Error>;
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::error::CompressionError;
+    use crate::traits::decoder::Decoder;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    /// Decompresses `input` given to `decoder` in pieces of `chunk` items
+    /// with `decompress`, into an output buffer of `out_len` bytes.
+    pub(crate) fn decompress_slices<D>(
+        decoder: &mut D,
+        input: &[D::Input],
+        chunk: usize,
+        out_len: usize,
+    ) -> Result<Vec<u8>, D::Error>
+    where
+        D: Decoder<Output = u8>,
+        CompressionError: From<D::Error>,
+    {
+        let mut ret = Vec::new();
+        let mut output = vec![0; out_len];
+        for mut piece in input.chunks(chunk) {
+            loop {
+                let status = decoder.decompress(piece, &mut output, false)?;
+                ret.extend_from_slice(&output[..status.produced]);
+                piece = &piece[status.consumed..];
+                if status.done {
+                    return Ok(ret);
+                }
+                // 出力に空きがあれば、入力は全て受け取っている
+                if status.produced < out_len {
+                    assert!(piece.is_empty());
+                    break;
+                }
+            }
+        }
+        loop {
+            let status = decoder.decompress(&[], &mut output, true)?;
+            ret.extend_from_slice(&output[..status.produced]);
+            if status.done {
+                return Ok(ret);
+            }
+        }
+    }
+}
@@ -8,6 +8,17 @@
 
 use crate::action::Action;
 use crate::error::CompressionError;
+use crate::status::Status;
+#[cfg(all(
+    any(feature = "bzip2", feature = "deflate", feature = "lzhuf"),
+    not(feature = "std")
+))]
+use alloc::vec;
+#[cfg(all(
+    any(feature = "bzip2", feature = "deflate", feature = "lzhuf"),
+    not(feature = "std")
+))]
+use alloc::vec::Vec;
 
 pub trait EncodeExt<I>
 where
@@ -90,4 +101,183 @@ where
         iter: &mut I,
         action: Action,
     ) -> Option<Result<Self::Out, Self::Error>>;
+
+    /// Compresses `input` into `output` until either the input is
+    /// exhausted or the output is full.
+    ///
+    /// The encoder keeps the data it can not write out yet, and the bytes
+    /// not consumed have to be passed to the next call. `done` is set once
+    /// all of the input has been consumed and the output of `action` has
+    /// been written completely.
+    fn compress(
+        &mut self,
+        input: &[Self::In],
+        output: &mut [Self::Out],
+        action: Action,
+    ) -> Result<Status, Self::Error>
+    where
+        Self::In: Clone,
+    {
+        let mut iter = input.iter().cloned();
+        let mut produced = 0;
+        let mut done = false;
+        while produced < output.len() {
+            match self.next(&mut iter, action) {
+                Some(Ok(s)) => {
+                    output[produced] = s;
+                    produced += 1;
+                }
+                Some(Err(e)) => return Err(e),
+                None => {
+                    done = true;
+                    break;
+                }
+            }
+        }
+        Ok(Status {
+            consumed: input.len() - iter.len(),
+            produced,
+            done,
+        })
+    }
+}
+
+/// The buffers of `Encoder::next` on top of `Encoder::compress`, which
+/// takes the input from the iterator in chunks.
+#[cfg(any(feature = "bzip2", feature = "deflate", feature = "lzhuf"))]
+#[derive(Debug)]
+pub(crate) struct EncodeBuffer<I, O> {
+    input: Vec<I>,
+    in_pos: usize,
+    output: Vec<O>,
+    out_pos: usize,
+    out_len: usize,
+    done: bool,
+}
+
+#[cfg(any(feature = "bzip2", feature = "deflate", feature = "lzhuf"))]
+impl<I, O> Default for EncodeBuffer<I, O> {
+    fn default() -> Self {
+        Self {
+            input: Vec::new(),
+            in_pos: 0,
+            output: Vec::new(),
+            out_pos: 0,
+            out_len: 0,
+            done: false,
+        }
+    }
+}
+
+#[cfg(any(feature = "bzip2", feature = "deflate", feature = "lzhuf"))]
+impl<I: Clone, O: Clone + Default> EncodeBuffer<I, O> {
+    /// Returns the next output already compressed, if any.
+    pub(crate) fn pop(&mut self) -> Option<O> {
+        if self.out_pos < self.out_len {
+            self.out_pos += 1;
+            Some(self.output[self.out_pos - 1].clone())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the next output of `encoder`, with the same protocol as
+    /// `Encoder::next`: `None` is returned once the input from `iter` is
+    /// exhausted and the output of `action` has been taken.
+    pub(crate) fn next<E, It>(
+        &mut self,
+        encoder: &mut E,
+        iter: &mut It,
+        action: Action,
+    ) -> Option<Result<O, E::Error>>
+    where
+        E: Encoder<In = I, Out = O>,
+        It: Iterator<Item = I>,
+        CompressionError: From<E::Error>,
+    {
+        loop {
+            if let Some(s) = self.pop() {
+                return Some(Ok(s));
+            }
+            if self.done {
+                self.done = false;
+                return None;
+            }
+            if self.in_pos == self.input.len() {
+                self.input.clear();
+                self.in_pos = 0;
+                self.input.extend(iter.by_ref().take(BUF_LEN));
+            }
+            if self.output.is_empty() {
+                self.output = vec![O::default(); BUF_LEN];
+            }
+            // 反復子が尽きるまでは、指示を保留する
+            let rest = &self.input[self.in_pos..];
+            let last = rest.is_empty();
+            let status = match encoder.compress(
+                rest,
+                &mut self.output,
+                if last { action } else { Action::Run },
+            ) {
+                Ok(status) => status,
+                Err(e) => return Some(Err(e)),
+            };
+            self.in_pos += status.consumed;
+            self.out_pos = 0;
+            self.out_len = status.produced;
+            self.done = last && status.done;
+        }
+    }
+}
+
+/// The length of the chunks of `EncodeBuffer`.
+#[cfg(any(feature = "bzip2", feature = "deflate", feature = "lzhuf"))]
+const BUF_LEN: usize = 0x1000;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::action::Action;
+    use crate::error::CompressionError;
+    use crate::traits::encoder::Encoder;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    /// Compresses `input` given to `encoder` in pieces of `chunk` items,
+    /// into an output buffer of `out_len` items.
+    pub(crate) fn compress_slices<E>(
+        encoder: &mut E,
+        input: &[E::In],
+        chunk: usize,
+        out_len: usize,
+    ) -> Result<Vec<E::Out>, E::Error>
+    where
+        E: Encoder,
+        E::In: Clone,
+        E::Out: Clone + Default,
+        CompressionError: From<E::Error>,
+    {
+        let mut ret = Vec::new();
+        let mut output = vec![E::Out::default(); out_len];
+        for mut piece in input.chunks(chunk) {
+            loop {
+                let status =
+                    encoder.compress(piece, &mut output, Action::Run)?;
+                ret.extend_from_slice(&output[..status.produced]);
+                piece = &piece[status.consumed..];
+                if status.done {
+                    assert!(piece.is_empty());
+                    break;
+                }
+            }
+        }
+        loop {
+            let status = encoder.compress(&[], &mut output, Action::Finish)?;
+            ret.extend_from_slice(&output[..status.produced]);
+            if status.done {
+                return Ok(ret);
+            }
+        }
+    }
 }
@@ -0,0 +1,277 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::error::CompressionError;
+use crate::zip::{
+    le16, le32, le64, CENTRAL_HEADER_LEN, CENTRAL_HEADER_SIGNATURE,
+    DESCRIPTOR_SIGNATURE, FLAG_DESCRIPTOR, FLAG_ENCRYPTED,
+    LOCAL_HEADER_SIGNATURE, VERSION_ZIP64, ZIP64_EXTRA_ID,
+};
+
+/// The metadata of an entry of a ZIP archive, as recorded in the central
+/// directory.
+///
+/// The sizes and the offset are the ones of the ZIP64 extra field if
+/// the 32 bit fields overflow.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ZipEntry {
+    pub(crate) version_made_by: u16,
+    pub(crate) version_needed: u16,
+    pub(crate) flags: u16,
+    pub(crate) method: u16,
+    pub(crate) dos_time: u16,
+    pub(crate) dos_date: u16,
+    pub(crate) crc32: u32,
+    pub(crate) compressed_size: u64,
+    pub(crate) size: u64,
+    pub(crate) external_attributes: u32,
+    pub(crate) header_offset: u64,
+    pub(crate) name: Vec<u8>,
+    pub(crate) extra: Vec<u8>,
+    pub(crate) comment: Vec<u8>,
+}
+
+impl ZipEntry {
+    /// Returns the raw name, which is UTF-8 if the flag bit 11 is set
+    /// and usually CP437 otherwise.
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    pub fn comment(&self) -> &[u8] {
+        &self.comment
+    }
+
+    /// Returns the raw extra field of the central directory.
+    pub fn extra(&self) -> &[u8] {
+        &self.extra
+    }
+
+    /// Returns the general purpose bit flags.
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// Returns the compression method number, which is a `ZipMethod::id`
+    /// if supported.
+    pub fn method(&self) -> u16 {
+        self.method
+    }
+
+    /// Returns the modification date in MS-DOS format.
+    pub fn dos_date(&self) -> u16 {
+        self.dos_date
+    }
+
+    /// Returns the modification time in MS-DOS format.
+    pub fn dos_time(&self) -> u16 {
+        self.dos_time
+    }
+
+    pub fn crc32(&self) -> u32 {
+        self.crc32
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+
+    /// Returns the uncompressed size.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the offset of the local file header.
+    pub fn header_offset(&self) -> u64 {
+        self.header_offset
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & FLAG_ENCRYPTED != 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.name.last() == Some(&b'/')
+    }
+
+    fn needs_zip64(&self) -> bool {
+        self.size >= 0xFFFF_FFFF
+            || self.compressed_size >= 0xFFFF_FFFF
+            || self.header_offset >= 0xFFFF_FFFF
+    }
+
+    /// Reads the central directory header at the start of `buf`, and
+    /// returns the entry with the length of the header. `position` is the
+    /// offset of `buf` in bits.
+    pub(crate) fn read_central(
+        buf: &[u8],
+        position: u64,
+    ) -> Result<(Self, usize), CompressionError> {
+        if buf.len() < CENTRAL_HEADER_LEN {
+            return Err(CompressionError::UnexpectedEof);
+        }
+        if le32(buf, 0) != CENTRAL_HEADER_SIGNATURE {
+            return Err(CompressionError::BadMagic { position });
+        }
+        let name_len = usize::from(le16(buf, 28));
+        let extra_len = usize::from(le16(buf, 30));
+        let comment_len = usize::from(le16(buf, 32));
+        let len = CENTRAL_HEADER_LEN + name_len + extra_len + comment_len;
+        if buf.len() < len {
+            return Err(CompressionError::UnexpectedEof);
+        }
+        let extra_pos = CENTRAL_HEADER_LEN + name_len;
+        let comment_pos = extra_pos + extra_len;
+        let mut entry = Self {
+            version_made_by: le16(buf, 4),
+            version_needed: le16(buf, 6),
+            flags: le16(buf, 8),
+            method: le16(buf, 10),
+            dos_time: le16(buf, 12),
+            dos_date: le16(buf, 14),
+            crc32: le32(buf, 16),
+            compressed_size: u64::from(le32(buf, 20)),
+            size: u64::from(le32(buf, 24)),
+            external_attributes: le32(buf, 38),
+            header_offset: u64::from(le32(buf, 42)),
+            name: buf[CENTRAL_HEADER_LEN..extra_pos].to_vec(),
+            extra: buf[extra_pos..comment_pos].to_vec(),
+            comment: buf[comment_pos..len].to_vec(),
+        };
+        entry
+            .read_zip64_extra()
+            .ok_or(CompressionError::InvalidHeader {
+                position: position + ((extra_pos as u64) << 3),
+            })?;
+        Ok((entry, len))
+    }
+
+    /// Replaces the overflowed fields with the ones of the ZIP64 extra
+    /// field, which holds only those in the order of the size, the
+    /// compressed size and the offset.
+    fn read_zip64_extra(&mut self) -> Option<()> {
+        let mut fields = [
+            &mut self.size,
+            &mut self.compressed_size,
+            &mut self.header_offset,
+        ];
+        if fields.iter().all(|f| **f != 0xFFFF_FFFF) {
+            return Some(());
+        }
+        let mut extra = &self.extra[..];
+        while extra.len() >= 4 {
+            let id = le16(extra, 0);
+            let len = usize::from(le16(extra, 2));
+            let data = extra.get(4..4 + len)?;
+            if id == ZIP64_EXTRA_ID {
+                let mut pos = 0;
+                for f in fields.iter_mut().filter(|f| ***f == 0xFFFF_FFFF) {
+                    **f = le64(data.get(pos..pos + 8)?, 0);
+                    pos += 8;
+                }
+                return Some(());
+            }
+            extra = &extra[4 + len..];
+        }
+        None
+    }
+
+    /// Writes the local file header. With the data descriptor, the CRC
+    /// and the sizes are left zero, and the ZIP64 extra field is reserved
+    /// since whether the sizes overflow is not known yet.
+    pub(crate) fn write_local(&self, out: &mut Vec<u8>) {
+        let descriptor = self.flags & FLAG_DESCRIPTOR != 0;
+        let (crc32, compressed_size, size) = if descriptor {
+            (0, 0, 0)
+        } else {
+            (self.crc32, self.compressed_size, self.size)
+        };
+        let zip64 =
+            descriptor || size >= 0xFFFF_FFFF || compressed_size >= 0xFFFF_FFFF;
+        let mut extra = Vec::new();
+        let mut version_needed = self.version_needed;
+        if zip64 {
+            // ローカルヘッダーでは、両方の長さを必ず書く
+            extra.extend_from_slice(&ZIP64_EXTRA_ID.to_le_bytes());
+            extra.extend_from_slice(&16u16.to_le_bytes());
+            extra.extend_from_slice(&size.to_le_bytes());
+            extra.extend_from_slice(&compressed_size.to_le_bytes());
+            version_needed = version_needed.max(VERSION_ZIP64);
+        }
+        let clamp = |v: u64| if zip64 { 0xFFFF_FFFF } else { v as u32 };
+
+        out.extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&version_needed.to_le_bytes());
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&self.method.to_le_bytes());
+        out.extend_from_slice(&self.dos_time.to_le_bytes());
+        out.extend_from_slice(&self.dos_date.to_le_bytes());
+        out.extend_from_slice(&crc32.to_le_bytes());
+        out.extend_from_slice(&clamp(compressed_size).to_le_bytes());
+        out.extend_from_slice(&clamp(size).to_le_bytes());
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        out.extend_from_slice(&self.name);
+        out.extend_from_slice(&extra);
+    }
+
+    /// Writes the data descriptor, whose sizes are always 64 bit as the
+    /// local file header has the ZIP64 extra field.
+    pub(crate) fn write_descriptor(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&DESCRIPTOR_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&self.crc32.to_le_bytes());
+        out.extend_from_slice(&self.compressed_size.to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+    }
+
+    /// Writes the central directory header, with the ZIP64 extra field
+    /// instead of `extra` if the sizes or the offset overflow.
+    pub(crate) fn write_central(&self, out: &mut Vec<u8>) {
+        let zip64 = self.needs_zip64();
+        let clamp = |v: u64| v.min(0xFFFF_FFFF) as u32;
+        let mut extra = Vec::new();
+        if zip64 {
+            let mut fields = Vec::new();
+            for &v in &[self.size, self.compressed_size, self.header_offset] {
+                if v >= 0xFFFF_FFFF {
+                    fields.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            extra.extend_from_slice(&ZIP64_EXTRA_ID.to_le_bytes());
+            extra.extend_from_slice(&(fields.len() as u16).to_le_bytes());
+            extra.extend_from_slice(&fields);
+        } else {
+            extra.extend_from_slice(&self.extra);
+        }
+        let version_needed = if zip64 {
+            self.version_needed.max(VERSION_ZIP64)
+        } else {
+            self.version_needed
+        };
+
+        out.extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&self.version_made_by.to_le_bytes());
+        out.extend_from_slice(&version_needed.to_le_bytes());
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&self.method.to_le_bytes());
+        out.extend_from_slice(&self.dos_time.to_le_bytes());
+        out.extend_from_slice(&self.dos_date.to_le_bytes());
+        out.extend_from_slice(&self.crc32.to_le_bytes());
+        out.extend_from_slice(&clamp(self.compressed_size).to_le_bytes());
+        out.extend_from_slice(&clamp(self.size).to_le_bytes());
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(self.comment.len() as u16).to_le_bytes());
+        // ディスク番号と内部属性
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&self.external_attributes.to_le_bytes());
+        out.extend_from_slice(&clamp(self.header_offset).to_le_bytes());
+        out.extend_from_slice(&self.name);
+        out.extend_from_slice(&extra);
+        out.extend_from_slice(&self.comment);
+    }
+}
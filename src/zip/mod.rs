@@ -0,0 +1,389 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(all(feature = "zip", feature = "std"))]
+
+pub(crate) mod entry;
+pub(crate) mod reader;
+pub(crate) mod writer;
+
+use crate::core::convert::TryInto;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4B50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4B50;
+const DESCRIPTOR_SIGNATURE: u32 = 0x0807_4B50;
+const END_SIGNATURE: u32 = 0x0605_4B50;
+const ZIP64_END_SIGNATURE: u32 = 0x0606_4B50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4B50;
+
+const LOCAL_HEADER_LEN: usize = 30;
+const CENTRAL_HEADER_LEN: usize = 46;
+const END_LEN: usize = 22;
+const ZIP64_END_LEN: usize = 56;
+const ZIP64_LOCATOR_LEN: usize = 20;
+const MAX_COMMENT_LEN: usize = 0xFFFF;
+
+/// The identifier of the extra field of the 64 bit sizes and offset.
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+const FLAG_ENCRYPTED: u16 = 0x0001;
+/// The flag that the sizes and the CRC follow the data.
+const FLAG_DESCRIPTOR: u16 = 0x0008;
+/// The flag that the name and the comment are UTF-8.
+const FLAG_UTF8: u16 = 0x0800;
+
+const VERSION_DEFAULT: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+const VERSION_BZIP2: u16 = 46;
+
+/// 1980-01-01 00:00:00 in MS-DOS format, the earliest representable.
+const DOS_DATE: u16 = (1 << 5) | 1;
+const DOS_TIME: u16 = 0;
+
+/// The compression method of an entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZipMethod {
+    Stored,
+    Deflated,
+    BZip2,
+}
+
+impl ZipMethod {
+    /// Returns the method number in the headers.
+    pub fn id(self) -> u16 {
+        match self {
+            ZipMethod::Stored => 0,
+            ZipMethod::Deflated => 8,
+            ZipMethod::BZip2 => 12,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        match id {
+            0 => Some(ZipMethod::Stored),
+            8 => Some(ZipMethod::Deflated),
+            12 => Some(ZipMethod::BZip2),
+            _ => None,
+        }
+    }
+
+    /// Returns the version needed to extract.
+    fn version(self) -> u16 {
+        match self {
+            ZipMethod::Stored | ZipMethod::Deflated => VERSION_DEFAULT,
+            ZipMethod::BZip2 => VERSION_BZIP2,
+        }
+    }
+}
+
+fn le16(buf: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes(buf[pos..pos + 2].try_into().unwrap())
+}
+
+fn le32(buf: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap())
+}
+
+fn le64(buf: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(buf[pos..pos + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CompressionError;
+    use crate::zip::entry::ZipEntry;
+    use crate::zip::reader::ZipArchive;
+    use crate::zip::writer::ZipWriter;
+    use rand::distributions::Standard;
+    use rand::{thread_rng, Rng};
+    use std::io::{self, Cursor, Read, Write};
+
+    fn read_entry(
+        archive: &mut ZipArchive<Cursor<Vec<u8>>>,
+        index: usize,
+    ) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let _ = archive.entry_reader(index)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn error(err: io::Error) -> CompressionError {
+        *err.into_inner()
+            .unwrap()
+            .downcast::<CompressionError>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_samples() {
+        let sample1 = include_bytes!("../../data/sample1.ref");
+        let sample2 = include_bytes!("../../data/sample2.ref");
+        let sample3 = include_bytes!("../../data/sample3.ref");
+
+        // データ記述子つきで書かれたもの
+        let zip = include_bytes!("../../data/sample1.zip").to_vec();
+        let mut archive = ZipArchive::new(Cursor::new(zip)).unwrap();
+        assert_eq!(archive.comment(), b"sample archive");
+        let names = archive
+            .entries()
+            .iter()
+            .map(|e| (e.name().to_vec(), e.method(), e.is_dir()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                (b"dir/".to_vec(), 0, true),
+                (b"sample1.ref".to_vec(), 8, false),
+                (b"dir/sample2.ref".to_vec(), 12, false),
+                (b"sample3.ref".to_vec(), 0, false),
+            ]
+        );
+        assert!(archive.entries()[1].flags() & FLAG_DESCRIPTOR != 0);
+        assert_eq!(read_entry(&mut archive, 0).unwrap(), b"");
+        for &(name, data) in &[
+            (&b"sample1.ref"[..], &sample1[..]),
+            (&b"dir/sample2.ref"[..], &sample2[..]),
+            (&b"sample3.ref"[..], &sample3[..]),
+        ] {
+            let index = archive.find(name).unwrap();
+            assert!(read_entry(&mut archive, index).unwrap() == data);
+        }
+
+        // ZIP64の拡張フィールドと終端レコード
+        let zip = include_bytes!("../../data/sample2.zip").to_vec();
+        let mut archive = ZipArchive::new(Cursor::new(zip)).unwrap();
+        assert_eq!(archive.entries().len(), 2);
+        assert_eq!(archive.entries()[0].size(), sample3.len() as u64);
+        assert!(read_entry(&mut archive, 0).unwrap() == sample3[..]);
+        assert_eq!(read_entry(&mut archive, 1).unwrap(), b"zip64\n");
+    }
+
+    #[test]
+    fn test_write() {
+        let random = thread_rng()
+            .sample_iter(&Standard)
+            .take(100_000)
+            .collect::<Vec<u8>>();
+        let files: Vec<(&[u8], ZipMethod, &[u8])> = vec![
+            (b"dir/", ZipMethod::Stored, b""),
+            (b"dir/empty", ZipMethod::Deflated, b""),
+            (
+                b"dir/sample1.ref",
+                ZipMethod::Deflated,
+                include_bytes!("../../data/sample1.ref"),
+            ),
+            (
+                b"sample2.ref",
+                ZipMethod::BZip2,
+                include_bytes!("../../data/sample2.ref"),
+            ),
+            ("r\u{e9}sum\u{e9}".as_bytes(), ZipMethod::Stored, &random),
+        ];
+
+        // 2025-01-01 12:00:00
+        let (dos_date, dos_time) = ((45 << 9) | (1 << 5) | 1, 12 << 11);
+        let mut writer = ZipWriter::new(Vec::new());
+        writer.set_comment(b"comment").unwrap();
+        writer.set_dos_date_time(dos_date, dos_time);
+        for &(name, method, data) in &files {
+            writer.start_entry(name, method).unwrap();
+            for chunk in data.chunks(10_000) {
+                writer.write_all(chunk).unwrap();
+            }
+        }
+        let zip = writer.finish().unwrap();
+
+        let mut archive = ZipArchive::new(Cursor::new(zip)).unwrap();
+        assert_eq!(archive.comment(), b"comment");
+        assert_eq!(archive.entries().len(), files.len());
+        for (i, &(name, method, data)) in files.iter().enumerate() {
+            let entry = &archive.entries()[i];
+            assert_eq!(entry.name(), name);
+            assert_eq!(entry.method(), method.id());
+            assert_eq!(entry.size(), data.len() as u64);
+            assert_eq!(entry.flags() & FLAG_UTF8 != 0, !name.is_ascii());
+            assert_eq!(
+                entry.flags() & FLAG_DESCRIPTOR != 0,
+                method != ZipMethod::Stored
+            );
+            assert_eq!(entry.dos_date(), dos_date);
+            assert_eq!(entry.dos_time(), dos_time);
+            assert!(read_entry(&mut archive, i).unwrap() == data);
+        }
+        assert!(archive.entries()[0].is_dir());
+    }
+
+    #[test]
+    fn test_zip64() {
+        let entry = ZipEntry {
+            version_needed: VERSION_DEFAULT,
+            size: 0x1_2345_6789,
+            compressed_size: 0x1234,
+            header_offset: 0x1_0000_0000,
+            name: b"large".to_vec(),
+            ..ZipEntry::default()
+        };
+        let mut cd = Vec::new();
+        entry.write_central(&mut cd);
+        let (read, len) = ZipEntry::read_central(&cd, 0).unwrap();
+        assert_eq!(len, cd.len());
+        assert_eq!(read.version_needed, VERSION_ZIP64);
+        assert_eq!(read.size(), entry.size());
+        assert_eq!(read.compressed_size(), entry.compressed_size());
+        assert_eq!(read.header_offset(), entry.header_offset());
+
+        // データ記述子で長さを書くエントリー
+        let entry = ZipEntry {
+            flags: FLAG_DESCRIPTOR,
+            crc32: 0x1234_5678,
+            ..entry
+        };
+        let mut local = Vec::new();
+        entry.write_local(&mut local);
+        let extra_pos = LOCAL_HEADER_LEN + entry.name().len();
+        assert_eq!(local.len(), extra_pos + 20);
+        assert_eq!(le16(&local, 4), VERSION_ZIP64);
+        assert_eq!(le32(&local, 14), 0);
+        assert_eq!(le32(&local, 18), 0xFFFF_FFFF);
+        assert_eq!(le16(&local, 28), 20);
+        assert_eq!(le16(&local, extra_pos), ZIP64_EXTRA_ID);
+        assert_eq!(le64(&local, extra_pos + 4), 0);
+        let mut descriptor = Vec::new();
+        entry.write_descriptor(&mut descriptor);
+        assert_eq!(descriptor.len(), 24);
+        assert_eq!(le32(&descriptor, 4), entry.crc32());
+        assert_eq!(le64(&descriptor, 8), entry.compressed_size());
+        assert_eq!(le64(&descriptor, 16), entry.size());
+        // 格納のエントリーは、ローカルヘッダーに長さを書く
+        let entry = ZipEntry { flags: 0, ..entry };
+        let mut local = Vec::new();
+        entry.write_local(&mut local);
+        assert_eq!(le32(&local, 14), entry.crc32());
+        assert_eq!(le64(&local, extra_pos + 4), entry.size());
+        assert_eq!(le64(&local, extra_pos + 12), entry.compressed_size());
+
+        // 圧縮したエントリーの、拡張フィールドとデータ記述子
+        let mut writer = ZipWriter::new(Vec::new());
+        writer.start_entry(b"data", ZipMethod::Deflated).unwrap();
+        writer.write_all(b"data").unwrap();
+        let zip = writer.finish().unwrap();
+        let archive = ZipArchive::new(Cursor::new(zip.clone())).unwrap();
+        let entry = &archive.entries()[0];
+        assert_eq!(le16(&zip, 28), 20);
+        let pos = LOCAL_HEADER_LEN + 4 + 20 + entry.compressed_size() as usize;
+        assert_eq!(le32(&zip, pos), DESCRIPTOR_SIGNATURE);
+        assert_eq!(le64(&zip, pos + 16), 4);
+
+        // 16ビットに収まらないエントリー数
+        let mut writer = ZipWriter::new(Vec::new());
+        for i in 0..0x1_0000u32 {
+            writer
+                .start_entry(&i.to_le_bytes(), ZipMethod::Stored)
+                .unwrap();
+        }
+        let zip = writer.finish().unwrap();
+        assert_eq!(le16(&zip, zip.len() - 12), 0xFFFF);
+        let archive = ZipArchive::new(Cursor::new(zip)).unwrap();
+        assert_eq!(archive.entries().len(), 0x1_0000);
+        assert_eq!(
+            archive.entries()[0x1_2345 & 0xFFFF].name(),
+            b"\x45\x23\0\0"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            error(
+                ZipArchive::new(Cursor::new(b"not zip".to_vec())).unwrap_err()
+            ),
+            CompressionError::BadMagic { position: 0 }
+        );
+        let mut writer = ZipWriter::new(Vec::new());
+        assert_eq!(
+            writer.write(b"data").unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            writer
+                .start_entry(&[b'a'; 0x1_0000], ZipMethod::Stored)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            writer.set_comment(&[b'a'; 0x1_0000]).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        writer.start_entry(b"data", ZipMethod::Stored).unwrap();
+        writer.write_all(b"stored data").unwrap();
+        let zip = writer.finish().unwrap();
+        // ローカルヘッダーと名前の後ろがデータ
+        let data_pos = LOCAL_HEADER_LEN + 4;
+        let cd_pos = data_pos + 11;
+
+        let mut checksum = zip.clone();
+        checksum[data_pos] ^= 1;
+        let mut archive = ZipArchive::new(Cursor::new(checksum)).unwrap();
+        assert!(matches!(
+            error(read_entry(&mut archive, 0).unwrap_err()),
+            CompressionError::ChecksumMismatch { position, .. }
+                if position == ((data_pos + 11) << 3) as u64
+        ));
+
+        let mut method = zip.clone();
+        method[cd_pos + 10] = 14;
+        let mut archive = ZipArchive::new(Cursor::new(method)).unwrap();
+        assert_eq!(
+            error(read_entry(&mut archive, 0).unwrap_err()),
+            CompressionError::UnsupportedMethod {
+                method: 14,
+                position: 8 << 3,
+            }
+        );
+
+        let mut length = zip.clone();
+        length[cd_pos + 24] = 10;
+        let mut archive = ZipArchive::new(Cursor::new(length)).unwrap();
+        assert!(matches!(
+            error(read_entry(&mut archive, 0).unwrap_err()),
+            CompressionError::LengthMismatch {
+                expected: 10,
+                actual: 11,
+                ..
+            }
+        ));
+
+        let mut header = zip;
+        header[cd_pos] = b'X';
+        assert_eq!(
+            error(ZipArchive::new(Cursor::new(header)).unwrap_err()),
+            CompressionError::BadMagic {
+                position: (cd_pos << 3) as u64,
+            }
+        );
+
+        // 完了に失敗しても、drop で中央ディレクトリを再び書かない
+        struct NoFlush<'a>(&'a mut Vec<u8>);
+        impl Write for NoFlush<'_> {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Err(io::ErrorKind::Other.into())
+            }
+        }
+        let mut zip = Vec::new();
+        let mut writer = ZipWriter::new(NoFlush(&mut zip));
+        writer.start_entry(b"data", ZipMethod::Deflated).unwrap();
+        writer.write_all(b"data").unwrap();
+        assert!(writer.finish().is_err());
+        let ends = zip.windows(4).filter(|w| le32(w, 0) == END_SIGNATURE);
+        assert_eq!(ends.count(), 1);
+    }
+}
@@ -0,0 +1,288 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bzip2::decoder::BZip2Decoder;
+use crate::checksum::Crc32;
+use crate::deflate::decoder::Deflater;
+use crate::error::CompressionError;
+use crate::io::decoder::DecoderReader;
+use crate::io::to_io_error;
+use crate::zip::entry::ZipEntry;
+use crate::zip::{
+    le16, le32, le64, ZipMethod, END_LEN, END_SIGNATURE, LOCAL_HEADER_LEN,
+    LOCAL_HEADER_SIGNATURE, MAX_COMMENT_LEN, ZIP64_END_LEN,
+    ZIP64_END_SIGNATURE, ZIP64_LOCATOR_LEN, ZIP64_LOCATOR_SIGNATURE,
+};
+use std::io::{self, Read, Seek, SeekFrom, Take};
+
+fn read_at<R: Read + Seek>(
+    inner: &mut R,
+    offset: u64,
+    buf: &mut [u8],
+) -> io::Result<()> {
+    let _ = inner.seek(SeekFrom::Start(offset))?;
+    inner.read_exact(buf)
+}
+
+/// A reader of a ZIP archive, which lists the entries from the central
+/// directory and extracts them one at a time.
+///
+/// The entries written with a data descriptor are read with the sizes
+/// and the CRC in the central directory.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+/// use std::io::{Cursor, Read, Write};
+///
+/// fn main() {
+///     # #[cfg(feature = "zip")]
+///     # {
+///     let mut writer = ZipWriter::new(Vec::new());
+///     writer.start_entry(b"data.txt", ZipMethod::Deflated).unwrap();
+///     writer.write_all(b"aabbaabbaabbaabb\n").unwrap();
+///     let archive = writer.finish().unwrap();
+///
+///     let mut archive = ZipArchive::new(Cursor::new(archive)).unwrap();
+///     let index = archive.find(b"data.txt").unwrap();
+///     let mut data = Vec::new();
+///     archive
+///         .entry_reader(index)
+///         .unwrap()
+///         .read_to_end(&mut data)
+///         .unwrap();
+///     assert_eq!(&data[..], &b"aabbaabbaabbaabb\n"[..]);
+///     # }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ZipArchive<R: Read + Seek> {
+    inner: R,
+    entries: Vec<ZipEntry>,
+    comment: Vec<u8>,
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    /// Reads the central directory of the archive in `inner`.
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let len = inner.seek(SeekFrom::End(0))?;
+        let tail_len = len.min((END_LEN + MAX_COMMENT_LEN) as u64);
+        let tail_pos = len - tail_len;
+        let mut tail = vec![0; tail_len as usize];
+        read_at(&mut inner, tail_pos, &mut tail)?;
+
+        // コメントの中に紛れないよう、後ろから探す
+        let end = (0..(tail.len() + 1).saturating_sub(END_LEN))
+            .rev()
+            .find(|&i| {
+                le32(&tail, i) == END_SIGNATURE
+                    && i + END_LEN + usize::from(le16(&tail, i + 20))
+                        <= tail.len()
+            })
+            .ok_or_else(|| {
+                to_io_error(CompressionError::BadMagic { position: 0 })
+            })?;
+        let end_pos = tail_pos + end as u64;
+        let comment_len = usize::from(le16(&tail, end + 20));
+        let comment = tail[end + END_LEN..end + END_LEN + comment_len].to_vec();
+        let mut count = u64::from(le16(&tail, end + 10));
+        let mut cd_size = u64::from(le32(&tail, end + 12));
+        let mut cd_offset = u64::from(le32(&tail, end + 16));
+
+        if end_pos >= ZIP64_LOCATOR_LEN as u64 {
+            let mut locator = [0; ZIP64_LOCATOR_LEN];
+            let locator_pos = end_pos - ZIP64_LOCATOR_LEN as u64;
+            read_at(&mut inner, locator_pos, &mut locator)?;
+            if le32(&locator, 0) == ZIP64_LOCATOR_SIGNATURE {
+                let pos = le64(&locator, 8);
+                let mut record = [0; ZIP64_END_LEN];
+                read_at(&mut inner, pos, &mut record)?;
+                if le32(&record, 0) != ZIP64_END_SIGNATURE {
+                    return Err(to_io_error(CompressionError::BadMagic {
+                        position: pos << 3,
+                    }));
+                }
+                count = le64(&record, 32);
+                cd_size = le64(&record, 40);
+                cd_offset = le64(&record, 48);
+            }
+        }
+        if !matches!(cd_offset.checked_add(cd_size), Some(e) if e <= end_pos) {
+            return Err(to_io_error(CompressionError::InvalidHeader {
+                position: (end_pos + 12) << 3,
+            }));
+        }
+
+        let mut cd = vec![0; cd_size as usize];
+        read_at(&mut inner, cd_offset, &mut cd)?;
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        for _ in 0..count {
+            let (entry, len) = ZipEntry::read_central(
+                &cd[pos..],
+                (cd_offset + pos as u64) << 3,
+            )
+            .map_err(to_io_error)?;
+            entries.push(entry);
+            pos += len;
+        }
+        Ok(Self {
+            inner,
+            entries,
+            comment,
+        })
+    }
+
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// Returns the comment of the archive.
+    pub fn comment(&self) -> &[u8] {
+        &self.comment
+    }
+
+    /// Returns the index of the first entry named `name`.
+    pub fn find(&self, name: &[u8]) -> Option<usize> {
+        self.entries.iter().position(|e| e.name() == name)
+    }
+
+    /// Returns a reader of the data of the entry at `index`, which checks
+    /// the size and the CRC when it reaches the end.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of range.
+    pub fn entry_reader(
+        &mut self,
+        index: usize,
+    ) -> io::Result<ZipEntryReader<'_, R>> {
+        let entry = &self.entries[index];
+        if entry.is_encrypted() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "encrypted entries are not supported",
+            ));
+        }
+        let offset = entry.header_offset();
+        let mut header = [0; LOCAL_HEADER_LEN];
+        read_at(&mut self.inner, offset, &mut header)?;
+        if le32(&header, 0) != LOCAL_HEADER_SIGNATURE {
+            return Err(to_io_error(CompressionError::BadMagic {
+                position: offset << 3,
+            }));
+        }
+        let method = ZipMethod::from_id(entry.method()).ok_or_else(|| {
+            to_io_error(CompressionError::UnsupportedMethod {
+                method: entry.method().min(0xFF) as u8,
+                position: (offset + 8) << 3,
+            })
+        })?;
+        let data_offset = offset
+            + LOCAL_HEADER_LEN as u64
+            + u64::from(le16(&header, 26))
+            + u64::from(le16(&header, 28));
+        let _ = self.inner.seek(SeekFrom::Start(data_offset))?;
+
+        let data = (&mut self.inner).take(entry.compressed_size());
+        let inner = match method {
+            ZipMethod::Stored => EntryData::Stored(data),
+            ZipMethod::Deflated => {
+                EntryData::Deflated(DecoderReader::new(data, Deflater::new()))
+            }
+            ZipMethod::BZip2 => {
+                EntryData::BZip2(DecoderReader::new(data, BZip2Decoder::new()))
+            }
+        };
+        Ok(ZipEntryReader {
+            inner,
+            crc32: Crc32::new(),
+            size: 0,
+            expected_crc32: entry.crc32(),
+            expected_size: entry.size(),
+            position: (data_offset + entry.compressed_size()) << 3,
+            checked: false,
+        })
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+#[derive(Debug)]
+enum EntryData<'a, R: Read> {
+    Stored(Take<&'a mut R>),
+    Deflated(DecoderReader<Take<&'a mut R>, Deflater>),
+    BZip2(DecoderReader<Take<&'a mut R>, BZip2Decoder>),
+}
+
+/// A `Read` adapter which yields the decompressed data of an entry of a
+/// `ZipArchive`.
+///
+/// A mismatch of the size or the CRC is returned as an error of
+/// `io::ErrorKind::InvalidData` at the end of the data.
+#[derive(Debug)]
+pub struct ZipEntryReader<'a, R: Read> {
+    inner: EntryData<'a, R>,
+    crc32: Crc32,
+    size: u64,
+    expected_crc32: u32,
+    expected_size: u64,
+    /// The end of the compressed data in bits.
+    position: u64,
+    checked: bool,
+}
+
+impl<'a, R: Read> ZipEntryReader<'a, R> {
+    fn check(&self) -> Result<(), CompressionError> {
+        if self.size != self.expected_size {
+            return Err(CompressionError::LengthMismatch {
+                expected: self.expected_size,
+                actual: self.size,
+                position: self.position,
+            });
+        }
+        if self.crc32.value() != self.expected_crc32 {
+            return Err(CompressionError::ChecksumMismatch {
+                expected: self.expected_crc32,
+                actual: self.crc32.value(),
+                position: self.position,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<'a, R: Read> Read for ZipEntryReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = match self.inner {
+            EntryData::Stored(ref mut r) => r.read(buf)?,
+            EntryData::Deflated(ref mut r) => r.read(buf)?,
+            EntryData::BZip2(ref mut r) => r.read(buf)?,
+        };
+        self.crc32.update(&buf[..n]);
+        self.size += n as u64;
+        // 記録より長い出力は、終わりまで読まずに止める
+        if self.size > self.expected_size
+            || (n == 0 && !buf.is_empty() && !self.checked)
+        {
+            self.checked = true;
+            self.check().map_err(to_io_error)?;
+        }
+        Ok(n)
+    }
+}
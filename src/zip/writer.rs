@@ -0,0 +1,364 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::action::Action;
+use crate::bzip2::encoder::BZip2Encoder;
+use crate::checksum::Crc32;
+use crate::deflate::encoder::Inflater;
+use crate::error::CompressionError;
+use crate::io::to_io_error;
+use crate::traits::encoder::Encoder;
+use crate::zip::entry::ZipEntry;
+use crate::zip::{
+    ZipMethod, DOS_DATE, DOS_TIME, END_SIGNATURE, FLAG_DESCRIPTOR, FLAG_UTF8,
+    MAX_COMMENT_LEN, VERSION_ZIP64, ZIP64_END_LEN, ZIP64_END_SIGNATURE,
+    ZIP64_LOCATOR_SIGNATURE,
+};
+use std::io::{self, Write};
+
+const BUF_SIZE: usize = 0x8000;
+const BZIP2_LEVEL: usize = 9;
+/// The MS-DOS directory attribute.
+const DIRECTORY_ATTRIBUTE: u32 = 0x10;
+
+/// Compresses `input` with `encoder` into `inner`, and returns the length
+/// of the output.
+fn encode<E, W>(
+    encoder: &mut E,
+    mut input: &[u8],
+    action: Action,
+    buf: &mut [u8],
+    inner: &mut W,
+) -> io::Result<u64>
+where
+    E: Encoder<In = u8, Out = u8>,
+    CompressionError: From<E::Error>,
+    W: Write,
+{
+    let mut len = 0;
+    loop {
+        let status =
+            encoder.compress(input, buf, action).map_err(to_io_error)?;
+        inner.write_all(&buf[..status.produced])?;
+        len += status.produced as u64;
+        input = &input[status.consumed..];
+        if status.done {
+            return Ok(len);
+        }
+    }
+}
+
+#[derive(Debug)]
+enum EntryEncoder {
+    /// The data held until the end of the entry.
+    Stored(Vec<u8>),
+    Deflated(Box<Inflater>),
+    BZip2(Box<BZip2Encoder>),
+}
+
+/// The entry being written.
+#[derive(Debug)]
+struct CurrentEntry {
+    encoder: EntryEncoder,
+    crc32: Crc32,
+    size: u64,
+    compressed_size: u64,
+}
+
+/// A `Write` adapter which writes a ZIP archive to the underlying writer.
+///
+/// The written data goes to the entry started last. The sizes and the CRC
+/// of a compressed entry are written in the data descriptor following the
+/// data, so the underlying writer need not be seekable. A stored entry is
+/// held in memory and written with them in its local header instead, as
+/// the end of its data could not be found without the sizes. The ZIP64
+/// extensions are used where the sizes, the offsets or the number of the
+/// entries do not fit in the 32 bit fields, and the ZIP64 extra field is
+/// always reserved in the local header of a compressed entry. The
+/// modification time of the entries is 1980-01-01 00:00:00 unless set by
+/// `set_dos_date_time`.
+///
+/// The archive is completed by `finish`, or on drop if `finish` was not
+/// called.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+/// use std::io::Write;
+///
+/// fn main() {
+///     # #[cfg(feature = "zip")]
+///     # {
+///     let mut writer = ZipWriter::new(Vec::new());
+///     writer.start_entry(b"docs/", ZipMethod::Stored).unwrap();
+///     writer.start_entry(b"docs/a.txt", ZipMethod::Deflated).unwrap();
+///     writer.write_all(b"aabbaabbaabbaabb\n").unwrap();
+///     writer.start_entry(b"docs/b.txt", ZipMethod::BZip2).unwrap();
+///     writer.write_all(b"ccddccddccddccdd\n").unwrap();
+///     let archive = writer.finish().unwrap();
+///     # }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ZipWriter<W: Write> {
+    inner: Option<W>,
+    /// The length of the archive written so far.
+    offset: u64,
+    entries: Vec<ZipEntry>,
+    current: Option<CurrentEntry>,
+    comment: Vec<u8>,
+    dos_date: u16,
+    dos_time: u16,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: Some(inner),
+            offset: 0,
+            entries: Vec::new(),
+            current: None,
+            comment: Vec::new(),
+            dos_date: DOS_DATE,
+            dos_time: DOS_TIME,
+            buf: vec![0; BUF_SIZE],
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Sets the comment of the archive, which must not exceed 65535
+    /// bytes.
+    pub fn set_comment(&mut self, comment: &[u8]) -> io::Result<()> {
+        if comment.len() > MAX_COMMENT_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "comment is too long",
+            ));
+        }
+        self.comment = comment.to_vec();
+        Ok(())
+    }
+
+    /// Sets the modification date and time in MS-DOS format of the
+    /// entries started after.
+    pub fn set_dos_date_time(&mut self, dos_date: u16, dos_time: u16) {
+        self.dos_date = dos_date;
+        self.dos_time = dos_time;
+    }
+
+    /// Completes the current entry and starts a new one named `name`,
+    /// which is a directory if it ends with `/`. A name of UTF-8 other
+    /// than ASCII is marked as such. `name` must not exceed 65535 bytes.
+    pub fn start_entry(
+        &mut self,
+        name: &[u8],
+        method: ZipMethod,
+    ) -> io::Result<()> {
+        if name.len() > 0xFFFF {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "name is too long",
+            ));
+        }
+        self.finish_entry()?;
+
+        let mut flags = 0;
+        if !name.is_ascii() && std::str::from_utf8(name).is_ok() {
+            flags |= FLAG_UTF8;
+        }
+        let mut version_needed = method.version();
+        if method != ZipMethod::Stored {
+            flags |= FLAG_DESCRIPTOR;
+            version_needed = version_needed.max(VERSION_ZIP64);
+        }
+        let entry = ZipEntry {
+            version_made_by: version_needed,
+            version_needed,
+            flags,
+            method: method.id(),
+            dos_time: self.dos_time,
+            dos_date: self.dos_date,
+            external_attributes: if name.last() == Some(&b'/') {
+                DIRECTORY_ATTRIBUTE
+            } else {
+                0
+            },
+            header_offset: self.offset,
+            name: name.to_vec(),
+            ..ZipEntry::default()
+        };
+
+        // 格納のエントリーは、長さが分かってからヘッダーを書く
+        if method != ZipMethod::Stored {
+            let mut header = Vec::new();
+            entry.write_local(&mut header);
+            self.write_raw(&header)?;
+        }
+
+        self.entries.push(entry);
+        self.current = Some(CurrentEntry {
+            encoder: match method {
+                ZipMethod::Stored => EntryEncoder::Stored(Vec::new()),
+                ZipMethod::Deflated => EntryEncoder::Deflated(Box::default()),
+                ZipMethod::BZip2 => EntryEncoder::BZip2(Box::new(
+                    BZip2Encoder::new(BZIP2_LEVEL),
+                )),
+            },
+            crc32: Crc32::new(),
+            size: 0,
+            compressed_size: 0,
+        });
+        Ok(())
+    }
+
+    /// Completes the archive and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let ret = self.finish_archive();
+        // 失敗しても、drop で再び完了させない
+        let inner = self.inner.take().unwrap();
+        ret.map(|()| inner)
+    }
+
+    fn write_raw(&mut self, data: &[u8]) -> io::Result<()> {
+        self.inner.as_mut().unwrap().write_all(data)?;
+        self.offset += data.len() as u64;
+        Ok(())
+    }
+
+    /// Compresses `input` into the current entry.
+    fn encode(&mut self, input: &[u8], action: Action) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        let current = match self.current {
+            Some(ref mut current) => current,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "no entry has been started",
+                ))
+            }
+        };
+        let len = match current.encoder {
+            EntryEncoder::Stored(ref mut data) => {
+                data.extend_from_slice(input);
+                0
+            }
+            EntryEncoder::Deflated(ref mut e) => {
+                encode(&mut **e, input, action, &mut self.buf, inner)?
+            }
+            EntryEncoder::BZip2(ref mut e) => {
+                encode(&mut **e, input, action, &mut self.buf, inner)?
+            }
+        };
+        current.crc32.update(input);
+        current.size += input.len() as u64;
+        current.compressed_size += len;
+        self.offset += len;
+        Ok(())
+    }
+
+    /// Completes the data of the current entry and writes its data
+    /// descriptor, or its local header and data if stored.
+    fn finish_entry(&mut self) -> io::Result<()> {
+        if self.current.is_none() {
+            return Ok(());
+        }
+        self.encode(&[], Action::Finish)?;
+        let current = self.current.take().unwrap();
+        let entry = self.entries.last_mut().unwrap();
+        entry.crc32 = current.crc32.value();
+        entry.size = current.size;
+        entry.compressed_size = current.compressed_size;
+
+        let mut out = Vec::new();
+        if let EntryEncoder::Stored(data) = current.encoder {
+            entry.compressed_size = data.len() as u64;
+            entry.write_local(&mut out);
+            out.extend_from_slice(&data);
+        } else {
+            entry.write_descriptor(&mut out);
+        }
+        self.write_raw(&out)
+    }
+
+    /// Writes the central directory and the end of central directory
+    /// record, preceded by the ZIP64 ones if needed.
+    fn finish_archive(&mut self) -> io::Result<()> {
+        self.finish_entry()?;
+        let cd_offset = self.offset;
+        let mut cd = Vec::new();
+        for entry in &self.entries {
+            entry.write_central(&mut cd);
+        }
+        self.write_raw(&cd)?;
+        let cd_size = cd.len() as u64;
+        let count = self.entries.len() as u64;
+
+        let mut end = Vec::new();
+        if count >= 0xFFFF || cd_size >= 0xFFFF_FFFF || cd_offset >= 0xFFFF_FFFF
+        {
+            let zip64_end_offset = self.offset;
+            end.extend_from_slice(&ZIP64_END_SIGNATURE.to_le_bytes());
+            end.extend_from_slice(&(ZIP64_END_LEN as u64 - 12).to_le_bytes());
+            end.extend_from_slice(&VERSION_ZIP64.to_le_bytes());
+            end.extend_from_slice(&VERSION_ZIP64.to_le_bytes());
+            end.extend_from_slice(&[0; 8]);
+            end.extend_from_slice(&count.to_le_bytes());
+            end.extend_from_slice(&count.to_le_bytes());
+            end.extend_from_slice(&cd_size.to_le_bytes());
+            end.extend_from_slice(&cd_offset.to_le_bytes());
+
+            end.extend_from_slice(&ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+            end.extend_from_slice(&[0; 4]);
+            end.extend_from_slice(&zip64_end_offset.to_le_bytes());
+            end.extend_from_slice(&1u32.to_le_bytes());
+        }
+        let count = count.min(0xFFFF) as u16;
+        end.extend_from_slice(&END_SIGNATURE.to_le_bytes());
+        end.extend_from_slice(&[0; 4]);
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&(cd_size.min(0xFFFF_FFFF) as u32).to_le_bytes());
+        end.extend_from_slice(
+            &(cd_offset.min(0xFFFF_FFFF) as u32).to_le_bytes(),
+        );
+        end.extend_from_slice(&(self.comment.len() as u16).to_le_bytes());
+        end.extend_from_slice(&self.comment);
+        self.write_raw(&end)?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Write for ZipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encode(buf, Action::Run)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.current.is_some() {
+            self.encode(&[], Action::Flush)?;
+        }
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for ZipWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.finish_archive();
+        }
+    }
+}
@@ -5,12 +5,14 @@
 //! version 2.0 (the "License"). You can obtain a copy of the License at
 //! <http://mozilla.org/MPL/2.0/>.
 
-use crate::adler32::Adler32;
 use crate::bitio::direction::right::Right;
 use crate::bitio::reader::{BitRead, BitReader};
+use crate::checksum::{Adler32, BufferedChecksum};
 use crate::core::hash::Hasher;
 use crate::deflate::decoder::DeflaterBase;
+use crate::deflate::index::{DeflateCheckpoint, DeflateIndex};
 use crate::error::CompressionError;
+use crate::status::Status;
 use crate::traits::decoder::{BitDecodeService, BitDecoderImpl, Decoder};
 #[cfg(not(feature = "std"))]
 #[allow(unused_imports)]
@@ -21,11 +23,14 @@ use alloc::vec::Vec;
 #[derive(Default, Debug)]
 pub(crate) struct ZlibDecoderBase {
     deflater: DeflaterBase,
-    adler32: Adler32,
+    adler32: BufferedChecksum<Adler32>,
     dict_hash: Option<u32>,
     header: Vec<u8>,
+    header_pos: u64,
     header_needlen: usize,
     header_checked: bool,
+    resumed: bool,
+    finished: bool,
 }
 
 impl ZlibDecoderBase {
@@ -34,11 +39,30 @@ impl ZlibDecoderBase {
         dict_idc.write(dict);
         Self {
             deflater: DeflaterBase::with_dict(dict),
-            adler32: Adler32::new(),
+            adler32: BufferedChecksum::default(),
             dict_hash: Some(dict_idc.finish() as u32),
             header: Vec::new(),
+            header_pos: 0,
             header_needlen: 0,
             header_checked: false,
+            resumed: false,
+            finished: false,
+        }
+    }
+
+    fn with_index(span: usize) -> Self {
+        Self {
+            deflater: DeflaterBase::with_index(span),
+            ..Self::default()
+        }
+    }
+
+    fn with_checkpoint(point: &DeflateCheckpoint) -> Self {
+        Self {
+            deflater: DeflaterBase::with_checkpoint(point),
+            header_checked: true,
+            resumed: true,
+            ..Self::default()
         }
     }
 }
@@ -53,8 +77,15 @@ impl BitDecodeService for ZlibDecoderBase {
         reader: &mut BitReader<Self::Direction>,
         iter: &mut I,
     ) -> Result<Option<u8>, Self::Error> {
+        if self.finished {
+            return Ok(None);
+        }
         loop {
+            reader.commit();
             if !self.header_checked {
+                if self.header.is_empty() {
+                    self.header_pos = reader.position();
+                }
                 let s = reader
                     .read_bits::<u8, _>(8, iter)
                     .map_err(|_| CompressionError::UnexpectedEof)?
@@ -66,7 +97,9 @@ impl BitDecodeService for ZlibDecoderBase {
                             if (self.header[1] & 0b10_0000) == 0b10_0000 {
                                 6
                             } else if self.dict_hash.is_some() {
-                                return Err(CompressionError::DataError);
+                                return Err(CompressionError::InvalidHeader {
+                                    position: self.header_pos + 8,
+                                });
                             } else {
                                 2
                             };
@@ -75,28 +108,39 @@ impl BitDecodeService for ZlibDecoderBase {
                     self.header.push(s);
                 }
                 if self.header_needlen == self.header.len() {
-                    if (self.header[0] & 0x0F) != 8 {
-                        return Err(CompressionError::DataError);
+                    let method = self.header[0] & 0x0F;
+                    if method != 8 {
+                        return Err(CompressionError::UnsupportedMethod {
+                            method,
+                            position: self.header_pos,
+                        });
                     }
                     if ((self.header[0] & 0xF0) >> 4) > 7 {
-                        return Err(CompressionError::DataError);
+                        return Err(CompressionError::InvalidHeader {
+                            position: self.header_pos,
+                        });
                     }
                     if ((u16::from(self.header[0]) << 8
                         | u16::from(self.header[1]))
                         % 31)
                         != 0
                     {
-                        return Err(CompressionError::DataError);
+                        return Err(CompressionError::InvalidHeader {
+                            position: self.header_pos + 8,
+                        });
                     }
-                    if let Some(dict_hash) = self.dict_hash {
+                    if self.header_needlen == 6 {
                         let dictid = self
                             .header
                             .as_slice()
                             .iter()
                             .skip(2)
                             .fold(0_u32, |s, &x| u32::from(x) | (s << 8));
-                        if dict_hash != dictid {
-                            return Err(CompressionError::DataError);
+                        if self.dict_hash != Some(dictid) {
+                            return Err(CompressionError::DictionaryRequired {
+                                id: dictid,
+                                position: self.header_pos + 16,
+                            });
                         }
                     }
                     self.header_checked = true;
@@ -105,28 +149,31 @@ impl BitDecodeService for ZlibDecoderBase {
                 // body
                 match self.deflater.next(reader, iter) {
                     Ok(Some(s)) => {
-                        self.adler32.write_u8(s);
+                        self.adler32.push(s);
                         return Ok(Some(s));
                     }
                     Ok(None) => {
                         let _ = reader.skip_to_next_byte();
-                        let c = (0..4)
-                            .map(|_| reader.read_bits::<u32, _>(8, iter))
-                            .fold(
-                                Ok(0_u32),
-                                |s: Result<_, CompressionError>, x| {
-                                    Ok(x.map_err(|_| {
-                                        CompressionError::UnexpectedEof
-                                    })?
-                                    .data()
-                                        | (s.map_err(|_| {
-                                            CompressionError::UnexpectedEof
-                                        })? << 8))
-                                },
-                            )?;
-                        if u64::from(c) != self.adler32.finish() {
-                            return Err(CompressionError::DataError);
+                        let position = reader.position();
+                        let c = (0..4).try_fold(0_u32, |s, _| {
+                            let x = reader
+                                .read_bits::<u32, _>(8, iter)
+                                .map_err(|_| CompressionError::UnexpectedEof)?;
+                            if x.len() != 8 {
+                                return Err(CompressionError::UnexpectedEof);
+                            }
+                            Ok(x.data() | (s << 8))
+                        })?;
+                        // 途中から再開した場合はチェックサムを検証できない
+                        let actual = self.adler32.value();
+                        if !self.resumed && c != actual {
+                            return Err(CompressionError::ChecksumMismatch {
+                                expected: c,
+                                actual,
+                                position,
+                            });
                         } else {
+                            self.finished = true;
                             return Ok(None);
                         }
                     }
@@ -157,6 +204,40 @@ impl ZlibDecoder {
             ),
         }
     }
+
+    /// Creates a decoder which builds a `DeflateIndex` with a checkpoint
+    /// every `span` bytes of the decompressed data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `span` is zero.
+    pub fn with_index(span: usize) -> Self {
+        Self {
+            inner: BitDecoderImpl::<ZlibDecoderBase>::with_service(
+                ZlibDecoderBase::with_index(span),
+                BitReader::new(),
+            ),
+        }
+    }
+
+    /// Creates a decoder which resumes the decoding at `point`.
+    ///
+    /// The input has to start at `point.input_offset()` of the zlib
+    /// stream. The checksum of the trailer is not verified.
+    pub fn with_checkpoint(point: &DeflateCheckpoint) -> Self {
+        Self {
+            inner: BitDecoderImpl::<ZlibDecoderBase>::with_service(
+                ZlibDecoderBase::with_checkpoint(point),
+                BitReader::new(),
+            ),
+        }
+    }
+
+    /// Returns the index built so far by a decoder created with
+    /// `with_index`.
+    pub fn index(&self) -> Option<&DeflateIndex> {
+        self.inner.service().deflater.index()
+    }
 }
 
 impl Default for ZlibDecoder {
@@ -178,4 +259,13 @@ impl Decoder for ZlibDecoder {
     ) -> Option<Result<Self::Output, Self::Error>> {
         self.inner.next(iter)
     }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        self.inner.decompress(input, output, finish)
+    }
 }
@@ -6,59 +6,30 @@
 //! <http://mozilla.org/MPL/2.0/>.
 
 use crate::action::Action;
-use crate::adler32::Adler32;
-use crate::core::borrow::BorrowMut;
+use crate::checksum::Adler32;
+use crate::core::cmp;
 use crate::core::hash::Hasher;
-use crate::core::marker::PhantomData;
 use crate::core::mem;
 use crate::deflate::encoder::Inflater;
+use crate::deflate::options::DeflateOptions;
 use crate::error::CompressionError;
-use crate::traits::encoder::Encoder;
+use crate::status::Status;
+use crate::traits::encoder::{EncodeBuffer, Encoder};
 #[cfg(not(feature = "std"))]
 #[allow(unused_imports)]
 use alloc::vec;
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
-struct ScanIterator<I: Iterator, BI: BorrowMut<I>, F: FnMut(&I::Item) -> ()> {
-    phantom: PhantomData<I>,
-    inner: BI,
-    closure: F,
-}
-
-impl<I: Iterator, BI: BorrowMut<I>, F: FnMut(&I::Item) -> ()> Iterator
-    for ScanIterator<I, BI, F>
-{
-    type Item = I::Item;
-    fn next(&mut self) -> Option<I::Item> {
-        let ret = self.inner.borrow_mut().next();
-        if let Some(ref s) = ret {
-            (self.closure)(s);
-        }
-        ret
-    }
-}
-
-impl<I: Iterator, BI: BorrowMut<I>, F: FnMut(&I::Item) -> ()>
-    ScanIterator<I, BI, F>
-{
-    pub(crate) fn new(inner: BI, closure: F) -> Self {
-        Self {
-            inner,
-            closure,
-            phantom: PhantomData,
-        }
-    }
-}
-
 #[derive(Debug)]
 pub struct ZlibEncoder {
     inflater: Inflater,
-    adler32: Option<Adler32>,
-    header_len: u8,
-    header: Vec<u8>,
-    hash: Option<u32>,
-    hashlen: u8,
+    adler32: Adler32,
+    /// The header and the trailer not written yet.
+    out: Vec<u8>,
+    out_pos: usize,
+    finished: bool,
+    buf: EncodeBuffer<u8, u8>,
 }
 
 impl Default for ZlibEncoder {
@@ -69,45 +40,75 @@ impl Default for ZlibEncoder {
 
 impl ZlibEncoder {
     pub fn new() -> Self {
-        // CM - Compression method - 32K deflate = 8
-        // CINFO - Window Size - 32K = 7
-        // FDICT = 0
-        // FLEVEL = 2
-        // FCHECK = 1C
-        Self {
-            inflater: Inflater::new(),
-            adler32: Some(Adler32::new()),
-            header: vec![0x78, 0xDA],
-            header_len: 2,
-            hash: None,
-            hashlen: 3,
-        }
+        Self::with_options(&DeflateOptions::default())
     }
 
     pub fn with_dict(dict: &[u8]) -> Self {
-        // CM - Compression method - 32K deflate = 8
-        // CINFO - Window Size - 32K = 7
-        // FDICT = 1
-        // FLEVEL = 2
-        // FCHECK = 25
+        Self::with_options_and_dict(&DeflateOptions::default(), dict)
+    }
+
+    pub fn with_options(options: &DeflateOptions) -> Self {
+        Self::with_inflater(
+            Inflater::with_options(options),
+            Self::header(options, None),
+        )
+    }
+
+    pub fn with_options_and_dict(
+        options: &DeflateOptions,
+        dict: &[u8],
+    ) -> Self {
         let mut dict_idc = Adler32::new();
         dict_idc.write(dict);
         let dict_hash = dict_idc.finish() as u32;
+        Self::with_inflater(
+            Inflater::with_options_and_dict(options, dict),
+            Self::header(options, Some(dict_hash)),
+        )
+    }
+
+    fn with_inflater(inflater: Inflater, header: Vec<u8>) -> Self {
         Self {
-            inflater: Inflater::with_dict(dict),
-            adler32: Some(Adler32::new()),
-            header: vec![
-                0x78,
-                0xF9,
+            inflater,
+            adler32: Adler32::new(),
+            out: header,
+            out_pos: 0,
+            finished: false,
+            buf: EncodeBuffer::default(),
+        }
+    }
+
+    pub(crate) fn header(
+        options: &DeflateOptions,
+        dict_hash: Option<u32>,
+    ) -> Vec<u8> {
+        // CM - Compression method - deflate = 8
+        // CINFO - Window Size - log2(window size) - 8
+        let cmf = ((options.window_bits as u8 - 8) << 4) | 8;
+        // FLEVEL - 0: fastest, 1: fast, 2: default, 3: maximum
+        let flevel = match options.level {
+            0 | 1 => 0,
+            2..=5 => 1,
+            6 => 2,
+            _ => 3,
+        };
+        let mut flg = flevel << 6;
+        if dict_hash.is_some() {
+            // FDICT
+            flg |= 0x20;
+        }
+        // FCHECK
+        flg |= ((31 - (u16::from(cmf) << 8 | u16::from(flg)) % 31) % 31) as u8;
+        let mut header = vec![cmf, flg];
+        if let Some(dict_hash) = dict_hash {
+            header.extend_from_slice(&[
                 (dict_hash >> 24) as u8,
                 (dict_hash >> 16) as u8,
                 (dict_hash >> 8) as u8,
                 dict_hash as u8,
-            ],
-            header_len: 6,
-            hash: None,
-            hashlen: 3,
+            ]);
         }
+        header
     }
 }
 
@@ -120,35 +121,61 @@ impl Encoder for ZlibEncoder {
         iter: &mut I,
         action: Action,
     ) -> Option<Result<u8, CompressionError>> {
-        let hlen = self.header_len;
-        if hlen > 0 {
-            let hlen_all = self.header.len();
-            self.header_len = hlen - 1;
-            Some(Ok(self.header[hlen_all - hlen as usize]))
-        } else if let Some(hash) = self.hash {
-            if self.hashlen == 0 {
-                None
-            } else {
-                self.hashlen -= 1;
-                Some(Ok((hash >> (self.hashlen << 3)) as u8))
+        if let Some(s) = self.buf.pop() {
+            return Some(Ok(s));
+        }
+        let mut buf = mem::take(&mut self.buf);
+        let ret = buf.next(self, iter, action);
+        self.buf = buf;
+        ret
+    }
+
+    fn compress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        action: Action,
+    ) -> Result<Status, CompressionError> {
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            let len = cmp::min(
+                self.out.len() - self.out_pos,
+                output.len() - produced,
+            );
+            output[produced..produced + len]
+                .copy_from_slice(&self.out[self.out_pos..self.out_pos + len]);
+            produced += len;
+            self.out_pos += len;
+            if self.out_pos < self.out.len() || self.finished {
+                return Ok(Status {
+                    consumed,
+                    produced,
+                    done: self.out_pos == self.out.len(),
+                });
             }
-        } else {
-            let mut adler32 = mem::replace(&mut self.adler32, None);
-            let ret = self.inflater.next(
-                &mut ScanIterator::<I, _, _>::new(iter, |x: &u8| {
-                    adler32.as_mut().unwrap().write_u8(*x)
-                }),
+            let status = self.inflater.compress(
+                &input[consumed..],
+                &mut output[produced..],
                 action,
-            );
-            let _ = mem::replace(&mut self.adler32, adler32);
-            if ret.is_none() {
-                let hash = self.adler32.as_mut().unwrap().finish() as u32;
-                let ret = (hash >> 24) as u8;
-                self.hash = Some(hash);
-                Some(Ok(ret))
-            } else {
-                ret
+            )?;
+            self.adler32
+                .write(&input[consumed..consumed + status.consumed]);
+            consumed += status.consumed;
+            produced += status.produced;
+            if !status.done || action != Action::Finish {
+                return Ok(Status {
+                    consumed,
+                    produced,
+                    done: status.done,
+                });
             }
+            self.out.clear();
+            self.out_pos = 0;
+            self.out.extend_from_slice(
+                &(self.adler32.finish() as u32).to_be_bytes(),
+            );
+            self.finished = true;
         }
     }
 }
@@ -169,7 +196,7 @@ mod tests {
 
         assert_eq!(
             ret,
-            Ok(vec![0x78, 0xDA, 0x4B, 0x04, 0x00, 0x00, 0x62, 0x00, 0x62])
+            Ok(vec![0x78, 0x9C, 0x4B, 0x04, 0x00, 0x00, 0x62, 0x00, 0x62])
         );
     }
 
@@ -185,9 +212,34 @@ mod tests {
         assert_eq!(
             ret,
             Ok(vec![
-                0x78, 0xF9, 0x00, 0x62, 0x00, 0x62, 0x4B, 0x04, 0x00, 0x00,
+                0x78, 0xBB, 0x00, 0x62, 0x00, 0x62, 0x4B, 0x04, 0x00, 0x00,
                 0x62, 0x00, 0x62,
             ])
         );
     }
+
+    #[test]
+    fn test_header() {
+        let header = |level, window_bits| {
+            let options = DeflateOptions {
+                window_bits,
+                ..DeflateOptions::new(level)
+            };
+            b"a".iter()
+                .cloned()
+                .encode(
+                    &mut ZlibEncoder::with_options(&options),
+                    Action::Finish,
+                )
+                .take(2)
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        assert_eq!(header(0, 15), Ok(vec![0x78, 0x01]));
+        assert_eq!(header(1, 15), Ok(vec![0x78, 0x01]));
+        assert_eq!(header(5, 15), Ok(vec![0x78, 0x5E]));
+        assert_eq!(header(6, 15), Ok(vec![0x78, 0x9C]));
+        assert_eq!(header(9, 15), Ok(vec![0x78, 0xDA]));
+        assert_eq!(header(9, 9), Ok(vec![0x18, 0xD3]));
+    }
 }
@@ -8,6 +8,7 @@
 
 pub(crate) mod decoder;
 pub(crate) mod encoder;
+pub(crate) mod parallel;
 
 #[cfg(test)]
 mod tests {
@@ -106,4 +107,265 @@ mod tests {
     fn test_multiblocks5() {
         test_rand_with_len(0x10_0001);
     }
+
+    #[test]
+    fn test_slice() {
+        use crate::traits::decoder::tests::decompress_slices;
+        use crate::traits::encoder::tests::compress_slices;
+
+        let rng = thread_rng();
+        let testarray = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cycle()
+            .take(0x1_0000)
+            .cloned()
+            .chain(rng.sample_iter(&Standard).take(0x1_0000))
+            .collect::<Vec<u8>>();
+        let expected = testarray
+            .iter()
+            .cloned()
+            .encode(&mut ZlibEncoder::new(), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        for &(chunk, out_len) in &[(1, 0x100), (3, 1), (0x1000, 0x8000)] {
+            let encoded = compress_slices(
+                &mut ZlibEncoder::new(),
+                &testarray,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(encoded == expected);
+            let decoded = decompress_slices(
+                &mut ZlibDecoder::new(),
+                &encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded == testarray);
+        }
+    }
+
+    #[test]
+    fn test_index() {
+        let rng = thread_rng();
+        let testarray = rng
+            .sample_iter::<u8, _>(&Standard)
+            .map(|x| x & 0x0F)
+            .take(0x4_0000)
+            .collect::<Vec<u8>>();
+        let encoded = testarray
+            .iter()
+            .cloned()
+            .encode(&mut ZlibEncoder::new(), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let mut decoder = ZlibDecoder::with_index(0x8000);
+        let _ = encoded
+            .iter()
+            .cloned()
+            .decode(&mut decoder)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let point = decoder.index().unwrap().points().last().unwrap();
+        let decoded = encoded[point.input_offset() as usize..]
+            .iter()
+            .cloned()
+            .decode(&mut ZlibDecoder::with_checkpoint(point))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(&testarray[point.output as usize..], &decoded[..]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_parallel() {
+        use crate::deflate::options::DeflateOptions;
+        use crate::io::encoder::EncoderWriter;
+        use crate::zlib::parallel::ParallelZlibEncoder;
+        use std::io::Write;
+
+        let rng = thread_rng();
+        let mut testarray = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cycle()
+            .take(0x1_0000)
+            .cloned()
+            .collect::<Vec<u8>>();
+        testarray.extend(rng.sample_iter::<u8, _>(&Standard).take(0x1_0000));
+        testarray.extend_from_slice(&testarray.clone()[..0x8000]);
+
+        for &len in &[0, 1, 0x1000, testarray.len()] {
+            let encoded = testarray[..len]
+                .iter()
+                .cloned()
+                .encode(
+                    &mut ParallelZlibEncoder::with_options(
+                        &DeflateOptions::default(),
+                        3,
+                        0x1000,
+                    ),
+                    Action::Finish,
+                )
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let decoded = encoded
+                .iter()
+                .cloned()
+                .decode(&mut ZlibDecoder::new())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(&testarray[..len], &decoded[..]);
+        }
+
+        let mut writer = EncoderWriter::new(
+            Vec::new(),
+            ParallelZlibEncoder::with_options(
+                &DeflateOptions::new(9),
+                4,
+                0x3000,
+            ),
+        );
+        for (i, chunk) in testarray.chunks(0x1234).enumerate() {
+            writer.write_all(chunk).unwrap();
+            if i % 5 == 0 {
+                writer.flush().unwrap();
+            }
+        }
+        let encoded = writer.finish().unwrap();
+        let decoded = encoded
+            .iter()
+            .cloned()
+            .decode(&mut ZlibDecoder::new())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(testarray, decoded);
+    }
+
+    #[test]
+    fn test_stream() {
+        use crate::traits::decoder::tests::decompress_slices;
+        use crate::traits::decoder::Decoder;
+
+        let rng = thread_rng();
+        let testarray = b"aabbaabbaaabbbaaabbbaabbaabb"
+            .iter()
+            .cycle()
+            .take(0x1_0000)
+            .cloned()
+            .chain(rng.sample_iter(&Standard).take(0x1_0000))
+            .collect::<Vec<u8>>();
+        let encoded = testarray
+            .iter()
+            .cloned()
+            .encode(&mut ZlibEncoder::new(), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let encoded_dict = testarray
+            .iter()
+            .cloned()
+            .encode(&mut ZlibEncoder::with_dict(b"aabb"), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        for &(chunk, out_len) in &[(1, 0x100), (3, 1), (0x1000, 0x8000)] {
+            let decoded = decompress_slices(
+                &mut ZlibDecoder::new(),
+                &encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert_eq!(testarray, decoded);
+            let decoded = decompress_slices(
+                &mut ZlibDecoder::with_dict(b"aabb"),
+                &encoded_dict,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert_eq!(testarray, decoded);
+        }
+
+        // 終わった後の呼び出しは何も出さずに終わりを返す
+        let mut decoder = ZlibDecoder::new();
+        let decoded =
+            decompress_slices(&mut decoder, &encoded, 0x1000, 0x1000).unwrap();
+        assert_eq!(testarray, decoded);
+        let status = decoder.decompress(&[], &mut [0; 0x10], true).unwrap();
+        assert!(status.done);
+        assert_eq!(status.produced, 0);
+
+        let len = encoded.len();
+        let mut corrupted = encoded;
+        corrupted[len - 1] ^= 1;
+        assert!(decompress_slices(
+            &mut ZlibDecoder::new(),
+            &corrupted,
+            1,
+            0x100
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        use crate::checksum::Adler32;
+        use crate::error::CompressionError;
+
+        let encoded = b"aabbaabb"
+            .iter()
+            .cloned()
+            .encode(&mut ZlibEncoder::with_dict(b"aabb"), Action::Finish)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut adler32 = Adler32::new();
+        adler32.update(b"aabb");
+        let decode = |data: &[u8], mut decoder: ZlibDecoder| {
+            data.iter()
+                .cloned()
+                .decode(&mut decoder)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let err = CompressionError::DictionaryRequired {
+            id: adler32.value(),
+            position: 16,
+        };
+        assert_eq!(decode(&encoded, ZlibDecoder::new()), Err(err));
+        assert_eq!(decode(&encoded, ZlibDecoder::with_dict(b"abab")), Err(err));
+
+        let mut corrupted = encoded.clone();
+        let len = corrupted.len();
+        corrupted[len - 1] ^= 1;
+        assert_eq!(
+            decode(&corrupted, ZlibDecoder::with_dict(b"aabb")),
+            Err(CompressionError::ChecksumMismatch {
+                expected: u32::from_be_bytes([
+                    encoded[len - 4],
+                    encoded[len - 3],
+                    encoded[len - 2],
+                    encoded[len - 1] ^ 1,
+                ]),
+                actual: u32::from_be_bytes([
+                    encoded[len - 4],
+                    encoded[len - 3],
+                    encoded[len - 2],
+                    encoded[len - 1],
+                ]),
+                position: ((len as u64) - 4) << 3,
+            })
+        );
+        assert_eq!(
+            decode(&encoded[..len - 2], ZlibDecoder::with_dict(b"aabb")),
+            Err(CompressionError::UnexpectedEof)
+        );
+        assert_eq!(
+            decode(&[0x79, 0x9C], ZlibDecoder::new()),
+            Err(CompressionError::UnsupportedMethod {
+                method: 9,
+                position: 0
+            })
+        );
+    }
 }
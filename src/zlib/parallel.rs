@@ -0,0 +1,141 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "std")]
+
+use crate::action::Action;
+use crate::checksum::Adler32;
+use crate::deflate::options::DeflateOptions;
+use crate::deflate::parallel::ParallelDeflater;
+use crate::error::CompressionError;
+use crate::traits::encoder::Encoder;
+use crate::zlib::encoder::ZlibEncoder;
+use std::collections::vec_deque::VecDeque;
+
+/// The zlib encoder which compresses the data on multiple threads.
+///
+/// See `ParallelGZipEncoder` for the way the data is split.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "zlib")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut ParallelZlibEncoder::new(4), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     # #[cfg(feature = "zlib")]
+///     let decompressed = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut ZlibDecoder::new())
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ParallelZlibEncoder {
+    deflater: ParallelDeflater,
+    queue: VecDeque<u8>,
+    adler32: u32,
+    finished: bool,
+}
+
+impl Default for ParallelZlibEncoder {
+    fn default() -> Self {
+        Self::new(ParallelDeflater::default_threads())
+    }
+}
+
+impl ParallelZlibEncoder {
+    pub fn new(threads: usize) -> Self {
+        Self::with_options(
+            &DeflateOptions::default(),
+            threads,
+            ParallelDeflater::DEFAULT_CHUNK_SIZE,
+        )
+    }
+
+    pub fn with_options(
+        options: &DeflateOptions,
+        threads: usize,
+        chunk_size: usize,
+    ) -> Self {
+        Self {
+            deflater: ParallelDeflater::new(options, threads, chunk_size),
+            queue: ZlibEncoder::header(options, None).into(),
+            adler32: 1,
+            finished: false,
+        }
+    }
+
+    fn compress(
+        &mut self,
+        finish: bool,
+        reset: bool,
+    ) -> Result<(), CompressionError> {
+        let chunks = self.deflater.compress(finish, reset, |data| {
+            let mut adler32 = Adler32::new();
+            adler32.update(data);
+            adler32.value()
+        })?;
+        for chunk in chunks {
+            self.adler32 =
+                Adler32::combine(self.adler32, chunk.checksum, chunk.len);
+            self.queue.extend(chunk.data);
+        }
+        Ok(())
+    }
+}
+
+impl Encoder for ParallelZlibEncoder {
+    type Error = CompressionError;
+    type In = u8;
+    type Out = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<u8, CompressionError>> {
+        while self.queue.is_empty() {
+            if self.deflater.is_full() {
+                if let Err(e) = self.compress(false, false) {
+                    return Some(Err(e));
+                }
+                continue;
+            }
+            match iter.next() {
+                Some(s) => self.deflater.push(s),
+                None => {
+                    if self.finished {
+                        self.finished = false;
+                        return None;
+                    }
+                    let ret = match action {
+                        Action::Run => return None,
+                        Action::Finish => self.compress(true, false),
+                        _ => self.compress(false, action == Action::FullFlush),
+                    };
+                    if let Err(e) = ret {
+                        return Some(Err(e));
+                    }
+                    if action == Action::Finish {
+                        self.queue.extend(&self.adler32.to_be_bytes());
+                    }
+                    self.finished = true;
+                }
+            }
+        }
+        self.queue.pop_front().map(Ok)
+    }
+}
@@ -0,0 +1,347 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bitio::backward::BackwardBitReader;
+use crate::bitio::direction::left::Left;
+use crate::error::CompressionError;
+use crate::huffman::decoder::HuffmanDecoder;
+use crate::zstd::fse::FseTable;
+use crate::zstd::huffman::read_huffman_table;
+use crate::zstd::{
+    DEFAULT_OFFSETS, LL_CODES, LL_DEFAULT, LL_DEFAULT_LOG, LL_MAX_LOG,
+    MAX_BLOCK_SIZE, MAX_OF_CODE, ML_CODES, ML_DEFAULT, ML_DEFAULT_LOG,
+    ML_MAX_LOG, OF_DEFAULT, OF_DEFAULT_LOG, OF_MAX_LOG,
+};
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Decodes `count` symbols of the Huffman stream `data`.
+fn decode_stream(
+    decoder: &mut HuffmanDecoder<Left>,
+    data: &[u8],
+    count: usize,
+    out: &mut Vec<u8>,
+) -> Option<()> {
+    let mut reader = BackwardBitReader::new(data).ok()?;
+    for _ in 0..count {
+        out.push(reader.decode(decoder).ok()? as u8);
+    }
+    if reader.remaining() == 0 {
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// Returns the little-endian value of `data`.
+fn le_value(data: &[u8]) -> usize {
+    data.iter().rev().fold(0, |v, &b| (v << 8) | usize::from(b))
+}
+
+/// Returns the offset of `value` and updates the repeated offsets.
+pub(crate) fn repeat_offset(
+    offsets: &mut [usize; 3],
+    value: usize,
+    literal_len: usize,
+) -> Option<usize> {
+    if value > 3 {
+        offsets[2] = offsets[1];
+        offsets[1] = offsets[0];
+        offsets[0] = value - 3;
+        return Some(offsets[0]);
+    }
+    // リテラル長が0のときは一つずれる
+    let index = value - 1 + if literal_len == 0 { 1 } else { 0 };
+    let offset = match index {
+        0 => return Some(offsets[0]),
+        3 => offsets[0].checked_sub(1).filter(|&o| o > 0)?,
+        _ => offsets[index],
+    };
+    if index != 1 {
+        offsets[2] = offsets[1];
+    }
+    offsets[1] = offsets[0];
+    offsets[0] = offset;
+    Some(offset)
+}
+
+/// The tables and the offsets which are carried over the blocks of a
+/// frame.
+#[derive(Clone, Debug)]
+pub(crate) struct Entropy {
+    pub(crate) huffman: Option<HuffmanDecoder<Left>>,
+    pub(crate) ll: Option<FseTable>,
+    pub(crate) of: Option<FseTable>,
+    pub(crate) ml: Option<FseTable>,
+    pub(crate) offsets: [usize; 3],
+}
+
+impl Default for Entropy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Entropy {
+    pub(crate) fn new() -> Self {
+        Self {
+            huffman: None,
+            ll: None,
+            of: None,
+            ml: None,
+            offsets: DEFAULT_OFFSETS,
+        }
+    }
+
+    /// Reads the literals section, and returns the literals and the
+    /// length of the section.
+    fn read_literals(
+        &mut self,
+        data: &[u8],
+        position: u64,
+    ) -> Result<(Vec<u8>, usize), CompressionError> {
+        let invalid = CompressionError::InvalidBlock { position };
+        let b0 = *data.first().ok_or(invalid)?;
+        let kind = b0 & 3;
+        let format = (b0 >> 2) & 3;
+        if kind < 2 {
+            // Raw, RLE
+            let head = match format {
+                0 | 2 => 1,
+                1 => 2,
+                _ => 3,
+            };
+            let size = le_value(data.get(..head).ok_or(invalid)?)
+                >> if head == 1 { 3 } else { 4 };
+            if size > MAX_BLOCK_SIZE {
+                return Err(invalid);
+            }
+            if kind == 0 {
+                let literals = data.get(head..head + size).ok_or(invalid)?;
+                Ok((literals.to_vec(), head + size))
+            } else {
+                let b = *data.get(head).ok_or(invalid)?;
+                Ok((vec![b; size], head + 1))
+            }
+        } else {
+            // Compressed, Treeless
+            let (head, bits) = match format {
+                0 | 1 => (3, 10),
+                2 => (4, 14),
+                _ => (5, 18),
+            };
+            let h = le_value(data.get(..head).ok_or(invalid)?);
+            let mask = (1 << bits) - 1;
+            let size = (h >> 4) & mask;
+            let comp_size = (h >> (4 + bits)) & mask;
+            if size > MAX_BLOCK_SIZE {
+                return Err(invalid);
+            }
+            let mut body = data.get(head..head + comp_size).ok_or(invalid)?;
+            if kind == 2 {
+                let (decoder, len) = read_huffman_table(body).ok_or(
+                    CompressionError::InvalidHuffmanTable { position },
+                )?;
+                self.huffman = Some(decoder);
+                body = &body[len..];
+            }
+            let decoder = self.huffman.as_mut().ok_or(invalid)?;
+            let invalid_code =
+                CompressionError::InvalidHuffmanCode { position };
+            let mut literals = Vec::with_capacity(size);
+            if format == 0 {
+                decode_stream(decoder, body, size, &mut literals)
+                    .ok_or(invalid_code)?;
+            } else {
+                let jump = body.get(..6).ok_or(invalid)?;
+                let mut streams = [0; 4];
+                for i in 0..3 {
+                    streams[i] = le_value(&jump[i << 1..(i + 1) << 1]);
+                }
+                let rest = (body.len() - 6)
+                    .checked_sub(streams[..3].iter().sum())
+                    .ok_or(invalid)?;
+                streams[3] = rest;
+                let count = (size + 3) >> 2;
+                let last = size.checked_sub(count * 3).ok_or(invalid)?;
+                let mut start = 6;
+                for (i, &len) in streams.iter().enumerate() {
+                    let n = if i < 3 { count } else { last };
+                    let stream = &body[start..start + len];
+                    decode_stream(decoder, stream, n, &mut literals)
+                        .ok_or(invalid_code)?;
+                    start += len;
+                }
+            }
+            Ok((literals, head + comp_size))
+        }
+    }
+
+    /// Reads the table of the `mode` at the start of `data`, and returns
+    /// the table and the length read.
+    fn read_table(
+        mode: u8,
+        data: &[u8],
+        prev: &Option<FseTable>,
+        default: &[i16],
+        default_log: usize,
+        max_symbol: usize,
+        max_log: usize,
+    ) -> Option<(FseTable, usize)> {
+        match mode {
+            // Predefined
+            0 => Some((FseTable::new(default, default_log)?, 0)),
+            // RLE
+            1 => match *data.first()? {
+                s if usize::from(s) <= max_symbol => {
+                    Some((FseTable::rle(s), 1))
+                }
+                _ => None,
+            },
+            // FSE_Compressed
+            2 => FseTable::read(data, max_symbol, max_log),
+            // Repeat
+            _ => Some((prev.clone()?, 0)),
+        }
+    }
+
+    /// Decodes the compressed block `data` and appends the data to
+    /// `window`, which holds the preceding data.
+    pub(crate) fn decode_block(
+        &mut self,
+        data: &[u8],
+        window: &mut Vec<u8>,
+        max_size: usize,
+        position: u64,
+    ) -> Result<(), CompressionError> {
+        let invalid = CompressionError::InvalidBlock { position };
+        let (literals, mut pos) = self.read_literals(data, position)?;
+        let start = window.len();
+
+        // Sequences Section Header
+        let b0 = usize::from(*data.get(pos).ok_or(invalid)?);
+        let (count, head) = match b0 {
+            0..=0x7F => (b0, 1),
+            0x80..=0xFE => {
+                let b1 = usize::from(*data.get(pos + 1).ok_or(invalid)?);
+                (((b0 - 0x80) << 8) + b1, 2)
+            }
+            _ => (
+                le_value(data.get(pos + 1..pos + 3).ok_or(invalid)?) + 0x7F00,
+                3,
+            ),
+        };
+        pos += head;
+        if count == 0 {
+            if pos != data.len() || literals.len() > max_size {
+                return Err(invalid);
+            }
+            window.extend_from_slice(&literals);
+            return Ok(());
+        }
+        let modes = *data.get(pos).ok_or(invalid)?;
+        if modes & 3 != 0 {
+            return Err(invalid);
+        }
+        pos += 1;
+        let (ll, len) = Self::read_table(
+            modes >> 6,
+            &data[pos..],
+            &self.ll,
+            &LL_DEFAULT,
+            LL_DEFAULT_LOG,
+            LL_CODES.len() - 1,
+            LL_MAX_LOG,
+        )
+        .ok_or(invalid)?;
+        pos += len;
+        let (of, len) = Self::read_table(
+            (modes >> 4) & 3,
+            &data[pos..],
+            &self.of,
+            &OF_DEFAULT,
+            OF_DEFAULT_LOG,
+            MAX_OF_CODE,
+            OF_MAX_LOG,
+        )
+        .ok_or(invalid)?;
+        pos += len;
+        let (ml, len) = Self::read_table(
+            (modes >> 2) & 3,
+            &data[pos..],
+            &self.ml,
+            &ML_DEFAULT,
+            ML_DEFAULT_LOG,
+            ML_CODES.len() - 1,
+            ML_MAX_LOG,
+        )
+        .ok_or(invalid)?;
+        pos += len;
+
+        let mut reader =
+            BackwardBitReader::new(&data[pos..]).map_err(|_| invalid)?;
+        let mut ll_state = ll.init(&mut reader);
+        let mut of_state = of.init(&mut reader);
+        let mut ml_state = ml.init(&mut reader);
+        let mut lit_pos = 0;
+        for i in 0..count {
+            let of_code = usize::from(of.symbol(of_state));
+            let (ml_base, ml_bits) = ML_CODES[usize::from(ml.symbol(ml_state))];
+            let (ll_base, ll_bits) = LL_CODES[usize::from(ll.symbol(ll_state))];
+            let of_value = (1 << of_code) + reader.read(of_code) as usize;
+            let match_len =
+                (ml_base as u64 + reader.read(usize::from(ml_bits))) as usize;
+            let literal_len =
+                (ll_base as u64 + reader.read(usize::from(ll_bits))) as usize;
+            if i + 1 < count {
+                ll_state = ll.update(ll_state, &mut reader);
+                ml_state = ml.update(ml_state, &mut reader);
+                of_state = of.update(of_state, &mut reader);
+            }
+
+            let offset =
+                repeat_offset(&mut self.offsets, of_value, literal_len)
+                    .ok_or(invalid)?;
+            let lits = literals
+                .get(lit_pos..lit_pos + literal_len)
+                .ok_or(invalid)?;
+            lit_pos += literal_len;
+            if window.len() - start + literal_len + match_len > max_size {
+                return Err(invalid);
+            }
+            window.extend_from_slice(lits);
+            if offset > window.len() {
+                return Err(CompressionError::InvalidDistance {
+                    distance: offset,
+                    position,
+                });
+            }
+            let from = window.len() - offset;
+            if offset >= match_len {
+                window.extend_from_within(from..from + match_len);
+            } else {
+                for j in from..from + match_len {
+                    let b = window[j];
+                    window.push(b);
+                }
+            }
+        }
+        if reader.remaining() != 0
+            || window.len() - start + literals.len() - lit_pos > max_size
+        {
+            return Err(invalid);
+        }
+        window.extend_from_slice(&literals[lit_pos..]);
+        self.ll = Some(ll);
+        self.of = Some(of);
+        self.ml = Some(ml);
+        Ok(())
+    }
+}
@@ -0,0 +1,417 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bitio::direction::right::Right;
+use crate::bitio::reader::{BitRead, BitReader};
+use crate::checksum::XxHash64;
+use crate::core::cmp;
+use crate::error::CompressionError;
+use crate::status::Status;
+use crate::traits::decoder::{BitDecodeService, BitDecoderImpl, Decoder};
+use crate::zstd::block::Entropy;
+use crate::zstd::dictionary::Dictionary;
+use crate::zstd::{MAGIC, MAX_BLOCK_SIZE, MAX_WINDOW_LOG, SKIPPABLE_MAGIC};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The length of the skippable frame read at once.
+const SKIP_CHUNK: u64 = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Frame,
+    /// The length of the skippable frame left.
+    Skip(u64),
+    Block,
+    Trailer,
+    Finished,
+}
+
+fn read_u8<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+) -> Result<u8, CompressionError> {
+    match reader.read_bits::<u8, _>(8, iter) {
+        Ok(b) if b.len() == 8 => Ok(b.data()),
+        _ => Err(CompressionError::UnexpectedEof),
+    }
+}
+
+fn read_le<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+    len: usize,
+) -> Result<u64, CompressionError> {
+    (0..len).try_fold(0, |v, i| {
+        Ok(v | (u64::from(read_u8(reader, iter)?) << (i << 3)))
+    })
+}
+
+fn read_bytes<I: Iterator<Item = u8>>(
+    reader: &mut BitReader<Right>,
+    iter: &mut I,
+    len: usize,
+) -> Result<Vec<u8>, CompressionError> {
+    (0..len).map(|_| read_u8(reader, iter)).collect()
+}
+
+#[derive(Debug)]
+pub(crate) struct ZstdDecoderBase {
+    state: State,
+    frame_no: usize,
+    dict: Option<Dictionary>,
+    entropy: Entropy,
+    // 辞書と復号したデータのうち、参照されうる部分
+    window: Vec<u8>,
+    out_pos: usize,
+    window_size: usize,
+    history: usize,
+    dict_id: Option<u32>,
+    content_size: Option<u64>,
+    produced: u64,
+    xxh64: Option<XxHash64>,
+}
+
+impl Default for ZstdDecoderBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ZstdDecoderBase {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: State::Frame,
+            frame_no: 0,
+            dict: None,
+            entropy: Entropy::new(),
+            window: Vec::new(),
+            out_pos: 0,
+            window_size: 0,
+            history: 0,
+            dict_id: None,
+            content_size: None,
+            produced: 0,
+            xxh64: None,
+        }
+    }
+
+    fn with_dict(dict: &[u8]) -> Self {
+        Self {
+            dict: Some(Dictionary::new(dict).expect("invalid dictionary")),
+            ..Self::new()
+        }
+    }
+
+    fn read_frame_header<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        if self.frame_no > 0 {
+            let next = reader
+                .peek_bits::<u8, _>(8, iter)
+                .map_err(|_| CompressionError::UnexpectedEof)?;
+            if next.is_empty() {
+                self.state = State::Finished;
+                return Ok(());
+            }
+        }
+        let magic = read_le(reader, iter, 4)? as u32;
+        if magic & !0x0F == SKIPPABLE_MAGIC {
+            let len = read_le(reader, iter, 4)?;
+            self.frame_no += 1;
+            self.state = State::Skip(len);
+            return Ok(());
+        }
+        if magic != MAGIC {
+            return Err(if self.frame_no == 0 {
+                CompressionError::BadMagic { position }
+            } else {
+                CompressionError::TrailingGarbage { position }
+            });
+        }
+
+        // Frame_Header_Descriptor
+        let fhd = read_u8(reader, iter)?;
+        if fhd & 0x08 != 0 {
+            return Err(CompressionError::InvalidHeader {
+                position: position + 32,
+            });
+        }
+        let single_segment = fhd & 0x20 != 0;
+        // Window_Descriptor
+        let window_size = if single_segment {
+            None
+        } else {
+            let wd = read_u8(reader, iter)?;
+            let log = 10 + u32::from(wd >> 3);
+            if log > MAX_WINDOW_LOG {
+                return Err(CompressionError::InvalidHeader {
+                    position: position + 40,
+                });
+            }
+            let base = 1_u64 << log;
+            Some(base + (base >> 3) * u64::from(wd & 7))
+        };
+        // Dictionary_ID
+        let dict_id =
+            read_le(reader, iter, [0, 1, 2, 4][usize::from(fhd & 3)])? as u32;
+        // Frame_Content_Size
+        let content_size = match fhd >> 6 {
+            0 if !single_segment => None,
+            0 => Some(read_le(reader, iter, 1)?),
+            1 => Some(read_le(reader, iter, 2)? + 256),
+            2 => Some(read_le(reader, iter, 4)?),
+            _ => Some(read_le(reader, iter, 8)?),
+        };
+        let window_size = match window_size.or(content_size) {
+            Some(s) if s <= 1 << MAX_WINDOW_LOG => s as usize,
+            _ => {
+                return Err(CompressionError::InvalidHeader {
+                    position: position + 32,
+                })
+            }
+        };
+        if dict_id != 0 && self.dict.as_ref().map(|d| d.id) != Some(dict_id) {
+            return Err(CompressionError::DictionaryRequired {
+                id: dict_id,
+                position,
+            });
+        }
+
+        self.window.clear();
+        match self.dict {
+            Some(ref dict) => {
+                self.window.extend_from_slice(&dict.content);
+                self.entropy = dict.entropy.clone();
+            }
+            None => self.entropy = Entropy::new(),
+        }
+        self.out_pos = self.window.len();
+        self.window_size = window_size;
+        self.history = window_size.saturating_add(self.window.len());
+        self.dict_id = if dict_id != 0 { Some(dict_id) } else { None };
+        self.content_size = content_size;
+        self.produced = 0;
+        self.xxh64 = if fhd & 0x04 != 0 {
+            Some(XxHash64::new())
+        } else {
+            None
+        };
+        self.frame_no += 1;
+        self.state = State::Block;
+        Ok(())
+    }
+
+    /// Drops the data which can no longer be referred to.
+    fn trim(&mut self) {
+        let keep = self.history;
+        let len = self.window.len();
+        if len > keep.saturating_mul(2).saturating_add(MAX_BLOCK_SIZE) {
+            let _ = self.window.drain(..len - keep);
+            self.out_pos -= len - keep;
+        }
+    }
+
+    fn read_block<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        let header = read_le(reader, iter, 3)? as usize;
+        let last = header & 1 != 0;
+        let size = header >> 3;
+        let max_size = cmp::min(self.window_size, MAX_BLOCK_SIZE);
+        let invalid = CompressionError::InvalidBlock { position };
+        let start = self.window.len();
+        match (header >> 1) & 3 {
+            // Raw_Block
+            0 => {
+                if size > max_size {
+                    return Err(invalid);
+                }
+                let data = read_bytes(reader, iter, size)?;
+                self.window.extend_from_slice(&data);
+            }
+            // RLE_Block
+            1 => {
+                if size > max_size {
+                    return Err(invalid);
+                }
+                let b = read_u8(reader, iter)?;
+                self.window.resize(start + size, b);
+            }
+            // Compressed_Block
+            2 => {
+                if size > MAX_BLOCK_SIZE {
+                    return Err(invalid);
+                }
+                let data = read_bytes(reader, iter, size)?;
+                self.entropy.decode_block(
+                    &data,
+                    &mut self.window,
+                    max_size,
+                    position + 24,
+                )?;
+            }
+            _ => return Err(invalid),
+        }
+        if let Some(ref mut xxh64) = self.xxh64 {
+            xxh64.update(&self.window[start..]);
+        }
+        self.produced += (self.window.len() - start) as u64;
+        if last {
+            self.state = State::Trailer;
+        }
+        Ok(())
+    }
+
+    fn read_trailer<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Right>,
+        iter: &mut I,
+    ) -> Result<(), CompressionError> {
+        let position = reader.position();
+        if let Some(expected) = self.content_size {
+            if expected != self.produced {
+                return Err(CompressionError::LengthMismatch {
+                    expected,
+                    actual: self.produced,
+                    position,
+                });
+            }
+        }
+        if let Some(ref xxh64) = self.xxh64 {
+            let expected = read_le(reader, iter, 4)? as u32;
+            let actual = xxh64.value() as u32;
+            if expected != actual {
+                return Err(CompressionError::ChecksumMismatch {
+                    expected,
+                    actual,
+                    position,
+                });
+            }
+        }
+        self.state = State::Frame;
+        Ok(())
+    }
+}
+
+impl BitDecodeService for ZstdDecoderBase {
+    type Direction = Right;
+    type Error = CompressionError;
+    type Output = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        reader: &mut BitReader<Self::Direction>,
+        iter: &mut I,
+    ) -> Result<Option<u8>, Self::Error> {
+        loop {
+            if self.out_pos < self.window.len() {
+                self.out_pos += 1;
+                return Ok(Some(self.window[self.out_pos - 1]));
+            }
+            reader.commit();
+            match self.state {
+                State::Frame => self.read_frame_header(reader, iter)?,
+                State::Skip(len) => {
+                    let n = cmp::min(len, SKIP_CHUNK);
+                    for _ in 0..n {
+                        let _ = read_u8(reader, iter)?;
+                    }
+                    self.state = if n == len {
+                        State::Frame
+                    } else {
+                        State::Skip(len - n)
+                    };
+                }
+                State::Block => {
+                    self.trim();
+                    self.read_block(reader, iter)?;
+                }
+                State::Trailer => self.read_trailer(reader, iter)?,
+                State::Finished => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The Zstandard decoder.
+///
+/// The decoder continues across the frames of the input until its end,
+/// skipping the skippable frames.
+#[derive(Debug)]
+pub struct ZstdDecoder {
+    inner: BitDecoderImpl<ZstdDecoderBase>,
+}
+
+impl ZstdDecoder {
+    pub fn new() -> Self {
+        Self {
+            inner: BitDecoderImpl::<ZstdDecoderBase>::new(),
+        }
+    }
+
+    /// Creates a decoder with the dictionary `dict`, which is used as the
+    /// raw content unless it starts with the magic of the dictionaries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dict` starts with the magic but is malformed.
+    pub fn with_dict(dict: &[u8]) -> Self {
+        Self {
+            inner: BitDecoderImpl::<ZstdDecoderBase>::with_service(
+                ZstdDecoderBase::with_dict(dict),
+                BitReader::new(),
+            ),
+        }
+    }
+
+    /// Returns the dictionary ID of the frame being decoded, once its
+    /// header has been read, or `None` if the frame has no ID.
+    pub fn dictionary_id(&self) -> Option<u32> {
+        self.inner.service().dict_id
+    }
+
+    /// Returns the length of the content of the frame being decoded,
+    /// once its header has been read, if the header has it.
+    pub fn content_size(&self) -> Option<u64> {
+        self.inner.service().content_size
+    }
+}
+
+impl Default for ZstdDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for ZstdDecoder {
+    type Input = u8;
+    type Output = u8;
+    type Error = CompressionError;
+
+    fn next<I: Iterator<Item = Self::Input>>(
+        &mut self,
+        iter: &mut I,
+    ) -> Option<Result<Self::Output, Self::Error>> {
+        self.inner.next(iter)
+    }
+
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Self::Error> {
+        self.inner.decompress(input, output, finish)
+    }
+}
@@ -0,0 +1,76 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::core::convert::TryInto;
+use crate::zstd::block::Entropy;
+use crate::zstd::fse::FseTable;
+use crate::zstd::huffman::read_huffman_table;
+use crate::zstd::{
+    DICT_MAGIC, LL_CODES, LL_MAX_LOG, MAX_OF_CODE, ML_CODES, ML_MAX_LOG,
+    OF_MAX_LOG,
+};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// The dictionary shared by the compressor and the decompressor.
+///
+/// A dictionary which does not start with the magic is the raw content,
+/// whose ID is 0.
+#[derive(Clone, Debug)]
+pub(crate) struct Dictionary {
+    pub(crate) id: u32,
+    pub(crate) content: Vec<u8>,
+    pub(crate) entropy: Entropy,
+}
+
+impl Dictionary {
+    pub(crate) fn new(data: &[u8]) -> Option<Self> {
+        if read_u32(data, 0) != Some(DICT_MAGIC) {
+            return Some(Self {
+                id: 0,
+                content: data.to_vec(),
+                entropy: Entropy::new(),
+            });
+        }
+        let id = read_u32(data, 4)?;
+        let mut pos = 8;
+        let (huffman, len) = read_huffman_table(&data[pos..])?;
+        pos += len;
+        let (of, len) =
+            FseTable::read(data.get(pos..)?, MAX_OF_CODE, OF_MAX_LOG)?;
+        pos += len;
+        let (ml, len) =
+            FseTable::read(data.get(pos..)?, ML_CODES.len() - 1, ML_MAX_LOG)?;
+        pos += len;
+        let (ll, len) =
+            FseTable::read(data.get(pos..)?, LL_CODES.len() - 1, LL_MAX_LOG)?;
+        pos += len;
+        let mut offsets = [0; 3];
+        for (i, o) in offsets.iter_mut().enumerate() {
+            *o = read_u32(data, pos + (i << 2))? as usize;
+        }
+        let content = data[pos + 12..].to_vec();
+        if offsets.iter().any(|&o| o == 0 || o > content.len()) {
+            return None;
+        }
+        Some(Self {
+            id,
+            content,
+            entropy: Entropy {
+                huffman: Some(huffman),
+                ll: Some(ll),
+                of: Some(of),
+                ml: Some(ml),
+                offsets,
+            },
+        })
+    }
+}
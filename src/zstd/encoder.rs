@@ -0,0 +1,645 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::action::Action;
+use crate::bitio::backward::BackwardBitWriter;
+use crate::checksum::XxHash64;
+use crate::core::cmp::{self, Ordering};
+use crate::error::CompressionError;
+use crate::lzss::encoder::LzssEncoder;
+use crate::lzss::LzssCode;
+use crate::traits::encoder::Encoder;
+use crate::zstd::block::repeat_offset;
+use crate::zstd::fse::{highbit, normalize, write_counts, FseEncoder};
+use crate::zstd::huffman::{write_huffman_stream, write_huffman_table};
+use crate::zstd::options::ZstdOptions;
+use crate::zstd::{
+    DEFAULT_OFFSETS, LL_CODES, LL_DEFAULT, LL_DEFAULT_LOG, LL_MAX_LOG, MAGIC,
+    MAX_BLOCK_SIZE, MAX_OF_CODE, ML_CODES, ML_DEFAULT, ML_DEFAULT_LOG,
+    ML_MAX_LOG, OF_DEFAULT, OF_DEFAULT_LOG, OF_MAX_LOG,
+};
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The minimum number of the literals compressed with the Huffman codes.
+const MIN_HUFFMAN_LITERALS: usize = 64;
+const MIN_MATCH: usize = 4;
+
+// The indices of the literal length, the offset and the match length
+// codes.
+const LL: usize = 0;
+const OF: usize = 1;
+const ML: usize = 2;
+
+// The predefined distribution, its log, the maximum log and the number of
+// the codes of each table.
+#[allow(clippy::type_complexity)]
+const TABLES: [(&[i16], usize, usize, usize); 3] = [
+    (&LL_DEFAULT, LL_DEFAULT_LOG, LL_MAX_LOG, LL_CODES.len()),
+    (&OF_DEFAULT, OF_DEFAULT_LOG, OF_MAX_LOG, MAX_OF_CODE + 1),
+    (&ML_DEFAULT, ML_DEFAULT_LOG, ML_MAX_LOG, ML_CODES.len()),
+];
+
+fn lzss_comparison(lhs: LzssCode, rhs: LzssCode) -> Ordering {
+    match (lhs, rhs) {
+        (
+            LzssCode::Reference {
+                len: llen,
+                pos: lpos,
+            },
+            LzssCode::Reference {
+                len: rlen,
+                pos: rpos,
+            },
+        ) => {
+            // 1バイトの長さは距離の1ビットに勝る
+            ((rlen << 3) + rpos.leading_zeros() as usize)
+                .cmp(&((llen << 3) + lpos.leading_zeros() as usize))
+        }
+        (LzssCode::Symbol(_), LzssCode::Symbol(_)) => Ordering::Equal,
+        (_, LzssCode::Symbol(_)) => Ordering::Greater,
+        (LzssCode::Symbol(_), _) => Ordering::Less,
+    }
+}
+
+type ZstdLzssEncoder = LzssEncoder<fn(LzssCode, LzssCode) -> Ordering>;
+
+/// Returns the code of `value` in the table of the baselines.
+fn code_of(codes: &[(u32, u8)], value: usize) -> usize {
+    match codes.binary_search_by_key(&value, |&(base, _)| base as usize) {
+        Ok(i) => i,
+        Err(i) => i - 1,
+    }
+}
+
+/// Returns the offset value of `offset`, which is one of the repeated
+/// offsets if possible.
+fn offset_value(
+    offsets: &[usize; 3],
+    offset: usize,
+    literal_len: usize,
+) -> usize {
+    let repeats = if literal_len == 0 {
+        [offsets[1], offsets[2], offsets[0] - 1]
+    } else {
+        *offsets
+    };
+    repeats
+        .iter()
+        .position(|&o| o == offset)
+        .map_or(offset + 3, |i| i + 1)
+}
+
+/// Writes the header of the raw or the RLE literals of `len` bytes.
+fn write_literals_header(kind: u32, len: usize, out: &mut Vec<u8>) {
+    let (format, head) = match len {
+        0..=0x1F => (0, 1),
+        0x20..=0xFFF => (1, 2),
+        _ => (3, 3),
+    };
+    let shift = if head == 1 { 3 } else { 4 };
+    let h = kind | (format << 2) | ((len as u32) << shift);
+    out.extend_from_slice(&h.to_le_bytes()[..head]);
+}
+
+/// Compresses the literals with the Huffman codes, and returns the
+/// literals section.
+fn compress_literals(literals: &[u8]) -> Option<Vec<u8>> {
+    let (mut body, codes) = write_huffman_table(literals)?;
+    let len = literals.len();
+    let single = len < 256;
+    if single {
+        body.extend(write_huffman_stream(&codes, literals));
+    } else {
+        let segment = (len + 3) >> 2;
+        let streams = (0..4)
+            .map(|i| {
+                let start = cmp::min(i * segment, len);
+                let end = cmp::min(start + segment, len);
+                write_huffman_stream(&codes, &literals[start..end])
+            })
+            .collect::<Vec<_>>();
+        for stream in &streams[..3] {
+            if stream.len() > 0xFFFF {
+                return None;
+            }
+            body.extend_from_slice(&(stream.len() as u16).to_le_bytes());
+        }
+        for stream in streams {
+            body.extend(stream);
+        }
+    }
+    let (format, bits, head) = match cmp::max(len, body.len()) {
+        _ if single => (0, 10, 3),
+        0..=0x3FF => (1, 10, 3),
+        0x400..=0x3FFF => (2, 14, 4),
+        _ => (3, 18, 5),
+    };
+    let h = 2
+        | (format << 2)
+        | ((len as u64) << 4)
+        | ((body.len() as u64) << (4 + bits));
+    let mut out = h.to_le_bytes()[..head].to_vec();
+    out.extend(body);
+    Some(out)
+}
+
+/// Writes the literals section.
+fn write_literals(literals: &[u8], out: &mut Vec<u8>) {
+    if literals.len() > 1 && literals.iter().all(|&b| b == literals[0]) {
+        write_literals_header(1, literals.len(), out);
+        out.push(literals[0]);
+        return;
+    }
+    if literals.len() >= MIN_HUFFMAN_LITERALS {
+        if let Some(data) = compress_literals(literals) {
+            if data.len() < literals.len() {
+                out.extend(data);
+                return;
+            }
+        }
+    }
+    write_literals_header(0, literals.len(), out);
+    out.extend_from_slice(literals);
+}
+
+/// The normalized counts of the codes, from which the decoder builds its
+/// table.
+#[derive(Clone, Debug)]
+struct Distribution {
+    counts: Vec<i16>,
+    log: usize,
+}
+
+impl Distribution {
+    fn rle(symbol: usize) -> Self {
+        let mut counts = vec![0; symbol + 1];
+        counts[symbol] = 1;
+        Self { counts, log: 0 }
+    }
+
+    /// Returns the estimated length of the codes of `freq` in 1/256 bits,
+    /// or `None` if a code has no state.
+    fn cost(&self, freq: &[usize]) -> Option<usize> {
+        freq.iter().enumerate().filter(|&(_, &f)| f > 0).try_fold(
+            0,
+            |sum, (s, &f)| {
+                let n = match *self.counts.get(s)? {
+                    0 => return None,
+                    c => cmp::max(c, 1) as usize,
+                };
+                let hb = highbit(n);
+                let log2 = (hb << 8) + ((n << 8) >> hb) - 256;
+                Some(sum + f * ((self.log << 8) - log2))
+            },
+        )
+    }
+}
+
+/// Chooses the cheapest table for `freq`, and returns its mode, its
+/// distribution and its description.
+fn choose_table(
+    freq: &[usize],
+    prev: &Option<Distribution>,
+    (default, default_log, max_log, _): (&[i16], usize, usize, usize),
+) -> (u8, Distribution, Vec<u8>) {
+    let mut symbols = freq.iter().enumerate().filter(|&(_, &f)| f > 0);
+    let first = symbols.next().map_or(0, |(s, _)| s);
+    if symbols.next().is_none() {
+        return (1, Distribution::rle(first), vec![first as u8]);
+    }
+
+    let (counts, log) = normalize(freq, max_log);
+    let description = write_counts(&counts, log);
+    let mut candidates = vec![(
+        0,
+        Distribution {
+            counts: default.to_vec(),
+            log: default_log,
+        },
+        Vec::new(),
+    )];
+    if let Some(prev) = prev {
+        candidates.push((3, prev.clone(), Vec::new()));
+    }
+    candidates.push((2, Distribution { counts, log }, description));
+    candidates
+        .into_iter()
+        .filter_map(|(mode, dist, description)| {
+            let cost = dist.cost(freq)? + (description.len() << 11);
+            Some((cost, mode, dist, description))
+        })
+        .min_by_key(|c| c.0)
+        .map(|(_, mode, dist, description)| (mode, dist, description))
+        .unwrap()
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Sequence {
+    literal_len: usize,
+    offset: usize,
+    match_len: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Code {
+    code: u8,
+    extra: u64,
+    bits: usize,
+}
+
+/// The tables and the offsets which are carried over the compressed
+/// blocks of a frame.
+#[derive(Clone, Debug)]
+struct BlockEncoder {
+    tables: [Option<Distribution>; 3],
+    offsets: [usize; 3],
+}
+
+impl BlockEncoder {
+    fn new() -> Self {
+        Self {
+            tables: [None, None, None],
+            offsets: DEFAULT_OFFSETS,
+        }
+    }
+
+    fn code(&mut self, seq: &Sequence) -> [Code; 3] {
+        let mut codes = [Code::default(); 3];
+        for &(t, table, value) in &[
+            (LL, &LL_CODES[..], seq.literal_len),
+            (ML, &ML_CODES[..], seq.match_len),
+        ] {
+            let code = code_of(table, value);
+            codes[t] = Code {
+                code: code as u8,
+                extra: (value - table[code].0 as usize) as u64,
+                bits: usize::from(table[code].1),
+            };
+        }
+        let value = offset_value(&self.offsets, seq.offset, seq.literal_len);
+        let _ = repeat_offset(&mut self.offsets, value, seq.literal_len);
+        let code = highbit(value);
+        codes[OF] = Code {
+            code: code as u8,
+            extra: (value - (1 << code)) as u64,
+            bits: code,
+        };
+        codes
+    }
+
+    /// Writes the sequences section.
+    fn write_sequences(
+        &mut self,
+        sequences: &[Sequence],
+        out: &mut Vec<u8>,
+    ) -> Option<()> {
+        let count = sequences.len();
+        match count {
+            0..=0x7F => out.push(count as u8),
+            0x80..=0x7EFF => out
+                .extend_from_slice(&[((count >> 8) + 0x80) as u8, count as u8]),
+            _ => {
+                out.push(0xFF);
+                out.extend_from_slice(&((count - 0x7F00) as u16).to_le_bytes());
+            }
+        }
+        if count == 0 {
+            return Some(());
+        }
+        let codes = sequences.iter().map(|s| self.code(s)).collect::<Vec<_>>();
+
+        let mut modes = 0;
+        let mut descriptions = Vec::new();
+        let mut encoders = Vec::with_capacity(3);
+        for (t, &table) in TABLES.iter().enumerate() {
+            let mut freq = vec![0; table.3];
+            for c in &codes {
+                freq[usize::from(c[t].code)] += 1;
+            }
+            let (mode, dist, description) =
+                choose_table(&freq, &self.tables[t], table);
+            modes |= mode << (6 - 2 * t);
+            descriptions.extend(description);
+            encoders.push(FseEncoder::new(&dist.counts, dist.log)?);
+            self.tables[t] = Some(dist);
+        }
+        out.push(modes);
+        out.extend(descriptions);
+
+        // 復号と逆の順序で書く
+        let mut writer = BackwardBitWriter::new();
+        let (last, rest) = codes.split_last()?;
+        let mut states = [0; 3];
+        for t in 0..3 {
+            states[t] = encoders[t].init(last[t].code);
+        }
+        for &t in &[LL, ML, OF] {
+            writer.write(last[t].extra, last[t].bits);
+        }
+        for c in rest.iter().rev() {
+            for &t in &[OF, ML, LL] {
+                encoders[t].encode(&mut states[t], c[t].code, &mut writer);
+            }
+            for &t in &[LL, ML, OF] {
+                writer.write(c[t].extra, c[t].bits);
+            }
+        }
+        for &t in &[ML, OF, LL] {
+            encoders[t].flush(states[t], &mut writer);
+        }
+        out.extend(writer.finish());
+        Some(())
+    }
+
+    /// Compresses the block, and returns its content.
+    fn compress(
+        &mut self,
+        literals: &[u8],
+        sequences: &[Sequence],
+    ) -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+        write_literals(literals, &mut out);
+        self.write_sequences(sequences, &mut out)?;
+        Some(out)
+    }
+}
+
+/// The Zstandard encoder, which writes a frame.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "zstd")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut ZstdEncoder::new(), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+///
+///     # #[cfg(feature = "zstd")]
+///     let decompressed = compressed
+///         .iter()
+///         .cloned()
+///         .decode(&mut ZstdDecoder::new())
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ZstdEncoder {
+    lzss: ZstdLzssEncoder,
+    options: ZstdOptions,
+    block_size: usize,
+    block: BlockEncoder,
+    // 入力のうち、まだブロックに書いていない部分
+    input: Vec<u8>,
+    block_len: usize,
+    literals: Vec<u8>,
+    sequences: Vec<Sequence>,
+    literal_len: usize,
+    content_len: u64,
+    xxh64: Option<XxHash64>,
+    out: Vec<u8>,
+    out_pos: usize,
+    written: u64,
+    header_written: bool,
+    finished: bool,
+}
+
+impl Default for ZstdEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ZstdEncoder {
+    pub fn new() -> Self {
+        Self::with_options(&ZstdOptions::default())
+    }
+
+    pub fn with_options(options: &ZstdOptions) -> Self {
+        options.validate();
+        // 内容が窓に収まるときは内容の長さだけ探せばよい
+        let window_size = match options.content_size {
+            Some(s) if s < options.window_size() as u64 => {
+                cmp::max(s as usize, 1)
+            }
+            _ => options.window_size(),
+        };
+        let block_size = cmp::min(window_size, MAX_BLOCK_SIZE);
+        let mut lzss = LzssEncoder::new(
+            lzss_comparison as fn(LzssCode, LzssCode) -> Ordering,
+            window_size,
+            block_size,
+            MIN_MATCH,
+            cmp::max(options.lazy_level, 1),
+        );
+        lzss.set_search_limit(options.max_chain, options.nice_length);
+        Self {
+            lzss,
+            options: *options,
+            block_size,
+            block: BlockEncoder::new(),
+            input: Vec::new(),
+            block_len: 0,
+            literals: Vec::new(),
+            sequences: Vec::new(),
+            literal_len: 0,
+            content_len: 0,
+            xxh64: if options.checksum {
+                Some(XxHash64::new())
+            } else {
+                None
+            },
+            out: Vec::new(),
+            out_pos: 0,
+            written: 0,
+            header_written: false,
+            finished: false,
+        }
+    }
+
+    fn write_header(&mut self) {
+        self.out.extend_from_slice(&MAGIC.to_le_bytes());
+        let content_size = self.options.content_size;
+        let single = matches!(
+            content_size,
+            Some(s) if s <= self.options.window_size() as u64
+        );
+        // Frame_Content_Size_Flag
+        let (flag, len) = match content_size {
+            None => (0, 0),
+            Some(s) if s < 0x100 => (0, 1),
+            Some(s) if s < 0x1_0100 => (1, 2),
+            Some(s) if s <= u64::from(u32::MAX) => (2, 4),
+            Some(_) => (3, 8),
+        };
+        let mut fhd = flag << 6;
+        if single {
+            fhd |= 0x20;
+        }
+        if self.xxh64.is_some() {
+            fhd |= 0x04;
+        }
+        self.out.push(fhd);
+        if !single {
+            self.out.push(((self.options.window_log - 10) << 3) as u8);
+        }
+        if let Some(s) = content_size {
+            let s = if flag == 1 { s - 0x100 } else { s };
+            self.out.extend_from_slice(&s.to_le_bytes()[..len]);
+        }
+    }
+
+    fn write_block(&mut self, last: bool) {
+        let len = self.block_len;
+        let content = &self.input[..len];
+        if let Some(ref mut xxh64) = self.xxh64 {
+            xxh64.update(content);
+        }
+        self.content_len += len as u64;
+
+        let rle = len > 1 && content.iter().all(|&b| b == content[0]);
+        let compressed = if rle {
+            None
+        } else {
+            let block = self.block.clone();
+            let data = self
+                .block
+                .compress(&self.literals, &self.sequences)
+                .filter(|data| data.len() < len);
+            // 圧縮しないブロックは表と繰り返しオフセットを変えない
+            if data.is_none() {
+                self.block = block;
+            }
+            data
+        };
+        let (kind, size) = match compressed {
+            Some(ref data) => (2, data.len()),
+            None if rle => (1, len),
+            None => (0, len),
+        };
+        let header = ((size as u32) << 3) | (kind << 1) | u32::from(last);
+        self.out.extend_from_slice(&header.to_le_bytes()[..3]);
+        match compressed {
+            Some(data) => self.out.extend(data),
+            None if rle => self.out.push(content[0]),
+            None => self.out.extend_from_slice(content),
+        }
+
+        let _ = self.input.drain(..len);
+        self.literals.clear();
+        self.sequences.clear();
+        self.literal_len = 0;
+        self.block_len = 0;
+    }
+
+    fn push_code(&mut self, code: LzssCode) {
+        let len = match code {
+            LzssCode::Symbol(_) => 1,
+            LzssCode::Reference { len, .. } => len,
+        };
+        if self.block_len + len > self.block_size {
+            self.write_block(false);
+        }
+        match code {
+            LzssCode::Symbol(b) => {
+                self.literals.push(b);
+                self.literal_len += 1;
+            }
+            LzssCode::Reference { len, pos } => {
+                self.sequences.push(Sequence {
+                    literal_len: self.literal_len,
+                    offset: pos + 1,
+                    match_len: len,
+                });
+                self.literal_len = 0;
+            }
+        }
+        self.block_len += len;
+    }
+
+    fn finish(&mut self) -> Result<(), CompressionError> {
+        self.write_block(true);
+        self.finished = true;
+        if let Some(expected) = self.options.content_size {
+            if expected != self.content_len {
+                return Err(CompressionError::LengthMismatch {
+                    expected,
+                    actual: self.content_len,
+                    position: (self.written + self.out.len() as u64) << 3,
+                });
+            }
+        }
+        if let Some(ref xxh64) = self.xxh64 {
+            self.out
+                .extend_from_slice(&(xxh64.value() as u32).to_le_bytes());
+        }
+        Ok(())
+    }
+}
+
+impl Encoder for ZstdEncoder {
+    type Error = CompressionError;
+    type In = u8;
+    type Out = u8;
+
+    fn next<I: Iterator<Item = u8>>(
+        &mut self,
+        iter: &mut I,
+        action: Action,
+    ) -> Option<Result<u8, CompressionError>> {
+        loop {
+            if self.out_pos < self.out.len() {
+                self.out_pos += 1;
+                return Some(Ok(self.out[self.out_pos - 1]));
+            }
+            self.written += self.out.len() as u64;
+            self.out.clear();
+            self.out_pos = 0;
+            if self.finished {
+                return None;
+            }
+            if !self.header_written {
+                self.write_header();
+                self.header_written = true;
+                continue;
+            }
+
+            let input = &mut self.input;
+            let code = self
+                .lzss
+                .next(&mut iter.by_ref().inspect(|&b| input.push(b)), action);
+            match code {
+                Some(Ok(code)) => self.push_code(code),
+                Some(Err(e)) => return Some(Err(e)),
+                None => match action {
+                    Action::Run => return None,
+                    Action::Finish => {
+                        if let Err(e) = self.finish() {
+                            return Some(Err(e));
+                        }
+                    }
+                    _ => {
+                        if self.block_len == 0 {
+                            return None;
+                        }
+                        self.write_block(false);
+                    }
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bitio::backward::{BackwardBitReader, BackwardBitWriter};
+use crate::bitio::direction::right::Right;
+use crate::bitio::reader::{BitRead, BitReader};
+use crate::bitio::small_bit_vec::SmallBitVec;
+use crate::bitio::writer::BitWriter;
+use crate::core::cmp;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Returns the floor of the base two logarithm of `value`.
+pub(crate) fn highbit(value: usize) -> usize {
+    63 - (value as u64).leading_zeros() as usize
+}
+
+/// Spreads the symbols over the states of the table of `1 << log`
+/// states.
+fn spread(counts: &[i16], log: usize) -> Option<Vec<u8>> {
+    let size = 1 << log;
+    let mut symbols = vec![0; size];
+    // 確率の低いシンボルは末尾に置く
+    let mut high = size;
+    for (s, &c) in counts.iter().enumerate() {
+        if c == -1 {
+            high = high.checked_sub(1)?;
+            symbols[high] = s as u8;
+        }
+    }
+    let step = (size >> 1) + (size >> 3) + 3;
+    let mut pos = 0;
+    for (s, &c) in counts.iter().enumerate() {
+        for _ in 0..c.max(0) {
+            symbols[pos] = s as u8;
+            loop {
+                pos = (pos + step) & (size - 1);
+                if pos < high {
+                    break;
+                }
+            }
+        }
+    }
+    if pos != 0 {
+        return None;
+    }
+    Some(symbols)
+}
+
+/// Normalizes the frequencies of the symbols so that their sum is a
+/// power of two up to `1 << max_log`, and returns the counts and the log.
+///
+/// `freq` must have two symbols at least.
+pub(crate) fn normalize(freq: &[usize], max_log: usize) -> (Vec<i16>, usize) {
+    let total = freq.iter().sum::<usize>();
+    let last = freq.iter().rposition(|&f| f > 0).unwrap_or(0);
+    let min_log = cmp::min(highbit(total) + 1, highbit(last.max(1)) + 2);
+    let log = cmp::min(max_log, highbit(total - 1).saturating_sub(2))
+        .max(min_log)
+        .clamp(MIN_LOG, max_log);
+    let size = 1 << log;
+    let mut counts = freq[..=last]
+        .iter()
+        .map(|&f| match f {
+            0 => 0,
+            f => cmp::max(1, (f * size + (total >> 1)) / total) as i16,
+        })
+        .collect::<Vec<_>>();
+    let mut sum = counts.iter().map(|&c| c as usize).sum::<usize>();
+    // 丸めの誤差は最も多いシンボルで調整する
+    while sum != size {
+        let (i, _) = counts
+            .iter()
+            .enumerate()
+            .max_by_key(|&(_, &c)| c)
+            .unwrap_or((0, &0));
+        if sum > size {
+            counts[i] -= 1;
+            sum -= 1;
+        } else {
+            counts[i] += (size - sum) as i16;
+            sum = size;
+        }
+    }
+    (counts, log)
+}
+
+/// Writes the table description of the normalized counts, which is read
+/// by `FseTable::read`.
+pub(crate) fn write_counts(counts: &[i16], log: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut writer = BitWriter::<Right>::new();
+    let mut write = |out: &mut Vec<u8>, value: usize, len: usize| {
+        let (data, n) = writer.write_bits(&SmallBitVec::new(value as u32, len));
+        out.extend((0..n).map(|i| (data >> (i << 3)) as u8));
+    };
+    write(&mut out, log - MIN_LOG, 4);
+
+    let mut remaining = (1 << log) + 1;
+    let mut threshold = 1 << log;
+    let mut len = log + 1;
+    let mut symbols = counts.iter();
+    let mut zero_run = false;
+    while remaining > 1 {
+        if zero_run {
+            let mut run = 0;
+            while symbols.as_slice().first() == Some(&0) {
+                let _ = symbols.next();
+                run += 1;
+            }
+            while run >= 3 {
+                write(&mut out, 3, 2);
+                run -= 3;
+            }
+            write(&mut out, run, 2);
+        }
+        let count = match symbols.next() {
+            Some(&c) => i32::from(c),
+            None => break,
+        };
+        let max = (2 * threshold - 1) - remaining;
+        remaining -= count.abs();
+        let mut value = count + 1;
+        if value >= threshold {
+            value += max;
+        }
+        write(
+            &mut out,
+            value as usize,
+            if value < max { len - 1 } else { len },
+        );
+        zero_run = count == 0;
+        while remaining < threshold {
+            len -= 1;
+            threshold >>= 1;
+        }
+    }
+    if let Some((data, n)) = writer.flush::<u32>() {
+        out.extend((0..n).map(|i| (data >> (i << 3)) as u8));
+    }
+    out
+}
+
+/// The minimum accuracy log of the table descriptions.
+const MIN_LOG: usize = 5;
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Entry {
+    symbol: u8,
+    bits: u8,
+    base: u16,
+}
+
+/// The decoding table of the finite state entropy codes.
+#[derive(Clone, Debug)]
+pub(crate) struct FseTable {
+    log: usize,
+    entries: Vec<Entry>,
+}
+
+impl FseTable {
+    /// Builds the table from the normalized counts of the symbols, where
+    /// `-1` stands for the probability less than 1.
+    pub(crate) fn new(counts: &[i16], log: usize) -> Option<Self> {
+        let size = 1 << log;
+        let mut next = counts
+            .iter()
+            .map(|&c| if c == -1 { 1 } else { c as u32 })
+            .collect::<Vec<_>>();
+        let entries = spread(counts, log)?
+            .into_iter()
+            .map(|symbol| {
+                let n = next[usize::from(symbol)];
+                next[usize::from(symbol)] += 1;
+                let bits = log as u32 - (31 - n.leading_zeros());
+                Entry {
+                    symbol,
+                    bits: bits as u8,
+                    base: ((n << bits) - size as u32) as u16,
+                }
+            })
+            .collect();
+        Some(Self { log, entries })
+    }
+
+    /// Creates the table which has only `symbol`.
+    pub(crate) fn rle(symbol: u8) -> Self {
+        Self {
+            log: 0,
+            entries: vec![Entry {
+                symbol,
+                bits: 0,
+                base: 0,
+            }],
+        }
+    }
+
+    /// Reads the table description at the start of `data`, and returns
+    /// the table and the length of the description.
+    pub(crate) fn read(
+        data: &[u8],
+        max_symbol: usize,
+        max_log: usize,
+    ) -> Option<(Self, usize)> {
+        let mut reader = BitReader::<Right>::new();
+        let mut iter = data.iter().cloned();
+        let log = reader
+            .read_bits::<u32, _>(4, &mut iter)
+            .map(|b| b.data())
+            .unwrap_or(0) as usize
+            + MIN_LOG;
+        if log > max_log {
+            return None;
+        }
+        let mut bits = 4;
+
+        let mut counts = Vec::new();
+        let mut remaining = (1 << log) + 1;
+        let mut threshold = 1 << log;
+        let mut len = log + 1;
+        let mut zero_run = false;
+        while remaining > 1 && counts.len() <= max_symbol {
+            if zero_run {
+                loop {
+                    let r = reader
+                        .read_bits::<u32, _>(2, &mut iter)
+                        .map(|b| b.data())
+                        .unwrap_or(0);
+                    bits += 2;
+                    counts.extend((0..r).map(|_| 0));
+                    if r != 3 {
+                        break;
+                    }
+                }
+                if counts.len() > max_symbol {
+                    return None;
+                }
+            }
+            let max = (2 * threshold - 1) - remaining;
+            let v = reader
+                .peek_bits::<u32, _>(len, &mut iter)
+                .map(|b| b.data())
+                .unwrap_or(0) as i32;
+            let mut count = if (v & (threshold - 1)) < max {
+                let _ = reader.skip_bits(len - 1, &mut iter);
+                bits += len - 1;
+                v & (threshold - 1)
+            } else {
+                let _ = reader.skip_bits(len, &mut iter);
+                bits += len;
+                let c = v & (2 * threshold - 1);
+                if c >= threshold {
+                    c - max
+                } else {
+                    c
+                }
+            };
+            count -= 1;
+            remaining -= count.abs();
+            if remaining < 1 {
+                return None;
+            }
+            counts.push(count as i16);
+            zero_run = count == 0;
+            while remaining < threshold {
+                len -= 1;
+                threshold >>= 1;
+            }
+        }
+        let used = (bits + 7) >> 3;
+        if remaining != 1 || used > data.len() {
+            return None;
+        }
+        Some((Self::new(&counts, log)?, used))
+    }
+
+    /// Reads the initial state.
+    pub(crate) fn init(&self, reader: &mut BackwardBitReader<'_>) -> usize {
+        reader.read(self.log) as usize
+    }
+
+    pub(crate) fn symbol(&self, state: usize) -> u8 {
+        self.entries[state].symbol
+    }
+
+    /// Reads the state following `state`.
+    pub(crate) fn update(
+        &self,
+        state: usize,
+        reader: &mut BackwardBitReader<'_>,
+    ) -> usize {
+        let e = self.entries[state];
+        usize::from(e.base) + reader.read(usize::from(e.bits)) as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Transform {
+    delta_bits: i64,
+    delta_state: i64,
+}
+
+/// The encoding table of the finite state entropy codes, which writes
+/// the streams read with the `FseTable` of the same counts.
+///
+/// The states are kept offset by the size of the table.
+#[derive(Clone, Debug)]
+pub(crate) struct FseEncoder {
+    log: usize,
+    states: Vec<u16>,
+    transforms: Vec<Transform>,
+}
+
+impl FseEncoder {
+    pub(crate) fn new(counts: &[i16], log: usize) -> Option<Self> {
+        let size = 1 << log;
+        let mut cumul = vec![0; counts.len() + 1];
+        for (s, &c) in counts.iter().enumerate() {
+            cumul[s + 1] =
+                cumul[s] + if c == -1 { 1 } else { c.max(0) as usize };
+        }
+        let mut next = cumul.clone();
+        let mut states = vec![0; size];
+        for (u, s) in spread(counts, log)?.into_iter().enumerate() {
+            let s = usize::from(s);
+            states[next[s]] = (size + u) as u16;
+            next[s] += 1;
+        }
+        let log = log as i64;
+        let transforms = counts
+            .iter()
+            .enumerate()
+            .map(|(s, &c)| match c {
+                0 => Transform {
+                    delta_bits: ((log + 1) << 16) - (1 << log),
+                    delta_state: 0,
+                },
+                -1 | 1 => Transform {
+                    delta_bits: (log << 16) - (1 << log),
+                    delta_state: cumul[s] as i64 - 1,
+                },
+                c => {
+                    let c = i64::from(c);
+                    let max_bits = log - highbit(c as usize - 1) as i64;
+                    Transform {
+                        delta_bits: (max_bits << 16) - (c << max_bits),
+                        delta_state: cumul[s] as i64 - c,
+                    }
+                }
+            })
+            .collect();
+        Some(Self {
+            log: log as usize,
+            states,
+            transforms,
+        })
+    }
+
+    /// Returns the initial state for `symbol`, which is decoded last.
+    pub(crate) fn init(&self, symbol: u8) -> usize {
+        let t = self.transforms[usize::from(symbol)];
+        let bits = (t.delta_bits + (1 << 15)) >> 16;
+        let value = (bits << 16) - t.delta_bits;
+        usize::from(self.states[((value >> bits) + t.delta_state) as usize])
+    }
+
+    /// Writes the bits of `state` and moves to the state for `symbol`.
+    pub(crate) fn encode(
+        &self,
+        state: &mut usize,
+        symbol: u8,
+        writer: &mut BackwardBitWriter,
+    ) {
+        let t = self.transforms[usize::from(symbol)];
+        let bits = ((*state as i64 + t.delta_bits) >> 16) as usize;
+        writer.write(*state as u64, bits);
+        *state = usize::from(
+            self.states[((*state >> bits) as i64 + t.delta_state) as usize],
+        );
+    }
+
+    /// Writes `state`, which is read first by `FseTable::init`.
+    pub(crate) fn flush(&self, state: usize, writer: &mut BackwardBitWriter) {
+        writer.write(state as u64, self.log);
+    }
+}
@@ -0,0 +1,232 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+use crate::bitio::backward::{BackwardBitReader, BackwardBitWriter};
+use crate::bitio::direction::left::Left;
+use crate::bitio::small_bit_vec::SmallBitVec;
+use crate::huffman::cano_huff_table::make_table;
+use crate::huffman::decoder::HuffmanDecoder;
+use crate::zstd::fse::{normalize, write_counts, FseEncoder, FseTable};
+use crate::zstd::MAX_HUFFMAN_BITS;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// The maximum accuracy log of the FSE table for the weights.
+const MAX_WEIGHT_LOG: usize = 6;
+
+/// The Huffman codes indexed by the symbols.
+pub(crate) type HuffmanCodes = Vec<Option<SmallBitVec<u16>>>;
+
+/// Decodes the weights compressed with the FSE in `data`.
+fn read_fse_weights(data: &[u8]) -> Option<Vec<u8>> {
+    let (table, used) = FseTable::read(data, MAX_HUFFMAN_BITS, MAX_WEIGHT_LOG)?;
+    let mut reader = BackwardBitReader::new(&data[used..]).ok()?;
+    let mut states = [table.init(&mut reader), table.init(&mut reader)];
+    let mut weights = Vec::new();
+    // 二つの状態を交互に使い、ストリームを読み切ったら残りの状態を出力する
+    for i in (0..2).cycle() {
+        if weights.len() > 255 {
+            return None;
+        }
+        weights.push(table.symbol(states[i]));
+        states[i] = table.update(states[i], &mut reader);
+        if reader.remaining() < 0 {
+            weights.push(table.symbol(states[i ^ 1]));
+            break;
+        }
+    }
+    Some(weights)
+}
+
+/// Assigns the codes from the weights, the longest codes first in the
+/// order of the symbols.
+fn assign_codes(mut weights: Vec<u8>) -> Option<HuffmanCodes> {
+    if weights.len() > 255
+        || weights.iter().any(|&w| usize::from(w) > MAX_HUFFMAN_BITS)
+    {
+        return None;
+    }
+    let total = weights
+        .iter()
+        .filter(|&&w| w > 0)
+        .map(|&w| 1_u32 << (w - 1))
+        .sum::<u32>();
+    if total == 0 {
+        return None;
+    }
+    // 最後のシンボルの重みは合計が2の冪になるように決まる
+    let log = 32 - total.leading_zeros() as usize;
+    let rest = (1 << log) - total;
+    if log > MAX_HUFFMAN_BITS || !rest.is_power_of_two() {
+        return None;
+    }
+    weights.push(rest.trailing_zeros() as u8 + 1);
+
+    let mut start = vec![0_u32; log + 2];
+    for &w in weights.iter().filter(|&&w| w > 0) {
+        start[usize::from(w) + 1] += 1 << (w - 1);
+    }
+    for w in 1..=log {
+        start[w + 1] += start[w];
+    }
+    Some(
+        weights
+            .iter()
+            .map(|&w| {
+                if w == 0 {
+                    return None;
+                }
+                let w = usize::from(w);
+                let index = start[w];
+                start[w] += 1 << (w - 1);
+                Some(SmallBitVec::new((index >> (w - 1)) as u16, log + 1 - w))
+            })
+            .collect(),
+    )
+}
+
+/// Reads the Huffman tree description at the start of `data`, and
+/// returns the decoder and the length of the description.
+pub(crate) fn read_huffman_table(
+    data: &[u8],
+) -> Option<(HuffmanDecoder<Left>, usize)> {
+    let header = usize::from(*data.first()?);
+    let (weights, len) = if header < 128 {
+        (read_fse_weights(data.get(1..=header)?)?, header + 1)
+    } else {
+        let count = header - 127;
+        let body = data.get(1..=(count + 1) >> 1)?;
+        let weights = (0..count)
+            .map(|i| {
+                if i & 1 == 0 {
+                    body[i >> 1] >> 4
+                } else {
+                    body[i >> 1] & 0x0F
+                }
+            })
+            .collect();
+        (weights, body.len() + 1)
+    };
+    let codes = assign_codes(weights)?;
+    let decoder = HuffmanDecoder::with_codes(codes, MAX_HUFFMAN_BITS).ok()?;
+    Some((decoder, len))
+}
+
+/// Compresses the weights with the FSE, using the two states alternately
+/// as `read_fse_weights`.
+fn write_fse_weights(weights: &[u8]) -> Option<Vec<u8>> {
+    let mut freq = vec![0; MAX_HUFFMAN_BITS + 1];
+    for &w in weights {
+        freq[usize::from(w)] += 1;
+    }
+    if weights.len() < 2 || freq.iter().filter(|&&f| f > 0).count() < 2 {
+        return None;
+    }
+    let (counts, log) = normalize(&freq, MAX_WEIGHT_LOG);
+    let encoder = FseEncoder::new(&counts, log)?;
+    let mut writer = BackwardBitWriter::new();
+    let n = weights.len();
+    let (mut states, mut i) = if n & 1 == 1 {
+        let mut states =
+            [encoder.init(weights[n - 1]), encoder.init(weights[n - 2])];
+        encoder.encode(&mut states[0], weights[n - 3], &mut writer);
+        (states, n - 3)
+    } else {
+        let states =
+            [encoder.init(weights[n - 2]), encoder.init(weights[n - 1])];
+        (states, n - 2)
+    };
+    while i > 0 {
+        encoder.encode(&mut states[1], weights[i - 1], &mut writer);
+        encoder.encode(&mut states[0], weights[i - 2], &mut writer);
+        i -= 2;
+    }
+    encoder.flush(states[1], &mut writer);
+    encoder.flush(states[0], &mut writer);
+    let mut data = write_counts(&counts, log);
+    data.extend(writer.finish());
+    // 最後の状態の更新でストリームが溢れないと復号できない
+    if read_fse_weights(&data)? == weights {
+        Some(data)
+    } else {
+        None
+    }
+}
+
+/// Builds the Huffman codes of `literals`, and returns the tree
+/// description and the codes indexed by the symbols.
+pub(crate) fn write_huffman_table(
+    literals: &[u8],
+) -> Option<(Vec<u8>, HuffmanCodes)> {
+    let mut freq = vec![0; 256];
+    for &b in literals {
+        freq[usize::from(b)] += 1;
+    }
+    if freq.iter().filter(|&&f| f > 0).count() < 2 {
+        return None;
+    }
+    let lens = make_table(&freq, MAX_HUFFMAN_BITS);
+    let max = usize::from(*lens.iter().max()?);
+    let kraft = lens
+        .iter()
+        .filter(|&&l| l > 0)
+        .map(|&l| 1 << (max - usize::from(l)))
+        .sum::<usize>();
+    if kraft != 1 << max {
+        return None;
+    }
+    let weights = lens
+        .iter()
+        .map(|&l| if l > 0 { (max + 1) as u8 - l } else { 0 })
+        .collect::<Vec<_>>();
+    // 最後のシンボルの重みは書かない
+    let weights = &weights[..weights.len() - 1];
+    let codes = assign_codes(weights.to_vec())?;
+
+    let direct = if weights.len() <= 128 {
+        let mut data = vec![(127 + weights.len()) as u8];
+        data.extend(
+            weights
+                .chunks(2)
+                .map(|w| (w[0] << 4) | w.get(1).cloned().unwrap_or(0)),
+        );
+        Some(data)
+    } else {
+        None
+    };
+    let compressed = write_fse_weights(weights)
+        .filter(|data| data.len() < 128)
+        .map(|data| {
+            let mut header = vec![data.len() as u8];
+            header.extend(data);
+            header
+        });
+    let description = match (direct, compressed) {
+        (Some(d), Some(c)) if c.len() < d.len() => c,
+        (Some(d), _) => d,
+        (None, c) => c?,
+    };
+    Some((description, codes))
+}
+
+/// Writes the Huffman stream of `data`, which is decoded from its first
+/// symbol.
+pub(crate) fn write_huffman_stream(
+    codes: &[Option<SmallBitVec<u16>>],
+    data: &[u8],
+) -> Vec<u8> {
+    let mut writer = BackwardBitWriter::new();
+    for &b in data.iter().rev() {
+        if let Some(ref code) = codes[usize::from(b)] {
+            writer.write(u64::from(code.data()), code.len());
+        }
+    }
+    writer.finish()
+}
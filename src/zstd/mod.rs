@@ -0,0 +1,486 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+#![cfg(feature = "zstd")]
+
+pub(crate) mod block;
+pub(crate) mod decoder;
+pub(crate) mod dictionary;
+pub(crate) mod encoder;
+pub(crate) mod fse;
+pub(crate) mod huffman;
+pub(crate) mod options;
+
+const MAGIC: u32 = 0xFD2F_B528;
+/// The magic of the skippable frames, whose lowest 4 bits are any.
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A50;
+const DICT_MAGIC: u32 = 0xEC30_A437;
+
+/// The maximum length of the data in a block.
+const MAX_BLOCK_SIZE: usize = 0x2_0000;
+const MAX_WINDOW_LOG: u32 = 31;
+const MAX_HUFFMAN_BITS: usize = 11;
+
+/// The offsets to be repeated at the start of a frame.
+const DEFAULT_OFFSETS: [usize; 3] = [1, 4, 8];
+
+/// The baselines and the numbers of the extra bits of the literal length
+/// codes.
+const LL_CODES: [(u32, u8); 36] = [
+    (0, 0),
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 0),
+    (12, 0),
+    (13, 0),
+    (14, 0),
+    (15, 0),
+    (16, 1),
+    (18, 1),
+    (20, 1),
+    (22, 1),
+    (24, 2),
+    (28, 2),
+    (32, 3),
+    (40, 3),
+    (48, 4),
+    (64, 6),
+    (128, 7),
+    (256, 8),
+    (512, 9),
+    (1024, 10),
+    (2048, 11),
+    (4096, 12),
+    (8192, 13),
+    (16384, 14),
+    (32768, 15),
+    (65536, 16),
+];
+
+/// The baselines and the numbers of the extra bits of the match length
+/// codes.
+const ML_CODES: [(u32, u8); 53] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 0),
+    (12, 0),
+    (13, 0),
+    (14, 0),
+    (15, 0),
+    (16, 0),
+    (17, 0),
+    (18, 0),
+    (19, 0),
+    (20, 0),
+    (21, 0),
+    (22, 0),
+    (23, 0),
+    (24, 0),
+    (25, 0),
+    (26, 0),
+    (27, 0),
+    (28, 0),
+    (29, 0),
+    (30, 0),
+    (31, 0),
+    (32, 0),
+    (33, 0),
+    (34, 0),
+    (35, 1),
+    (37, 1),
+    (39, 1),
+    (41, 1),
+    (43, 2),
+    (47, 2),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 5),
+    (131, 7),
+    (259, 8),
+    (515, 9),
+    (1027, 10),
+    (2051, 11),
+    (4099, 12),
+    (8195, 13),
+    (16387, 14),
+    (32771, 15),
+    (65539, 16),
+];
+
+const MAX_OF_CODE: usize = 31;
+const LL_MAX_LOG: usize = 9;
+const ML_MAX_LOG: usize = 9;
+const OF_MAX_LOG: usize = 8;
+
+/// The predefined distributions of the literal length, the match length
+/// and the offset codes.
+const LL_DEFAULT: [i16; 36] = [
+    4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    3, 2, 1, 1, 1, 1, 1, -1, -1, -1, -1,
+];
+const ML_DEFAULT: [i16; 53] = [
+    1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1,
+    -1, -1, -1, -1,
+];
+const OF_DEFAULT: [i16; 29] = [
+    1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1,
+    -1, -1, -1, -1,
+];
+const LL_DEFAULT_LOG: usize = 6;
+const ML_DEFAULT_LOG: usize = 6;
+const OF_DEFAULT_LOG: usize = 5;
+
+#[cfg(test)]
+mod tests {
+    use crate::action::Action;
+    use crate::checksum::XxHash64;
+    use crate::error::CompressionError;
+    use crate::traits::decoder::DecodeExt;
+    use crate::traits::encoder::{EncodeExt, Encoder};
+    use crate::zstd::decoder::ZstdDecoder;
+    use crate::zstd::encoder::ZstdEncoder;
+    use crate::zstd::fse::{normalize, write_counts, FseTable};
+    use crate::zstd::options::ZstdOptions;
+    #[cfg(not(feature = "std"))]
+    #[allow(unused_imports)]
+    use alloc::vec;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    const SKIPPABLE: [u8; 11] = [
+        0x5A, 0x2A, 0x4D, 0x18, 0x03, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03,
+    ];
+
+    fn decode(
+        encoded: &[u8],
+        decoder: &mut ZstdDecoder,
+    ) -> Result<Vec<u8>, CompressionError> {
+        encoded.iter().cloned().decode(decoder).collect()
+    }
+
+    fn check(encoded: &[u8], decoder: &mut ZstdDecoder, expected: &[u8]) {
+        let decoded = decode(encoded, decoder).unwrap();
+        assert_eq!(decoded.len(), expected.len());
+        assert!(decoded == expected);
+    }
+
+    fn encode(
+        data: &[u8],
+        encoder: &mut ZstdEncoder,
+    ) -> Result<Vec<u8>, CompressionError> {
+        data.iter()
+            .cloned()
+            .encode(encoder, Action::Finish)
+            .collect()
+    }
+
+    fn check_encode(data: &[u8], options: &ZstdOptions) -> Vec<u8> {
+        let encoded =
+            encode(data, &mut ZstdEncoder::with_options(options)).unwrap();
+        check(&encoded, &mut ZstdDecoder::new(), data);
+        encoded
+    }
+
+    /// Returns a frame of a raw block and a RLE block with the checksum,
+    /// and its content.
+    fn raw_frame() -> (Vec<u8>, Vec<u8>) {
+        let mut content = b"zstandard".to_vec();
+        content.extend_from_slice(&[b'a'; 100]);
+        let mut xxh64 = XxHash64::new();
+        xxh64.update(&content);
+
+        // Single_Segment_Flag, Content_Checksum_Flag, 4 bytes FCS
+        let mut frame = vec![0x28, 0xB5, 0x2F, 0xFD, 0xA4];
+        frame.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&[9 << 3, 0, 0]);
+        frame.extend_from_slice(b"zstandard");
+        frame.extend_from_slice(&[0x23, 0x03, 0x00, b'a']);
+        frame.extend_from_slice(&(xxh64.value() as u32).to_le_bytes());
+        (frame, content)
+    }
+
+    #[test]
+    fn test_samples() {
+        check(
+            include_bytes!("../../data/sample1.zst"),
+            &mut ZstdDecoder::new(),
+            include_bytes!("../../data/sample1.ref"),
+        );
+        check(
+            include_bytes!("../../data/sample2.zst"),
+            &mut ZstdDecoder::new(),
+            include_bytes!("../../data/sample2.ref"),
+        );
+        check(
+            include_bytes!("../../data/sample3.zst"),
+            &mut ZstdDecoder::new(),
+            include_bytes!("../../data/sample3.ref"),
+        );
+    }
+
+    #[test]
+    fn test_frames() {
+        let (frame, content) = raw_frame();
+        let mut decoder = ZstdDecoder::new();
+        check(&frame, &mut decoder, &content);
+        assert_eq!(decoder.content_size(), Some(content.len() as u64));
+        assert_eq!(decoder.dictionary_id(), None);
+
+        let mut encoded = SKIPPABLE.to_vec();
+        encoded.extend_from_slice(&frame);
+        encoded.extend_from_slice(include_bytes!("../../data/sample3.zst"));
+        encoded.extend_from_slice(&SKIPPABLE);
+        let mut expected = content;
+        expected.extend_from_slice(include_bytes!("../../data/sample3.ref"));
+        check(&encoded, &mut ZstdDecoder::new(), &expected);
+        check(&SKIPPABLE, &mut ZstdDecoder::new(), &[]);
+    }
+
+    #[test]
+    fn test_dict() {
+        let dict = include_bytes!("../../data/sample4.dict");
+        let encoded = include_bytes!("../../data/sample4.zst");
+        let id = u32::from_le_bytes([dict[4], dict[5], dict[6], dict[7]]);
+        let mut decoder = ZstdDecoder::with_dict(dict);
+        check(
+            encoded,
+            &mut decoder,
+            include_bytes!("../../data/sample4.ref"),
+        );
+        assert_eq!(decoder.dictionary_id(), Some(id));
+        assert_eq!(
+            decode(encoded, &mut ZstdDecoder::new()),
+            Err(CompressionError::DictionaryRequired { id, position: 0 })
+        );
+
+        // 生の辞書
+        check(
+            include_bytes!("../../data/sample4.rawdict.zst"),
+            &mut ZstdDecoder::with_dict(include_bytes!(
+                "../../data/sample1.ref"
+            )),
+            include_bytes!("../../data/sample4.ref"),
+        );
+    }
+
+    #[test]
+    fn test_stream() {
+        use crate::traits::decoder::tests::decompress_slices;
+
+        let (frame, content) = raw_frame();
+        let mut encoded = SKIPPABLE.to_vec();
+        encoded.extend_from_slice(&frame);
+        encoded.extend_from_slice(include_bytes!("../../data/sample1.zst"));
+        let mut expected = content;
+        expected.extend_from_slice(include_bytes!("../../data/sample1.ref"));
+        for &(chunk, out_len) in &[(1, 0x1000), (7, 1), (0x1000, 0x8000)] {
+            let decoded = decompress_slices(
+                &mut ZstdDecoder::new(),
+                &encoded,
+                chunk,
+                out_len,
+            )
+            .unwrap();
+            assert!(decoded == expected);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let (frame, _) = raw_frame();
+        let len = frame.len() as u64;
+        assert_eq!(
+            decode(b"not zstd", &mut ZstdDecoder::new()),
+            Err(CompressionError::BadMagic { position: 0 })
+        );
+        assert_eq!(
+            decode(&[], &mut ZstdDecoder::new()),
+            Err(CompressionError::UnexpectedEof)
+        );
+
+        let mut garbage = frame.clone();
+        garbage.extend_from_slice(b"garbage");
+        assert_eq!(
+            decode(&garbage, &mut ZstdDecoder::new()),
+            Err(CompressionError::TrailingGarbage { position: len << 3 })
+        );
+
+        let mut reserved = frame.clone();
+        reserved[4] |= 0x08;
+        assert_eq!(
+            decode(&reserved, &mut ZstdDecoder::new()),
+            Err(CompressionError::InvalidHeader { position: 32 })
+        );
+
+        let mut checksum = frame.clone();
+        checksum[frame.len() - 1] ^= 1;
+        assert!(matches!(
+            decode(&checksum, &mut ZstdDecoder::new()),
+            Err(CompressionError::ChecksumMismatch { position, .. })
+                if position == (len - 4) << 3
+        ));
+
+        let mut length = frame.clone();
+        length[5] += 1;
+        assert_eq!(
+            decode(&length, &mut ZstdDecoder::new()),
+            Err(CompressionError::LengthMismatch {
+                expected: 110,
+                actual: 109,
+                position: (len - 4) << 3,
+            })
+        );
+
+        let mut block = frame.clone();
+        block[9] |= 0x06;
+        assert_eq!(
+            decode(&block, &mut ZstdDecoder::new()),
+            Err(CompressionError::InvalidBlock { position: 9 << 3 })
+        );
+
+        let encoded = include_bytes!("../../data/sample1.zst");
+        assert_eq!(
+            decode(&encoded[..encoded.len() - 1], &mut ZstdDecoder::new()),
+            Err(CompressionError::UnexpectedEof)
+        );
+        let mut corrupted = encoded.to_vec();
+        corrupted[encoded.len() / 2] ^= 0x55;
+        assert!(decode(&corrupted, &mut ZstdDecoder::new()).is_err());
+    }
+
+    #[test]
+    fn test_write_counts() {
+        for &(ref freq, max_log) in &[
+            (vec![1, 0, 0, 0, 5, 100, 3, 0, 0, 0, 0, 1], 6),
+            (
+                vec![7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7],
+                9,
+            ),
+            ((0..53).map(|i| (i * 7) % 11).collect::<Vec<_>>(), 9),
+            (vec![1000, 1], 8),
+        ] {
+            let (counts, log) = normalize(freq, max_log);
+            assert!(log <= max_log);
+            assert_eq!(
+                counts.iter().map(|&c| c as usize).sum::<usize>(),
+                1 << log
+            );
+            let mut data = write_counts(&counts, log);
+            let len = data.len();
+            data.push(0xFF);
+            let (_, used) =
+                FseTable::read(&data, freq.len() - 1, max_log).unwrap();
+            assert_eq!(used, len);
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let options = ZstdOptions::default();
+        check_encode(&[], &options);
+        check_encode(b"a", &options);
+        check_encode(b"aabbaabbaabbaabb\n", &options);
+        check_encode(&[0x55; 0x4_0000], &options);
+        check_encode(include_bytes!("../../data/sample3.ref"), &options);
+        for &level in &[1, 3, 9, 19] {
+            let options = ZstdOptions::new(level);
+            check_encode(include_bytes!("../../data/sample1.ref"), &options);
+            check_encode(include_bytes!("../../data/sample2.ref"), &options);
+        }
+    }
+
+    #[test]
+    fn test_encode_options() {
+        let data = include_bytes!("../../data/sample4.ref");
+        let options = ZstdOptions {
+            window_log: 10,
+            checksum: false,
+            ..ZstdOptions::new(6)
+        };
+        let encoded = check_encode(data, &options);
+        // Window_Descriptor, no Content_Checksum_Flag
+        assert_eq!(encoded[4], 0);
+        assert_eq!(encoded[5], 0);
+
+        let options = ZstdOptions {
+            content_size: Some(data.len() as u64),
+            ..ZstdOptions::default()
+        };
+        let encoded = check_encode(data, &options);
+        let mut decoder = ZstdDecoder::new();
+        check(&encoded, &mut decoder, data);
+        assert_eq!(decoder.content_size(), Some(data.len() as u64));
+
+        for &len in &[0_usize, 1, 255, 256, 0x1_00FF, 0x1_0100] {
+            let data =
+                (0..len).map(|i| ((i * i) >> 7) as u8).collect::<Vec<_>>();
+            let options = ZstdOptions {
+                content_size: Some(len as u64),
+                ..ZstdOptions::new(1)
+            };
+            let encoded = check_encode(&data, &options);
+            let mut decoder = ZstdDecoder::new();
+            check(&encoded, &mut decoder, &data);
+            assert_eq!(decoder.content_size(), Some(len as u64));
+        }
+
+        let options = ZstdOptions {
+            content_size: Some(3),
+            ..ZstdOptions::default()
+        };
+        assert!(matches!(
+            encode(b"abcd", &mut ZstdEncoder::with_options(&options)),
+            Err(CompressionError::LengthMismatch {
+                expected: 3,
+                actual: 4,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_encode_flush() {
+        let data = include_bytes!("../../data/sample1.ref");
+        let mut encoder = ZstdEncoder::new();
+        let mut encoded = Vec::new();
+        for (i, chunk) in data.chunks(0x1000).enumerate() {
+            let action = if i % 3 == 0 {
+                Action::Flush
+            } else {
+                Action::Run
+            };
+            let mut iter = chunk.iter().cloned();
+            while let Some(b) = encoder.next(&mut iter, action) {
+                encoded.push(b.unwrap());
+            }
+        }
+        encoded.extend(
+            Vec::new()
+                .into_iter()
+                .encode(&mut encoder, Action::Finish)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+        );
+        check(&encoded, &mut ZstdDecoder::new(), data);
+    }
+}
@@ -0,0 +1,119 @@
+//! rust-compression
+//!
+//! # Licensing
+//! This Source Code is subject to the terms of the Mozilla Public License
+//! version 2.0 (the "License"). You can obtain a copy of the License at
+//! <http://mozilla.org/MPL/2.0/>.
+
+/// The parameters of the Zstandard encoder.
+///
+/// `ZstdOptions::new(level)` fills in the search parameters for the
+/// compression level `1..=19`; the fields may be tuned afterwards.
+///
+/// # Examples
+///
+/// ```rust
+/// use compression::prelude::*;
+///
+/// fn main() {
+///     # #[cfg(feature = "zstd")]
+///     let options = ZstdOptions {
+///         window_log: 16,
+///         content_size: Some(17),
+///         ..ZstdOptions::new(9)
+///     };
+///     # #[cfg(feature = "zstd")]
+///     let compressed = b"aabbaabbaabbaabb\n"
+///         .into_iter()
+///         .cloned()
+///         .encode(&mut ZstdEncoder::with_options(&options), Action::Finish)
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZstdOptions {
+    /// `1` is the fastest and `19` is the best compression.
+    pub level: usize,
+    /// The base two logarithm of the window size, `10..=27`.
+    pub window_log: usize,
+    /// The number of following positions searched for a longer match
+    /// before a match is emitted. `1` is greedy matching.
+    pub lazy_level: usize,
+    /// The maximum number of hash chain entries examined per search.
+    pub max_chain: usize,
+    /// The match length which stops the search.
+    pub nice_length: usize,
+    /// The length of the input written in the frame header. Finishing the
+    /// frame fails if the input has a different length.
+    pub content_size: Option<u64>,
+    /// Whether the XXH64 checksum of the input ends the frame.
+    pub checksum: bool,
+}
+
+impl Default for ZstdOptions {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LEVEL)
+    }
+}
+
+impl ZstdOptions {
+    const DEFAULT_LEVEL: usize = 3;
+    const MIN_WINDOW_LOG: usize = 10;
+    const MAX_WINDOW_LOG: usize = 27;
+
+    // window_log, lazy_level, max_chain, nice_length
+    const LEVEL_TABLE: [(usize, usize, usize, usize); 19] = [
+        (17, 1, 4, 16),
+        (18, 1, 8, 24),
+        (19, 1, 16, 32),
+        (19, 2, 16, 32),
+        (19, 2, 32, 48),
+        (20, 2, 64, 64),
+        (20, 3, 64, 96),
+        (20, 3, 128, 128),
+        (20, 3, 256, 128),
+        (21, 3, 256, 192),
+        (21, 4, 512, 256),
+        (21, 4, 1024, 256),
+        (21, 4, 1024, 512),
+        (21, 4, 2048, 512),
+        (21, 4, 2048, 1024),
+        (21, 5, 4096, 1024),
+        (21, 5, 4096, 2048),
+        (21, 6, 4096, 4096),
+        (21, 6, 4096, 0x2_0000),
+    ];
+
+    pub fn new(level: usize) -> Self {
+        if !(1..=19).contains(&level) {
+            panic!("invalid level");
+        }
+        let (window_log, lazy_level, max_chain, nice_length) =
+            Self::LEVEL_TABLE[level - 1];
+        Self {
+            level,
+            window_log,
+            lazy_level,
+            max_chain,
+            nice_length,
+            content_size: None,
+            checksum: true,
+        }
+    }
+
+    pub(crate) fn validate(&self) {
+        if !(1..=19).contains(&self.level) {
+            panic!("invalid level");
+        }
+        if !(Self::MIN_WINDOW_LOG..=Self::MAX_WINDOW_LOG)
+            .contains(&self.window_log)
+        {
+            panic!("invalid window log");
+        }
+    }
+
+    pub(crate) fn window_size(&self) -> usize {
+        1 << self.window_log
+    }
+}